@@ -0,0 +1,133 @@
+use std::fs::File;
+use std::io::{IoSlice, Seek, SeekFrom, Write};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use yadon::{ApplyOptions, Yadon};
+
+/// Number of writes recorded for `contiguous_writes`.
+const WRITES: usize = 4096;
+
+/// Number of writes recorded for `small_writes`.
+const SMALL_WRITES: usize = 100_000;
+
+/// Number of 1 MiB extents recorded for `parallel_writes`.
+#[cfg(feature = "fs")]
+const PARALLEL_EXTENTS: u64 = 256;
+
+/// A file which counts the write calls made on it.
+struct CountingFile {
+    file: File,
+    write_calls: usize,
+}
+
+impl Write for CountingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_calls += 1;
+        self.file.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        self.write_calls += 1;
+        self.file.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for CountingFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+fn contiguous_writes(c: &mut Criterion) {
+    let mut yadon = Yadon::new(Some(0), None);
+    for i in 0..WRITES {
+        yadon.write_all(&[i as u8; 16]).unwrap();
+    }
+
+    let path = std::env::temp_dir().join(format!("yadon-bench-{}", std::process::id()));
+    let mut target = CountingFile {
+        file: File::create(&path).unwrap(),
+        write_calls: 0,
+    };
+
+    let mut group = c.benchmark_group("contiguous_writes");
+    for vectored_writes in [false, true] {
+        let options = ApplyOptions {
+            vectored_writes,
+            ..Default::default()
+        };
+
+        target.write_calls = 0;
+        yadon.apply_with_options(&mut target, &options).unwrap();
+        println!("vectored_writes: {}, {} writes applied with {} write calls", vectored_writes, WRITES, target.write_calls);
+
+        let name = if vectored_writes { "vectored" } else { "sequential" };
+        group.bench_function(name, |b| b.iter(|| yadon.apply_with_options(&mut target, &options).unwrap()));
+    }
+    group.finish();
+    std::fs::remove_file(&path).unwrap();
+}
+
+fn small_writes(c: &mut Criterion) {
+    let mut yadon = Yadon::new(Some(0), None);
+    for i in 0..SMALL_WRITES {
+        yadon.write_all(&(i as u64).to_le_bytes()).unwrap();
+    }
+
+    let path = std::env::temp_dir().join(format!("yadon-bench-small-{}", std::process::id()));
+    let mut target = CountingFile {
+        file: File::create(&path).unwrap(),
+        write_calls: 0,
+    };
+
+    let mut group = c.benchmark_group("small_writes");
+    group.sample_size(10);
+    for coalesce_writes in [false, true] {
+        let options = ApplyOptions {
+            vectored_writes: false,
+            coalesce_writes,
+            ..Default::default()
+        };
+
+        target.write_calls = 0;
+        yadon.apply_with_options(&mut target, &options).unwrap();
+        println!("coalesce_writes: {}, {} writes applied with {} write calls", coalesce_writes, SMALL_WRITES, target.write_calls);
+
+        let name = if coalesce_writes { "coalesced" } else { "naive" };
+        group.bench_function(name, |b| b.iter(|| yadon.apply_with_options(&mut target, &options).unwrap()));
+    }
+    group.finish();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "fs")]
+fn parallel_writes(c: &mut Criterion) {
+    // Every other extent is written, in reverse, so that the writes have to be sorted and left gaps between.
+    let mut yadon = Yadon::new(Some(0), None);
+    for i in (0..PARALLEL_EXTENTS).rev() {
+        yadon.seek(SeekFrom::Start((i * 2) << 20)).unwrap();
+        yadon.fill(i as u8, 1 << 20).unwrap();
+    }
+
+    let path = std::env::temp_dir().join(format!("yadon-bench-parallel-{}", std::process::id()));
+    let file = File::create(&path).unwrap();
+
+    let mut group = c.benchmark_group("parallel_writes");
+    group.sample_size(10);
+    group.bench_function("positional", |b| b.iter(|| yadon.apply_positional(&file, yadon::CheckPolicy::Strict).unwrap()));
+    for threads in [2, 4, 8] {
+        group.bench_function(format!("{} threads", threads), |b| b.iter(|| yadon.apply_parallel(&file, threads).unwrap()));
+    }
+    group.finish();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(not(feature = "fs"))]
+fn parallel_writes(_: &mut Criterion) {}
+
+criterion_group!(benches, contiguous_writes, small_writes, parallel_writes);
+criterion_main!(benches);
@@ -0,0 +1,113 @@
+//! Records a patch and lays it down into a fixed buffer using only `core` and `alloc`, to check that `yadon` builds
+//! without its `std` feature, and with `--no-default-features`, that `YadonFixed` needs no heap at all. Build with
+//! `cargo build --manifest-path ci/no_std/Cargo.toml`, or run the test with
+//! `cargo test --manifest-path ci/no_std/Cargo.toml`.
+#![no_std]
+
+use yadon::io::SeekFrom;
+use yadon::{FixedError, YadonFixed};
+#[cfg(feature = "alloc")]
+use yadon::{ApplyError, Yadon};
+#[cfg(feature = "embedded-io")]
+use yadon::{embedded, CheckPolicy};
+
+/// Patches a header into `image`: a magic number at the start, and a zeroed footer in the last four bytes.
+#[cfg(feature = "alloc")]
+pub fn patch_header(image: &mut [u8]) -> Result<usize, ApplyError> {
+    let mut yadon = Yadon::new(Some(0), Some(image.len() as u64));
+    yadon.write(b"YADN")?;
+    yadon.seek(SeekFrom::End(-4))?;
+    yadon.fill(0, 4)?;
+    yadon.flush()?;
+    yadon.materialize_into_slice(image)
+}
+
+/// Patches the same header as `patch_header()` into a target with `embedded_io`'s shape.
+#[cfg(feature = "embedded-io")]
+pub fn patch_header_embedded<T>(target: &mut T, len: u64) -> Result<usize, ApplyError>
+where T: embedded::Write + embedded::Seek {
+    let mut yadon = Yadon::new(Some(0), Some(len));
+    yadon.write(b"YADN")?;
+    yadon.seek(SeekFrom::End(-4))?;
+    yadon.fill(0, 4)?;
+    yadon.flush()?;
+    yadon.apply_embedded(target, CheckPolicy::Strict)
+}
+
+/// Patches the same header as `patch_header()`, without allocating.
+pub fn patch_header_fixed(image: &mut [u8]) -> Result<usize, FixedError> {
+    let mut yadon = YadonFixed::<4, 4>::new(Some(0), Some(image.len() as u64));
+    yadon.write(b"YADN")?;
+    yadon.seek(SeekFrom::End(-4))?;
+    yadon.fill(0, 4)?;
+    yadon.flush()?;
+    yadon.materialize_into(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Target with `embedded_io`'s shape over a slice.
+    #[cfg(feature = "embedded-io")]
+    struct SliceTarget<'a> {
+        data: &'a mut [u8],
+        position: usize,
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded::ErrorType for SliceTarget<'_> {
+        type Error = embedded::ErrorKind;
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded::Write for SliceTarget<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, embedded::ErrorKind> {
+            let len = buf.len().min(self.data.len().saturating_sub(self.position));
+            if len == 0 && !buf.is_empty() {
+                return Err(embedded::ErrorKind::WriteZero);
+            }
+            self.data[self.position..self.position + len].copy_from_slice(&buf[0..len]);
+            self.position += len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> Result<(), embedded::ErrorKind> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded::Seek for SliceTarget<'_> {
+        fn seek(&mut self, pos: embedded::SeekFrom) -> Result<u64, embedded::ErrorKind> {
+            let (base, offset) = match pos {
+                embedded::SeekFrom::Start(offset) => (offset, 0),
+                embedded::SeekFrom::End(offset) => (self.data.len() as u64, offset),
+                embedded::SeekFrom::Current(offset) => (self.position as u64, offset),
+            };
+            let position = base.checked_add_signed(offset).ok_or(embedded::ErrorKind::InvalidInput)?;
+            self.position = position as usize;
+            Ok(position)
+        }
+    }
+
+    #[test]
+    fn header_patched() {
+        let mut image = [0xffu8; 12];
+        assert_eq!(patch_header_fixed(&mut image).unwrap(), 8);
+        assert_eq!(image, *b"YADN\xff\xff\xff\xff\0\0\0\0");
+        #[cfg(feature = "alloc")]
+        {
+            let mut heap_image = [0xffu8; 12];
+            assert_eq!(patch_header(&mut heap_image).unwrap(), 8);
+            assert_eq!(heap_image, image);
+        }
+        #[cfg(feature = "embedded-io")]
+        {
+            let mut target_image = [0xffu8; 12];
+            let mut target = SliceTarget { data: &mut target_image, position: 0 };
+            assert_eq!(patch_header_embedded(&mut target, 12).unwrap(), 8);
+            assert_eq!(target_image, image);
+        }
+    }
+}
@@ -0,0 +1,2547 @@
+use std::collections::{BTreeMap, BinaryHeap};
+use std::convert::TryFrom;
+use std::io::{Cursor, IoSlice, Read, Seek, SeekFrom, Write};
+use std::fmt::{self, Debug, Display};
+use std::ops::{ControlFlow, Range};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::check::Checker;
+use crate::crc::Crc32;
+use crate::extent::Extents;
+use crate::operation::repeated_len;
+use crate::{ApplyError, CheckPolicy, ChecksumMismatch, Divergence, LengthMode, MaterializeError, OpStore, SessionError, SessionState, WriteOperation, Yadon};
+
+/// Targets which can be resized, such as files.
+pub trait SetLen {
+    /// Truncates or extends the target to `len` bytes. Extended space is filled with zeroes.
+    fn set_len(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+impl SetLen for std::fs::File {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        std::fs::File::set_len(self, len)
+    }
+}
+
+impl SetLen for Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        let len = usize::try_from(len)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "length does not fit in memory"))?;
+        self.get_mut().resize(len, 0);
+        Ok(())
+    }
+}
+
+/// Targets which can make previously written data durable, such as files.
+pub trait SyncTarget {
+    /// Blocks until everything written so far has reached the underlying storage.
+    fn sync(&mut self) -> std::io::Result<()>;
+}
+
+impl SyncTarget for std::fs::File {
+    /// Calls `File::sync_data()`, which also persists the metadata needed to read the data back, such as the length.
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.sync_data()
+    }
+}
+
+/// A target being applied to, along with whichever capabilities it has beyond `Write + Seek`.
+struct ApplyTarget<'a, T> {
+    inner: &'a mut T,
+    set_len: Option<fn(&mut T, u64) -> std::io::Result<()>>,
+    sync: Option<fn(&mut T) -> std::io::Result<()>>,
+    read: Option<ReadFn<T>>,
+    /// Number of seek operations replayed so far.
+    seeks: usize,
+    /// Number of seek operations skipped because they wouldn't have moved the target.
+    seeks_elided: usize,
+    /// Number of bytes written since the target was last flushed.
+    unflushed: u64,
+    /// Where the target is positioned, if known.
+    position: Option<u64>,
+    /// What to tell about progress after each operation, if anything is listening.
+    progress: Option<ProgressObserver<'a>>,
+    /// Paces writes according to `ApplyOptions::rate_limit`.
+    limiter: RateLimiter,
+    /// If resuming an `ApplySession`, the index of the next operation, and where the target must be positioned for it.
+    resume_from: Option<(usize, u64)>,
+    /// Number of operations which have been applied completely, counting from the first.
+    ops_completed: usize,
+    /// Where the target was positioned after the last operation which was applied completely, if known.
+    completed_position: Option<u64>,
+    /// If set, operations which would change the target are skipped, seeking past their data instead.
+    dry_run: bool,
+    /// For `Yadon::apply_transactional()`, the offset and previous contents of each extent which has been written to,
+    /// in order.
+    journal: Option<Vec<(u64, Vec<u8>)>>,
+    /// For `Yadon::apply_verified()`, the index of each operation which has changed the target's contents, with where the
+    /// target was positioned when it started, in order.
+    writes: Option<Vec<(usize, u64)>>,
+    /// For `Yadon::apply_with_hook()`, what to tell about each operation before and after it's applied.
+    hook: Option<&'a mut dyn ApplyHook>,
+    /// For `Yadon::apply_mapped()`, what to do with each operation instead of applying it as it is.
+    map: Option<&'a mut MapFn<'a>>,
+    /// Index of each operation which the hook or the map skipped, in the order they were skipped.
+    skipped: Vec<usize>,
+    /// Index of each operation which the map replaced, in order.
+    replaced: Vec<usize>,
+    /// Each replacement which expects to write a different number of bytes than the operation it replaced.
+    resized: Vec<ResizedOp>,
+}
+
+/// Paces writes to a number of bytes per second. Each write may start once the previous writes have had as long as
+/// the limit allows for them, so writes don't burst after a pause.
+#[derive(Debug)]
+struct RateLimiter {
+    bytes_per_second: Option<u64>,
+    sleep: fn(Duration),
+    started: Instant,
+    /// Total time spent waiting. Time is counted as at least this, so that a `sleep` which doesn't really wait, as in
+    /// tests, still makes progress.
+    slept: Duration,
+    /// When the next write may start, since `started`.
+    next_write: Duration,
+}
+
+impl RateLimiter {
+    fn new(options: &ApplyOptions) -> Self {
+        RateLimiter {
+            bytes_per_second: options.rate_limit.map(|bytes_per_second| bytes_per_second.max(1)),
+            sleep: options.rate_limit_sleep,
+            started: Instant::now(),
+            slept: Duration::ZERO,
+            next_write: Duration::ZERO,
+        }
+    }
+
+    /// The most bytes to write in one call, to keep the pace even.
+    fn chunk_size(&self) -> usize {
+        self.bytes_per_second.map_or(usize::MAX, |bytes_per_second| usize::try_from(bytes_per_second / 10).unwrap_or(usize::MAX).max(1))
+    }
+
+    /// Waits until the next write may start.
+    fn wait(&mut self) {
+        if self.bytes_per_second.is_some() {
+            let now = self.started.elapsed().max(self.slept);
+            if self.next_write > now {
+                (self.sleep)(self.next_write - now);
+                self.slept += self.next_write - now;
+            }
+        }
+    }
+
+    /// Accounts for a write of `bytes_written` bytes which has just been made.
+    fn wrote(&mut self, bytes_written: usize) {
+        if let Some(bytes_per_second) = self.bytes_per_second {
+            let now = self.started.elapsed().max(self.slept);
+            self.next_write = self.next_write.max(now) + Duration::from_secs_f64(bytes_written as f64 / bytes_per_second as f64);
+        }
+    }
+}
+
+/// A callback for `Yadon::apply_with_progress()`, along with the progress it was last told about.
+struct ProgressObserver<'a> {
+    callback: &'a mut dyn FnMut(Progress),
+    progress: Progress,
+}
+
+/// How far `Yadon::apply_with_progress()` has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of operations applied so far.
+    pub ops_completed: usize,
+    /// Number of stored operations.
+    pub total_ops: usize,
+    /// Number of bytes written so far.
+    pub bytes_written: u64,
+    /// Number of bytes the simulation expects to be written in total.
+    pub total_bytes: u64,
+}
+
+/// Interposes on `Yadon::apply_with_hook()`, which tells it about each operation before and after applying it, such as
+/// to take a lock before writing somewhere shared, or to log every operation. It isn't given the target, so it can't
+/// change the target behind the apply's back.
+pub trait ApplyHook {
+    /// Called before the operation at `index` is applied. Returning `ControlFlow::Break` skips the operation, or stops
+    /// applying, as `ApplyOptions::hook_break` says.
+    fn before_op(&mut self, _index: usize, _operation: &WriteOperation) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    /// Called after the operation at `index` has been applied, with its outcome, or the error which is about to stop
+    /// applying. Isn't called for operations which were skipped.
+    fn after_op(&mut self, _index: usize, _operation: &WriteOperation, _result: &Result<OpOutcome, ApplyError>) {}
+}
+
+impl<H> ApplyHook for &mut H where H: ApplyHook + ?Sized {
+    fn before_op(&mut self, index: usize, operation: &WriteOperation) -> ControlFlow<()> {
+        (**self).before_op(index, operation)
+    }
+
+    fn after_op(&mut self, index: usize, operation: &WriteOperation, result: &Result<OpOutcome, ApplyError>) {
+        (**self).after_op(index, operation, result)
+    }
+}
+
+/// What an `ApplyHook` is told about an operation which has been applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpOutcome {
+    /// Number of bytes the operation wrote.
+    pub bytes_written: usize,
+    /// Where the target is positioned after the operation, if known.
+    pub position: Option<u64>,
+}
+
+/// What `Yadon::apply_mapped()` does with an operation.
+#[derive(Debug)]
+pub enum OpAction {
+    /// Apply the operation as it is.
+    Keep,
+    /// Skip the operation, as `HookBreak::Skip` does.
+    Skip,
+    /// Apply this operation instead, checking its results against its own expected values.
+    Replace(WriteOperation),
+}
+
+/// A replacement given by `Yadon::apply_mapped()`'s map which expects to write a different number of bytes than the
+/// operation it replaced, which is likely to leave the operations after it somewhere other than where they were
+/// recorded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResizedOp {
+    /// Index of the operation which was replaced.
+    pub index: usize,
+    /// Number of bytes the operation which was replaced expects to write.
+    pub original: u64,
+    /// Number of bytes its replacement expects to write.
+    pub replacement: u64,
+}
+
+/// The outcome of `Yadon::apply_mapped()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MappedReport {
+    /// What was applied, as `Yadon::apply_report()` reports it, with `ops_skipped` listing the operations which were
+    /// skipped.
+    pub report: ApplyReport,
+    /// Index of each operation which was replaced, in order.
+    pub ops_replaced: Vec<usize>,
+    /// Each replacement which changed the number of bytes written, in order.
+    pub resized: Vec<ResizedOp>,
+}
+
+/// `Read::read()` on a target whose type doesn't require it to be readable.
+type ReadFn<T> = fn(&mut T, &mut [u8]) -> std::io::Result<usize>;
+/// `Write::write()` on a target whose type doesn't require it to be writable.
+type WriteFn<T> = fn(&mut T, &[u8]) -> std::io::Result<usize>;
+/// The map given to `Yadon::apply_mapped()`.
+type MapFn<'a> = dyn FnMut(usize, &WriteOperation) -> OpAction + 'a;
+
+impl<'a, T> ApplyTarget<'a, T> {
+    fn new(inner: &'a mut T) -> Self {
+        ApplyTarget {
+            inner,
+            set_len: None,
+            sync: None,
+            read: None,
+            seeks: 0,
+            seeks_elided: 0,
+            unflushed: 0,
+            position: None,
+            progress: None,
+            limiter: RateLimiter::new(&ApplyOptions::default()),
+            resume_from: None,
+            ops_completed: 0,
+            completed_position: None,
+            dry_run: false,
+            journal: None,
+            writes: None,
+            hook: None,
+            map: None,
+            skipped: Vec::new(),
+            replaced: Vec::new(),
+            resized: Vec::new(),
+        }
+    }
+
+    /// Tells the progress callback, if there is one, that `ops_completed` operations have been applied, the last of
+    /// which wrote `bytes_written` bytes.
+    fn completed(&mut self, ops_completed: usize, bytes_written: usize) {
+        self.ops_completed = ops_completed;
+        self.completed_position = self.position;
+        if let Some(observer) = &mut self.progress {
+            observer.progress.ops_completed = ops_completed;
+            observer.progress.bytes_written += bytes_written as u64;
+            (observer.callback)(observer.progress);
+        }
+    }
+
+    /// Moves the known position forward after writing `bytes_written` bytes.
+    fn advance(&mut self, bytes_written: usize) {
+        self.position = self.position.map(|position| position + bytes_written as u64);
+    }
+
+    /// Whether this target is able to apply `operation`.
+    fn supports(&self, operation: &WriteOperation, options: &ApplyOptions) -> bool {
+        match operation {
+            WriteOperation::SetLen(_) => self.set_len.is_some(),
+            WriteOperation::Sync => self.sync.is_some() || options.sync_fallback == SyncFallback::Flush,
+            WriteOperation::CopyWithin { .. } | WriteOperation::AssertBytes { .. } => self.read.is_some(),
+            // The extent a custom operation writes to isn't known in advance, so it can't be journaled.
+            WriteOperation::Custom(..) => self.journal.is_none() && self.writes.is_none(),
+            _ => true,
+        }
+    }
+}
+
+/// Size of the buffer used to expand `WriteOperation::Fill` and `WriteOperation::Repeat` during apply.
+pub(crate) const FILL_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Largest buffer which `ApplyOptions::coalesce_writes` copies writes into.
+const COALESCE_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Size of the buffer used to move data for `WriteOperation::CopyWithin` during apply.
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Options controlling how `Yadon::apply_with_options()` replays the stored operations.
+#[derive(Debug, Clone)]
+pub struct ApplyOptions {
+    /// Which results of seeks / writes will be compared to the simulated return values. The apply will fail if any of
+    /// them are different.
+    pub check_policy: CheckPolicy,
+    /// If set, recorded `flush()` calls are replayed on the target at the same points they were made. Unset this for
+    /// targets where flushing after individual operations is expensive; the target is still flushed once at the end.
+    pub replay_flushes: bool,
+    /// What to do with durability barriers when the target can't sync.
+    pub sync_fallback: SyncFallback,
+    /// If set, every `WriteOperation::AssertBytes` is checked before anything is written, as
+    /// `Yadon::check_preconditions()` does, so a failed precondition leaves the target untouched.
+    pub check_preconditions_first: bool,
+    /// If set, the operations are first walked through without writing anything, seeking over each write instead, to
+    /// check the positions of seeks and any `WriteOperation::AssertBytes`. If the recording has a fixed length, the
+    /// target is also checked to be long enough for every write. Only if all of that succeeds are the operations
+    /// applied for real, so for targets whose length doesn't change while applying, a divergence leaves them untouched.
+    pub validate_first: bool,
+    /// If set, writes whose data is entirely zero are replaced with a seek over the same number of bytes, which is only
+    /// correct if the target is already zeroed there. Skipped writes count as having written all of their bytes.
+    /// Writes which are only partly zero are still written in full.
+    pub skip_zero_writes: bool,
+    /// If set, runs of consecutive `WriteOperation::Write` are applied with a single `write_vectored()` call where
+    /// possible. Targets which don't support vectored writes, or which write less than the whole run, fall back to
+    /// writing the rest of the run one operation at a time.
+    pub vectored_writes: bool,
+    /// If set, runs of consecutive `WriteOperation::Write` are copied into one buffer and applied with a single
+    /// `write()` call, which suits targets where each call is expensive but vectored writes aren't supported. The
+    /// stored operations are left as they are. If the target writes less than the whole buffer, each operation which
+    /// was written completely is checked as having succeeded, and the operation where the target stopped is finished on
+    /// its own and checked as if it had been applied alone. Takes precedence over `vectored_writes`.
+    pub coalesce_writes: bool,
+    /// What to do when the target writes less than it was given.
+    pub short_write_behavior: ShortWrite,
+    /// If set, no single `write()` or `write_vectored()` call on the target is given more than this many bytes. Larger
+    /// operations are written in several calls, in order, and checked as a whole. This changes the number of `write()`
+    /// calls made on the target, but not the bytes written.
+    pub max_write_size: Option<usize>,
+    /// If set, seeks to where the target is already positioned, such as `SeekFrom::Current(0)`, aren't made on the
+    /// target. Their expected positions are still checked, against the position the target is known to be at. Seeks are
+    /// made as usual whenever that position isn't known, such as after a short `WriteOperation::CopyWithin`.
+    pub elide_seeks: bool,
+    /// The order in which the stored writes are applied.
+    pub order: ApplyOrder,
+    /// If set, the target is returned to the position it was at before applying, even if applying fails. Reports still
+    /// give the position applying ended at.
+    pub restore_position: bool,
+    /// When the target is flushed, besides at the recorded `flush()` calls.
+    pub flush_policy: FlushPolicy,
+    /// If set, applying stops with `ApplyError::Cancelled` once this is set to `true`. It's checked before each
+    /// operation, and between the chunks large operations are written in.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// If set, writes are paced so that no more than this many bytes are written per second, on average. Operations
+    /// are written in chunks of a tenth of this, and runs of writes are applied one operation at a time, so that the
+    /// pace is even. Seeks aren't limited.
+    pub rate_limit: Option<u64>,
+    /// How to wait when writing faster than `rate_limit` allows.
+    pub rate_limit_sleep: fn(Duration),
+    /// What `Yadon::apply_with_hook()` does when its `ApplyHook` breaks before an operation.
+    pub hook_break: HookBreak,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        ApplyOptions {
+            check_policy: CheckPolicy::Strict,
+            replay_flushes: true,
+            sync_fallback: SyncFallback::Error,
+            check_preconditions_first: false,
+            validate_first: false,
+            skip_zero_writes: false,
+            vectored_writes: true,
+            coalesce_writes: false,
+            short_write_behavior: ShortWrite::FailFast,
+            max_write_size: None,
+            elide_seeks: false,
+            order: ApplyOrder::Recorded,
+            restore_position: false,
+            flush_policy: FlushPolicy::AtEnd,
+            cancel: None,
+            rate_limit: None,
+            rate_limit_sleep: std::thread::sleep,
+            hook_break: HookBreak::Skip,
+        }
+    }
+}
+
+impl ApplyOptions {
+    /// Whether `cancel` has been set.
+    fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+}
+
+/// The outcome of `Yadon::apply_collect()`.
+#[derive(Debug)]
+pub struct ApplySummary {
+    /// Total number of bytes written to the target.
+    pub bytes_written: usize,
+    /// Every divergence between the simulated and actual results, in the order they happened.
+    pub divergences: Vec<Divergence>,
+}
+
+impl ApplySummary {
+    /// Number of operations with at least one divergence.
+    pub fn diverged_operations(&self) -> usize {
+        let mut indices: Vec<_> = self.divergences.iter().map(|divergence| divergence.index).collect();
+        indices.dedup();
+        indices.len()
+    }
+
+    /// Whether the target ended up as the simulation expected, with no divergences.
+    pub fn is_clean(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+/// The outcome of `Yadon::apply_report()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyReport {
+    /// Number of operations applied.
+    pub ops_applied: usize,
+    /// Total number of bytes written to the target, which may be less than expected if checking is disabled.
+    pub bytes_written: usize,
+    /// Number of seeks made on the target for the stored operations, including the initial seek to `start`. Seeks
+    /// which operations such as `WriteOperation::CopyWithin` make internally aren't counted.
+    pub seeks_issued: usize,
+    /// Number of seeks which `ApplyOptions::elide_seeks` skipped because they wouldn't have moved the target.
+    pub seeks_elided: usize,
+    /// Position of the target after applying.
+    pub final_position: u64,
+    /// How long applying took.
+    pub elapsed: Duration,
+    /// Index of each operation which an `ApplyHook` skipped, in order.
+    pub ops_skipped: Vec<usize>,
+}
+
+impl Display for ApplyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "applied {} operations ({} bytes written, {} seeks", self.ops_applied, self.bytes_written, self.seeks_issued)?;
+        if self.seeks_elided > 0 {
+            write!(f, ", {} elided", self.seeks_elided)?;
+        }
+        if !self.ops_skipped.is_empty() {
+            write!(f, ", {} operations skipped", self.ops_skipped.len())?;
+        }
+        write!(f, "), ending at position {}, in {:?}", self.final_position, self.elapsed)
+    }
+}
+
+/// The outcome of `Yadon::apply_range()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RangeReport {
+    /// Number of bytes written to the target, from the parts of writes within the range.
+    pub bytes_applied: u64,
+    /// Number of bytes the stored writes would have written outside the range.
+    pub bytes_skipped: u64,
+}
+
+/// The outcome of `Yadon::apply_diff_only()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiffReport {
+    /// Number of bytes written to the target, which differed from what it contained.
+    pub bytes_written: u64,
+    /// Number of bytes which the stored operations would have written, but which the target already contained.
+    pub bytes_unchanged: u64,
+}
+
+/// The outcome of `Yadon::apply_blockwise()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BlockReport {
+    /// Number of blocks which were read and written back.
+    pub blocks_written: u64,
+    /// Number of bytes written back, including those of the blocks which the stored writes left as they were.
+    pub bytes_written: u64,
+}
+
+/// Target wrapper for `Yadon::apply_diff_only()`, which reads what each write would overwrite, and only writes the
+/// bytes which differ.
+struct SkipUnchanged<'a, T> {
+    inner: &'a mut T,
+    /// Where `inner` is positioned, if known.
+    position: Option<u64>,
+    /// Buffer for what the target contains.
+    existing: Vec<u8>,
+    report: DiffReport,
+}
+
+impl<'a, T> Write for SkipUnchanged<'a, T> where T: Read + Write + Seek {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let position = match self.position.take() {
+            Some(position) => position,
+            None => retry_interrupted(|| self.inner.stream_position())?,
+        };
+        let buf = &buf[0..buf.len().min(COPY_CHUNK_SIZE)];
+        self.existing.resize(buf.len(), 0);
+        let mut filled = 0;
+        while filled < buf.len() {
+            match retry_interrupted(|| self.inner.read(&mut self.existing[filled..]))? {
+                0 => break,
+                bytes_read => filled += bytes_read,
+            }
+        }
+        // Bytes past the end of the target always differ.
+        let existing = &self.existing;
+        let differs = |i: usize| i >= filled || buf[i] != existing[i];
+        let mut inner_position = position + filled as u64;
+        let mut done = 0;
+        while done < buf.len() {
+            let unchanged = (done..buf.len()).take_while(|i| !differs(*i)).count();
+            self.report.bytes_unchanged += unchanged as u64;
+            done += unchanged;
+            if done == buf.len() {
+                break;
+            }
+            let run = (done..buf.len()).take_while(|i| differs(*i)).count();
+            if inner_position != position + done as u64 {
+                inner_position = retry_seek(self.inner, SeekFrom::Start(position + done as u64))?;
+            }
+            let bytes_written = self.inner.write(&buf[done..done + run])?;
+            self.report.bytes_written += bytes_written as u64;
+            inner_position += bytes_written as u64;
+            done += bytes_written;
+            if bytes_written < run {
+                self.position = Some(inner_position);
+                return Ok(done);
+            }
+        }
+        if inner_position != position + buf.len() as u64 {
+            inner_position = retry_seek(self.inner, SeekFrom::Start(position + buf.len() as u64))?;
+        }
+        self.position = Some(inner_position);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a, T> Read for SkipUnchanged<'a, T> where T: Read {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let position = self.position.take();
+        let bytes_read = self.inner.read(buf)?;
+        self.position = position.map(|position| position + bytes_read as u64);
+        Ok(bytes_read)
+    }
+}
+
+impl<'a, T> Seek for SkipUnchanged<'a, T> where T: Seek {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = None;
+        let position = self.inner.seek(pos)?;
+        self.position = Some(position);
+        Ok(position)
+    }
+}
+
+/// A byte which `Yadon::nor_flash_violations()` found a write would program over the target's contents, but which
+/// would set a bit that is clear, so the target needs an erase first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitViolation {
+    /// Offset of the byte.
+    pub offset: u64,
+    /// The byte which the target contains, or which an earlier write would have left there.
+    pub old: u8,
+    /// The byte which would be written.
+    pub new: u8,
+}
+
+/// Target wrapper for `Yadon::apply_nor_flash()` and `Yadon::nor_flash_violations()`, which reads what each write would
+/// overwrite, and checks that the write only clears bits, as programming NOR flash does. Bytes past the end of the
+/// target are taken to be erased, so any byte may be written there.
+struct ProgramOnly<'a, T> {
+    inner: &'a mut T,
+    /// Where `inner` is positioned, if known.
+    position: Option<u64>,
+    /// Buffer for what the target contains.
+    existing: Vec<u8>,
+    /// Writes to `inner`, if the writes are to be made rather than only checked.
+    write: Option<WriteFn<T>>,
+    flush: Option<fn(&mut T) -> std::io::Result<()>>,
+    /// What the writes so far would have written, if they're only checked. Reads see these bytes in place of what the
+    /// target contains.
+    unwritten: BTreeMap<u64, u8>,
+    violations: Vec<BitViolation>,
+}
+
+impl<'a, T> ProgramOnly<'a, T> where T: Read + Seek {
+    fn new(inner: &'a mut T) -> Self {
+        ProgramOnly {
+            inner,
+            position: None,
+            existing: Vec::new(),
+            write: None,
+            flush: None,
+            unwritten: BTreeMap::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    /// Fills `existing` with the `len` bytes a write at `position` would program over, and returns how many of them
+    /// were read from the target, leaving it positioned after those.
+    fn read_existing(&mut self, position: u64, len: usize) -> std::io::Result<usize> {
+        self.existing.clear();
+        self.existing.resize(len, 0xff);
+        let filled = read_up_to(self.inner, &mut self.existing)?;
+        self.existing[filled..].fill(0xff);
+        for (&offset, &byte) in self.unwritten.range(position..position + len as u64) {
+            self.existing[(offset - position) as usize] = byte;
+        }
+        Ok(filled)
+    }
+}
+
+impl<'a, T> Write for ProgramOnly<'a, T> where T: Read + Seek {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let position = match self.position.take() {
+            Some(position) => position,
+            None => retry_interrupted(|| self.inner.stream_position())?,
+        };
+        let buf = &buf[0..buf.len().min(COPY_CHUNK_SIZE)];
+        let filled = self.read_existing(position, buf.len())?;
+        let existing = &self.existing;
+        let violations = (0..buf.len())
+            .filter(|&i| existing[i] & buf[i] != buf[i])
+            .map(|i| BitViolation { offset: position + i as u64, old: existing[i], new: buf[i] });
+        let violated = self.violations.len();
+        self.violations.extend(violations);
+
+        let end = position + buf.len() as u64;
+        match self.write {
+            None => {
+                self.unwritten.extend((position..end).zip(buf.iter().copied()));
+                self.position = Some(retry_seek(self.inner, SeekFrom::Start(end))?);
+                Ok(buf.len())
+            },
+            Some(_) if self.violations.len() > violated => {
+                Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "write would set bits"))
+            },
+            Some(write) => {
+                if filled > 0 {
+                    retry_seek(self.inner, SeekFrom::Start(position))?;
+                }
+                let bytes_written = write(self.inner, buf)?;
+                self.position = Some(position + bytes_written as u64);
+                Ok(bytes_written)
+            },
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.flush {
+            Some(flush) => flush(self.inner),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, T> Read for ProgramOnly<'a, T> where T: Read + Seek {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let position = match (self.position.take(), self.write) {
+            (None, None) => Some(retry_interrupted(|| self.inner.stream_position())?),
+            (position, _) => position,
+        };
+        let bytes_read = self.inner.read(buf)?;
+        self.position = position.map(|position| position + bytes_read as u64);
+        // Reads see what the writes so far would have written, where the target has bytes to read.
+        if let Some(position) = position {
+            for (&offset, &byte) in self.unwritten.range(position..position + bytes_read as u64) {
+                buf[(offset - position) as usize] = byte;
+            }
+        }
+        Ok(bytes_read)
+    }
+}
+
+impl<'a, T> Seek for ProgramOnly<'a, T> where T: Seek {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = None;
+        let position = self.inner.seek(pos)?;
+        self.position = Some(position);
+        Ok(position)
+    }
+}
+
+/// The outcome of `Yadon::detect_applied()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetectReport {
+    /// Number of operations, from the first, which the target already reflects.
+    pub ops_applied: usize,
+    /// Index of the first operation which the target doesn't reflect, or `None` if it reflects them all.
+    pub first_mismatch: Option<usize>,
+    /// Where the target would be positioned to apply the first operation it doesn't reflect, or after every operation.
+    pub position: u64,
+}
+
+impl DetectReport {
+    /// The state to resume an `ApplySession` from, so that it applies the operations which the target doesn't reflect.
+    pub fn session_state(&self) -> SessionState {
+        SessionState {
+            next_op: self.ops_applied,
+            position: Some(self.position),
+        }
+    }
+}
+
+/// Totals from replaying the stored operations.
+struct Replayed {
+    operations: usize,
+    bytes_written: usize,
+    seeks: usize,
+    seeks_elided: usize,
+    /// Where the target was left, if this was found before restoring its position.
+    final_position: Option<u64>,
+}
+
+/// What `Yadon::apply_with_hook()` does when `ApplyHook::before_op()` returns `ControlFlow::Break`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HookBreak {
+    /// Skip that operation, noting it in `ApplyReport::ops_skipped`, and carry on with the next. The target's contents
+    /// are left as they were, but it's still moved to where the operation would have left it, so that the operations
+    /// after land where they were recorded to; so a skipped seek is still made on the target.
+    #[default]
+    Skip,
+    /// Stop applying, failing with `ApplyError::Cancelled` as if `ApplyOptions::cancel` had been set.
+    Abort,
+}
+
+/// When `Yadon::apply_with_options()` flushes the target, besides replaying recorded flushes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Never, which suits targets where flushing has side effects, such as finishing a compressed frame.
+    Never,
+    /// Once, after every operation has been applied.
+    #[default]
+    AtEnd,
+    /// After every operation, and at the end. Runs of writes are applied one operation at a time.
+    EveryOp,
+    /// Whenever at least this many bytes have been written since the target was last flushed, and at the end.
+    EveryNBytes(u64),
+}
+
+/// The order in which `Yadon::apply_with_options()` applies the stored writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApplyOrder {
+    /// The order they were recorded in.
+    #[default]
+    Recorded,
+    /// Sorted by the offset they were recorded at, each preceded by a `SeekFrom::Start` seek to that offset, which
+    /// suits targets where seeking backwards is slow. This is only possible if no two writes overlap, and the log
+    /// contains only writes, seeks and flushes; anything else fails before the target is touched. Recorded seeks are
+    /// only used to find the offsets of the writes, and recorded flushes are dropped. The stored operations are left
+    /// as they are.
+    Offset,
+}
+
+/// What to do when the target writes only part of a buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShortWrite {
+    /// Move on to the next operation, leaving the divergence to the check policy.
+    #[default]
+    FailFast,
+    /// Write the rest of the buffer, like `Write::write_all()`, until all of it is written or the target writes
+    /// nothing. Operations are then only short if the target stopped accepting data.
+    Retry,
+}
+
+/// How to apply `WriteOperation::Sync` to a target which doesn't implement `SyncTarget`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncFallback {
+    /// Fail with `ApplyError::UnsupportedOperation` before applying anything.
+    Error,
+    /// Flush the target instead, which gives no durability guarantee.
+    Flush,
+}
+
+impl Yadon {
+    /// Applies the stored operations on a target writer. Operations are not consumed, and may be replayed again.
+    /// If a `start` position was specified, this will seek to that position before applying.
+    /// If `check_return_values` is set, the result of each seek / write will be compared to the
+    /// simulated return value, and the apply will fail if it is different.
+    /// Writes and seeks which fail with `ErrorKind::Interrupted` are retried.
+    pub fn apply<T>(&self, target: &mut T, check_return_values: bool) -> Result<usize, ApplyError> where T: Write + Seek {
+        let check_policy = if check_return_values { CheckPolicy::Strict } else { CheckPolicy::None };
+        self.apply_with_policy(target, check_policy)
+    }
+
+    /// Applies the stored operations on a target writer, as `apply_with_policy()` does, consuming the `Yadon` so that
+    /// it can't be applied again, whether or not this succeeds.
+    pub fn apply_once<T>(self, target: &mut T, check_policy: CheckPolicy) -> Result<usize, ApplyError> where T: Write + Seek {
+        self.apply_with_policy(target, check_policy)
+    }
+
+    /// Applies the stored operations on a target writer, as `apply()` does, checking the results of seeks and writes
+    /// according to `check_policy`.
+    pub fn apply_with_policy<T>(&self, target: &mut T, check_policy: CheckPolicy) -> Result<usize, ApplyError> where T: Write + Seek {
+        self.apply_with_options(target, &ApplyOptions {
+            check_policy,
+            ..Default::default()
+        })
+    }
+
+    /// Applies the stored operations on a byte slice, writing into it directly, with the same outcome as
+    /// `apply_with_policy()` on a `Cursor` over it. The slice never grows, so writes past its end are cut short, and
+    /// diverge if `check_policy` checks writes. As the slice can't be read or resized, the operations which need that,
+    /// base checksums, a generation stamp and durability barriers fail with `ApplyError::UnsupportedOperation` before
+    /// anything is written, as they would on a `Cursor` with `ApplyOptions::default()`.
+    pub fn apply_to_slice(&self, buf: &mut [u8], check_policy: CheckPolicy) -> Result<usize, ApplyError> {
+        self.replay_into_slice(buf, check_policy, true)
+    }
+
+    /// Applies the stored operations on a byte array, as `apply_to_slice()` does.
+    pub fn apply_to_array<const N: usize>(&self, buf: &mut [u8; N], check_policy: CheckPolicy) -> Result<usize, ApplyError> {
+        self.apply_to_slice(&mut buf[..], check_policy)
+    }
+
+    /// Lays the stored operations down into a fresh buffer, and returns it. The buffer is `length` bytes long, or if
+    /// that isn't set, as long as `base` or the furthest write, whichever is longer. It starts as a copy of `base`, and
+    /// anything else is filled with `gap_fill()`. The operations are applied with `CheckPolicy::Strict`. The buffer can
+    /// be read and resized, and durability barriers only flush it, so any operation can be laid down.
+    pub fn materialize(&self, base: Option<&[u8]>) -> Result<Vec<u8>, MaterializeError> {
+        let mut buf = Vec::new();
+        self.materialize_onto(&mut buf, base)?;
+        Ok(buf)
+    }
+
+    /// Lays the stored operations down into `buf`, as `materialize()` does without a base, replacing its contents but
+    /// reusing its allocation.
+    pub fn materialize_into(&self, buf: &mut Vec<u8>) -> Result<(), MaterializeError> {
+        self.materialize_onto(buf, None)
+    }
+
+    fn materialize_onto(&self, buf: &mut Vec<u8>, base: Option<&[u8]>) -> Result<(), MaterializeError> {
+        let base = base.unwrap_or_default();
+        let len = self.length.unwrap_or_else(|| self.written_end.max(base.len() as u64));
+        let len_usize = usize::try_from(len).map_err(|_| MaterializeError::TooLarge(len))?;
+        buf.clear();
+        buf.try_reserve_exact(len_usize).map_err(|_| MaterializeError::TooLarge(len))?;
+        let copied = base.len().min(len_usize);
+        buf.extend_from_slice(&base[0..copied]);
+        buf.resize(len_usize, self.gap_fill);
+
+        let mut cursor = Cursor::new(std::mem::take(buf));
+        let mut target = ApplyTarget::new(&mut cursor);
+        target.set_len = Some(|target, len| target.set_len(len));
+        target.read = Some(|target, buf| target.read(buf));
+        let options = ApplyOptions {
+            sync_fallback: SyncFallback::Flush,
+            ..Default::default()
+        };
+        let replayed = self.replay(&mut target, &options, &mut Checker::new(self, options.check_policy));
+        *buf = cursor.into_inner();
+        replayed?;
+        Ok(())
+    }
+
+    /// Applies the stored operations on a target writer, as `apply()` does, with finer control over the replay.
+    pub fn apply_with_options<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError> where T: Write + Seek {
+        self.replay(&mut ApplyTarget::new(target), options, &mut Checker::new(self, options.check_policy))
+            .map(|replayed| replayed.bytes_written)
+    }
+
+    /// Applies the stored operations on a target writer, as `apply()` does, but continues after seek positions or
+    /// numbers of bytes written diverge, collecting every divergence into the summary. Only fails on errors which
+    /// prevent replaying any further, such as IO errors.
+    pub fn apply_collect<T>(&self, target: &mut T) -> Result<ApplySummary, ApplyError> where T: Write + Seek {
+        let options = ApplyOptions::default();
+        let mut checker = Checker::new(self, options.check_policy);
+        checker.divergences = Some(vec![]);
+        let replayed = self.replay(&mut ApplyTarget::new(target), &options, &mut checker)?;
+        Ok(ApplySummary {
+            bytes_written: replayed.bytes_written,
+            divergences: checker.divergences.unwrap_or_default(),
+        })
+    }
+
+    /// Applies the stored operations on a target writer, as `apply_with_options()` does, returning a report of what was
+    /// done rather than just the number of bytes written.
+    pub fn apply_report<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<ApplyReport, ApplyError> where T: Write + Seek {
+        self.report(&mut ApplyTarget::new(target), options)
+    }
+
+    /// Applies the operations in `store`, rather than those in `operations`, as `apply_with_options()` does, reading
+    /// them back one at a time so that they needn't all be in memory. The recording's `start`, base checksums, apply
+    /// limit and generation stamp are used as they are by `apply_with_options()`, but writes aren't batched, and
+    /// `options.order` and `options.validate_first` are ignored. The store is read twice: first to check that the
+    /// target can apply every operation, so that nothing is applied if it can't, then to apply them. Use
+    /// `Yadon::store()` to apply the store this `Yadon` records into, or pass any other.
+    pub fn apply_store<S, T>(&self, store: &S, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError>
+    where S: OpStore + ?Sized, T: Write + Seek {
+        self.check_apply_limit()?;
+        let mut target = ApplyTarget::new(target);
+        let mut checker = Checker::new(self, options.check_policy);
+        self.check_base(&mut target)?;
+        store.for_each_operation(&mut |_, operation| match target.supports(operation, options) {
+            true => Ok(()),
+            false => Err(ApplyError::UnsupportedOperation(operation.name())),
+        })?;
+        target.limiter = RateLimiter::new(options);
+        if let Some(start) = self.start {
+            let seek_pos = retry_seek(target.inner, SeekFrom::Start(start))?;
+            target.seeks += 1;
+            target.position = Some(seek_pos);
+            checker.position(start, seek_pos)?;
+        }
+        let mut total_bytes_written = 0;
+        store.for_each_operation(&mut |index, operation| {
+            target.check_cancelled(index, total_bytes_written, options)?;
+            let context = |error: ApplyError| error.with_context(self.location_of(index), self.label_of(index).cloned());
+            checker.begin(index, operation, target.position);
+            let bytes_written = target.apply_operation(operation, options, &mut checker)
+                .map_err(|error| target.cancelled_by(error, index, total_bytes_written, options))
+                .map_err(context)?;
+            target.flush_after(index, bytes_written, options).map_err(context)?;
+            target.completed(index + 1, bytes_written);
+            total_bytes_written += bytes_written;
+            Ok(())
+        })?;
+        self.write_stamp(&mut target, options)?;
+        target.finish(options)?;
+        self.count_apply();
+        Ok(total_bytes_written)
+    }
+
+    /// Like `apply_report()`, for a target which can apply every kind of operation, such as a `File`.
+    #[cfg(feature = "fs")]
+    pub(crate) fn apply_report_all<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<ApplyReport, ApplyError>
+    where T: Read + Write + Seek + SetLen + SyncTarget {
+        let mut target = ApplyTarget::new(target);
+        target.set_len = Some(|target, len| target.set_len(len));
+        target.sync = Some(|target| target.sync());
+        target.read = Some(|target, buf| target.read(buf));
+        self.report(&mut target, options)
+    }
+
+    /// Applies the stored operations on each of `targets` in turn, as `apply_report()` does, returning each target's
+    /// index with its outcome. A target failing doesn't stop the others from being applied to, unless
+    /// `stop_at_first_failure` is set, in which case the failed target's outcome is the last one returned. Each target
+    /// counts towards `applied_count()` separately, so `max_applies()` can cut the batch short.
+    pub fn apply_all<'t, T>(
+        &self,
+        targets: impl IntoIterator<Item = &'t mut T>,
+        options: &ApplyOptions,
+        stop_at_first_failure: bool,
+    ) -> Vec<(usize, Result<ApplyReport, ApplyError>)> where T: Write + Seek + 't {
+        let mut results = Vec::new();
+        for (index, target) in targets.into_iter().enumerate() {
+            let result = self.apply_report(target, options);
+            let failed = result.is_err();
+            results.push((index, result));
+            if failed && stop_at_first_failure {
+                break;
+            }
+        }
+        results
+    }
+
+    fn report<T>(&self, target: &mut ApplyTarget<T>, options: &ApplyOptions) -> Result<ApplyReport, ApplyError> where T: Write + Seek {
+        let started = Instant::now();
+        let replayed = self.replay(target, options, &mut Checker::new(self, options.check_policy))?;
+        let final_position = match replayed.final_position {
+            Some(final_position) => final_position,
+            None => retry_interrupted(|| target.inner.stream_position())?,
+        };
+        Ok(ApplyReport {
+            ops_applied: replayed.operations,
+            bytes_written: replayed.bytes_written,
+            seeks_issued: replayed.seeks,
+            seeks_elided: replayed.seeks_elided,
+            final_position,
+            elapsed: started.elapsed(),
+            ops_skipped: core::mem::take(&mut target.skipped),
+        })
+    }
+
+    /// Applies only the parts of the stored writes which fall within `range`, seeking to each with `SeekFrom::Start`.
+    /// Writes which straddle an end of the range are cut down to fit, and the rest are skipped without touching the
+    /// target. As with `ApplyOrder::Offset`, the log may only contain writes, seeks and flushes; recorded seeks are only
+    /// used to find the offsets of the writes, and recorded flushes are dropped.
+    pub fn apply_range<T>(&self, target: &mut T, range: Range<u64>, options: &ApplyOptions) -> Result<RangeReport, ApplyError>
+    where T: Write + Seek {
+        let writes = self.write_extents()?;
+        self.check_can_apply()?;
+        let mut target = ApplyTarget::new(target);
+        self.check_base(&mut target)?;
+        target.limiter = RateLimiter::new(options);
+        let checker = &mut Checker::new(self, options.check_policy);
+        let mut report = RangeReport::default();
+        for (index, extent) in writes {
+            let start = extent.start.max(range.start);
+            let end = extent.end.min(range.end);
+            if start >= end {
+                report.bytes_skipped += extent.end - extent.start;
+                continue;
+            }
+            report.bytes_skipped += (extent.end - extent.start) - (end - start);
+
+            let operation = &self.operations[index];
+            let context = |error: ApplyError| error.with_context(self.location_of(index), self.label_of(index).cloned());
+            checker.begin(index, operation, target.position);
+            target.apply_operation(&WriteOperation::Seek(SeekFrom::Start(start), start), options, checker).map_err(context)?;
+            let skip = start - extent.start;
+            let len = end - start;
+            let bytes_written = match operation {
+                WriteOperation::Write(data, _) => {
+                    write_data(target.inner, &data[skip as usize..(skip + len) as usize], options, &mut target.limiter)? as u64
+                },
+                WriteOperation::Fill { byte, .. } => write_pattern(target.inner, &[*byte], len, options, &mut target.limiter)?,
+                WriteOperation::Repeat { pattern, .. } => {
+                    // Start the pattern partway through, where the range cuts into it.
+                    let phase = (skip % pattern.len() as u64) as usize;
+                    let pattern = [&pattern[phase..], &pattern[..phase]].concat();
+                    write_pattern(target.inner, &pattern, len, options, &mut target.limiter)?
+                },
+                _ => unreachable!("only writes have extents"),
+            };
+            target.advance(bytes_written as usize);
+            checker.written(len as usize, bytes_written as usize).map_err(context)?;
+            report.bytes_applied += bytes_written;
+        }
+        target.finish(options)?;
+        self.count_apply();
+        Ok(report)
+    }
+
+    /// Applies the stored operations on a target writer, as `apply_with_options()` does, calling `progress` after every
+    /// operation with how far applying has got.
+    pub fn apply_with_progress<T, F>(&self, target: &mut T, options: &ApplyOptions, mut progress: F) -> Result<usize, ApplyError>
+    where T: Write + Seek, F: FnMut(Progress) {
+        let mut target = ApplyTarget::new(target);
+        target.progress = Some(ProgressObserver { callback: &mut progress, progress: self.no_progress() });
+        self.replay(&mut target, options, &mut Checker::new(self, options.check_policy))
+            .map(|replayed| replayed.bytes_written)
+    }
+
+    /// Applies the stored operations on a target writer, as `apply_report()` does, calling `hook` before and after each
+    /// operation. Runs of writes are applied one operation at a time, so that the hook is told about each.
+    /// # Example
+    /// ```
+    /// use yadon::{ApplyError, ApplyHook, ApplyOptions, OpOutcome, WriteOperation, Yadon};
+    /// use std::io::{Cursor, Write};
+    /// use std::ops::ControlFlow;
+    /// /// Leaves the header, which is written first, as it is, and logs what's written after it.
+    /// #[derive(Default)]
+    /// struct KeepHeader {
+    ///     log: Vec<String>,
+    /// }
+    /// impl ApplyHook for KeepHeader {
+    ///     fn before_op(&mut self, index: usize, _operation: &WriteOperation) -> ControlFlow<()> {
+    ///         if index == 0 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+    ///     }
+    ///     fn after_op(&mut self, index: usize, _operation: &WriteOperation, result: &Result<OpOutcome, ApplyError>) {
+    ///         if let Ok(outcome) = result {
+    ///             self.log.push(format!("operation {} wrote {} bytes", index, outcome.bytes_written));
+    ///         }
+    ///     }
+    /// }
+    /// let mut yadon = Yadon::new(Some(0), Some(8));
+    /// yadon.write_all(b"head").unwrap();
+    /// yadon.write_all(b"body").unwrap();
+    /// let mut target = Cursor::new(vec![0u8; 8]);
+    /// let mut hook = KeepHeader::default();
+    /// let report = yadon.apply_with_hook(&mut target, &ApplyOptions::default(), &mut hook).unwrap();
+    /// assert_eq!(report.ops_skipped, vec![0]);
+    /// assert_eq!(hook.log, vec!["operation 1 wrote 4 bytes"]);
+    /// assert_eq!(target.get_ref(), b"\0\0\0\0body");
+    /// ```
+    pub fn apply_with_hook<T, H>(&self, target: &mut T, options: &ApplyOptions, mut hook: H) -> Result<ApplyReport, ApplyError>
+    where T: Write + Seek, H: ApplyHook {
+        let mut target = ApplyTarget::new(target);
+        target.hook = Some(&mut hook);
+        self.report(&mut target, options)
+    }
+
+    /// Applies the stored operations on a target writer, as `apply_report()` does, calling `map` with each operation and
+    /// its index first to decide what to do with it: apply it as it is, skip it, or apply a replacement for it, whose
+    /// results are checked against its own expected values. The stored operations are left as they are. Keeping the
+    /// positions in the replacements consistent with the operations around them is up to `map`, but the report lists
+    /// each replacement which expects to write a different number of bytes than the operation it replaced, so that
+    /// mistakes show up. Runs of writes are applied one operation at a time, so that `map` is called with each. Fails
+    /// with `ApplyError::UnsupportedOperation` if the target can't apply a replacement, having applied the operations
+    /// before it.
+    pub fn apply_mapped<T, F>(&self, target: &mut T, options: &ApplyOptions, mut map: F) -> Result<MappedReport, ApplyError>
+    where T: Write + Seek, F: FnMut(usize, &WriteOperation) -> OpAction {
+        let mut target = ApplyTarget::new(target);
+        target.map = Some(&mut map);
+        let report = self.report(&mut target, options)?;
+        Ok(MappedReport { report, ops_replaced: target.replaced, resized: target.resized })
+    }
+
+    /// Like `apply_report()`, calling `progress` as `apply_with_progress()` does.
+    pub(crate) fn apply_report_with_progress<T>(&self, target: &mut T, options: &ApplyOptions, progress: &mut dyn FnMut(Progress))
+        -> Result<ApplyReport, ApplyError> where T: Write + Seek {
+        let mut target = ApplyTarget::new(target);
+        target.progress = Some(ProgressObserver { callback: progress, progress: self.no_progress() });
+        self.report(&mut target, options)
+    }
+
+    /// The progress before anything has been applied.
+    pub(crate) fn no_progress(&self) -> Progress {
+        let mut total_bytes = 0;
+        match &self.store {
+            // Applying fails anyway if the store can't be read, so neither will the total.
+            Some(store) => drop(store.for_each_operation(&mut |_, operation| {
+                total_bytes += operation.expected_bytes_written();
+                Ok(())
+            })),
+            None => total_bytes = self.operations.iter().map(WriteOperation::expected_bytes_written).sum(),
+        }
+        Progress { ops_completed: 0, total_ops: self.operation_count(), bytes_written: 0, total_bytes }
+    }
+
+    /// Applies the stored operations on a target writer which can also be resized, replaying `WriteOperation::SetLen`
+    /// in order with the other operations.
+    pub fn apply_with_setlen<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError> where T: Write + Seek + SetLen {
+        let mut target = ApplyTarget::new(target);
+        target.set_len = Some(|target, len| target.set_len(len));
+        self.replay(&mut target, options, &mut Checker::new(self, options.check_policy))
+            .map(|replayed| replayed.bytes_written)
+    }
+
+    /// Applies the stored operations on a target writer which can make writes durable, syncing it at each
+    /// `WriteOperation::Sync` barrier.
+    pub fn apply_durable<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError> where T: Write + Seek + SyncTarget {
+        let mut target = ApplyTarget::new(target);
+        target.sync = Some(|target| target.sync());
+        self.replay(&mut target, options, &mut Checker::new(self, options.check_policy))
+            .map(|replayed| replayed.bytes_written)
+    }
+
+    /// Applies the stored operations on a target which can also be read from, which is required for operations that
+    /// depend on the existing contents of the target, such as `WriteOperation::CopyWithin`.
+    pub fn apply_readable<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError> where T: Read + Write + Seek {
+        let mut target = ApplyTarget::new(target);
+        target.read = Some(|target, buf| target.read(buf));
+        self.replay(&mut target, options, &mut Checker::new(self, options.check_policy))
+            .map(|replayed| replayed.bytes_written)
+    }
+
+    /// Applies the stored operations on a target which can also be read from, as `apply_readable()` does, then reads back
+    /// everything that was written, checking that it matches the CRC-32 of the recorded data. This catches targets which
+    /// claim to have written data they didn't, and torn writes, which checking the results of writes can't. Parts of
+    /// writes which are later overwritten or copied over are only checked as they end up. Writes are
+    /// never batched, and stored `WriteOperation::Custom` operations are unsupported, as what they write isn't known.
+    pub fn apply_verified<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError>
+    where T: Read + Write + Seek {
+        let options = ApplyOptions {
+            vectored_writes: false,
+            coalesce_writes: false,
+            ..options.clone()
+        };
+        let mut target = ApplyTarget::new(target);
+        target.read = Some(|target, buf| target.read(buf));
+        target.writes = Some(Vec::new());
+        let replayed = self.replay(&mut target, &options, &mut Checker::new(self, options.check_policy))?;
+        let position = retry_interrupted(|| target.inner.stream_position())?;
+
+        // Later writes take precedence, so go backwards, only checking what hasn't been covered yet.
+        let mut covered = Extents::default();
+        if let (Some(stamp), Some((offset, _))) = (self.stamp_operation(), self.generation_stamp) {
+            verify_extent(target.inner, &stamp, 0, offset..offset + 8)?;
+            covered.insert(offset..offset + 8);
+        }
+        for (index, offset) in target.writes.take().unwrap_or_default().into_iter().rev() {
+            let operation = &self.operations[index];
+            let extent = match operation {
+                WriteOperation::CopyWithin { dst, len, .. } => *dst..dst + len,
+                _ => {
+                    let extent = offset..offset + operation.expected_bytes_written();
+                    for gap in covered.uncovered(extent.clone()) {
+                        verify_extent(target.inner, operation, gap.start - offset, gap)?;
+                    }
+                    extent
+                },
+            };
+            if !extent.is_empty() {
+                covered.insert(extent);
+            }
+        }
+        retry_seek(target.inner, SeekFrom::Start(position))?;
+        Ok(replayed.bytes_written)
+    }
+
+    /// Applies the stored operations on a target which can also be read from, as `apply_readable()` does, but only
+    /// writes the bytes which differ from what the target already contains. Each write first reads what it would
+    /// overwrite, and is split into smaller writes of the ranges which differ, seeking over the rest. This suits media
+    /// where writes are costly, at the price of reading everything that's written. Writes are never batched.
+    pub fn apply_diff_only<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<DiffReport, ApplyError>
+    where T: Read + Write + Seek {
+        let options = ApplyOptions {
+            vectored_writes: false,
+            coalesce_writes: false,
+            ..options.clone()
+        };
+        let mut target = SkipUnchanged {
+            inner: target,
+            position: None,
+            existing: Vec::new(),
+            report: DiffReport::default(),
+        };
+        self.apply_readable(&mut target, &options)?;
+        Ok(target.report)
+    }
+
+    /// Applies the stored operations on NOR flash, or an image of it, as `apply_readable()` does, but first reads what
+    /// each write would overwrite and checks that it only clears bits. A write which would set a bit fails with
+    /// `ApplyError::WouldSetBits` for the first such byte, before any of it is written, though earlier writes are left
+    /// applied. Bytes past the end of the target are taken to be erased. Writes are never batched.
+    pub fn apply_nor_flash<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError>
+    where T: Read + Write + Seek {
+        let options = ApplyOptions {
+            vectored_writes: false,
+            coalesce_writes: false,
+            ..options.clone()
+        };
+        let mut target = ProgramOnly::new(target);
+        target.write = Some(|target, buf| target.write(buf));
+        target.flush = Some(|target| target.flush());
+        let applied = self.apply_readable(&mut target, &options);
+        match target.violations.first() {
+            Some(&BitViolation { offset, old, new }) => Err(ApplyError::WouldSetBits { offset, old, new }),
+            None => applied,
+        }
+    }
+
+    /// Finds every byte which `apply_nor_flash()` would refuse to program, without writing to the target, so that the
+    /// erases it needs can be planned. Each write is checked against what the target contains, or what earlier writes
+    /// would have left there, and reads made while applying see those writes too. The violations are in the order
+    /// they'd be written, and none means `apply_nor_flash()` would succeed. This doesn't count as applying the
+    /// operations. The target's position is restored afterwards.
+    pub fn nor_flash_violations<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<Vec<BitViolation>, ApplyError>
+    where T: Read + Seek {
+        let options = ApplyOptions {
+            vectored_writes: false,
+            coalesce_writes: false,
+            ..options.clone()
+        };
+        let position = retry_interrupted(|| target.stream_position())?;
+        let mut target = ProgramOnly::new(target);
+        let checked = {
+            let mut target = ApplyTarget::new(&mut target);
+            target.read = Some(|target, buf| target.read(buf));
+            self.replay_operations(&mut target, &options, &mut Checker::new(self, options.check_policy))
+        };
+        let restored = retry_seek(target.inner, SeekFrom::Start(position));
+        checked?;
+        restored?;
+        Ok(target.violations)
+    }
+
+    /// Finds the blocks of `block_size` bytes which the stored writes touch, for targets which are written a whole block
+    /// at a time, such as managed flash. Block `n` starts at offset `n * block_size`, and the blocks are given in
+    /// ascending order, each once. The generation stamp, if there is one, counts as a write. As with
+    /// `ApplyOrder::Offset`, only writes, seeks and flushes can be laid out by block, and every write needs a known
+    /// offset. Fails with `io::ErrorKind::InvalidInput` if `block_size` is 0.
+    pub fn dirty_blocks(&self, block_size: u64) -> Result<Vec<u64>, ApplyError> {
+        let mut writes = self.stamped_write_extents()?;
+        writes.sort_by_key(|(_, extent)| extent.start);
+        blocks_touched(&writes, block_size)
+    }
+
+    /// Applies the stored operations by reading every block of `block_size` bytes which they touch, laying the writes
+    /// over it, and writing the whole block back, for targets which can only be written in aligned blocks. Each dirty
+    /// block, as found by `dirty_blocks()`, is read and written once, in ascending order, and no other block is
+    /// written. Where a block runs past the end of the target, only as much of it as the target has, or as the writes
+    /// reach, is written back, and anything between is `gap_fill()`. Overlapping writes resolve as they would when
+    /// applied in order. Base checksums and the generation stamp are checked first, as `apply_readable()` does. The
+    /// writes are taken to land where they did while recording, rather than checked as they're applied.
+    pub fn apply_blockwise<T>(&self, target: &mut T, block_size: u64) -> Result<BlockReport, ApplyError>
+    where T: Read + Write + Seek {
+        self.check_can_apply()?;
+        let stamp = self.stamp_operation();
+        let mut writes = self.stamped_write_extents()?;
+        writes.sort_by_key(|(_, extent)| extent.start);
+        let blocks = blocks_touched(&writes, block_size)?;
+        let operation_at = |index: usize| self.operations.get(index).or(stamp.as_ref()).expect("write index is in range");
+        {
+            let mut target = ApplyTarget::new(&mut *target);
+            target.read = Some(|target, buf| target.read(buf));
+            self.check_base(&mut target)?;
+        }
+        let block_len = usize::try_from(block_size)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "block size does not fit in memory"))?;
+
+        // The writes are by where they start, so that those within each block can be found as the blocks ascend.
+        // Writes are dropped once they end before the block.
+        let mut writes = writes.into_iter().peekable();
+        let mut covering: Vec<(usize, Range<u64>)> = Vec::new();
+        let mut buf = vec![0u8; block_len];
+        let mut report = BlockReport::default();
+        for block in blocks {
+            let offset = block * block_size;
+            let block_end = offset + block_size;
+            covering.retain(|(_, extent)| extent.end > offset);
+            while let Some(write) = writes.next_if(|(_, extent)| extent.start < block_end) {
+                covering.push(write);
+            }
+            covering.sort_by_key(|(index, _)| *index);
+
+            retry_seek(target, SeekFrom::Start(offset))?;
+            let filled = read_up_to(target, &mut buf)?;
+            buf[filled..].fill(self.gap_fill);
+            let mut len = filled;
+            for (index, extent) in &covering {
+                let start = extent.start.max(offset);
+                let end = extent.end.min(block_end);
+                if start >= end {
+                    continue;
+                }
+                let range = (start - offset) as usize..(end - offset) as usize;
+                recorded_bytes(operation_at(*index), start - extent.start, &mut buf[range.clone()]);
+                len = len.max(range.end);
+            }
+            retry_seek(target, SeekFrom::Start(offset))?;
+            retry_interrupted(|| target.write_all(&buf[0..len]))?;
+            report.blocks_written += 1;
+            report.bytes_written += len as u64;
+        }
+        retry_interrupted(|| target.flush())?;
+        self.count_apply();
+        Ok(report)
+    }
+
+    /// Produces the patched image as one sequential stream of `total_len` bytes, for targets which can't seek, such as
+    /// sockets. Each byte comes from the last write which covers it, so overlapping writes resolve as they would when
+    /// applying to a copy of `base`. Other bytes come from `base`, which is read alongside, or are `gap_fill` where
+    /// there is no base or it has ended. Writes past `total_len` are cut off. As with `ApplyOrder::Offset`, only
+    /// writes, seeks and flushes can be streamed, and every write needs a known offset. The generation stamp, if there
+    /// is one, is written last, but neither it nor any base checksums are checked against `base`. Memory use is bounded
+    /// by the number of stored writes.
+    pub fn apply_streaming<W, R>(&self, mut out: W, mut base: Option<R>, total_len: u64, gap_fill: u8) -> Result<(), ApplyError>
+    where W: Write, R: Read {
+        self.check_can_apply()?;
+        let stamp = self.stamp_operation();
+        let mut writes = self.stamped_write_extents()?;
+        writes.sort_by_key(|(_, extent)| extent.start);
+        let operation_at = |index: usize| self.operations.get(index).or(stamp.as_ref()).expect("write index is in range");
+
+        let mut writes = writes.into_iter().peekable();
+        // The writes covering the position, latest first. Writes which have ended are only removed once they're latest.
+        let mut covering = BinaryHeap::new();
+        let mut buf = vec![0u8; COPY_CHUNK_SIZE];
+        let mut position = 0;
+        while position < total_len {
+            while let Some((index, extent)) = writes.next_if(|(_, extent)| extent.start <= position) {
+                covering.push((index, extent.start, extent.end));
+            }
+            while covering.peek().is_some_and(|&(_, _, end)| end <= position) {
+                covering.pop();
+            }
+            let next_start = writes.peek().map_or(u64::MAX, |(_, extent)| extent.start);
+            let end = match covering.peek() {
+                Some(&(_, _, end)) => end.min(next_start),
+                None => next_start,
+            }.min(total_len);
+            let mut remaining = end - position;
+            while remaining > 0 {
+                let chunk = &mut buf[0..remaining.min(COPY_CHUNK_SIZE as u64) as usize];
+                let base_read = match &mut base {
+                    Some(reader) => read_up_to(reader, chunk)?,
+                    None => 0,
+                };
+                if base_read < chunk.len() {
+                    base = None;
+                }
+                match covering.peek() {
+                    Some(&(index, start, _)) => recorded_bytes(operation_at(index), position - start, chunk),
+                    None => chunk[base_read..].fill(gap_fill),
+                }
+                retry_interrupted(|| out.write_all(chunk))?;
+                position += chunk.len() as u64;
+                remaining -= chunk.len() as u64;
+            }
+        }
+        retry_interrupted(|| out.flush())?;
+        self.count_apply();
+        Ok(())
+    }
+
+    /// Applies the stored operations on a target which can also be read from, so that if applying fails, the target is
+    /// restored to how it was. Before each write, the bytes it's about to overwrite are read and saved; on failure,
+    /// they're written back in reverse order, and the original error is returned. This needs memory for as many bytes
+    /// as `rollback_size()` returns. Bytes written past the original end of the target are left in place, as the
+    /// target can't be truncated. Stored `WriteOperation::Custom` operations are unsupported, as what they overwrite
+    /// isn't known in advance, and writes are never batched.
+    pub fn apply_transactional<T>(&self, target: &mut T, options: &ApplyOptions) -> Result<usize, ApplyError>
+    where T: Read + Write + Seek {
+        let options = ApplyOptions {
+            vectored_writes: false,
+            coalesce_writes: false,
+            ..options.clone()
+        };
+        let mut target = ApplyTarget::new(target);
+        target.read = Some(|target, buf| target.read(buf));
+        target.journal = Some(Vec::new());
+        match self.replay(&mut target, &options, &mut Checker::new(self, options.check_policy)) {
+            Ok(replayed) => Ok(replayed.bytes_written),
+            Err(error) => match target.roll_back() {
+                Ok(()) => Err(error),
+                Err(rollback_error) => Err(ApplyError::RollbackFailed { error: Box::new(error), rollback_error }),
+            },
+        }
+    }
+
+    /// Applies the stored operations on a target which can also be read from, as `apply_report()` does, also returning
+    /// a `Yadon` which undoes them. Before each write, the bytes it's about to overwrite are read and recorded into the
+    /// undo log at the same offset, latest first, so that applying the undo log to the patched target restores it. The
+    /// undo log has the same `start`, `length` and `LengthMode` as this one. If applying extended the target, the undo
+    /// log ends by truncating it back to its original length, so it has to be applied with `apply_with_setlen()`. As
+    /// with `apply_transactional()`, this needs memory for `rollback_size()` bytes, writes are never batched, and
+    /// stored `WriteOperation::Custom` operations are unsupported.
+    pub fn apply_and_capture_undo<T>(&self, target: &mut T) -> Result<(ApplyReport, Yadon), ApplyError> where T: Read + Write + Seek {
+        let options = ApplyOptions {
+            vectored_writes: false,
+            ..Default::default()
+        };
+        let position = retry_interrupted(|| target.stream_position())?;
+        let original_len = retry_seek(target, SeekFrom::End(0))?;
+        retry_seek(target, SeekFrom::Start(position))?;
+
+        let mut target = ApplyTarget::new(target);
+        target.read = Some(|target, buf| target.read(buf));
+        target.journal = Some(Vec::new());
+        let report = self.report(&mut target, &options)?;
+
+        let mut undo = Yadon::new(self.start, self.length).with_length_mode(self.length_mode);
+        for (offset, contents) in target.journal.take().unwrap_or_default().iter().rev() {
+            undo.seek(SeekFrom::Start(*offset))?;
+            undo.write_all(contents)?;
+        }
+        if retry_seek(target.inner, SeekFrom::End(0))? > original_len {
+            undo.set_len(original_len)?;
+        }
+        retry_seek(target.inner, SeekFrom::Start(report.final_position))?;
+        Ok((report, undo))
+    }
+
+    /// The most bytes which `apply_transactional()` saves to be able to roll the target back, which is the total
+    /// length of the stored writes and copies.
+    pub fn rollback_size(&self) -> u64 {
+        self.operations.iter()
+            .filter(|operation| !matches!(operation, WriteOperation::Custom(..)))
+            .map(WriteOperation::expected_bytes_written)
+            .sum()
+    }
+
+    /// Checks every `WriteOperation::AssertBytes` against the target without writing anything, restoring the target's
+    /// position afterwards. The assertions are all checked against the target as it is now, so this is only meaningful
+    /// for assertions about bytes which aren't modified earlier in the log.
+    pub fn check_preconditions<T>(&self, target: &mut T) -> Result<(), ApplyError> where T: Read + Seek {
+        if self.store.is_some() {
+            return Err(ApplyError::InStore);
+        }
+        let position = retry_interrupted(|| target.stream_position())?;
+        self.check_base_checksums(target, |target, buf| target.read(buf))?;
+        check_preconditions(target, |target, buf| target.read(buf), &self.operations)?;
+        retry_seek(target, SeekFrom::Start(position))?;
+        Ok(())
+    }
+
+    /// Computes the CRC-32 of `range` of the target, as `require_base_checksum()` would, restoring the target's position
+    /// afterwards. An end of `u64::MAX` stands for the end of the target.
+    pub fn base_checksum<T>(&self, target: &mut T, range: Range<u64>) -> Result<u32, ApplyError> where T: Read + Seek {
+        let position = retry_interrupted(|| target.stream_position())?;
+        let range = self.base_range(target, range)?;
+        let crc32 = base_checksum(target, |target, buf| target.read(buf), range);
+        retry_seek(target, SeekFrom::Start(position))?;
+        Ok(crc32?)
+    }
+
+    /// Resolves an end of `u64::MAX` in a range given to `require_base_checksum()`.
+    fn base_range<T>(&self, target: &mut T, range: Range<u64>) -> std::io::Result<Range<u64>> where T: Seek {
+        if range.end != u64::MAX {
+            return Ok(range);
+        }
+        let end = match self.length {
+            Some(length) => length,
+            None => retry_seek(target, SeekFrom::End(0))?,
+        };
+        Ok(range.start..end)
+    }
+
+    /// Checks every range given to `require_base_checksum()` against the target, moving its position.
+    fn check_base_checksums<T>(&self, target: &mut T, read: ReadFn<T>) -> Result<(), ApplyError> where T: Seek {
+        let mut mismatches = Vec::new();
+        for (range, expected) in &self.base_checksums {
+            let range = self.base_range(target, range.clone())?;
+            let actual = base_checksum(target, read, range.clone())?;
+            if actual != *expected {
+                mismatches.push(ChecksumMismatch { range, expected: *expected, actual });
+            }
+        }
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(ApplyError::BaseChecksumMismatch(mismatches)),
+        }
+    }
+
+    /// Checks the base checksums and the generation stamp before applying, unless resuming or validating, leaving the
+    /// target where it was.
+    fn check_base<T>(&self, target: &mut ApplyTarget<T>) -> Result<(), ApplyError> where T: Seek {
+        if target.dry_run || target.resume_from.is_some() {
+            return Ok(());
+        }
+        if !self.base_checksums.is_empty() {
+            let read = target.read.ok_or(ApplyError::UnsupportedOperation("base checksum"))?;
+            let position = retry_interrupted(|| target.inner.stream_position())?;
+            self.check_base_checksums(target.inner, read)?;
+            retry_seek(target.inner, SeekFrom::Start(position))?;
+        }
+        if let Some((offset, generation)) = self.generation_stamp {
+            let read = target.read.ok_or(ApplyError::UnsupportedOperation("generation stamp"))?;
+            if let (Some(length), LengthMode::Fixed) = (self.length, self.length_mode) {
+                if offset.checked_add(8).is_none_or(|end| end > length) {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "generation stamp extends past the end").into());
+                }
+            }
+            let position = retry_interrupted(|| target.inner.stream_position())?;
+            retry_seek(target.inner, SeekFrom::Start(offset))?;
+            let mut stamp = [0u8; 8];
+            let mut filled = 0;
+            while filled < stamp.len() {
+                match retry_interrupted(|| read(target.inner, &mut stamp[filled..]))? {
+                    0 => break,
+                    bytes_read => filled += bytes_read,
+                }
+            }
+            retry_seek(target.inner, SeekFrom::Start(position))?;
+            let stored = u64::from_le_bytes(stamp);
+            if stored >= generation {
+                return Err(ApplyError::GenerationNotNewer { stored, generation });
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the generation stamp, if there is one, once every operation has been applied, leaving the target where
+    /// the last operation did.
+    fn write_stamp<T>(&self, target: &mut ApplyTarget<T>, options: &ApplyOptions) -> Result<(), ApplyError> where T: Write + Seek {
+        let stamp = match self.stamp_operation() {
+            Some(stamp) if !target.dry_run => stamp,
+            _ => return Ok(()),
+        };
+        let (offset, _) = self.generation_stamp.expect("generation stamp");
+        let position = match target.position {
+            Some(position) => position,
+            None => retry_interrupted(|| target.inner.stream_position())?,
+        };
+        target.position = Some(retry_seek(target.inner, SeekFrom::Start(offset))?);
+        if target.journal.is_some() {
+            target.stash(&stamp)?;
+        }
+        if let WriteOperation::Write(data, _) = &stamp {
+            if write_data(target.inner, data, options, &mut target.limiter)? < data.len() {
+                return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "generation stamp was cut short").into());
+            }
+        }
+        target.position = Some(retry_seek(target.inner, SeekFrom::Start(position))?);
+        Ok(())
+    }
+
+    /// The write of the generation stamp, if there is one.
+    pub(crate) fn stamp_operation(&self) -> Option<WriteOperation> {
+        self.generation_stamp.map(|(_, generation)| WriteOperation::Write(generation.to_le_bytes().to_vec(), 8))
+    }
+
+    /// Finds how many of the stored operations the target already reflects, such as after applying was interrupted, by
+    /// reading what each write would have written, in order, without writing anything. Seeks, flushes, durability
+    /// barriers, preconditions and zero-length writes count as applied, as does resizing if the target already has
+    /// that length. Copies and custom operations can't be told apart from not having been applied, so they count as
+    /// not applied. If `start` isn't set, the target is taken to be positioned where applying started. The target's
+    /// position is restored afterwards. `DetectReport::session_state()` gives the state to resume an `ApplySession`
+    /// from.
+    pub fn detect_applied<T>(&self, target: &mut T) -> Result<DetectReport, ApplyError> where T: Read + Seek {
+        if self.store.is_some() {
+            return Err(ApplyError::InStore);
+        }
+        let position = retry_interrupted(|| target.stream_position())?;
+        let detected = self.detect_prefix(target, self.start.unwrap_or(position));
+        retry_seek(target, SeekFrom::Start(position))?;
+        detected
+    }
+
+    fn detect_prefix<T>(&self, target: &mut T, mut position: u64) -> Result<DetectReport, ApplyError> where T: Read + Seek {
+        let read: ReadFn<T> = |target, buf| target.read(buf);
+        for (index, operation) in self.operations.iter().enumerate() {
+            let applied = match operation {
+                WriteOperation::Seek(pos, _) | WriteOperation::DeferredSeek(pos) => {
+                    let resolved = match pos {
+                        SeekFrom::Start(offset) => Some(*offset),
+                        SeekFrom::Current(offset) => position.checked_add_signed(*offset),
+                        SeekFrom::End(offset) => retry_seek(target, SeekFrom::End(0))?.checked_add_signed(*offset),
+                    };
+                    position = resolved.ok_or_else(|| {
+                        std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+                    })?;
+                    true
+                },
+                WriteOperation::Write(..) | WriteOperation::Fill { .. } | WriteOperation::Repeat { .. } => {
+                    let applied = contains_recorded(target, read, operation, position)?;
+                    if applied {
+                        position += operation.expected_bytes_written();
+                    }
+                    applied
+                },
+                WriteOperation::SetLen(len) => retry_seek(target, SeekFrom::End(0))? == *len,
+                WriteOperation::Flush | WriteOperation::Sync | WriteOperation::AssertBytes { .. } => true,
+                WriteOperation::CopyWithin { .. } | WriteOperation::Custom(..) => false,
+            };
+            if !applied {
+                return Ok(DetectReport { ops_applied: index, first_mismatch: Some(index), position });
+            }
+        }
+        Ok(DetectReport { ops_applied: self.operations.len(), first_mismatch: None, position })
+    }
+
+    /// Applies the operations from `state.next_op` on a target for an `ApplySession`, updating `state` with how far
+    /// applying got.
+    pub(crate) fn apply_from<T>(&self, target: &mut T, options: &ApplyOptions, state: &mut SessionState)
+        -> Result<usize, SessionError> where T: Write + Seek {
+        let mut target = ApplyTarget::new(target);
+        if state.next_op > 0 {
+            match state.position {
+                Some(position) => target.resume_from = Some((state.next_op, position)),
+                None => return Err(SessionError {
+                    ops_completed: state.next_op,
+                    error: ApplyError::UnresolvedOffset(state.next_op),
+                }),
+            }
+        }
+        let replayed = self.replay(&mut target, options, &mut Checker::new(self, options.check_policy));
+        *state = SessionState {
+            next_op: target.ops_completed,
+            position: target.completed_position,
+        };
+        replayed
+            .map(|replayed| replayed.bytes_written)
+            .map_err(|error| SessionError { ops_completed: state.next_op, error })
+    }
+
+    /// Replays the stored operations on a target, restoring its position afterwards if the options ask for it.
+    fn replay<T>(&self, target: &mut ApplyTarget<T>, options: &ApplyOptions, checker: &mut Checker) -> Result<Replayed, ApplyError>
+    where T: Write + Seek {
+        self.check_can_apply()?;
+        if !options.restore_position {
+            return self.replay_operations(target, options, checker).inspect(|_| self.count_apply());
+        }
+        let position = retry_interrupted(|| target.inner.stream_position())?;
+        let replayed = self.replay_operations(target, options, checker).and_then(|mut replayed| {
+            replayed.final_position = Some(retry_interrupted(|| target.inner.stream_position())?);
+            Ok(replayed)
+        });
+        let restored = retry_seek(target.inner, SeekFrom::Start(position));
+        let replayed = replayed?;
+        restored?;
+        self.count_apply();
+        Ok(replayed)
+    }
+
+    /// Replays the stored operations on a target. Fails before touching the target if there are operations which the
+    /// target has no way to apply.
+    fn replay_operations<T>(&self, target: &mut ApplyTarget<T>, options: &ApplyOptions, checker: &mut Checker)
+        -> Result<Replayed, ApplyError> where T: Write + Seek {
+        self.check_base(target)?;
+        if options.validate_first {
+            self.validate(target, options, checker)?;
+        }
+        target.limiter = RateLimiter::new(options);
+        let by_offset = match options.order {
+            ApplyOrder::Recorded => None,
+            ApplyOrder::Offset => Some(self.offset_order()?),
+        };
+        if let Some(unsupported) = self.operations.iter().find(|operation| !target.supports(operation, options)) {
+            return Err(ApplyError::UnsupportedOperation(unsupported.name()));
+        }
+        if options.check_preconditions_first {
+            if let Some(read) = target.read {
+                check_preconditions(target.inner, read, &self.operations)?;
+            }
+        }
+        let first = target.resume_from.map_or(0, |(first, _)| first);
+        let start = match target.resume_from {
+            Some((_, position)) => Some(position),
+            None => self.start,
+        };
+        target.ops_completed = first;
+        if let Some(start) = start {
+            let seek_pos = retry_seek(target.inner, SeekFrom::Start(start))?;
+            target.seeks += 1;
+            target.position = Some(seek_pos);
+            // If this fails, something is wrong with the seek.
+            checker.position(start, seek_pos)?;
+        }
+        target.completed_position = target.position;
+        let mut total_bytes_written: usize = 0;
+        if let Some(by_offset) = by_offset {
+            for (completed, (index, offset)) in by_offset.into_iter().enumerate() {
+                target.check_cancelled(completed, total_bytes_written, options)?;
+                let operation = &self.operations[index];
+                let context = |error: ApplyError| error.with_context(self.location_of(index), self.label_of(index).cloned());
+                checker.begin(index, operation, target.position);
+                target.apply_operation(&WriteOperation::Seek(SeekFrom::Start(offset), offset), options, checker).map_err(context)?;
+                checker.begin(index, operation, target.position);
+                let bytes_written = target.apply_hooked(index, operation, completed, total_bytes_written, options, checker)
+                    .map_err(|error| target.cancelled_by(error, completed, total_bytes_written, options))
+                    .map_err(context)?;
+                target.flush_after(index, bytes_written, options).map_err(context)?;
+                target.completed(completed + 1, bytes_written);
+                total_bytes_written += bytes_written;
+            }
+            // Seeks and flushes aren't applied on their own, so they're all done once the writes are.
+            target.completed(self.operations.len(), 0);
+            self.write_stamp(target, options)?;
+            target.finish(options)?;
+            return Ok(Replayed {
+                operations: self.operations.len(),
+                bytes_written: total_bytes_written,
+                seeks: target.seeks,
+                seeks_elided: target.seeks_elided,
+                final_position: None,
+            });
+        }
+        let mut index = first;
+        while index < self.operations.len() {
+            target.check_cancelled(index, total_bytes_written, options)?;
+            let batched = (options.vectored_writes || options.coalesce_writes)
+                && options.flush_policy != FlushPolicy::EveryOp
+                && options.rate_limit.is_none()
+                && target.hook.is_none()
+                && target.map.is_none();
+            let run_len = match batched {
+                true => self.operations[index..].iter().take_while(|operation| vectored_data(operation, options).is_some()).count(),
+                false => 0,
+            };
+            if run_len > 1 {
+                let bytes_written = target.apply_writes(&self.operations[index..index + run_len], index, options, checker)
+                    .map_err(|error| target.cancelled_by(error, index, total_bytes_written, options))?;
+                let last = index + run_len - 1;
+                target.flush_after(last, bytes_written, options)
+                    .map_err(|error| error.with_context(self.location_of(last), self.label_of(last).cloned()))?;
+                total_bytes_written += bytes_written;
+                index += run_len;
+                continue;
+            }
+            let context = |error: ApplyError| error.with_context(self.location_of(index), self.label_of(index).cloned());
+            checker.begin(index, &self.operations[index], target.position);
+            let bytes_written = target.apply_hooked(index, &self.operations[index], index, total_bytes_written, options, checker)
+                .map_err(|error| target.cancelled_by(error, index, total_bytes_written, options))
+                .map_err(context)?;
+            target.flush_after(index, bytes_written, options).map_err(context)?;
+            target.completed(index + 1, bytes_written);
+            total_bytes_written += bytes_written;
+            index += 1;
+        }
+        self.write_stamp(target, options)?;
+        target.finish(options)?;
+        Ok(Replayed {
+            operations: index - first,
+            bytes_written: total_bytes_written,
+            seeks: target.seeks,
+            seeks_elided: target.seeks_elided,
+            final_position: None,
+        })
+    }
+}
+
+impl Yadon {
+    /// Walks through the operations for `ApplyOptions::validate_first`, without changing the target.
+    fn validate<T>(&self, target: &mut ApplyTarget<T>, options: &ApplyOptions, checker: &mut Checker) -> Result<(), ApplyError>
+    where T: Write + Seek {
+        if let (Some(_), LengthMode::Fixed) = (self.length, self.length_mode) {
+            let position = retry_interrupted(|| target.inner.stream_position())?;
+            let target_len = retry_seek(target.inner, SeekFrom::End(0))?;
+            retry_seek(target.inner, SeekFrom::Start(position))?;
+            if target_len < self.written_end {
+                return Err(ApplyError::TargetTooShort { required: self.written_end, actual: target_len });
+            }
+        }
+        let dry_run_options = ApplyOptions {
+            validate_first: false,
+            vectored_writes: false,
+            coalesce_writes: false,
+            replay_flushes: false,
+            flush_policy: FlushPolicy::Never,
+            rate_limit: None,
+            ..options.clone()
+        };
+        let progress = target.progress.take();
+        let (ops_completed, completed_position) = (target.ops_completed, target.completed_position);
+        target.dry_run = true;
+        let validated = self.replay_operations(target, &dry_run_options, checker);
+        target.dry_run = false;
+        target.progress = progress;
+        target.position = None;
+        target.ops_completed = ops_completed;
+        target.completed_position = completed_position;
+        validated.map(|_| ())
+    }
+
+    /// Finds the index and extent of every write which writes anything, in the order they were recorded. Fails if
+    /// there's anything other than writes, seeks and flushes, as the effect of anything else depends on the order
+    /// it's applied in.
+    pub(crate) fn write_extents(&self) -> Result<Vec<(usize, Range<u64>)>, ApplyError> {
+        if self.store.is_some() {
+            return Err(ApplyError::InStore);
+        }
+        let mut position = self.start;
+        let mut writes = Vec::new();
+        for (index, operation) in self.operations.iter().enumerate() {
+            let len = match operation {
+                WriteOperation::Seek(pos, expected_position) => {
+                    position = match pos {
+                        SeekFrom::Current(_) if position.is_none() => None,
+                        _ => Some(*expected_position),
+                    };
+                    continue;
+                },
+                WriteOperation::Flush => continue,
+                WriteOperation::Repeat { pattern, count } => repeated_len(pattern, *count)?,
+                WriteOperation::Write(..) | WriteOperation::Fill { .. } => operation.expected_bytes_written(),
+                _ => return Err(ApplyError::OrderDependent(operation.name())),
+            };
+            let offset = position.ok_or(ApplyError::UnresolvedOffset(index))?;
+            let end = offset.checked_add(len)
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "write would overflow the position"))?;
+            if len > 0 {
+                writes.push((index, offset..end));
+            }
+            position = Some(end);
+        }
+        Ok(writes)
+    }
+
+    /// The extent of every write, as `write_extents()` finds them, and of the generation stamp, if there is one, as if
+    /// it were the operation after the last.
+    pub(crate) fn stamped_write_extents(&self) -> Result<Vec<(usize, Range<u64>)>, ApplyError> {
+        let mut writes = self.write_extents()?;
+        if let (Some((offset, _)), Some(stamp)) = (self.generation_stamp, self.stamp_operation()) {
+            writes.push((self.operations.len(), offset..offset + stamp.expected_bytes_written()));
+        }
+        Ok(writes)
+    }
+
+    /// Finds the index and offset of every write, in the order of their offsets, for `ApplyOrder::Offset`.
+    pub(crate) fn offset_order(&self) -> Result<Vec<(usize, u64)>, ApplyError> {
+        let mut writes = self.write_extents()?;
+        writes.sort_by_key(|(_, extent)| extent.start);
+
+        // Until an overlap is found, the previous write is the one reaching furthest.
+        for pair in writes.windows(2) {
+            let (previous, write) = (&pair[0], &pair[1]);
+            if write.1.start < previous.1.end {
+                let (first, second) = if previous.0 < write.0 { (previous, write) } else { (write, previous) };
+                return Err(ApplyError::OverlappingWrites { first: first.clone(), second: second.clone() });
+            }
+        }
+        Ok(writes.into_iter().map(|(index, extent)| (index, extent.start)).collect())
+    }
+}
+
+impl<'a, T> ApplyTarget<'a, T> where T: Write + Seek {
+    /// Applies a single operation, returning the number of bytes it wrote.
+    fn apply_operation(&mut self, operation: &WriteOperation, options: &ApplyOptions, checker: &mut Checker) -> Result<usize, ApplyError> {
+        if self.dry_run {
+            if let Some(bytes_written) = self.skip_operation(operation, checker)? {
+                return Ok(bytes_written);
+            }
+        }
+        if self.journal.is_some() {
+            self.stash(operation)?;
+        }
+        if self.writes.is_some() {
+            self.record_write(operation, checker)?;
+        }
+        let target = &mut *self.inner;
+        match operation {
+            WriteOperation::Write(data, expected_bytes_written) => {
+                if options.skip_zero_writes && data.iter().all(|byte| *byte == 0) {
+                    self.position = Some(retry_seek(target, SeekFrom::Current(data.len() as i64))?);
+                    self.seeks += 1;
+                    return Ok(data.len());
+                }
+                let bytes_written = write_data(target, data, options, &mut self.limiter)?;
+                self.advance(bytes_written);
+                checker.written(*expected_bytes_written, bytes_written)
+            },
+            WriteOperation::Seek(pos, expected_position) => {
+                let new_position = if elides(self.position, *pos, options) {
+                    self.seeks_elided += 1;
+                    self.position.expect("known position")
+                } else {
+                    self.seeks += 1;
+                    retry_seek(target, *pos)?
+                };
+                self.position = Some(new_position);
+                checker.position(*expected_position, new_position)?;
+                Ok(0)
+            },
+            WriteOperation::DeferredSeek(pos) => {
+                if elides(self.position, *pos, options) {
+                    self.seeks_elided += 1;
+                } else {
+                    self.position = Some(retry_seek(target, *pos)?);
+                    self.seeks += 1;
+                }
+                Ok(0)
+            },
+            WriteOperation::Flush => {
+                if options.replay_flushes {
+                    target.flush()?;
+                    self.unflushed = 0;
+                }
+                Ok(0)
+            },
+            WriteOperation::Fill { byte, len } => {
+                if options.skip_zero_writes && *byte == 0 {
+                    self.position = Some(retry_seek(target, SeekFrom::Current(*len as i64))?);
+                    self.seeks += 1;
+                    return Ok(*len as usize);
+                }
+                let bytes_written = write_pattern(target, &[*byte], *len, options, &mut self.limiter)? as usize;
+                self.advance(bytes_written);
+                checker.written(*len as usize, bytes_written)
+            },
+            WriteOperation::Repeat { pattern, count } => {
+                let expected_bytes_written = repeated_len(pattern, *count)? as usize;
+                if options.skip_zero_writes && pattern.iter().all(|byte| *byte == 0) {
+                    self.position = Some(retry_seek(target, SeekFrom::Current(expected_bytes_written as i64))?);
+                    self.seeks += 1;
+                    return Ok(expected_bytes_written);
+                }
+                let bytes_written = write_pattern(target, pattern, expected_bytes_written as u64, options, &mut self.limiter)? as usize;
+                self.advance(bytes_written);
+                checker.written(expected_bytes_written, bytes_written)
+            },
+            WriteOperation::SetLen(len) => {
+                // Checked to be present before starting.
+                if let Some(set_len) = self.set_len {
+                    set_len(target, *len)?;
+                }
+                Ok(0)
+            },
+            WriteOperation::Sync => {
+                match self.sync {
+                    Some(sync) => sync(target)?,
+                    None => target.flush()?,
+                }
+                Ok(0)
+            },
+            WriteOperation::CopyWithin { src, dst, len } => {
+                // Checked to be present before starting.
+                let read = self.read.expect("readable target");
+                let bytes_written = copy_within(target, read, *src, *dst, *len, options, &mut self.limiter)? as usize;
+                // A short copy could leave the target anywhere in the copied range.
+                self.position = if bytes_written as u64 == *len { Some(dst + len) } else { None };
+                checker.written(*len as usize, bytes_written)
+            },
+            WriteOperation::AssertBytes { offset, expected } => {
+                // Checked to be present before starting.
+                let read = self.read.expect("readable target");
+                let position = retry_interrupted(|| target.stream_position())?;
+                check_bytes(target, read, *offset, expected)?;
+                retry_seek(target, SeekFrom::Start(position))?;
+                Ok(0)
+            },
+            WriteOperation::Custom(custom, expected) => {
+                let outcome = custom.apply(target)?;
+                self.position = Some(outcome.position);
+                let bytes_written = checker.written(expected.bytes_written as usize, outcome.bytes_written as usize)?;
+                checker.position(expected.position, outcome.position)?;
+                Ok(bytes_written)
+            }
+        }
+    }
+
+    /// Applies the operation at `index` as `apply_operation()` does, first asking the map, if there is one, what to apply
+    /// instead, then the hook, if there is one, whether to, and telling the hook the outcome after. `ops_applied` and
+    /// `bytes_written` are how far applying has got, in case the hook stops it.
+    fn apply_hooked(&mut self, index: usize, operation: &WriteOperation, ops_applied: usize, bytes_written: usize,
+        options: &ApplyOptions, checker: &mut Checker) -> Result<usize, ApplyError> {
+        let action = match &mut self.map {
+            Some(map) => map(index, operation),
+            None => OpAction::Keep,
+        };
+        let replacement;
+        let operation = match action {
+            OpAction::Keep => operation,
+            OpAction::Skip => {
+                self.skipped.push(index);
+                return self.pass_over(operation, options, checker).map(|()| 0);
+            },
+            OpAction::Replace(replaced_by) => {
+                if !self.supports(&replaced_by, options) {
+                    return Err(ApplyError::UnsupportedOperation(replaced_by.name()));
+                }
+                self.replaced.push(index);
+                let (original, replacement_bytes) = (operation.expected_bytes_written(), replaced_by.expected_bytes_written());
+                if original != replacement_bytes {
+                    self.resized.push(ResizedOp { index, original, replacement: replacement_bytes });
+                }
+                checker.begin(index, &replaced_by, self.position);
+                replacement = replaced_by;
+                &replacement
+            },
+        };
+        let hook = match self.hook.take() {
+            Some(hook) => hook,
+            None => return self.apply_operation(operation, options, checker),
+        };
+        let result = match hook.before_op(index, operation) {
+            ControlFlow::Break(()) if options.hook_break == HookBreak::Abort => Err(self.cancel(ops_applied, bytes_written, options)),
+            ControlFlow::Break(()) => {
+                self.skipped.push(index);
+                self.pass_over(operation, options, checker).map(|()| 0)
+            },
+            ControlFlow::Continue(()) => {
+                let result = self.apply_operation(operation, options, checker)
+                    .map(|bytes_written| OpOutcome { bytes_written, position: self.position });
+                hook.after_op(index, operation, &result);
+                result.map(|outcome| outcome.bytes_written)
+            },
+        };
+        self.hook = Some(hook);
+        result
+    }
+
+    /// Skips an operation for `HookBreak::Skip`, leaving the target's contents as they were, but moving it to where the
+    /// operation would have left it.
+    fn pass_over(&mut self, operation: &WriteOperation, options: &ApplyOptions, checker: &mut Checker) -> Result<(), ApplyError> {
+        match operation {
+            WriteOperation::Seek(..) | WriteOperation::DeferredSeek(_) => self.apply_operation(operation, options, checker).map(drop),
+            WriteOperation::AssertBytes { .. } => Ok(()),
+            _ => self.skip_operation(operation, checker).map(drop),
+        }
+    }
+
+    /// Flushes the target after the operation at `index` wrote `bytes_written` bytes, if the flush policy asks for it.
+    fn flush_after(&mut self, index: usize, bytes_written: usize, options: &ApplyOptions) -> Result<(), ApplyError> {
+        self.unflushed += bytes_written as u64;
+        let due = match options.flush_policy {
+            FlushPolicy::Never | FlushPolicy::AtEnd => false,
+            FlushPolicy::EveryOp => true,
+            FlushPolicy::EveryNBytes(bytes) => self.unflushed >= bytes,
+        };
+        if due {
+            self.inner.flush().map_err(|source| ApplyError::FlushFailed {
+                op_index: index,
+                source,
+                location: None,
+                label: None,
+            })?;
+            self.unflushed = 0;
+        }
+        Ok(())
+    }
+
+    /// Stops with `ApplyError::Cancelled` if applying has been cancelled.
+    fn check_cancelled(&mut self, ops_applied: usize, bytes_written: usize, options: &ApplyOptions) -> Result<(), ApplyError> {
+        match options.cancelled() {
+            true => Err(self.cancel(ops_applied, bytes_written, options)),
+            false => Ok(()),
+        }
+    }
+
+    /// Turns `error` into `ApplyError::Cancelled` if it's from an operation which was cancelled partway through.
+    fn cancelled_by(&mut self, error: ApplyError, ops_applied: usize, bytes_written: usize, options: &ApplyOptions) -> ApplyError {
+        match &error {
+            ApplyError::Io(io_error) if io_error.get_ref().is_some_and(|inner| inner.is::<Cancellation>()) => {
+                self.cancel(ops_applied, bytes_written, options)
+            },
+            _ => error,
+        }
+    }
+
+    /// Flushes the target as the flush policy asks for, then reports the cancellation.
+    fn cancel(&mut self, ops_applied: usize, bytes_written: usize, options: &ApplyOptions) -> ApplyError {
+        match self.finish(options) {
+            Ok(()) => ApplyError::Cancelled { ops_applied, bytes_written },
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Flushes the target once every operation has been applied, unless the flush policy is `FlushPolicy::Never`.
+    fn finish(&mut self, options: &ApplyOptions) -> std::io::Result<()> {
+        match options.flush_policy {
+            FlushPolicy::Never => Ok(()),
+            _ => self.inner.flush(),
+        }
+    }
+
+    /// Saves the current contents of the extent which `operation` is about to write to in the journal, leaving the
+    /// target where it was. Only as much as the target contains is saved.
+    fn stash(&mut self, operation: &WriteOperation) -> std::io::Result<()> {
+        let (offset, len) = match operation {
+            WriteOperation::Write(..) | WriteOperation::Fill { .. } | WriteOperation::Repeat { .. } => {
+                let offset = match self.position {
+                    Some(position) => position,
+                    None => retry_interrupted(|| self.inner.stream_position())?,
+                };
+                (offset, operation.expected_bytes_written())
+            },
+            WriteOperation::CopyWithin { dst, len, .. } => (*dst, *len),
+            _ => return Ok(()),
+        };
+        // Checked to be present before starting.
+        let read = self.read.expect("readable target");
+        let mut contents = vec![0u8; len as usize];
+        let mut filled = 0;
+        retry_seek(self.inner, SeekFrom::Start(offset))?;
+        while filled < contents.len() {
+            match retry_interrupted(|| read(self.inner, &mut contents[filled..]))? {
+                0 => break,
+                bytes_read => filled += bytes_read,
+            }
+        }
+        contents.truncate(filled);
+        retry_seek(self.inner, SeekFrom::Start(offset))?;
+        if let Some(journal) = &mut self.journal {
+            journal.push((offset, contents));
+        }
+        Ok(())
+    }
+
+    /// Notes where `operation` is applied, if it changes the target's contents, for verifying it afterwards.
+    fn record_write(&mut self, operation: &WriteOperation, checker: &Checker) -> std::io::Result<()> {
+        match operation {
+            WriteOperation::Write(..) | WriteOperation::Fill { .. } | WriteOperation::Repeat { .. }
+            | WriteOperation::CopyWithin { .. } => {},
+            _ => return Ok(()),
+        }
+        let offset = match self.position {
+            Some(position) => position,
+            None => retry_interrupted(|| self.inner.stream_position())?,
+        };
+        if let (Some(writes), Some(index)) = (&mut self.writes, checker.index) {
+            writes.push((index, offset));
+        }
+        Ok(())
+    }
+
+    /// Writes the contents saved in the journal back, in reverse order, emptying it.
+    fn roll_back(&mut self) -> std::io::Result<()> where T: Write + Seek {
+        let journal = self.journal.take().unwrap_or_default();
+        for (offset, contents) in journal.iter().rev() {
+            retry_seek(self.inner, SeekFrom::Start(*offset))?;
+            self.inner.write_all(contents)?;
+        }
+        self.inner.flush()
+    }
+
+    /// For a dry run, skips an operation which would change the target, leaving the target where the operation would
+    /// have. Returns `None` for operations which don't change the target, which can be applied as usual.
+    fn skip_operation(&mut self, operation: &WriteOperation, checker: &mut Checker) -> Result<Option<usize>, ApplyError> {
+        let pos = match operation {
+            WriteOperation::Write(..) | WriteOperation::Fill { .. } | WriteOperation::Repeat { .. } => {
+                SeekFrom::Current(operation.expected_bytes_written() as i64)
+            },
+            WriteOperation::CopyWithin { dst, len, .. } => SeekFrom::Start(dst + len),
+            WriteOperation::Custom(_, expected) => SeekFrom::Start(expected.position),
+            WriteOperation::Flush | WriteOperation::Sync | WriteOperation::SetLen(_) => return Ok(Some(0)),
+            WriteOperation::Seek(..) | WriteOperation::DeferredSeek(_) | WriteOperation::AssertBytes { .. } => return Ok(None),
+        };
+        self.position = Some(retry_seek(self.inner, pos)?);
+        let expected_bytes_written = operation.expected_bytes_written() as usize;
+        Ok(Some(checker.written(expected_bytes_written, expected_bytes_written)?))
+    }
+
+    /// Applies a run of `WriteOperation::Write`, starting at index `first_index`, using vectored or coalesced writes,
+    /// returning the number of bytes written. If the target stops partway through an operation, the rest of that operation is written
+    /// on its own, so that each operation's result can be checked as if it had been applied alone.
+    fn apply_writes(&mut self, operations: &[WriteOperation], first_index: usize, options: &ApplyOptions, checker: &mut Checker)
+        -> Result<usize, ApplyError> {
+        let writes: Vec<(&[u8], usize)> = operations.iter().filter_map(|operation| vectored_data(operation, options)).collect();
+        let mut coalesced = Vec::new();
+        let mut total_bytes_written = 0;
+        let mut i = 0;
+        while i < writes.len() {
+            let first = i;
+            let mut remaining = options.max_write_size.unwrap_or(usize::MAX).max(1);
+            if options.coalesce_writes {
+                remaining = remaining.min(COALESCE_BUFFER_SIZE);
+            }
+            let mut slices = Vec::new();
+            for (data, _) in &writes[i..] {
+                if remaining == 0 {
+                    break;
+                }
+                let len = data.len().min(remaining);
+                slices.push(IoSlice::new(&data[0..len]));
+                remaining -= len;
+            }
+            let mut bytes_written = if options.coalesce_writes {
+                coalesced.clear();
+                for slice in &slices {
+                    coalesced.extend_from_slice(slice);
+                }
+                retry_write(self.inner, &coalesced)?
+            } else {
+                retry_interrupted(|| self.inner.write_vectored(&slices))?
+            };
+            while i < writes.len() && bytes_written >= writes[i].0.len() {
+                let (data, expected_bytes_written) = writes[i];
+                bytes_written -= data.len();
+                checker.begin(first_index + i, &operations[i], self.position);
+                self.advance(data.len());
+                let written = checker.written(expected_bytes_written, data.len())?;
+                self.completed(first_index + i + 1, written);
+                total_bytes_written += written;
+                i += 1;
+            }
+            if i < writes.len() && (bytes_written > 0 || i == first) {
+                // The combined write stopped partway through this operation.
+                let (data, expected_bytes_written) = writes[i];
+                checker.begin(first_index + i, &operations[i], self.position);
+                if bytes_written > 0 {
+                    bytes_written += write_data(self.inner, &data[bytes_written..], options, &mut self.limiter)?;
+                }
+                self.advance(bytes_written);
+                let written = checker.written(expected_bytes_written, bytes_written)?;
+                self.completed(first_index + i + 1, written);
+                total_bytes_written += written;
+                i += 1;
+            }
+        }
+        Ok(total_bytes_written)
+    }
+}
+
+/// Whether seeking to `pos` from `position` can be skipped, because the target is already there. Seeks relative to the
+/// end are never skipped, since the length of the target isn't known.
+fn elides(position: Option<u64>, pos: SeekFrom, options: &ApplyOptions) -> bool {
+    let destination = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::Current(offset) => position.and_then(|position| position.checked_add_signed(offset)),
+        SeekFrom::End(_) => None,
+    };
+    options.elide_seeks && destination.is_some() && destination == position
+}
+
+/// The data and expected return value of an operation which can be applied as part of a vectored write.
+fn vectored_data<'o>(operation: &'o WriteOperation, options: &ApplyOptions) -> Option<(&'o [u8], usize)> {
+    match operation {
+        WriteOperation::Write(data, expected_bytes_written) => {
+            if options.skip_zero_writes && data.iter().all(|byte| *byte == 0) {
+                return None;
+            }
+            Some((data, *expected_bytes_written))
+        },
+        _ => None,
+    }
+}
+
+/// Checks every `WriteOperation::AssertBytes` in `operations` against the target's current contents.
+fn check_preconditions<T>(target: &mut T, read: ReadFn<T>, operations: &[WriteOperation]) -> Result<(), ApplyError> where T: Seek {
+    for operation in operations {
+        if let WriteOperation::AssertBytes { offset, expected } = operation {
+            check_bytes(target, read, *offset, expected)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the target at `offset` and compares it with `expected`, in bounded chunks.
+fn check_bytes<T>(target: &mut T, read: ReadFn<T>, offset: u64, expected: &[u8]) -> Result<(), ApplyError> where T: Seek {
+    retry_seek(target, SeekFrom::Start(offset))?;
+    let mut buf = vec![0u8; expected.len().min(COPY_CHUNK_SIZE)];
+    let mut checked = 0;
+    while checked < expected.len() {
+        let chunk_len = (expected.len() - checked).min(buf.len());
+        let bytes_read = retry_interrupted(|| read(target, &mut buf[0..chunk_len]))?;
+        let expected_chunk = &expected[checked..checked + chunk_len];
+        if let Some(i) = (0..bytes_read).find(|i| buf[*i] != expected_chunk[*i]) {
+            return Err(ApplyError::PreconditionFailed {
+                offset: offset + (checked + i) as u64,
+                expected: expected_chunk[i],
+                actual: Some(buf[i]),
+                location: None,
+                label: None,
+            });
+        }
+        if bytes_read == 0 {
+            return Err(ApplyError::PreconditionFailed {
+                offset: offset + checked as u64,
+                expected: expected_chunk[0],
+                actual: None,
+                location: None,
+                label: None,
+            });
+        }
+        checked += bytes_read;
+    }
+    Ok(())
+}
+
+/// Computes the CRC-32 of `range` of the target. A target which ends before the range does gives the checksum of what it
+/// contains.
+fn base_checksum<T>(target: &mut T, read: ReadFn<T>, range: Range<u64>) -> std::io::Result<u32> where T: Seek {
+    retry_seek(target, SeekFrom::Start(range.start))?;
+    let len = range.end.saturating_sub(range.start);
+    let mut buf = vec![0u8; len.min(COPY_CHUNK_SIZE as u64) as usize];
+    let mut crc = Crc32::new();
+    let mut hashed = 0u64;
+    while hashed < len {
+        let chunk_len = (len - hashed).min(buf.len() as u64) as usize;
+        match retry_interrupted(|| read(target, &mut buf[0..chunk_len]))? {
+            0 => break,
+            bytes_read => {
+                crc.update(&buf[0..bytes_read]);
+                hashed += bytes_read as u64;
+            },
+        }
+    }
+    Ok(crc.finish())
+}
+
+/// The blocks of `block_size` bytes which `writes` touch, in ascending order, given the writes in the order of where
+/// they start.
+fn blocks_touched(writes: &[(usize, Range<u64>)], block_size: u64) -> Result<Vec<u64>, ApplyError> {
+    if block_size == 0 {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "block size is zero").into());
+    }
+    let mut blocks: Vec<u64> = Vec::new();
+    for (_, extent) in writes {
+        let first = match blocks.last() {
+            Some(&last) => (extent.start / block_size).max(last + 1),
+            None => extent.start / block_size,
+        };
+        blocks.extend(first..=(extent.end - 1) / block_size);
+    }
+    Ok(blocks)
+}
+
+/// Fills `buf` with the bytes which `operation` writes, starting from byte `from` of what it writes.
+pub(crate) fn recorded_bytes(operation: &WriteOperation, from: u64, buf: &mut [u8]) {
+    for (i, byte) in buf.iter_mut().enumerate() {
+        let i = from + i as u64;
+        *byte = match operation {
+            WriteOperation::Write(data, _) => data[i as usize],
+            WriteOperation::Fill { byte, .. } => *byte,
+            WriteOperation::Repeat { pattern, .. } => pattern[(i % pattern.len() as u64) as usize],
+            _ => unreachable!("only writes of known data are recorded"),
+        };
+    }
+}
+
+/// Whether the target contains the bytes which `operation` writes, at `offset`, moving its position.
+fn contains_recorded<T>(target: &mut T, read: ReadFn<T>, operation: &WriteOperation, offset: u64) -> std::io::Result<bool>
+where T: Seek {
+    let len = operation.expected_bytes_written();
+    let mut expected = vec![0u8; len.min(COPY_CHUNK_SIZE as u64) as usize];
+    let mut actual = vec![0u8; expected.len()];
+    retry_seek(target, SeekFrom::Start(offset))?;
+    let mut checked = 0u64;
+    while checked < len {
+        let chunk_len = (len - checked).min(expected.len() as u64) as usize;
+        recorded_bytes(operation, checked, &mut expected[0..chunk_len]);
+        let mut filled = 0;
+        while filled < chunk_len {
+            match retry_interrupted(|| read(target, &mut actual[filled..chunk_len]))? {
+                0 => return Ok(false),
+                bytes_read => filled += bytes_read,
+            }
+        }
+        if actual[0..chunk_len] != expected[0..chunk_len] {
+            return Ok(false);
+        }
+        checked += chunk_len as u64;
+    }
+    Ok(true)
+}
+
+/// Reads into `buf` until it's full or `reader` ends, returning how much was read.
+fn read_up_to<R>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> where R: Read {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match retry_interrupted(|| reader.read(&mut buf[filled..]))? {
+            0 => break,
+            bytes_read => filled += bytes_read,
+        }
+    }
+    Ok(filled)
+}
+
+/// Checks that `extent` of the target has the same CRC-32 as the bytes which `operation` wrote there, starting from byte
+/// `skip` of what it wrote.
+fn verify_extent<T>(target: &mut T, operation: &WriteOperation, skip: u64, extent: Range<u64>) -> Result<(), ApplyError>
+where T: Read + Seek {
+    let len = extent.end - extent.start;
+    let mut expected_crc = Crc32::new();
+    let mut actual_crc = Crc32::new();
+    let mut expected = vec![0u8; len.min(COPY_CHUNK_SIZE as u64) as usize];
+    let mut actual = vec![0u8; expected.len()];
+    retry_seek(target, SeekFrom::Start(extent.start))?;
+    let mut checked = 0u64;
+    let mut target_ended = false;
+    while checked < len {
+        let chunk_len = (len - checked).min(expected.len() as u64) as usize;
+        recorded_bytes(operation, skip + checked, &mut expected[0..chunk_len]);
+        expected_crc.update(&expected[0..chunk_len]);
+        let mut filled = 0;
+        while filled < chunk_len && !target_ended {
+            match retry_interrupted(|| target.read(&mut actual[filled..chunk_len]))? {
+                0 => target_ended = true,
+                bytes_read => filled += bytes_read,
+            }
+        }
+        actual_crc.update(&actual[0..filled]);
+        checked += chunk_len as u64;
+    }
+    if target_ended || expected_crc.finish() != actual_crc.finish() {
+        return Err(ApplyError::VerificationFailed { offset: extent.start, len });
+    }
+    Ok(())
+}
+
+/// Copies `len` bytes within `target` from `src` to `dst` in bounded chunks, choosing the direction so that overlapping
+/// ranges are copied correctly. Leaves the target positioned after the copied bytes. Stops early if the target comes
+/// up short while writing.
+fn copy_within<T>(target: &mut T, read: ReadFn<T>, src: u64, dst: u64, len: u64, options: &ApplyOptions, limiter: &mut RateLimiter)
+    -> std::io::Result<u64> where T: Write + Seek {
+    let mut buf = vec![0u8; len.min(COPY_CHUNK_SIZE as u64) as usize];
+    let backwards = dst > src && dst < src + len;
+    let mut copied = 0u64;
+    while copied < len {
+        if copied > 0 {
+            cancellation_point(options)?;
+        }
+        let chunk_len = (len - copied).min(buf.len() as u64);
+        let offset = if backwards { len - copied - chunk_len } else { copied };
+        let chunk = &mut buf[0..chunk_len as usize];
+
+        retry_seek(target, SeekFrom::Start(src + offset))?;
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match retry_interrupted(|| read(target, &mut chunk[filled..]))? {
+                0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                bytes_read => filled += bytes_read,
+            }
+        }
+
+        retry_seek(target, SeekFrom::Start(dst + offset))?;
+        let bytes_written = write_data(target, chunk, options, limiter)?;
+        copied += bytes_written as u64;
+        if bytes_written < chunk.len() {
+            return Ok(copied);
+        }
+    }
+    if backwards {
+        retry_seek(target, SeekFrom::Start(dst + len))?;
+    }
+    Ok(copied)
+}
+
+/// The payload of the IO error which stops an operation partway through once applying is cancelled.
+#[derive(Debug)]
+struct Cancellation;
+
+impl Display for Cancellation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "apply was cancelled")
+    }
+}
+
+impl std::error::Error for Cancellation {}
+
+/// Fails with a `Cancellation` if applying has been cancelled, between the chunks of an operation.
+fn cancellation_point(options: &ApplyOptions) -> std::io::Result<()> {
+    match options.cancelled() {
+        true => Err(std::io::Error::other(Cancellation)),
+        false => Ok(()),
+    }
+}
+
+/// Calls `operation` until it returns something other than `ErrorKind::Interrupted`, like `Write::write_all()` does.
+/// An interrupted call hasn't done anything, so it's always safe to retry.
+pub(crate) fn retry_interrupted<R>(mut operation: impl FnMut() -> std::io::Result<R>) -> std::io::Result<R> {
+    loop {
+        match operation() {
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+fn retry_write<T>(target: &mut T, buf: &[u8]) -> std::io::Result<usize> where T: Write + ?Sized {
+    retry_interrupted(|| target.write(buf))
+}
+
+/// Writes `buf` to `target` in calls no larger than `options.max_write_size`, paced by `limiter`, returning how much was
+/// written. A short write ends early unless `options.short_write_behavior` is `ShortWrite::Retry`.
+fn write_data<T>(target: &mut T, buf: &[u8], options: &ApplyOptions, limiter: &mut RateLimiter) -> std::io::Result<usize>
+where T: Write + ?Sized {
+    if buf.is_empty() {
+        return retry_write(target, buf);
+    }
+    let max_write_size = options.max_write_size.unwrap_or(usize::MAX).min(limiter.chunk_size()).max(1);
+    let mut written = 0;
+    while written < buf.len() {
+        if written > 0 {
+            cancellation_point(options)?;
+        }
+        let end = buf.len().min(written.saturating_add(max_write_size));
+        limiter.wait();
+        let bytes_written = retry_write(target, &buf[written..end])?;
+        limiter.wrote(bytes_written);
+        written += bytes_written;
+        if bytes_written == 0 || (written < end && options.short_write_behavior == ShortWrite::FailFast) {
+            break;
+        }
+    }
+    Ok(written)
+}
+
+fn retry_seek<T>(target: &mut T, pos: SeekFrom) -> std::io::Result<u64> where T: Seek + ?Sized {
+    retry_interrupted(|| target.seek(pos))
+}
+
+/// Writes `pattern` repeatedly to `target` until `len` bytes have been written, in bounded chunks which never split a
+/// repetition across chunk boundaries. Stops early if the target comes up short.
+fn write_pattern<T>(target: &mut T, pattern: &[u8], len: u64, options: &ApplyOptions, limiter: &mut RateLimiter) -> std::io::Result<u64>
+where T: Write {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+    let repetitions_per_chunk = (FILL_CHUNK_SIZE / pattern.len()).max(1) as u64;
+    let needed_repetitions = len.div_ceil(pattern.len() as u64);
+    let chunk = pattern.repeat(repetitions_per_chunk.min(needed_repetitions) as usize);
+    let mut written = 0u64;
+    while written < len {
+        if written > 0 {
+            cancellation_point(options)?;
+        }
+        let chunk_len = (len - written).min(chunk.len() as u64) as usize;
+        let bytes_written = write_data(target, &chunk[0..chunk_len], options, limiter)?;
+        written += bytes_written as u64;
+        if bytes_written < chunk_len {
+            break;
+        }
+    }
+    Ok(written)
+}
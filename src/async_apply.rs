@@ -0,0 +1,171 @@
+use std::future::poll_fn;
+use std::io::SeekFrom;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::apply::FILL_CHUNK_SIZE;
+use crate::check::Checker;
+use crate::operation::repeated_len;
+use crate::{ApplyError, CheckPolicy, WriteOperation, Yadon};
+
+/// Asynchronous targets which can be written to and seeked, for `Yadon::apply_async()`. The methods have the same
+/// contracts as those of `futures::io::AsyncWrite` and `AsyncSeek`, so wrapping a target from any async runtime only
+/// takes forwarding them.
+pub trait AsyncWriteSeek {
+    /// Attempts to write bytes from `buf`, returning how many were written.
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>>;
+    /// Attempts to flush what has been written.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>>;
+    /// Attempts to seek to `pos`, returning the new position.
+    fn poll_seek(self: Pin<&mut Self>, cx: &mut Context<'_>, pos: SeekFrom) -> Poll<std::io::Result<u64>>;
+}
+
+impl<T> AsyncWriteSeek for &mut T where T: AsyncWriteSeek + Unpin + ?Sized {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+
+    fn poll_seek(mut self: Pin<&mut Self>, cx: &mut Context<'_>, pos: SeekFrom) -> Poll<std::io::Result<u64>> {
+        Pin::new(&mut **self).poll_seek(cx, pos)
+    }
+}
+
+/// Records asynchronously, so that async code which writes to an `AsyncWriteSeek` can be given a `Yadon`. Recording
+/// never waits, so every call is ready straight away, with the result the synchronous method gives.
+impl AsyncWriteSeek for Yadon {
+    fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(self.get_mut().write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.get_mut().flush())
+    }
+
+    fn poll_seek(self: Pin<&mut Self>, _: &mut Context<'_>, pos: SeekFrom) -> Poll<std::io::Result<u64>> {
+        Poll::Ready(self.get_mut().seek(pos))
+    }
+}
+
+/// Calls `operation` until it returns something other than `ErrorKind::Interrupted`.
+async fn retry_interrupted<T, R>(target: &mut T, mut operation: impl FnMut(Pin<&mut T>, &mut Context<'_>) -> Poll<std::io::Result<R>>)
+    -> std::io::Result<R> where T: Unpin + ?Sized {
+    loop {
+        match poll_fn(|cx| operation(Pin::new(&mut *target), cx)).await {
+            Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+async fn write<T>(target: &mut T, buf: &[u8]) -> std::io::Result<usize> where T: AsyncWriteSeek + Unpin + ?Sized {
+    retry_interrupted(target, |target, cx| target.poll_write(cx, buf)).await
+}
+
+async fn seek<T>(target: &mut T, pos: SeekFrom) -> std::io::Result<u64> where T: AsyncWriteSeek + Unpin + ?Sized {
+    retry_interrupted(target, |target, cx| target.poll_seek(cx, pos)).await
+}
+
+async fn flush<T>(target: &mut T) -> std::io::Result<()> where T: AsyncWriteSeek + Unpin + ?Sized {
+    retry_interrupted(target, |target, cx| target.poll_flush(cx)).await
+}
+
+/// Writes `len` bytes of `pattern` repeated, stopping at the first short write, as the synchronous apply does.
+async fn write_pattern<T>(target: &mut T, pattern: &[u8], len: u64) -> std::io::Result<u64> where T: AsyncWriteSeek + Unpin + ?Sized {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+    let repetitions_per_chunk = (FILL_CHUNK_SIZE / pattern.len()).max(1) as u64;
+    let chunk = pattern.repeat(repetitions_per_chunk.min(len.div_ceil(pattern.len() as u64)) as usize);
+    let mut written = 0u64;
+    while written < len {
+        let chunk_len = (len - written).min(chunk.len() as u64) as usize;
+        let bytes_written = write(target, &chunk[0..chunk_len]).await?;
+        written += bytes_written as u64;
+        if bytes_written < chunk_len {
+            break;
+        }
+    }
+    Ok(written)
+}
+
+impl Yadon {
+    /// Applies the stored operations on an asynchronous target, as `apply_with_policy()` does with the default
+    /// options: seeking to `start` first, checking seeks and writes according to `check_policy`, replaying flushes and
+    /// flushing once at the end. Only writes, fills, repeats, seeks and flushes can be applied asynchronously, and base
+    /// checksums and generation stamps can't be checked; anything else fails with
+    /// `ApplyError::UnsupportedOperation` before the target is touched.
+    pub async fn apply_async<T>(&self, target: &mut T, check_policy: CheckPolicy) -> Result<usize, ApplyError>
+    where T: AsyncWriteSeek + Unpin + ?Sized {
+        self.check_can_apply()?;
+        let unsupported = self.operations.iter().find(|operation| !matches!(operation,
+            WriteOperation::Write(..)
+            | WriteOperation::Fill { .. }
+            | WriteOperation::Repeat { .. }
+            | WriteOperation::Seek(..)
+            | WriteOperation::DeferredSeek(_)
+            | WriteOperation::Flush));
+        if let Some(unsupported) = unsupported {
+            return Err(ApplyError::UnsupportedOperation(unsupported.name()));
+        }
+        if !self.base_checksums.is_empty() {
+            return Err(ApplyError::UnsupportedOperation("base checksum"));
+        }
+        if self.generation_stamp.is_some() {
+            return Err(ApplyError::UnsupportedOperation("generation stamp"));
+        }
+
+        let mut checker = Checker::new(self, check_policy);
+        let mut position = None;
+        if let Some(start) = self.start {
+            let seek_pos = seek(target, SeekFrom::Start(start)).await?;
+            position = Some(seek_pos);
+            checker.position(start, seek_pos)?;
+        }
+        let mut total_bytes_written = 0;
+        for (index, operation) in self.operations.iter().enumerate() {
+            checker.begin(index, operation, position);
+            let applied = async {
+                let bytes_written = match operation {
+                    WriteOperation::Write(data, expected_bytes_written) => {
+                        let bytes_written = write(target, data).await?;
+                        checker.written(*expected_bytes_written, bytes_written)?
+                    },
+                    WriteOperation::Fill { byte, len } => {
+                        let bytes_written = write_pattern(target, &[*byte], *len).await? as usize;
+                        checker.written(*len as usize, bytes_written)?
+                    },
+                    WriteOperation::Repeat { pattern, count } => {
+                        let expected_bytes_written = repeated_len(pattern, *count)?;
+                        let bytes_written = write_pattern(target, pattern, expected_bytes_written).await? as usize;
+                        checker.written(expected_bytes_written as usize, bytes_written)?
+                    },
+                    WriteOperation::Seek(pos, expected_position) => {
+                        let new_position = seek(target, *pos).await?;
+                        position = Some(new_position);
+                        checker.position(*expected_position, new_position)?;
+                        return Ok(0);
+                    },
+                    WriteOperation::DeferredSeek(pos) => {
+                        position = Some(seek(target, *pos).await?);
+                        return Ok(0);
+                    },
+                    WriteOperation::Flush => {
+                        flush(target).await?;
+                        return Ok(0);
+                    },
+                    _ => unreachable!("checked to be supported before starting"),
+                };
+                position = position.map(|position| position + bytes_written as u64);
+                Ok::<_, ApplyError>(bytes_written)
+            };
+            total_bytes_written += applied.await
+                .map_err(|error| error.with_context(self.location_of(index), self.label_of(index).cloned()))?;
+        }
+        flush(target).await?;
+        self.count_apply();
+        Ok(total_bytes_written)
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{Seek, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+use crate::{ApplyError, ApplyOptions, ApplyReport, Progress, Yadon};
+
+/// An apply running on a worker thread, started by `Yadon::apply_in_background()`. Dropping the handle detaches the
+/// worker, which carries on applying to the end.
+#[derive(Debug)]
+pub struct ApplyHandle {
+    thread: JoinHandle<Result<ApplyReport, ApplyError>>,
+    ops_completed: Arc<AtomicUsize>,
+    bytes_written: Arc<AtomicU64>,
+    /// The progress before anything was applied, giving the totals.
+    started: Progress,
+    cancel: Arc<AtomicBool>,
+}
+
+impl ApplyHandle {
+    /// Waits for applying to finish, and returns its outcome. If the worker panicked, so does this.
+    pub fn join(self) -> Result<ApplyReport, ApplyError> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// How far applying has got, without waiting.
+    pub fn try_progress(&self) -> Progress {
+        Progress {
+            ops_completed: self.ops_completed.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            ..self.started
+        }
+    }
+
+    /// Asks applying to stop, as `ApplyOptions::cancel` does, so that `join()` returns `ApplyError::Cancelled`
+    /// unless applying had already finished.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether applying has finished, so that `join()` won't wait.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+}
+
+impl Yadon {
+    /// Applies the stored operations on `target` from a new thread, as `apply_report()` does, returning straight away
+    /// with a handle to follow, cancel or wait for the apply. The stored operations are shared with the thread rather
+    /// than copied. If `options.cancel` is set, cancelling the handle sets that flag. A recording which is done with can
+    /// be frozen into a `ReplayLog` with `freeze()`, whose clones can each be applied in the background this way.
+    pub fn apply_in_background<T>(self: Arc<Self>, mut target: T, options: ApplyOptions) -> ApplyHandle
+    where T: Write + Seek + Send + 'static {
+        let cancel = options.cancel.clone().unwrap_or_default();
+        let options = ApplyOptions {
+            cancel: Some(cancel.clone()),
+            ..options
+        };
+        let ops_completed = Arc::new(AtomicUsize::new(0));
+        let bytes_written = Arc::new(AtomicU64::new(0));
+        let started = self.no_progress();
+        let thread = {
+            let ops_completed = ops_completed.clone();
+            let bytes_written = bytes_written.clone();
+            std::thread::spawn(move || {
+                self.apply_report_with_progress(&mut target, &options, &mut |progress| {
+                    ops_completed.store(progress.ops_completed, Ordering::Relaxed);
+                    bytes_written.store(progress.bytes_written, Ordering::Relaxed);
+                })
+            })
+        };
+        ApplyHandle { thread, ops_completed, bytes_written, started, cancel }
+    }
+}
@@ -0,0 +1,178 @@
+//! Applies, shows, verifies and exports recordings encoded by `Yadon::to_bytes()`, or written by `Yadon::to_script()`.
+
+use std::error::Error;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use clap::{value_parser, Arg, ArgMatches, Command};
+use yadon::{ApplyError, AtomicOptions, DecodeError, IpsError, Yadon};
+
+/// The arguments were wrong, the log couldn't be decoded, or it holds something this command can't do.
+const EXIT_FAILURE: u8 = 1;
+/// Reading or writing a file failed.
+const EXIT_IO: u8 = 2;
+/// Seeking or writing the file ended up somewhere other than it did while recording.
+const EXIT_DIVERGED: u8 = 3;
+/// The file doesn't hold what the log requires of it, or what the log would have written.
+const EXIT_VERIFICATION: u8 = 4;
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  bad arguments, an undecodable log, or an operation the command can't handle
+  2  an I/O error
+  3  seeks or writes diverged from the recording
+  4  verification failed: the file doesn't hold what the log requires or would have written";
+
+/// Why a command failed, with the exit code that tells it apart.
+struct Failure {
+    code: u8,
+    message: String,
+}
+
+impl Failure {
+    fn new(code: u8, message: impl Into<String>) -> Self {
+        Failure { code, message: message.into() }
+    }
+
+    fn io(path: &Path, error: std::io::Error) -> Self {
+        Failure::new(EXIT_IO, format!("{}: {}", path.display(), error))
+    }
+
+    fn apply(path: &Path, error: ApplyError) -> Self {
+        let code = match &error {
+            ApplyError::Io(_) | ApplyError::FlushFailed { .. } | ApplyError::RollbackFailed { .. } => EXIT_IO,
+            ApplyError::SeekDiverged(_) | ApplyError::NumBytesWrittenDiverge(_) => EXIT_DIVERGED,
+            ApplyError::TargetTooShort { .. }
+            | ApplyError::VerificationFailed { .. }
+            | ApplyError::BaseChecksumMismatch(_)
+            | ApplyError::GenerationNotNewer { .. }
+            | ApplyError::PreconditionFailed { .. } => EXIT_VERIFICATION,
+            _ => EXIT_FAILURE,
+        };
+        Failure::new(code, format!("{}: {}", path.display(), chain(&error)))
+    }
+}
+
+/// `error` followed by each of its sources.
+fn chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        source = error.source();
+    }
+    message
+}
+
+fn command() -> Command {
+    let path = |name: &'static str, help: &'static str| Arg::new(name).help(help).required(true).value_parser(value_parser!(PathBuf));
+    let log = || path("LOG", "A recording encoded by Yadon::to_bytes(), or a script written by Yadon::to_script()");
+    Command::new("yadon")
+        .about("Applies, shows, verifies and exports recorded write operations")
+        .after_help(EXIT_CODES)
+        .subcommand_required(true)
+        .subcommand(Command::new("apply")
+            .about("Applies the log to FILE, which is left unchanged if applying fails")
+            .arg(log())
+            .arg(path("FILE", "The file to patch, which must already exist")))
+        .subcommand(Command::new("show")
+            .about("Prints the operations in the log, one per line")
+            .arg(log()))
+        .subcommand(Command::new("verify")
+            .about("Checks that the log lines up with FILE and that FILE already reflects every operation in it")
+            .arg(log())
+            .arg(path("FILE", "The file to check, which isn't written to")))
+        .subcommand(Command::new("export-ips")
+            .about("Writes the log's writes as an IPS patch")
+            .arg(log())
+            .arg(path("OUT", "Where to write the IPS patch")))
+}
+
+fn path_arg<'m>(matches: &'m ArgMatches, name: &str) -> &'m Path {
+    matches.get_one::<PathBuf>(name).expect("path arguments are required")
+}
+
+/// Reads the log at `path`, trying it as a script if it isn't an encoded recording.
+fn load(path: &Path) -> Result<Yadon, Failure> {
+    let bytes = std::fs::read(path).map_err(|error| Failure::io(path, error))?;
+    let error = match Yadon::from_bytes(&bytes) {
+        Ok(yadon) => return Ok(yadon),
+        Err(error) => error,
+    };
+    match (&error, std::str::from_utf8(&bytes)) {
+        (DecodeError::BadMagic, Ok(script)) => Yadon::from_script(script)
+            .map_err(|error| Failure::new(EXIT_FAILURE, format!("{}: {}", path.display(), error))),
+        _ => Err(Failure::new(EXIT_FAILURE, format!("{}: {}", path.display(), error))),
+    }
+}
+
+fn apply(log: &Yadon, file: &Path) -> Result<(), Failure> {
+    let report = log.apply_atomic(file, &AtomicOptions::default()).map_err(|error| Failure::apply(file, error))?;
+    println!("applied {} operations, writing {} bytes", report.ops_applied, report.bytes_written);
+    Ok(())
+}
+
+fn verify(log: &Yadon, path: &Path) -> Result<(), Failure> {
+    let mut file = File::open(path).map_err(|error| Failure::io(path, error))?;
+    let validation = log.validate_against(&mut file).map_err(|error| Failure::apply(path, error))?;
+    if let Some(truncated) = validation.truncated_writes.first() {
+        return Err(Failure::new(EXIT_VERIFICATION, format!(
+            "{}: operation {} writes {} bytes at {:#x}, but only {} fit in the file",
+            path.display(), truncated.op_index, truncated.len, truncated.offset, truncated.fits,
+        )));
+    }
+    let detected = log.detect_applied(&mut file).map_err(|error| Failure::apply(path, error))?;
+    match detected.first_mismatch {
+        Some(op_index) => Err(Failure::new(EXIT_VERIFICATION, format!(
+            "{}: reflects the first {} operations, but not operation {}",
+            path.display(), detected.ops_applied, op_index,
+        ))),
+        None => {
+            println!("{} reflects all {} operations", path.display(), detected.ops_applied);
+            Ok(())
+        },
+    }
+}
+
+fn export_ips(log: &Yadon, log_path: &Path, out: &Path) -> Result<(), Failure> {
+    let patch = log.to_ips().map_err(|error| match error {
+        IpsError::Apply(error) => Failure::apply(log_path, error),
+        error => Failure::new(EXIT_FAILURE, format!("{}: {}", log_path.display(), error)),
+    })?;
+    std::fs::write(out, &patch).map_err(|error| Failure::io(out, error))
+}
+
+fn run(matches: &ArgMatches) -> Result<(), Failure> {
+    let (name, matches) = matches.subcommand().expect("a subcommand is required");
+    let log_path = path_arg(matches, "LOG");
+    let log = load(log_path)?;
+    match name {
+        "apply" => apply(&log, path_arg(matches, "FILE")),
+        "show" => {
+            print!("{}", log);
+            Ok(())
+        },
+        "verify" => verify(&log, path_arg(matches, "FILE")),
+        "export-ips" => export_ips(&log, log_path, path_arg(matches, "OUT")),
+        _ => unreachable!("every subcommand is handled"),
+    }
+}
+
+fn main() -> ExitCode {
+    let matches = match command().try_get_matches() {
+        Ok(matches) => matches,
+        Err(error) => {
+            let _ = error.print();
+            return if error.use_stderr() { ExitCode::from(EXIT_FAILURE) } else { ExitCode::SUCCESS };
+        },
+    };
+    match run(&matches) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            eprintln!("yadon: {}", failure.message);
+            ExitCode::from(failure.code)
+        },
+    }
+}
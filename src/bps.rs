@@ -0,0 +1,279 @@
+//! Exporting the stored operations as a BPS patch, and recording the writes of one. A patch is `BPS1`, then the source
+//! length, target length and metadata length as varints, the metadata, then actions until the 12 byte footer, which is
+//! the CRC-32s of the source, the target and the rest of the patch, each 4 little-endian bytes.
+//!
+//! A varint holds 7 bits in each byte, least significant first, with the top bit set on the last byte, and each byte
+//! after the first counting from one past the largest value the bytes before it could hold. An action is a varint of
+//! its length less one, shifted left by two, with the kind of action in the low two bits. The target is written from
+//! its start, with each action writing its length in bytes:
+//!
+//! - `SourceRead`, 0, copies the bytes of the source at the same offset.
+//! - `TargetRead`, 1, writes the bytes which follow it in the patch.
+//! - `SourceCopy`, 2, and `TargetCopy`, 3, copy bytes from the source or from what's been written of the target, from
+//!   an offset kept for each. The offset is first moved by a varint of its distance shifted left by one, with the low bit
+//!   set if it moves backwards, and moves forward past each byte copied. A `TargetCopy` may copy bytes which it has
+//!   itself written.
+
+use core::convert::TryFrom;
+use core::ops::Range;
+use std::io::{SeekFrom, Write};
+use crate::crc::crc32;
+use crate::{BpsError, Yadon};
+
+/// What every BPS patch starts with.
+const MAGIC: &[u8; 4] = b"BPS1";
+/// The length of the CRC-32s every BPS patch ends with.
+const FOOTER_LEN: usize = 12;
+
+const SOURCE_READ: u64 = 0;
+const TARGET_READ: u64 = 1;
+const SOURCE_COPY: u64 = 2;
+const TARGET_COPY: u64 = 3;
+
+/// The shortest run of one byte which `to_bps()` writes as one byte followed by a `TargetCopy` of it, rather than
+/// as part of a `TargetRead`.
+const RUN_MIN_LEN: usize = 4;
+
+impl Yadon {
+    /// Exports the stored operations as a BPS patch against `source`, which any BPS patcher can apply to `source` to
+    /// give what `materialize()` lays down onto it. Bytes which are the same as in the source are copied from it, runs
+    /// of one byte are copied from the target as they're written, and anything else is written out in the patch.
+    pub fn to_bps(&self, source: &[u8]) -> Result<Vec<u8>, BpsError> {
+        let target = self.materialize(Some(source))?;
+        let mut patch = MAGIC.to_vec();
+        put_varint(&mut patch, source.len() as u64);
+        put_varint(&mut patch, target.len() as u64);
+        put_varint(&mut patch, 0);
+
+        let unchanged = |i: usize| source.get(i) == Some(&target[i]);
+        // Where the bytes not yet written out, which are written out in the patch, start.
+        let mut literal = 0;
+        let mut target_offset = 0;
+        let mut i = 0;
+        while i < target.len() {
+            if unchanged(i) {
+                put_target_read(&mut patch, &target[literal..i]);
+                let len = (i..target.len()).take_while(|&j| unchanged(j)).count();
+                put_action(&mut patch, SOURCE_READ, len);
+                i += len;
+                literal = i;
+                continue;
+            }
+            let run = (i..target.len()).take_while(|&j| target[j] == target[i] && !unchanged(j)).count();
+            if run >= RUN_MIN_LEN {
+                // The first byte is written out, and each of the rest is copied from the byte before it.
+                put_target_read(&mut patch, &target[literal..i + 1]);
+                put_action(&mut patch, TARGET_COPY, run - 1);
+                put_offset(&mut patch, &mut target_offset, i as u64);
+                target_offset += run as u64 - 1;
+                literal = i + run;
+            }
+            i += run;
+        }
+        put_target_read(&mut patch, &target[literal..]);
+
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(&target).to_le_bytes());
+        patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+        Ok(patch)
+    }
+
+    /// Records the writes which the BPS patch `patch` makes to `source`. The patch is checked against its own CRC-32,
+    /// `source` against the length and CRC-32 the patch was made against, and the patched source against the CRC-32 it
+    /// should have, before anything is recorded. Each run of actions which doesn't just copy the source in place
+    /// becomes a seek from the start, followed by a write of the bytes it gives. The recording requires the target to
+    /// have the source's CRC-32, as `require_base_checksum()` does, or if the patch shortens the source, to have the
+    /// CRC-32 of as much of the source as is kept. Its length is the source's, and if the patch changes the length,
+    /// resizing to the new length is recorded before the writes, so `materialize()` should be used to lay it down onto a
+    /// copy of the source. The metadata is skipped.
+    pub fn from_bps(patch: &[u8], source: &[u8]) -> Result<Yadon, BpsError> {
+        if !patch.starts_with(MAGIC) {
+            return Err(BpsError::BadMagic);
+        }
+        let footer = patch.len().checked_sub(FOOTER_LEN).filter(|&footer| footer >= MAGIC.len())
+            .ok_or(BpsError::Truncated { offset: MAGIC.len() })?;
+        let expected = |at: usize| u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]]);
+        let actual = crc32(&patch[..footer + 8]);
+        if actual != expected(footer + 8) {
+            return Err(BpsError::PatchChecksum { expected: expected(footer + 8), actual });
+        }
+
+        let mut input = Input { patch: &patch[..footer], offset: MAGIC.len() };
+        let source_len = input.varint()?;
+        if source_len != source.len() as u64 {
+            return Err(BpsError::SourceSize { expected: source_len, actual: source.len() as u64 });
+        }
+        let actual = crc32(source);
+        if actual != expected(footer) {
+            return Err(BpsError::SourceChecksum { expected: expected(footer), actual });
+        }
+        let target_len_offset = input.offset;
+        let target_len = input.varint()?;
+        let metadata_len = input.len()?;
+        input.take(metadata_len)?;
+
+        let mut target = Vec::new();
+        usize::try_from(target_len).ok().and_then(|target_len| target.try_reserve_exact(target_len).ok())
+            .ok_or(BpsError::Invalid { offset: target_len_offset, reason: "target is too long to hold in memory" })?;
+        // The ranges of the target which aren't copied from the source in place, merged where they touch.
+        let mut written: Vec<Range<usize>> = Vec::new();
+        let (mut source_offset, mut target_offset) = (0u64, 0u64);
+        while input.offset < footer {
+            let action_offset = input.offset;
+            let action = input.varint()?;
+            let len = usize::try_from((action >> 2) + 1)
+                .map_err(|_| BpsError::Invalid { offset: action_offset, reason: "action is too long" })?;
+            let start = target.len();
+            let end = start.checked_add(len).filter(|&end| end as u64 <= target_len)
+                .ok_or(BpsError::Invalid { offset: action_offset, reason: "action writes past the end of the target" })?;
+            let past_source = BpsError::Invalid { offset: action_offset, reason: "action reads past the end of the source" };
+            match action & 3 {
+                SOURCE_READ => target.extend_from_slice(source.get(start..end).ok_or(past_source)?),
+                TARGET_READ => target.extend_from_slice(input.take(len)?),
+                SOURCE_COPY => {
+                    source_offset = input.offset_from(source_offset)?;
+                    let from = usize::try_from(source_offset).ok()
+                        .and_then(|from| source.get(from..from.checked_add(len)?))
+                        .ok_or(past_source)?;
+                    target.extend_from_slice(from);
+                    source_offset += len as u64;
+                },
+                _ => {
+                    target_offset = input.offset_from(target_offset)?;
+                    if target_offset >= start as u64 {
+                        return Err(BpsError::Invalid { offset: action_offset, reason: "action copies target not yet written" });
+                    }
+                    // Copied a byte at a time, as the copy may read what it's writing.
+                    for from in target_offset as usize..target_offset as usize + len {
+                        target.push(target[from]);
+                    }
+                    target_offset += len as u64;
+                },
+            }
+            if action & 3 != SOURCE_READ {
+                match written.last_mut() {
+                    Some(last) if last.end == start => last.end = end,
+                    _ => written.push(start..end),
+                }
+            }
+        }
+        if target.len() as u64 != target_len {
+            return Err(BpsError::Invalid { offset: footer, reason: "actions end before the end of the target" });
+        }
+        let actual = crc32(&target);
+        if actual != expected(footer + 4) {
+            return Err(BpsError::TargetChecksum { expected: expected(footer + 4), actual });
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(source_len));
+        if target_len < source_len {
+            yadon.require_base_checksum(0..target_len, crc32(&source[..target_len as usize]));
+        } else {
+            yadon.require_base_checksum(0..source_len, expected(footer));
+        }
+        if target_len != source_len {
+            yadon.set_len(target_len).expect("resizing can't fail");
+        }
+        for range in written {
+            yadon.seek(SeekFrom::Start(range.start as u64)).expect("seeking from the start can't fail");
+            yadon.write_all(&target[range]).expect("writes within the length can't fail");
+        }
+        Ok(yadon)
+    }
+}
+
+/// The rest of a patch being read, before its footer.
+struct Input<'a> {
+    patch: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BpsError> {
+        let taken = self.offset.checked_add(len).and_then(|end| self.patch.get(self.offset..end))
+            .ok_or(BpsError::Truncated { offset: self.offset })?;
+        self.offset += len;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64, BpsError> {
+        let start = self.offset;
+        read_varint(self.patch, &mut self.offset).map_err(|error| match error {
+            VarintError::Truncated => BpsError::Truncated { offset: start },
+            VarintError::TooLarge => BpsError::Invalid { offset: start, reason: "varint doesn't fit in 64 bits" },
+        })
+    }
+
+    fn len(&mut self) -> Result<usize, BpsError> {
+        let start = self.offset;
+        usize::try_from(self.varint()?).map_err(|_| BpsError::Invalid { offset: start, reason: "length doesn't fit in memory" })
+    }
+
+    /// Reads how far a copy's offset moves, and moves `offset` by it.
+    fn offset_from(&mut self, offset: u64) -> Result<u64, BpsError> {
+        let start = self.offset;
+        let delta = self.varint()?;
+        let moved = match delta & 1 {
+            0 => offset.checked_add(delta >> 1),
+            _ => offset.checked_sub(delta >> 1),
+        };
+        moved.ok_or(BpsError::Invalid { offset: start, reason: "copy offset moves out of range" })
+    }
+}
+
+/// Why a varint couldn't be read.
+pub(crate) enum VarintError {
+    /// The input ended before the last byte of the varint.
+    Truncated,
+    /// The varint doesn't fit in a `u64`.
+    TooLarge,
+}
+
+/// Reads a varint, as BPS and UPS patches encode them, from `input` at `offset`, moving `offset` past it.
+pub(crate) fn read_varint(input: &[u8], offset: &mut usize) -> Result<u64, VarintError> {
+    let (mut value, mut shift) = (0u64, 1u64);
+    loop {
+        let byte = *input.get(*offset).ok_or(VarintError::Truncated)?;
+        *offset += 1;
+        value = u64::from(byte & 0x7f).checked_mul(shift).and_then(|bits| value.checked_add(bits)).ok_or(VarintError::TooLarge)?;
+        if byte & 0x80 != 0 {
+            return Ok(value);
+        }
+        shift = shift.checked_mul(0x80).ok_or(VarintError::TooLarge)?;
+        value = value.checked_add(shift).ok_or(VarintError::TooLarge)?;
+    }
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let bits = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | bits);
+            return;
+        }
+        out.push(bits);
+        value -= 1;
+    }
+}
+
+fn put_action(out: &mut Vec<u8>, action: u64, len: usize) {
+    put_varint(out, ((len as u64 - 1) << 2) | action);
+}
+
+/// Moves the copy offset `from` to `to`.
+fn put_offset(out: &mut Vec<u8>, from: &mut u64, to: u64) {
+    match to.checked_sub(*from) {
+        Some(forwards) => put_varint(out, forwards << 1),
+        None => put_varint(out, (*from - to) << 1 | 1),
+    }
+    *from = to;
+}
+
+/// Writes out `bytes` as a `TargetRead`, if there are any.
+fn put_target_read(out: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        put_action(out, TARGET_READ, bytes.len());
+        out.extend_from_slice(bytes);
+    }
+}
@@ -0,0 +1,138 @@
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::sync::atomic::Ordering;
+use crate::{ApplyError, Confusion, Divergence, DivergenceKind, WriteOperation, Yadon};
+
+/// Which results of applying the stored operations are compared with the simulated return values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckPolicy {
+    /// Check both seek positions and the number of bytes written.
+    Strict,
+    /// Only check seek positions, including the initial seek to `start`.
+    SeeksOnly,
+    /// Only check the number of bytes written.
+    WritesOnly,
+    /// Don't check anything.
+    None,
+}
+
+impl CheckPolicy {
+    /// Whether seek positions are checked.
+    pub fn checks_seeks(self) -> bool {
+        matches!(self, CheckPolicy::Strict | CheckPolicy::SeeksOnly)
+    }
+
+    /// Whether the number of bytes written is checked.
+    pub fn checks_writes(self) -> bool {
+        matches!(self, CheckPolicy::Strict | CheckPolicy::WritesOnly)
+    }
+}
+
+/// Compares the results of applying operations with their simulated values, according to the check policy.
+pub(crate) struct Checker<'y> {
+    yadon: &'y Yadon,
+    policy: CheckPolicy,
+    /// Index of the operation being applied, or `None` before the first operation.
+    pub(crate) index: Option<usize>,
+    /// The target offset which the operation being applied addresses, if known.
+    offset: Option<u64>,
+    /// Length of the data which the operation being applied writes, if it writes any.
+    len: Option<usize>,
+    /// If set, divergences are collected here instead of failing the apply.
+    pub(crate) divergences: Option<Vec<Divergence>>,
+}
+
+impl<'y> Checker<'y> {
+    pub(crate) fn new(yadon: &'y Yadon, policy: CheckPolicy) -> Self {
+        Checker {
+            yadon,
+            policy,
+            index: None,
+            offset: None,
+            len: None,
+            divergences: None,
+        }
+    }
+
+    /// Starts checking the operation at `index`, with the target at `position`.
+    pub(crate) fn begin(&mut self, index: usize, operation: &WriteOperation, position: Option<u64>) {
+        self.index = Some(index);
+        self.offset = match operation {
+            WriteOperation::CopyWithin { dst, .. } => Some(*dst),
+            WriteOperation::AssertBytes { offset, .. } => Some(*offset),
+            _ => position,
+        };
+        self.len = match operation {
+            WriteOperation::Write(data, _) => Some(data.len()),
+            WriteOperation::Fill { len, .. } | WriteOperation::CopyWithin { len, .. } => Some(*len as usize),
+            WriteOperation::Repeat { pattern, count } => (pattern.len() as u64).checked_mul(*count).map(|len| len as usize),
+            _ => None,
+        };
+    }
+
+    /// Compares the number of bytes an operation wrote with the simulated value, if the check policy includes writes.
+    pub(crate) fn written(&mut self, expected: usize, actual: usize) -> Result<usize, ApplyError> {
+        if self.policy.checks_writes() && expected != actual {
+            let confusion = self.confusion(expected, actual);
+            self.diverged(DivergenceKind::BytesWritten(confusion))?;
+        }
+        Ok(actual)
+    }
+
+    /// Compares the position after a seek with the simulated value, if the check policy includes seeks.
+    pub(crate) fn position(&mut self, expected: u64, actual: u64) -> Result<(), ApplyError> {
+        if self.policy.checks_seeks() && expected != actual {
+            let confusion = self.confusion(expected, actual);
+            self.diverged(DivergenceKind::Seek(confusion))?;
+        }
+        Ok(())
+    }
+
+    fn confusion<V>(&self, expected: V, actual: V) -> Confusion<V> where V: Debug {
+        let mut confusion = Confusion::new(expected, actual);
+        confusion.op_index = self.index;
+        confusion.offset = self.offset;
+        confusion.len = self.len;
+        if let Some(index) = self.index {
+            confusion.location = self.yadon.location_of(index);
+            confusion.label = self.yadon.label_of(index).cloned();
+        }
+        confusion
+    }
+
+    fn diverged(&mut self, kind: DivergenceKind) -> Result<(), ApplyError> {
+        match &mut self.divergences {
+            Some(divergences) => {
+                divergences.push(Divergence { index: self.index, kind });
+                Ok(())
+            },
+            None => Err(kind.into()),
+        }
+    }
+}
+
+impl Yadon {
+    /// Fails if the stored operations can't be applied from `operations`, as they're in the store given to
+    /// `with_store()`, or if they've already been applied as many times as `max_applies()` allows.
+    pub(crate) fn check_can_apply(&self) -> Result<(), ApplyError> {
+        #[cfg(feature = "std")]
+        if self.store.is_some() {
+            return Err(ApplyError::InStore);
+        }
+        self.check_apply_limit()
+    }
+
+    /// Fails if the stored operations have already been applied as many times as `max_applies()` allows.
+    pub(crate) fn check_apply_limit(&self) -> Result<(), ApplyError> {
+        match self.max_applies() {
+            Some(max_applies) if self.applied_count() >= max_applies => {
+                Err(ApplyError::ApplyLimitReached { applied: self.applied_count(), max_applies })
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn count_apply(&self) {
+        self.applied.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::fork::ConcatenateError;
+#[cfg(feature = "std")]
+use crate::{SetLen, SyncTarget};
+use crate::{ComposeError, LengthMode, WriteOperation, Yadon};
+
+impl Yadon {
+    /// Makes one recording whose effect is that of applying this one, then `other`, to the same target. The operations
+    /// are copied, with a seek to `other.start`, if it has one, between them, so that `other` starts where it was
+    /// recorded to rather than wherever this one ends. The composed recording has this one's `start`, base checksums
+    /// and apply limit, `other`'s generation stamp, and `other`'s length, if it has one, or else this one's, along with
+    /// `other`'s recording settings, so that recording can carry on into it.
+    ///
+    /// Applying this recording onto `other`, as `Yadon` is `Write + Seek`, also records what applying it would do, but
+    /// only works as long as `other` has this recording's length, so that seeks from the end land where they were
+    /// recorded to, and records each fill or repeated write as the writes it's applied as. Composing has neither
+    /// limitation.
+    ///
+    /// Fails with `ComposeError::LengthMismatch` if this recording leaves its length other than the length `other` was
+    /// recorded against, as they can't both be of the same target; that's only known if `other` neither resizes nor
+    /// grows its length. Fails with `ComposeError::BaseChecksums` if `other` requires base checksums, or with
+    /// `ComposeError::GenerationStamp` if this recording stamps a generation, as neither can be checked or stamped
+    /// partway through applying. Fails with `ComposeError::UnsupportedOperation` if either holds a
+    /// `WriteOperation::Custom`, which can't be copied, and with `ComposeError::Inconsistent` if `other` has no `start`
+    /// and its seeks don't agree with where this recording leaves the position. Fails with `ComposeError::InStore` if
+    /// the operations of either are in the store given to `with_store()`.
+    /// # Example
+    /// ```
+    /// use yadon::Yadon;
+    /// use yadon::io::SeekFrom;
+    /// let mut first = Yadon::new(Some(0), Some(8));
+    /// first.write(b"first").unwrap();
+    /// let mut second = Yadon::new(Some(4), Some(8));
+    /// second.seek(SeekFrom::End(-2)).unwrap();
+    /// second.write(b"2!").unwrap();
+    ///
+    /// let composed = first.compose(&second).unwrap();
+    /// let mut target = [0u8; 8];
+    /// composed.materialize_into_slice(&mut target).unwrap();
+    /// assert_eq!(&target, b"first\x002!");
+    /// ```
+    pub fn compose(&self, other: &Yadon) -> Result<Yadon, ComposeError> {
+        if self.in_store() || other.in_store() {
+            return Err(ComposeError::InStore);
+        }
+        let resizes = other.operations.iter().any(|operation| matches!(operation, WriteOperation::SetLen(_)));
+        if let (Some(first), Some(second), LengthMode::Fixed, false) = (self.length, other.length, other.length_mode, resizes) {
+            if first != second {
+                return Err(ComposeError::LengthMismatch { first, second });
+            }
+        }
+        if !other.base_checksums.is_empty() {
+            return Err(ComposeError::BaseChecksums);
+        }
+        if self.generation_stamp.is_some() {
+            return Err(ComposeError::GenerationStamp);
+        }
+        let mut composed = Yadon::concatenate(&[self, other]).map_err(|error| match error {
+            ConcatenateError::Uncopyable(name) => ComposeError::UnsupportedOperation(name),
+            ConcatenateError::Inconsistent(reason) => ComposeError::Inconsistent(reason),
+            ConcatenateError::InStore => ComposeError::InStore,
+        })?;
+        composed.length = other.length.or(self.length);
+        composed.generation_stamp = other.generation_stamp;
+        Ok(composed)
+    }
+}
+
+/// Records resizing, as `Yadon::set_len()` does, so that `apply_with_setlen()` can apply a recording onto a `Yadon`.
+#[cfg(feature = "std")]
+impl SetLen for Yadon {
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        Yadon::set_len(self, len)
+    }
+}
+
+/// Records a durability barrier, as `Yadon::sync_barrier()` does, so that `apply_durable()` can apply a recording onto
+/// a `Yadon`.
+#[cfg(feature = "std")]
+impl SyncTarget for Yadon {
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.sync_barrier()
+    }
+}
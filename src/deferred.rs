@@ -0,0 +1,91 @@
+use std::io::{Seek, SeekFrom, Write};
+use crate::{LengthMode, Yadon};
+
+/// A `Write + Seek` which leaves its target untouched until it's flushed. Writes and seeks are recorded, as a `Yadon`
+/// records them, against the target's position and length when it was wrapped or last flushed, and growing past its
+/// end as a `Cursor<Vec<u8>>` does. `flush()` applies them to the target, then flushes it.
+///
+/// Dropping a `DeferredWriter` discards whatever hasn't been flushed, as does `into_inner()`. Call `commit()` to apply
+/// it and get the target back.
+/// # Example
+/// ```
+/// use yadon::DeferredWriter;
+/// use std::io::{Cursor, Seek, SeekFrom, Write};
+/// let mut writer = DeferredWriter::new(Cursor::new(vec![0u8; 4])).unwrap();
+/// writer.seek(SeekFrom::End(0)).unwrap();
+/// writer.write_all(b"more").unwrap();
+/// assert_eq!(writer.get_ref().get_ref(), &[0; 4]);
+/// writer.flush().unwrap();
+/// assert_eq!(writer.get_ref().get_ref(), b"\0\0\0\0more");
+/// ```
+#[derive(Debug)]
+pub struct DeferredWriter<T> {
+    target: T,
+    pending: Yadon,
+}
+
+impl<T> DeferredWriter<T> where T: Write + Seek {
+    /// Wraps `target`, leaving it positioned where it was.
+    pub fn new(mut target: T) -> std::io::Result<Self> {
+        let pending = recording_for(&mut target)?;
+        Ok(DeferredWriter { target, pending })
+    }
+
+    /// The target, which hasn't been written to since the last flush.
+    pub fn get_ref(&self) -> &T {
+        &self.target
+    }
+
+    /// What has been written since the last flush, and will be applied by the next.
+    pub fn pending(&self) -> &Yadon {
+        &self.pending
+    }
+
+    /// Discards whatever hasn't been flushed, and unwraps the target.
+    pub fn into_inner(self) -> T {
+        self.target
+    }
+
+    /// Flushes, then unwraps the target. The target can't be had back if this fails, so flush first if it's needed
+    /// afterwards.
+    #[must_use = "the target is dropped if committing fails"]
+    pub fn commit(mut self) -> std::io::Result<T> {
+        self.flush()?;
+        Ok(self.target)
+    }
+}
+
+/// A recording against where `target` is positioned and how long it is, leaving it positioned where it was.
+fn recording_for<T>(target: &mut T) -> std::io::Result<Yadon> where T: Seek {
+    let position = target.stream_position()?;
+    let length = target.seek(SeekFrom::End(0))?;
+    target.seek(SeekFrom::Start(position))?;
+    Ok(Yadon::new(Some(position), Some(length)).with_length_mode(LengthMode::Growable))
+}
+
+impl<T> Write for DeferredWriter<T> where T: Write + Seek {
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.write(buf)
+    }
+
+    /// Applies what has been written since the last flush to the target, then flushes the target and starts recording
+    /// afresh from where it was left. If applying fails, with an `ApplyError` which can be had with
+    /// `io::Error::get_ref()` and `downcast_ref::<ApplyError>()` unless it was an I/O error, the target may be partly
+    /// written, and what was written is kept, to be applied again by the next flush.
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.pending.operations.is_empty() {
+            self.pending.apply(&mut self.target, true)?;
+        }
+        self.target.flush()?;
+        self.pending = recording_for(&mut self.target)?;
+        Ok(())
+    }
+}
+
+impl<T> Seek for DeferredWriter<T> where T: Write + Seek {
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.pending.seek(pos)
+    }
+}
@@ -0,0 +1,60 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::io::SeekFrom;
+use crate::{DiffError, LengthMode, Yadon};
+
+/// Options for `Yadon::from_diff()`.
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Runs of differing bytes separated by fewer than this many identical bytes are written as one run, identical
+    /// bytes included, trading a longer patch for fewer operations. At 0, runs are never joined.
+    pub join_gap: usize,
+    /// How `new` being longer than `old` is recorded. With `LengthMode::Fixed`, the recording's `length` is that of
+    /// `new`, so the target must already be that long, or grow as it's written past its end. With
+    /// `LengthMode::Growable`, it starts as the length of `old`, and the recording writes past it with that mode.
+    pub length_mode: LengthMode,
+    /// Whether `new` being shorter than `old` is recorded as resizing the target, which can only be applied by
+    /// `apply_with_setlen()` or `materialize()`. If not set, it fails with `DiffError::Shrinks`.
+    pub allow_set_len: bool,
+}
+
+impl Yadon {
+    /// Records the writes which turn `old` into `new`: each run of bytes which differ becomes a seek from the start,
+    /// followed by a write of the bytes of `new`. Bytes past the end of `old` always differ. Runs separated by fewer
+    /// than `options.join_gap` identical bytes are joined. If `new` is shorter, resizing to its length is recorded
+    /// before the writes, if `options.allow_set_len` is set.
+    pub fn from_diff(old: &[u8], new: &[u8], options: DiffOptions) -> Result<Yadon, DiffError> {
+        let (old_len, new_len) = (old.len() as u64, new.len() as u64);
+        if new_len < old_len && !options.allow_set_len {
+            return Err(DiffError::Shrinks { old_len, new_len });
+        }
+        let differs = |i: usize| old.get(i) != Some(&new[i]);
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        let mut i = 0;
+        while i < new.len() {
+            if !differs(i) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            i += (i..new.len()).take_while(|&j| differs(j)).count();
+            match runs.last_mut() {
+                Some(last) if start - last.end < options.join_gap => last.end = i,
+                _ => runs.push(start..i),
+            }
+        }
+
+        let mut yadon = match options.length_mode {
+            LengthMode::Fixed => Yadon::new(Some(0), Some(old_len.max(new_len))),
+            LengthMode::Growable => Yadon::new(Some(0), Some(old_len)).with_length_mode(LengthMode::Growable),
+        };
+        if new_len < old_len {
+            yadon.set_len(new_len).expect("resizing can't fail");
+        }
+        for run in runs {
+            yadon.seek(SeekFrom::Start(run.start as u64)).expect("seeking from the start can't fail");
+            yadon.write(&new[run]).expect("writes within the length can't fail");
+        }
+        Ok(yadon)
+    }
+}
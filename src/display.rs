@@ -0,0 +1,177 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Display};
+use crate::io::SeekFrom;
+use crate::{WriteOperation, Yadon};
+
+/// How many bytes of each byte string `Debug` shows, unless a precision is given.
+const DEBUG_PREVIEW_BYTES: usize = 32;
+/// How many bytes of each operation `Display` shows, unless a precision is given.
+const DISPLAY_PREVIEW_BYTES: usize = 8;
+
+/// Shows the fields as a derived `Debug` would, except that no more than the formatter's precision, or 32, bytes of
+/// each write, repeated pattern or expected precondition are shown, followed by how many more there are, so that a
+/// recording of large writes can be logged. `{:.8?}` shows 8 bytes of each, for example. A store given to
+/// `with_store()` is shown as the number of operations in it, as is the recording this one was forked from.
+impl Debug for Yadon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = f.precision().unwrap_or(DEBUG_PREVIEW_BYTES);
+        let operations: Vec<_> = self.operations.iter().map(|operation| DebugOperation(operation, limit)).collect();
+        let mut debug = f.debug_struct("Yadon");
+        debug.field("operations", &operations)
+            .field("virtual_position", &self.virtual_position)
+            .field("start", &self.start)
+            .field("length", &self.length)
+            .field("defer_end_seeks", &self.defer_end_seeks)
+            .field("append_only", &self.append_only)
+            .field("deny_overwrite", &self.deny_overwrite)
+            .field("written_extents", &self.written_extents)
+            .field("reserved_regions", &self.reserved_regions)
+            .field("end_unresolved", &self.end_unresolved)
+            .field("written_end", &self.written_end)
+            .field("overflow_policy", &self.overflow_policy)
+            .field("length_mode", &self.length_mode);
+        #[cfg(feature = "track-callers")]
+        debug.field("locations", &self.locations);
+        debug.field("labels", &self.labels)
+            .field("base_checksums", &self.base_checksums)
+            .field("applied", &self.applied)
+            .field("max_applies", &self.max_applies)
+            .field("generation_stamp", &self.generation_stamp)
+            .field("gap_fill", &self.gap_fill);
+        #[cfg(feature = "std")]
+        debug.field("guard_error", &self.guard_error)
+            .field("store", &self.store.as_ref().map(|store| store.len()))
+            .field("store_failed", &self.store_failed);
+        #[cfg(feature = "fs")]
+        debug.field("refuse_custom", &self.refuse_custom);
+        debug.field("parent", &self.parent.as_ref().map(|parent| parent.operations.len()));
+        debug.finish()
+    }
+}
+
+/// An operation, shown as its derived `Debug` would, with its bytes cut short.
+struct DebugOperation<'a>(&'a WriteOperation, usize);
+
+impl Debug for DebugOperation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = self.1;
+        match self.0 {
+            WriteOperation::Write(data, len) => f.debug_tuple("Write").field(&DebugBytes(data, limit)).field(len).finish(),
+            WriteOperation::Repeat { pattern, count } => {
+                f.debug_struct("Repeat").field("pattern", &DebugBytes(pattern, limit)).field("count", count).finish()
+            },
+            WriteOperation::AssertBytes { offset, expected } => {
+                f.debug_struct("AssertBytes").field("offset", offset).field("expected", &DebugBytes(expected, limit)).finish()
+            },
+            operation => operation.fmt(f),
+        }
+    }
+}
+
+/// Bytes, shown as a list of no more than `.1` of them, followed by how many more there are.
+struct DebugBytes<'a>(&'a [u8], usize);
+
+impl Debug for DebugBytes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (bytes, limit) = (self.0, self.1);
+        let mut list = f.debug_list();
+        list.entries(bytes.iter().take(limit));
+        if bytes.len() > limit {
+            list.entry(&format_args!("... +{} bytes", bytes.len() - limit));
+        }
+        list.finish()
+    }
+}
+
+/// Renders the stored operations as a plan, one line per operation: its index, its kind, the range of the target it
+/// writes, or checks, or where it moves the position to, how many bytes it writes, and the first of them in hex. The
+/// formatter's precision gives how many bytes to show, or 8 if it isn't given. Where a range depends on an end which
+/// wasn't known when recording, it's shown as `?`. Operations in the store given to `with_store()` are read back from
+/// it, failing with `fmt::Error` if it can't be read.
+/// # Example
+/// ```
+/// use yadon::Yadon;
+/// use yadon::io::SeekFrom;
+/// let mut yadon = Yadon::new(Some(0), Some(32));
+/// yadon.seek(SeekFrom::Start(4)).unwrap();
+/// yadon.write(&[1, 2, 3]).unwrap();
+/// yadon.fill(0xff, 12).unwrap();
+/// yadon.flush().unwrap();
+/// assert_eq!(format!("{:.2}", yadon), "    \
+///     0 seek          -> 0x4
+///     1 write         0x4..0x7                 3 bytes  01 02 ...
+///     2 fill          0x7..0x13               12 bytes  ff ff ...
+///     3 flush
+/// ");
+/// ```
+impl Display for Yadon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limit = f.precision().unwrap_or(DISPLAY_PREVIEW_BYTES);
+        let range = |start: Option<u64>, len: u64| match start {
+            Some(start) => format!("{:#x}..{:#x}", start, start.saturating_add(len)),
+            None => "?".to_string(),
+        };
+        let mut position = self.start;
+        self.try_for_each_recorded(|index, operation| {
+            let (target, written) = match operation {
+                WriteOperation::Write(data, _) => {
+                    let len = data.len() as u64;
+                    (range(position, len), Some((len, hex(data, len, limit))))
+                },
+                WriteOperation::Fill { byte, len } => {
+                    let preview = vec![*byte; (*len).min(limit as u64) as usize];
+                    (range(position, *len), Some((*len, hex(&preview, *len, limit))))
+                },
+                WriteOperation::Repeat { pattern, count } => {
+                    let len = (pattern.len() as u64).saturating_mul(*count);
+                    let preview: Vec<u8> = pattern.iter().copied().cycle().take(len.min(limit as u64) as usize).collect();
+                    (range(position, len), Some((len, hex(&preview, len, limit))))
+                },
+                WriteOperation::CopyWithin { src, dst, len } => {
+                    (range(Some(*dst), *len), Some((*len, format!("from {}", range(Some(*src), *len)))))
+                },
+                WriteOperation::AssertBytes { offset, expected } => {
+                    let len = expected.len() as u64;
+                    (range(Some(*offset), len), Some((len, hex(expected, len, limit))))
+                },
+                WriteOperation::Seek(_, expected) => (format!("-> {:#x}", expected), None),
+                WriteOperation::DeferredSeek(pos) => (format!("-> {:?}", pos), None),
+                WriteOperation::SetLen(len) => (format!("-> {:#x}", len), None),
+                WriteOperation::Flush | WriteOperation::Sync => (String::new(), None),
+                #[cfg(feature = "std")]
+                WriteOperation::Custom(op, result) => {
+                    (format!("-> {:#x}", result.position), Some((result.bytes_written, format!("{:?}", op))))
+                },
+            };
+            position = match operation {
+                WriteOperation::Write(..) | WriteOperation::Fill { .. } | WriteOperation::Repeat { .. } => {
+                    position.zip(written.as_ref()).map(|(position, (len, _))| position.saturating_add(*len))
+                },
+                WriteOperation::CopyWithin { dst, len, .. } => Some(dst + len),
+                WriteOperation::Seek(SeekFrom::Current(_), _) if position.is_none() => None,
+                WriteOperation::Seek(_, expected) => Some(*expected),
+                WriteOperation::DeferredSeek(_) => None,
+                #[cfg(feature = "std")]
+                WriteOperation::Custom(_, result) => Some(result.position),
+                _ => position,
+            };
+            let line = match written {
+                Some((len, bytes)) => format!("{:5} {:13} {:20} {:5} bytes  {}", index, operation.name(), target, len, bytes),
+                None => format!("{:5} {:13} {}", index, operation.name(), target),
+            };
+            writeln!(f, "{}", line.trim_end())
+        }, |_| fmt::Error)
+    }
+}
+
+/// The first `limit` of `bytes`, which are the first of `len`, in hex.
+fn hex(bytes: &[u8], len: u64, limit: usize) -> String {
+    let mut hex: Vec<String> = bytes.iter().take(limit).map(|byte| format!("{:02x}", byte)).collect();
+    if len > limit as u64 {
+        hex.push("...".to_string());
+    }
+    hex.join(" ")
+}
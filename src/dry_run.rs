@@ -0,0 +1,194 @@
+use std::convert::TryFrom;
+use std::io::{Seek, SeekFrom};
+use crate::{ApplyError, Confusion, DryRunError, LengthMode, WriteOperation, Yadon};
+
+/// The outcome of `Yadon::dry_run()`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DryRunReport {
+    /// Number of bytes which would be written to the target.
+    pub bytes_written: u64,
+    /// The end of the furthest byte which would be written, or 0 if nothing would be.
+    pub max_offset: u64,
+    /// Writes which would be cut short by the end of the target, in order.
+    pub truncated_writes: Vec<TruncatedWrite>,
+    /// `SeekFrom::End` seeks which would end up somewhere other than where they did while recording, in order.
+    pub diverging_seeks: Vec<DivergingSeek>,
+}
+
+impl DryRunReport {
+    /// Whether the stored operations would apply to the target exactly as they were recorded.
+    pub fn fits(&self) -> bool {
+        self.truncated_writes.is_empty() && self.diverging_seeks.is_empty()
+    }
+}
+
+/// The outcome of `Yadon::validate_against()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The length of the target.
+    pub target_len: u64,
+    /// Number of seeks made on the target, including the seek to `start`, but not the seeks to find its length and
+    /// restore its position.
+    pub seeks_issued: usize,
+    /// Number of bytes which would be written to the target.
+    pub bytes_written: u64,
+    /// Writes which would be cut short by the end of the target, in order.
+    pub truncated_writes: Vec<TruncatedWrite>,
+}
+
+/// A write which `Yadon::dry_run()` or `Yadon::validate_against()` found would be cut short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TruncatedWrite {
+    /// Index of the operation in `Yadon::operations`.
+    pub op_index: usize,
+    /// Where the write would start.
+    pub offset: u64,
+    /// Number of bytes the operation was recorded writing.
+    pub len: u64,
+    /// Number of those bytes which would fit.
+    pub fits: u64,
+}
+
+/// A seek which `Yadon::dry_run()` found would end up somewhere else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DivergingSeek {
+    /// Index of the operation in `Yadon::operations`.
+    pub op_index: usize,
+    /// Where the seek ended up while recording.
+    pub expected: u64,
+    /// Where the seek would end up.
+    pub actual: u64,
+}
+
+impl Yadon {
+    /// Simulates applying the stored operations to a target which is `target_len` bytes long, without needing one. As
+    /// while recording, the target is taken to start at `start`, or at 0 if that isn't set, and with
+    /// `LengthMode::Growable`, writes past the end extend it. Fails if a seek would move before the start of the
+    /// target or overflow the position, or with `DryRunError::InStore` if the operations are in the store given to
+    /// `with_store()`.
+    pub fn dry_run(&self, target_len: u64) -> Result<DryRunReport, DryRunError> {
+        if self.store.is_some() {
+            return Err(DryRunError::InStore);
+        }
+        self.simulate(self.start.unwrap_or(0), target_len, |_, _, _| Ok(()), DryRunError::OutOfRange)
+    }
+
+    /// Checks that the stored operations line up with `target`, without writing to it. Only the seeks are issued to the
+    /// target, each as a `SeekFrom::Start` seek to where it would end up, and they're checked against the positions
+    /// they had while recording. The target's length is found once beforehand, to resolve `SeekFrom::End` seeks, and
+    /// writes are simulated against it as `dry_run()` does. The target's position is restored afterwards. Fails with
+    /// `ApplyError::InStore`, without touching the target, if the operations are in the store given to `with_store()`.
+    pub fn validate_against<T>(&self, target: &mut T) -> Result<ValidationReport, ApplyError> where T: Seek {
+        if self.store.is_some() {
+            return Err(ApplyError::InStore);
+        }
+        let position = target.stream_position()?;
+        let validated = self.validate_positions(target, position);
+        target.seek(SeekFrom::Start(position))?;
+        validated
+    }
+
+    fn validate_positions<T>(&self, target: &mut T, position: u64) -> Result<ValidationReport, ApplyError> where T: Seek {
+        let target_len = target.seek(SeekFrom::End(0))?;
+        let mut seeks_issued = 0;
+        if let Some(start) = self.start {
+            let actual = target.seek(SeekFrom::Start(start))?;
+            seeks_issued += 1;
+            if actual != start {
+                return Err(ApplyError::SeekDiverged(Confusion::new(start, actual)));
+            }
+        }
+        let report = self.simulate(self.start.unwrap_or(position), target_len, |index, operation, resolved| {
+            let actual = target.seek(SeekFrom::Start(resolved))?;
+            seeks_issued += 1;
+            let expected = match operation {
+                WriteOperation::Seek(_, expected_position) => *expected_position,
+                _ => resolved,
+            };
+            if actual != expected {
+                let mut confusion = Confusion::new(expected, actual);
+                confusion.op_index = Some(index);
+                confusion.offset = Some(resolved);
+                return Err(ApplyError::SeekDiverged(confusion)
+                    .with_context(self.location_of(index), self.label_of(index).cloned()));
+            }
+            Ok(())
+        }, |_| ApplyError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")))?;
+        Ok(ValidationReport {
+            target_len,
+            seeks_issued,
+            bytes_written: report.bytes_written,
+            truncated_writes: report.truncated_writes,
+        })
+    }
+
+    /// Walks through the stored operations as if applying them to a target of `target_len` bytes, positioned at
+    /// `position`. `seek` is called with each seek, and where it would end up.
+    fn simulate<E>(
+        &self,
+        mut position: u64,
+        target_len: u64,
+        mut seek: impl FnMut(usize, &WriteOperation, u64) -> Result<(), E>,
+        out_of_range: fn(usize) -> E,
+    ) -> Result<DryRunReport, E> {
+        let mut report = DryRunReport::default();
+        let mut len = target_len;
+        for (index, operation) in self.operations.iter().enumerate() {
+            let resolve = |pos: SeekFrom| {
+                let (base, offset) = match pos {
+                    SeekFrom::Start(offset) => return Some(offset),
+                    SeekFrom::Current(offset) => (position, offset),
+                    SeekFrom::End(offset) => (len, offset),
+                };
+                u64::try_from(base as i128 + offset as i128).ok()
+            };
+            let (offset, written) = match operation {
+                WriteOperation::Seek(pos, expected_position) => {
+                    position = resolve(*pos).ok_or_else(|| out_of_range(index))?;
+                    seek(index, operation, position)?;
+                    if matches!(pos, SeekFrom::End(_)) && position != *expected_position {
+                        report.diverging_seeks.push(DivergingSeek { op_index: index, expected: *expected_position, actual: position });
+                    }
+                    continue;
+                },
+                WriteOperation::DeferredSeek(pos) => {
+                    position = resolve(*pos).ok_or_else(|| out_of_range(index))?;
+                    seek(index, operation, position)?;
+                    continue;
+                },
+                WriteOperation::SetLen(new_len) => {
+                    len = *new_len;
+                    continue;
+                },
+                WriteOperation::Custom(custom, _) => {
+                    let simulated = custom.simulate(position, Some(len));
+                    report.bytes_written += simulated.bytes_written;
+                    report.max_offset = report.max_offset.max(simulated.position);
+                    position = simulated.position;
+                    continue;
+                },
+                WriteOperation::Write(..) | WriteOperation::Fill { .. } | WriteOperation::Repeat { .. } => {
+                    (position, operation.expected_bytes_written())
+                },
+                WriteOperation::CopyWithin { dst, .. } => (*dst, operation.expected_bytes_written()),
+                WriteOperation::Flush | WriteOperation::Sync | WriteOperation::AssertBytes { .. } => continue,
+            };
+            let fits = match self.length_mode {
+                LengthMode::Fixed => written.min(len.saturating_sub(offset)),
+                LengthMode::Growable => written,
+            };
+            if fits < written {
+                report.truncated_writes.push(TruncatedWrite { op_index: index, offset, len: written, fits });
+            }
+            position = offset.checked_add(fits).ok_or_else(|| out_of_range(index))?;
+            if fits > 0 {
+                report.bytes_written += fits;
+                report.max_offset = report.max_offset.max(position);
+            }
+            if self.length_mode == LengthMode::Growable {
+                len = len.max(position);
+            }
+        }
+        Ok(report)
+    }
+}
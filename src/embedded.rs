@@ -0,0 +1,371 @@
+//! Traits with the shape of `embedded_io`'s `Write` and `Seek`, for storage stacks on embedded targets, behind the
+//! `embedded-io` feature. `Yadon` implements them, so that code which writes through them can be recorded, and
+//! `Yadon::apply_embedded()` applies to any target which implements them. The methods have the same contracts as
+//! `embedded_io`'s, so wrapping a target which implements those only takes forwarding them.
+//!
+//! The contracts differ from `std::io`'s in how a write of which nothing fits is reported: `embedded_io` doesn't allow
+//! `Ok(0)` for a non-empty buffer, and fails with `ErrorKind::WriteZero` instead. Recording fails that way rather than
+//! recording an empty write, and applying takes `ErrorKind::WriteZero` from the target as a write of nothing, which is
+//! checked against the recording as a short write would be.
+
+use crate::check::Checker;
+use crate::io;
+use crate::operation::repeated_len;
+use crate::{fitting_len, ApplyError, CheckPolicy, LengthMode, WriteOperation, Yadon};
+
+/// The kinds of error which a target can fail with, as with `embedded_io::ErrorKind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Any other error.
+    Other,
+    /// Something wasn't found.
+    NotFound,
+    /// The operation lacked the permissions it needed.
+    PermissionDenied,
+    /// The remote refused the connection.
+    ConnectionRefused,
+    /// The remote reset the connection.
+    ConnectionReset,
+    /// The remote aborted the connection.
+    ConnectionAborted,
+    /// There's no connection.
+    NotConnected,
+    /// The address is already in use.
+    AddrInUse,
+    /// The address isn't available.
+    AddrNotAvailable,
+    /// The other end of a pipe was closed.
+    BrokenPipe,
+    /// Something already exists.
+    AlreadyExists,
+    /// A parameter was incorrect, such as a seek to a negative position.
+    InvalidInput,
+    /// Data wasn't valid for the operation.
+    InvalidData,
+    /// The operation timed out.
+    TimedOut,
+    /// The operation was interrupted, and can be tried again.
+    Interrupted,
+    /// The operation isn't supported, such as a seek relative to an unknown end.
+    Unsupported,
+    /// Memory ran out.
+    OutOfMemory,
+    /// Nothing of a non-empty write could be written.
+    WriteZero,
+}
+
+/// An error which a target can fail with, as with `embedded_io::Error`.
+pub trait Error: core::fmt::Debug {
+    /// The kind of error.
+    fn kind(&self) -> ErrorKind;
+}
+
+impl Error for ErrorKind {
+    fn kind(&self) -> ErrorKind {
+        *self
+    }
+}
+
+/// The error type of a target, as with `embedded_io::ErrorType`.
+pub trait ErrorType {
+    /// The error the target's methods fail with.
+    type Error: Error;
+}
+
+impl<T> ErrorType for &mut T where T: ErrorType + ?Sized {
+    type Error = T::Error;
+}
+
+/// Where to seek to, as with `embedded_io::SeekFrom`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SeekFrom {
+    /// This many bytes from the start.
+    Start(u64),
+    /// This many bytes from the end, which may be negative.
+    End(i64),
+    /// This many bytes from the current position, which may be negative.
+    Current(i64),
+}
+
+impl From<io::SeekFrom> for SeekFrom {
+    fn from(pos: io::SeekFrom) -> Self {
+        match pos {
+            io::SeekFrom::Start(offset) => SeekFrom::Start(offset),
+            io::SeekFrom::End(offset) => SeekFrom::End(offset),
+            io::SeekFrom::Current(offset) => SeekFrom::Current(offset),
+        }
+    }
+}
+
+impl From<SeekFrom> for io::SeekFrom {
+    fn from(pos: SeekFrom) -> Self {
+        match pos {
+            SeekFrom::Start(offset) => io::SeekFrom::Start(offset),
+            SeekFrom::End(offset) => io::SeekFrom::End(offset),
+            SeekFrom::Current(offset) => io::SeekFrom::Current(offset),
+        }
+    }
+}
+
+/// Targets which can be written to, as with `embedded_io::Write`.
+pub trait Write: ErrorType {
+    /// Writes bytes from `buf`, returning how many were written, which is only 0 if `buf` is empty. Fails with
+    /// `ErrorKind::WriteZero` if nothing of a non-empty `buf` can be written.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error>;
+    /// Flushes what has been written.
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<T> Write for &mut T where T: Write + ?Sized {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        T::write(self, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        T::flush(self)
+    }
+}
+
+/// Targets which can be seeked, as with `embedded_io::Seek`.
+pub trait Seek: ErrorType {
+    /// Seeks to `pos`, returning the new position.
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error>;
+
+    /// The current position.
+    fn stream_position(&mut self) -> Result<u64, Self::Error> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
+
+impl<T> Seek for &mut T where T: Seek + ?Sized {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Self::Error> {
+        T::seek(self, pos)
+    }
+}
+
+impl ErrorType for Yadon {
+    type Error = ErrorKind;
+}
+
+/// Records writes as `Yadon::write()` does. A write of which nothing fits fails with `ErrorKind::WriteZero`, recording
+/// nothing, where `Yadon::write()` would record an empty write and return `Ok(0)`.
+impl Write for Yadon {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, ErrorKind> {
+        let length = match self.length_mode {
+            LengthMode::Fixed => self.length,
+            LengthMode::Growable => None,
+        };
+        let position = self.virtual_position.or(self.start);
+        if !buf.is_empty() && fitting_len(position, length, buf.len() as u64, self.overflow_policy) == Ok(0) {
+            return Err(ErrorKind::WriteZero);
+        }
+        Yadon::write(self, buf).map_err(|error| error_kind(error.kind()))
+    }
+
+    fn flush(&mut self) -> Result<(), ErrorKind> {
+        Yadon::flush(self).map_err(|error| error_kind(error.kind()))
+    }
+}
+
+/// Records seeks as `Yadon::seek()` does, so a seek from the end without a `length` fails with
+/// `ErrorKind::Unsupported`.
+impl Seek for Yadon {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, ErrorKind> {
+        Yadon::seek(self, pos.into()).map_err(|error| error_kind(error.kind()))
+    }
+}
+
+/// The kind of error a target fails with for a recording error of kind `kind`.
+#[cfg_attr(not(feature = "std"), allow(unreachable_patterns))]
+fn error_kind(kind: io::ErrorKind) -> ErrorKind {
+    match kind {
+        io::ErrorKind::InvalidInput => ErrorKind::InvalidInput,
+        io::ErrorKind::InvalidData => ErrorKind::InvalidData,
+        io::ErrorKind::Unsupported => ErrorKind::Unsupported,
+        io::ErrorKind::WriteZero => ErrorKind::WriteZero,
+        _ => ErrorKind::Other,
+    }
+}
+
+/// The error applying fails with for an error from a target. Without `std`, kinds which recording can't fail with
+/// become `io::ErrorKind::Other`.
+fn apply_error<E>(error: E) -> ApplyError where E: Error {
+    #[cfg(feature = "std")]
+    let kind = match error.kind() {
+        ErrorKind::Other => io::ErrorKind::Other,
+        ErrorKind::NotFound => io::ErrorKind::NotFound,
+        ErrorKind::PermissionDenied => io::ErrorKind::PermissionDenied,
+        ErrorKind::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+        ErrorKind::ConnectionReset => io::ErrorKind::ConnectionReset,
+        ErrorKind::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+        ErrorKind::NotConnected => io::ErrorKind::NotConnected,
+        ErrorKind::AddrInUse => io::ErrorKind::AddrInUse,
+        ErrorKind::AddrNotAvailable => io::ErrorKind::AddrNotAvailable,
+        ErrorKind::BrokenPipe => io::ErrorKind::BrokenPipe,
+        ErrorKind::AlreadyExists => io::ErrorKind::AlreadyExists,
+        ErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
+        ErrorKind::InvalidData => io::ErrorKind::InvalidData,
+        ErrorKind::TimedOut => io::ErrorKind::TimedOut,
+        ErrorKind::Interrupted => io::ErrorKind::Interrupted,
+        ErrorKind::Unsupported => io::ErrorKind::Unsupported,
+        ErrorKind::OutOfMemory => io::ErrorKind::OutOfMemory,
+        ErrorKind::WriteZero => io::ErrorKind::WriteZero,
+    };
+    #[cfg(not(feature = "std"))]
+    let kind = match error.kind() {
+        ErrorKind::InvalidInput => io::ErrorKind::InvalidInput,
+        ErrorKind::InvalidData => io::ErrorKind::InvalidData,
+        ErrorKind::Unsupported => io::ErrorKind::Unsupported,
+        ErrorKind::WriteZero => io::ErrorKind::WriteZero,
+        _ => io::ErrorKind::Other,
+    };
+    ApplyError::Io(kind.into())
+}
+
+/// Writes `buf` to `target`, retrying interrupted writes. A write of which nothing could be written is returned as 0
+/// bytes written, as `std::io` would return it, for the check to compare with what was recorded.
+fn write<T>(target: &mut T, buf: &[u8]) -> Result<usize, ApplyError> where T: Write + ?Sized {
+    loop {
+        match target.write(buf) {
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(error) if error.kind() == ErrorKind::WriteZero => return Ok(0),
+            result => return result.map_err(apply_error),
+        }
+    }
+}
+
+/// Seeks `target` to `pos`, retrying interrupted seeks.
+fn seek<T>(target: &mut T, pos: io::SeekFrom) -> Result<u64, ApplyError> where T: Seek + ?Sized {
+    loop {
+        match target.seek(pos.into()) {
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            result => return result.map_err(apply_error),
+        }
+    }
+}
+
+/// Flushes `target`, retrying interrupted flushes.
+fn flush<T>(target: &mut T) -> Result<(), ApplyError> where T: Write + ?Sized {
+    loop {
+        match target.flush() {
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            result => return result.map_err(apply_error),
+        }
+    }
+}
+
+/// Size of the buffer which fills and short repeated patterns are written from, which is on the stack, so that
+/// applying doesn't allocate.
+const PATTERN_BUFFER_SIZE: usize = 256;
+
+/// Writes `len` bytes of `pattern` repeated, stopping at the first short write, as the `std` apply does.
+fn write_pattern<T>(target: &mut T, pattern: &[u8], len: u64) -> Result<u64, ApplyError> where T: Write + ?Sized {
+    if pattern.is_empty() {
+        return Ok(0);
+    }
+    let mut buffer = [0u8; PATTERN_BUFFER_SIZE];
+    let chunk = match pattern.len() <= PATTERN_BUFFER_SIZE {
+        true => {
+            let repeated = PATTERN_BUFFER_SIZE / pattern.len() * pattern.len();
+            for (byte, pattern_byte) in buffer[0..repeated].iter_mut().zip(pattern.iter().cycle()) {
+                *byte = *pattern_byte;
+            }
+            &buffer[0..repeated]
+        },
+        false => pattern,
+    };
+    let mut written = 0u64;
+    while written < len {
+        let chunk_len = (len - written).min(chunk.len() as u64) as usize;
+        let bytes_written = write(target, &chunk[0..chunk_len])?;
+        written += bytes_written as u64;
+        if bytes_written < chunk_len {
+            break;
+        }
+    }
+    Ok(written)
+}
+
+impl Yadon {
+    /// Applies the stored operations on a target with `embedded_io`'s shape, as `apply_with_policy()` does with the
+    /// default options: seeking to `start` first, checking seeks and writes according to `check_policy`, replaying
+    /// flushes and flushing once at the end. A write which the target fails with `ErrorKind::WriteZero` is checked as
+    /// a write of nothing. Only writes, fills, repeats, seeks and flushes can be applied this way, and base checksums
+    /// and generation stamps can't be checked; anything else fails with `ApplyError::UnsupportedOperation` before the
+    /// target is touched. Doesn't need `std`.
+    pub fn apply_embedded<T>(&self, target: &mut T, check_policy: CheckPolicy) -> Result<usize, ApplyError>
+    where T: Write + Seek + ?Sized {
+        self.check_can_apply()?;
+        let unsupported = self.operations.iter().find(|operation| !matches!(operation,
+            WriteOperation::Write(..)
+            | WriteOperation::Fill { .. }
+            | WriteOperation::Repeat { .. }
+            | WriteOperation::Seek(..)
+            | WriteOperation::DeferredSeek(_)
+            | WriteOperation::Flush));
+        if let Some(unsupported) = unsupported {
+            return Err(ApplyError::UnsupportedOperation(unsupported.name()));
+        }
+        if !self.base_checksums.is_empty() {
+            return Err(ApplyError::UnsupportedOperation("base checksum"));
+        }
+        if self.generation_stamp.is_some() {
+            return Err(ApplyError::UnsupportedOperation("generation stamp"));
+        }
+
+        let mut checker = Checker::new(self, check_policy);
+        let mut position = None;
+        if let Some(start) = self.start {
+            let seek_pos = seek(target, io::SeekFrom::Start(start))?;
+            position = Some(seek_pos);
+            checker.position(start, seek_pos)?;
+        }
+        let mut total_bytes_written = 0;
+        for (index, operation) in self.operations.iter().enumerate() {
+            checker.begin(index, operation, position);
+            let mut apply = || {
+                let bytes_written = match operation {
+                    WriteOperation::Write(data, expected_bytes_written) => {
+                        let bytes_written = write(target, data)?;
+                        checker.written(*expected_bytes_written, bytes_written)?
+                    },
+                    WriteOperation::Fill { byte, len } => {
+                        let bytes_written = write_pattern(target, &[*byte], *len)? as usize;
+                        checker.written(*len as usize, bytes_written)?
+                    },
+                    WriteOperation::Repeat { pattern, count } => {
+                        let expected_bytes_written = repeated_len(pattern, *count)?;
+                        let bytes_written = write_pattern(target, pattern, expected_bytes_written)? as usize;
+                        checker.written(expected_bytes_written as usize, bytes_written)?
+                    },
+                    WriteOperation::Seek(pos, expected_position) => {
+                        let new_position = seek(target, *pos)?;
+                        position = Some(new_position);
+                        checker.position(*expected_position, new_position)?;
+                        return Ok(0);
+                    },
+                    WriteOperation::DeferredSeek(pos) => {
+                        position = Some(seek(target, *pos)?);
+                        return Ok(0);
+                    },
+                    WriteOperation::Flush => {
+                        flush(target)?;
+                        return Ok(0);
+                    },
+                    _ => unreachable!("checked to be supported before starting"),
+                };
+                position = position.map(|position| position + bytes_written as u64);
+                Ok::<_, ApplyError>(bytes_written)
+            };
+            let result = apply();
+            #[cfg(feature = "std")]
+            let result = result
+                .map_err(|error| error.with_context(self.location_of(index), self.label_of(index).cloned()));
+            total_bytes_written += result?;
+        }
+        flush(target)?;
+        self.count_apply();
+        Ok(total_bytes_written)
+    }
+}
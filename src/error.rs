@@ -0,0 +1,955 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Display};
+use core::ops::Range;
+use core::panic::Location;
+use crate::io;
+
+/// Errors that may occur while applying `Yadon`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ApplyError {
+    /// IO error while trying to replay operations.
+    Io(io::Error),
+    /// Seek position diverged while trying to replay operations.
+    SeekDiverged(Confusion<u64>),
+    /// Number of bytes written diverged while trying to replay operations.
+    NumBytesWrittenDiverge(Confusion<usize>),
+    /// The stored operations include an operation which the target has no way to apply. Nothing was applied.
+    UnsupportedOperation(&'static str),
+    /// `ApplyOrder::Offset` or `Yadon::apply_range()` was requested, but the stored operations include an operation
+    /// whose effect depends on the order it's applied in. Nothing was applied.
+    OrderDependent(&'static str),
+    /// The position of the target for the operation at this index isn't known, because nothing before it gave an
+    /// absolute position. This happens when `ApplyOrder::Offset` is requested for a write with no known offset, or when
+    /// resuming an `ApplySession` whose position wasn't known. Nothing was applied.
+    UnresolvedOffset(usize),
+    /// `ApplyOrder::Offset` was requested, but two writes overlap, so the result would depend on their order. Each
+    /// write is given with its index and the extent it covers. Nothing was applied.
+    OverlappingWrites {
+        /// The earlier of the two writes.
+        first: (usize, Range<u64>),
+        /// The later of the two writes.
+        second: (usize, Range<u64>),
+    },
+    /// Flushing the target after an operation failed, as `ApplyOptions::flush_policy` asked for.
+    FlushFailed {
+        /// Index of the operation after which the target was being flushed.
+        op_index: usize,
+        /// The error from flushing.
+        source: io::Error,
+        /// Where the operation was recorded, if the `track-callers` feature is enabled.
+        location: Option<&'static Location<'static>>,
+        /// The label the operation was recorded under, if any.
+        label: Option<Arc<str>>,
+    },
+    /// `ApplyOptions::cancel` was set, or an `ApplyHook` broke with `HookBreak::Abort`, so applying stopped. The first `ops_applied` operations were applied completely,
+    /// and wrote `bytes_written` bytes; the next may have been applied partly.
+    Cancelled {
+        /// Number of operations which were applied completely. With `ApplyOrder::Offset`, this counts writes in the
+        /// order of their offsets.
+        ops_applied: usize,
+        /// Number of bytes those operations wrote.
+        bytes_written: usize,
+    },
+    /// `ApplyOptions::validate_first` was set, and the target is shorter than the recording's fixed length requires.
+    /// Nothing was applied.
+    TargetTooShort {
+        /// The end of the furthest write.
+        required: u64,
+        /// The length of the target.
+        actual: u64,
+    },
+    /// `Yadon::apply_transactional()` failed, and then restoring the target failed too, so the target may be left
+    /// partly applied.
+    RollbackFailed {
+        /// Why applying failed.
+        error: Box<ApplyError>,
+        /// Why rolling back failed.
+        rollback_error: io::Error,
+    },
+    /// `Yadon::apply_verified()` read back an extent of the target which didn't match what was written there.
+    VerificationFailed {
+        /// Offset of the extent.
+        offset: u64,
+        /// Length of the extent.
+        len: u64,
+    },
+    /// Ranges of the target didn't have the checksums given to `Yadon::require_base_checksum()`, so it isn't the image
+    /// the stored operations were recorded against. Nothing was applied.
+    BaseChecksumMismatch(Vec<ChecksumMismatch>),
+    /// The stored operations have already been applied as many times as `Yadon::max_applies()` allows. Nothing was
+    /// applied.
+    ApplyLimitReached {
+        /// Number of times the stored operations have been applied.
+        applied: u64,
+        /// The most times they may be applied.
+        max_applies: u64,
+    },
+    /// The target has already been stamped with a generation no older than the one given to
+    /// `Yadon::with_generation_stamp()`, so it has already been patched. Nothing was applied.
+    GenerationNotNewer {
+        /// The generation the target was stamped with.
+        stored: u64,
+        /// The generation which applying would stamp.
+        generation: u64,
+    },
+    /// The target did not contain the bytes required by a `WriteOperation::AssertBytes`.
+    PreconditionFailed {
+        /// Offset of the first byte which differed.
+        offset: u64,
+        /// The byte which the precondition required.
+        expected: u8,
+        /// The byte which the target contained, or `None` if the target ended first.
+        actual: Option<u8>,
+        /// Where the precondition was recorded, if the `track-callers` feature is enabled.
+        location: Option<&'static Location<'static>>,
+        /// The label the precondition was recorded under, if any.
+        label: Option<Arc<str>>,
+    },
+    /// `Yadon::apply_nor_flash()` found a write which would set a bit that is clear in the target, which NOR flash
+    /// can't do without an erase. Nothing of that write was written.
+    WouldSetBits {
+        /// Offset of the first byte which would set a bit.
+        offset: u64,
+        /// The byte which the target contained.
+        old: u8,
+        /// The byte which would have been written.
+        new: u8,
+    },
+    /// The operations were recorded into the store given to `Yadon::with_store()`, so they can only be applied with
+    /// `Yadon::apply_store()`. Nothing was applied.
+    InStore,
+}
+
+impl ApplyError {
+    /// Attaches the location and label of the operation which caused this error, where the error has room for them.
+    #[cfg(feature = "std")]
+    pub(crate) fn with_context(
+        mut self,
+        operation_location: Option<&'static Location<'static>>,
+        operation_label: Option<Arc<str>>,
+    ) -> Self {
+        match &mut self {
+            ApplyError::SeekDiverged(Confusion { location, label, .. })
+            | ApplyError::NumBytesWrittenDiverge(Confusion { location, label, .. })
+            | ApplyError::PreconditionFailed { location, label, .. }
+            | ApplyError::FlushFailed { location, label, .. } => {
+                *location = operation_location;
+                *label = operation_label;
+            },
+            _ => {}
+        }
+        self
+    }
+}
+
+impl Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyError::Io(_) => write!(f, "io error while trying to replay operations"),
+            ApplyError::SeekDiverged(confusion) => {
+                write!(f, "seek position diverged while trying to replay operations: {}", confusion)
+            },
+            ApplyError::NumBytesWrittenDiverge(confusion) => {
+                write!(f, "number of bytes written diverged while trying to replay operations: {}", confusion)
+            },
+            ApplyError::UnsupportedOperation(name) => write!(f, "target is unable to apply {} operations", name),
+            ApplyError::OrderDependent(name) => {
+                write!(f, "stored operations can't be reordered or filtered, as they include {} operations", name)
+            },
+            ApplyError::UnresolvedOffset(index) => write!(f, "offset of operation {} can't be resolved", index),
+            ApplyError::OverlappingWrites { first, second } => write!(
+                f,
+                "operation {} writing {:?} overlaps operation {} writing {:?}, so they can't be reordered",
+                first.0, first.1, second.0, second.1,
+            ),
+            ApplyError::FlushFailed { op_index, label, .. } => {
+                write!(f, "flushing the target after operation {} failed{}", op_index, in_label(label.as_deref()))
+            },
+            ApplyError::Cancelled { ops_applied, bytes_written } => {
+                write!(f, "apply was cancelled after {} operations ({} bytes written)", ops_applied, bytes_written)
+            },
+            ApplyError::TargetTooShort { required, actual } => {
+                write!(f, "target is {} bytes long, but the stored writes require {} bytes", actual, required)
+            },
+            ApplyError::RollbackFailed { error, .. } => {
+                write!(f, "rolling back the target failed after apply failed: {}", error)
+            },
+            ApplyError::VerificationFailed { offset, len } => {
+                write!(f, "target contents at offset {} ({} bytes) did not match what was written", offset, len)
+            },
+            ApplyError::BaseChecksumMismatch(found) => {
+                write!(f, "target isn't the expected base image: {}", mismatches(found))
+            },
+            ApplyError::ApplyLimitReached { applied, max_applies } => write!(
+                f,
+                "stored operations have already been applied {} times, the most allowed is {}",
+                applied, max_applies,
+            ),
+            ApplyError::GenerationNotNewer { stored, generation } => write!(
+                f,
+                "target is stamped with generation {}, which isn't older than generation {}",
+                stored, generation,
+            ),
+            ApplyError::PreconditionFailed { offset, label, .. } => write!(
+                f,
+                "target contents at offset {} did not match precondition{}",
+                offset, in_label(label.as_deref()),
+            ),
+            ApplyError::WouldSetBits { offset, old, new } => write!(
+                f,
+                "writing {:#04x} over {:#04x} at offset {} would set bits, which needs an erase first",
+                new, old, offset,
+            ),
+            ApplyError::InStore => write!(f, "stored operations are in a store, which only apply_store() applies"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ApplyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApplyError::Io(source)
+            | ApplyError::FlushFailed { source, .. }
+            | ApplyError::RollbackFailed { rollback_error: source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ApplyError {
+    fn from(error: io::Error) -> Self {
+        ApplyError::Io(error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ApplyError> for std::io::Error {
+    /// Unwraps `ApplyError::Io`, and wraps any other error so that it can be recovered with
+    /// `io::Error::get_ref()` and `downcast_ref::<ApplyError>()`.
+    fn from(error: ApplyError) -> Self {
+        let kind = match error {
+            ApplyError::Io(error) => return error,
+            ApplyError::UnsupportedOperation(_) => std::io::ErrorKind::Unsupported,
+            ApplyError::FlushFailed { ref source, .. } => source.kind(),
+            ApplyError::RollbackFailed { ref rollback_error, .. } => rollback_error.kind(),
+            ApplyError::Cancelled { .. } => std::io::ErrorKind::Other,
+            ApplyError::OrderDependent(_)
+            | ApplyError::InStore
+            | ApplyError::ApplyLimitReached { .. }
+            | ApplyError::GenerationNotNewer { .. }
+            | ApplyError::UnresolvedOffset(_)
+            | ApplyError::OverlappingWrites { .. } => std::io::ErrorKind::InvalidInput,
+            ApplyError::SeekDiverged(_)
+            | ApplyError::NumBytesWrittenDiverge(_)
+            | ApplyError::PreconditionFailed { .. }
+            | ApplyError::WouldSetBits { .. }
+            | ApplyError::TargetTooShort { .. }
+            | ApplyError::VerificationFailed { .. }
+            | ApplyError::BaseChecksumMismatch(_) => std::io::ErrorKind::InvalidData,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+/// Errors that may occur during `Yadon::dry_run()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DryRunError {
+    /// The operation at this index would move the position before the start of the target, or past the largest
+    /// position.
+    OutOfRange(usize),
+    /// The operations are in the store given to `Yadon::with_store()`, which can't be dry run.
+    InStore,
+}
+
+impl Display for DryRunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DryRunError::OutOfRange(index) => write!(f, "operation {} would move the position out of range", index),
+            DryRunError::InStore => write!(f, "stored operations are in a store, which can't be dry run"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DryRunError {}
+
+/// Errors that may occur during `Yadon::materialize()`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MaterializeError {
+    /// Laying down the stored operations failed.
+    Apply(ApplyError),
+    /// A buffer of this many bytes couldn't be allocated.
+    TooLarge(u64),
+}
+
+impl Display for MaterializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterializeError::Apply(_) => write!(f, "applying to the buffer failed"),
+            MaterializeError::TooLarge(len) => write!(f, "a buffer of {} bytes can't be allocated", len),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MaterializeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MaterializeError::Apply(error) => Some(error),
+            MaterializeError::TooLarge(_) => None,
+        }
+    }
+}
+
+impl From<ApplyError> for MaterializeError {
+    fn from(error: ApplyError) -> Self {
+        MaterializeError::Apply(error)
+    }
+}
+
+/// Errors that may occur during `Yadon::from_bytes()`. Offsets are from the start of the input.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The input doesn't start with the magic bytes, so it isn't an encoded `Yadon`.
+    BadMagic,
+    /// The input is in a version of the format which this version of the crate doesn't know.
+    UnsupportedVersion(u8),
+    /// The input ended partway through something which was still being read. The offset is where the input, or the
+    /// operation being read, ended.
+    Truncated {
+        /// Where the input ran out.
+        offset: usize,
+    },
+    /// An operation has a tag which isn't known.
+    UnknownTag {
+        /// The tag.
+        tag: u8,
+        /// Where the tag is.
+        offset: usize,
+    },
+    /// A value isn't valid, such as a varint which doesn't fit in a `u64`, or a label which isn't UTF-8.
+    Invalid {
+        /// Where the value starts.
+        offset: usize,
+        /// What is wrong with it.
+        reason: &'static str,
+    },
+    /// The values are each valid, but don't agree with one another, such as a seek which couldn't end up where it
+    /// expects to from where the operations before it left the position.
+    Inconsistent(String),
+    /// More bytes follow the last operation.
+    TrailingBytes {
+        /// Where they start.
+        offset: usize,
+    },
+    /// Reading the input failed.
+    Io(io::Error),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "input isn't an encoded recording"),
+            DecodeError::UnsupportedVersion(version) => write!(f, "format version {} isn't supported", version),
+            DecodeError::Truncated { offset } => write!(f, "input ended unexpectedly at offset {}", offset),
+            DecodeError::UnknownTag { tag, offset } => write!(f, "unknown operation tag {} at offset {}", tag, offset),
+            DecodeError::Invalid { offset, reason } => write!(f, "invalid value at offset {}: {}", offset, reason),
+            DecodeError::Inconsistent(reason) => write!(f, "recording is inconsistent: {}", reason),
+            DecodeError::TrailingBytes { offset } => write!(f, "unexpected bytes after the last operation, at offset {}", offset),
+            DecodeError::Io(_) => write!(f, "io error while reading the input"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Errors that may occur during `Yadon::to_ips()` and `Yadon::from_ips()`. Offsets into a patch are from its start.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum IpsError {
+    /// The stored writes can't be resolved to where they land.
+    Apply(ApplyError),
+    /// A write reaches this offset, which is past the largest an IPS patch can address.
+    OffsetTooLarge(u64),
+    /// A write starts at offset 0x454f46, which reads as the end of an IPS patch, and the byte before it isn't written,
+    /// so the record can't be started a byte earlier instead.
+    EofOffset,
+    /// The patch doesn't start with `PATCH`, so it isn't an IPS patch.
+    BadMagic,
+    /// The patch ended partway through a record, or before `EOF`.
+    Truncated {
+        /// Where the record which was cut short starts, or where the patch ended if it was between records.
+        offset: usize,
+    },
+    /// A record can't be applied, such as an RLE record of no bytes, or one which writes past the length given.
+    Invalid {
+        /// Where the record starts.
+        offset: usize,
+        /// What is wrong with it.
+        reason: &'static str,
+    },
+    /// More bytes follow `EOF` than the 3 byte length which a patch may end with.
+    TrailingBytes {
+        /// Where they start.
+        offset: usize,
+    },
+}
+
+impl Display for IpsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpsError::Apply(_) => write!(f, "the stored writes can't be resolved"),
+            IpsError::OffsetTooLarge(offset) => write!(f, "offset {:#x} is past the largest an IPS patch can address", offset),
+            IpsError::EofOffset => write!(f, "a write starts at offset 0x454f46, which can't be the offset of an IPS record"),
+            IpsError::BadMagic => write!(f, "input isn't an IPS patch"),
+            IpsError::Truncated { offset } => write!(f, "patch ended partway through the record at offset {}", offset),
+            IpsError::Invalid { offset, reason } => write!(f, "invalid record at offset {}: {}", offset, reason),
+            IpsError::TrailingBytes { offset } => write!(f, "unexpected bytes after EOF, at offset {}", offset),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IpsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IpsError::Apply(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ApplyError> for IpsError {
+    fn from(error: ApplyError) -> Self {
+        IpsError::Apply(error)
+    }
+}
+
+/// Errors that may occur during `Yadon::to_bps()` and `Yadon::from_bps()`. Offsets into a patch are from its start.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BpsError {
+    /// Laying the stored operations down onto the source failed.
+    Materialize(MaterializeError),
+    /// The patch doesn't start with `BPS1`, so it isn't a BPS patch.
+    BadMagic,
+    /// The patch ended partway through something which was still being read.
+    Truncated {
+        /// Where the value which was cut short starts.
+        offset: usize,
+    },
+    /// A value isn't valid, such as an action which reads past the end of the source.
+    Invalid {
+        /// Where the value starts.
+        offset: usize,
+        /// What is wrong with it.
+        reason: &'static str,
+    },
+    /// The source isn't as long as the patch was made against.
+    SourceSize {
+        /// The length the patch gives.
+        expected: u64,
+        /// The length of the source.
+        actual: u64,
+    },
+    /// The CRC-32 of the source isn't the one the patch was made against.
+    SourceChecksum {
+        /// The CRC-32 the patch gives.
+        expected: u32,
+        /// The CRC-32 of the source.
+        actual: u32,
+    },
+    /// The CRC-32 of the patched source isn't the one the patch gives.
+    TargetChecksum {
+        /// The CRC-32 the patch gives.
+        expected: u32,
+        /// The CRC-32 of the patched source.
+        actual: u32,
+    },
+    /// The CRC-32 of the patch isn't the one it ends with, so it has been corrupted.
+    PatchChecksum {
+        /// The CRC-32 the patch ends with.
+        expected: u32,
+        /// The CRC-32 of the rest of the patch.
+        actual: u32,
+    },
+}
+
+impl Display for BpsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BpsError::Materialize(_) => write!(f, "laying the stored operations onto the source failed"),
+            BpsError::BadMagic => write!(f, "input isn't a BPS patch"),
+            BpsError::Truncated { offset } => write!(f, "patch ended unexpectedly, reading the value at offset {}", offset),
+            BpsError::Invalid { offset, reason } => write!(f, "invalid value at offset {}: {}", offset, reason),
+            BpsError::SourceSize { expected, actual } => {
+                write!(f, "source is {} bytes long, but the patch is for a source of {} bytes", actual, expected)
+            },
+            BpsError::SourceChecksum { expected, actual } => {
+                write!(f, "source has checksum {:#010x}, but the patch is for {:#010x}", actual, expected)
+            },
+            BpsError::TargetChecksum { expected, actual } => {
+                write!(f, "patched source has checksum {:#010x}, expected {:#010x}", actual, expected)
+            },
+            BpsError::PatchChecksum { expected, actual } => {
+                write!(f, "patch has checksum {:#010x}, expected {:#010x}", actual, expected)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BpsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BpsError::Materialize(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<MaterializeError> for BpsError {
+    fn from(error: MaterializeError) -> Self {
+        BpsError::Materialize(error)
+    }
+}
+
+/// Errors that may occur during `Yadon::from_ups()`. Offsets into a patch are from its start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpsError {
+    /// The patch doesn't start with `UPS1`, so it isn't a UPS patch.
+    BadMagic,
+    /// The patch ended partway through something which was still being read.
+    Truncated {
+        /// Where the value which was cut short starts.
+        offset: usize,
+    },
+    /// A value isn't valid, such as a hunk which changes bytes past the end of the output.
+    Invalid {
+        /// Where the value starts.
+        offset: usize,
+        /// What is wrong with it.
+        reason: &'static str,
+    },
+    /// The source isn't as long as the patch's input size.
+    InputSize {
+        /// The input size the patch gives.
+        expected: u64,
+        /// The length of the source.
+        actual: u64,
+    },
+    /// The CRC-32 of the source isn't the input checksum the patch gives.
+    InputChecksum {
+        /// The CRC-32 the patch gives.
+        expected: u32,
+        /// The CRC-32 of the source.
+        actual: u32,
+    },
+    /// The CRC-32 of the patched source isn't the output checksum the patch gives.
+    OutputChecksum {
+        /// The CRC-32 the patch gives.
+        expected: u32,
+        /// The CRC-32 of the patched source.
+        actual: u32,
+    },
+    /// The CRC-32 of the patch isn't the one it ends with, so it has been corrupted.
+    PatchChecksum {
+        /// The CRC-32 the patch ends with.
+        expected: u32,
+        /// The CRC-32 of the rest of the patch.
+        actual: u32,
+    },
+}
+
+impl Display for UpsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpsError::BadMagic => write!(f, "input isn't a UPS patch"),
+            UpsError::Truncated { offset } => write!(f, "patch ended unexpectedly, reading the value at offset {}", offset),
+            UpsError::Invalid { offset, reason } => write!(f, "invalid value at offset {}: {}", offset, reason),
+            UpsError::InputSize { expected, actual } => {
+                write!(f, "input size check failed: source is {} bytes long, but the patch is for {} bytes", actual, expected)
+            },
+            UpsError::InputChecksum { expected, actual } => {
+                write!(f, "input checksum check failed: source has checksum {:#010x}, expected {:#010x}", actual, expected)
+            },
+            UpsError::OutputChecksum { expected, actual } => {
+                write!(f, "output checksum check failed: patched source has checksum {:#010x}, expected {:#010x}", actual, expected)
+            },
+            UpsError::PatchChecksum { expected, actual } => {
+                write!(f, "patch checksum check failed: patch has checksum {:#010x}, expected {:#010x}", actual, expected)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UpsError {}
+
+/// Error that may occur during `Yadon::from_script()`, with where in the script it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScriptError {
+    /// The line, counting from 1.
+    pub line: usize,
+    /// The column, in characters, counting from 1.
+    pub column: usize,
+    /// What is wrong.
+    pub message: String,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ScriptError {}
+
+/// Errors that may occur during `Yadon::from_patches()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PatchError {
+    /// The patch at this index ends past the largest offset a `u64` can hold.
+    Overflow {
+        /// The index of the patch.
+        index: usize,
+    },
+    /// The patch at this index ends past the length.
+    OutOfBounds {
+        /// The index of the patch.
+        index: usize,
+        /// Where the patch ends.
+        end: u64,
+        /// The length.
+        length: u64,
+    },
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Overflow { index } => write!(f, "patch {} ends past the largest possible offset", index),
+            PatchError::OutOfBounds { index, end, length } => {
+                write!(f, "patch {} ends at offset {}, past the length of {}", index, end, length)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+/// Errors that may occur during `Yadon::from_diff()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiffError {
+    /// `new` is shorter than `old`, and `DiffOptions::allow_set_len` isn't set, so the diff can't be recorded.
+    Shrinks {
+        /// The length of `old`.
+        old_len: u64,
+        /// The length of `new`.
+        new_len: u64,
+    },
+}
+
+impl Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::Shrinks { old_len, new_len } => {
+                write!(f, "new is {} bytes long, shorter than the {} of old, and resizing isn't allowed", new_len, old_len)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DiffError {}
+
+/// Errors that may occur during `Yadon::compose()` or `Yadon::flatten()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ComposeError {
+    /// The recordings have different lengths, so they can't both be of the same target.
+    LengthMismatch {
+        /// The length of the first recording.
+        first: u64,
+        /// The length of the second.
+        second: u64,
+    },
+    /// The second recording requires base checksums, which can't be checked partway through applying.
+    BaseChecksums,
+    /// The first recording stamps a generation, which can't be stamped partway through applying.
+    GenerationStamp,
+    /// One of the recordings holds an operation of this kind, which can't be copied.
+    UnsupportedOperation(&'static str),
+    /// The second recording has no `start`, and its seeks don't agree with where the first leaves the position.
+    Inconsistent(String),
+    /// The operations of one of the recordings are in the store given to `Yadon::with_store()`, so can't be copied.
+    InStore,
+}
+
+impl Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComposeError::LengthMismatch { first, second } => {
+                write!(f, "recordings are of targets {} and {} bytes long, which can't be the same", first, second)
+            },
+            ComposeError::BaseChecksums => {
+                write!(f, "second recording requires base checksums, which can't be checked partway")
+            },
+            ComposeError::GenerationStamp => {
+                write!(f, "first recording stamps a generation, which can't be stamped partway")
+            },
+            ComposeError::UnsupportedOperation(name) => write!(f, "{} operations can't be copied", name),
+            ComposeError::Inconsistent(reason) => {
+                write!(f, "second recording doesn't carry on from the first: {}", reason)
+            },
+            ComposeError::InStore => write!(f, "operations are in a store, which can't be copied"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ComposeError {}
+
+/// Errors that may occur during `Yadon::merge()` or `Yadon::extend_from()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MergeError {
+    /// The recordings have different lengths, so they can't both be of the same target.
+    LengthMismatch {
+        /// The length of the first recording.
+        first: u64,
+        /// The length of the second.
+        second: u64,
+    },
+    /// The recordings stamp different generations, as offset and generation, only one of which can be stamped.
+    GenerationStampMismatch {
+        /// The generation stamp of the first recording.
+        first: (u64, u64),
+        /// The generation stamp of the second.
+        second: (u64, u64),
+    },
+    /// The second recording holds an operation of this kind, which can't be copied.
+    UnsupportedOperation(&'static str),
+    /// The second recording's seeks don't agree with where the first leaves the position.
+    Inconsistent(String),
+    /// The operations of one of the recordings are in the store given to `Yadon::with_store()`, so can't be merged.
+    InStore,
+}
+
+impl Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::LengthMismatch { first, second } => {
+                write!(f, "recordings are of targets {} and {} bytes long, which can't be the same", first, second)
+            },
+            MergeError::GenerationStampMismatch { first, second } => {
+                write!(f, "recordings stamp generation {} at {} and {} at {}", first.1, first.0, second.1, second.0)
+            },
+            MergeError::UnsupportedOperation(name) => write!(f, "{} operations can't be copied", name),
+            MergeError::Inconsistent(reason) => {
+                write!(f, "second recording doesn't carry on from the first: {}", reason)
+            },
+            MergeError::InStore => write!(f, "operations are in a store, which can't be merged"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MergeError {}
+
+/// Errors that may occur during `Yadon::translate()` or `Yadon::translated()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TranslateError {
+    /// The operation at this index would land below zero or past `u64::MAX`.
+    OutOfRange {
+        /// Index of the operation, counting from the first stored operation.
+        index: usize,
+    },
+    /// This position, other than one an operation refers to, would be moved below zero or past `u64::MAX`.
+    FieldOutOfRange(&'static str),
+    /// The operation at this index is of this kind, whose positions can't be known.
+    UnsupportedOperation {
+        /// Index of the operation, counting from the first stored operation.
+        index: usize,
+        /// Name of the kind of operation.
+        name: &'static str,
+    },
+    /// The stored operations don't agree with one another, so can't be copied.
+    Inconsistent(String),
+    /// The operations are in the store given to `Yadon::with_store()`, so can't be translated.
+    InStore,
+}
+
+impl Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslateError::OutOfRange { index } => write!(f, "operation {} would land out of range", index),
+            TranslateError::FieldOutOfRange(field) => write!(f, "{} would be moved out of range", field),
+            TranslateError::UnsupportedOperation { index, name } => {
+                write!(f, "operation {} is a {} operation, which can't be translated", index, name)
+            },
+            TranslateError::Inconsistent(reason) => write!(f, "operations are inconsistent: {}", reason),
+            TranslateError::InStore => write!(f, "operations are in a store, which can't be translated"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TranslateError {}
+
+/// Applying an `ApplySession` stopped partway through.
+#[derive(Debug)]
+pub struct SessionError {
+    /// Number of operations which have been applied completely, counting from the first stored operation. The session
+    /// resumes from the next one.
+    pub ops_completed: usize,
+    /// Why applying stopped.
+    pub error: ApplyError,
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "apply stopped after {} operations", self.ops_completed)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SessionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// A range of the target whose checksum didn't match, found while checking `Yadon::require_base_checksum()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    /// The range which was checked, with any end of `u64::MAX` resolved.
+    pub range: Range<u64>,
+    /// The CRC-32 which was required.
+    pub expected: u32,
+    /// The CRC-32 of the target.
+    pub actual: u32,
+}
+
+fn mismatches(mismatches: &[ChecksumMismatch]) -> String {
+    let mismatches: Vec<String> = mismatches.iter()
+        .map(|mismatch| format!("{:?} has checksum {:#010x}, expected {:#010x}", mismatch.range, mismatch.actual, mismatch.expected))
+        .collect();
+    mismatches.join(", ")
+}
+
+fn in_label(label: Option<&str>) -> String {
+    match label {
+        Some(label) => format!(" (in \"{}\")", label),
+        None => String::new(),
+    }
+}
+
+/// A divergence collected by `Yadon::apply_collect()`.
+#[derive(Debug)]
+pub struct Divergence {
+    /// Index of the operation which diverged, or `None` for the seek to `start` before the first operation.
+    pub index: Option<usize>,
+    /// What diverged.
+    pub kind: DivergenceKind,
+}
+
+/// What diverged while applying an operation.
+#[derive(Debug)]
+pub enum DivergenceKind {
+    /// The position after a seek.
+    Seek(Confusion<u64>),
+    /// The number of bytes written.
+    BytesWritten(Confusion<usize>),
+}
+
+impl From<DivergenceKind> for ApplyError {
+    fn from(kind: DivergenceKind) -> Self {
+        match kind {
+            DivergenceKind::Seek(confusion) => ApplyError::SeekDiverged(confusion),
+            DivergenceKind::BytesWritten(confusion) => ApplyError::NumBytesWrittenDiverge(confusion),
+        }
+    }
+}
+
+/// During apply, there was divergence between the expected return value of an operation, and its result.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Confusion<T>
+where T: Debug {
+    /// The value which we returned when the operation was first simulated.
+    pub expected: T,
+    /// The value which was returned when trying to apply this operation to another Write + Seek.
+    pub actual: T,
+    /// Where the operation was recorded, if the `track-callers` feature is enabled.
+    pub location: Option<&'static Location<'static>>,
+    /// The label the operation was recorded under, if any.
+    pub label: Option<Arc<str>>,
+    /// Index of the operation in `Yadon::operations`, or `None` for the seek to `start` before the first operation.
+    pub op_index: Option<usize>,
+    /// The target offset which the operation addressed, if known. This is where the target was positioned when the
+    /// operation started, or the destination of a copy.
+    pub offset: Option<u64>,
+    /// For operations which write, the length of the data they were to write.
+    pub len: Option<usize>,
+}
+
+impl<T> Confusion<T> where T: Debug {
+    pub(crate) fn new(expected: T, actual: T) -> Self {
+        Confusion {
+            expected,
+            actual,
+            location: None,
+            label: None,
+            op_index: None,
+            offset: None,
+            len: None,
+        }
+    }
+}
+
+impl<T> Display for Confusion<T> where T: Debug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {:?}, got {:?}", self.expected, self.actual)?;
+        match self.op_index {
+            Some(op_index) => write!(f, " (operation {}", op_index)?,
+            None => write!(f, " (seek to start")?,
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        if let Some(len) = self.len {
+            write!(f, ", writing {} bytes", len)?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, ", in \"{}\"", label)?;
+        }
+        if let Some(location) = self.location {
+            write!(f, ", recorded at {}", location)?;
+        }
+        write!(f, ")")
+    }
+}
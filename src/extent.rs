@@ -0,0 +1,97 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// A set of disjoint byte ranges. Touching ranges are merged, so lookups and insertions are O(log n).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Extents {
+    /// Start of each range, mapped to its end.
+    ranges: BTreeMap<u64, u64>,
+}
+
+impl Extents {
+    /// The range in the set which intersects `range`, if any.
+    pub(crate) fn overlapping(&self, range: &Range<u64>) -> Option<Range<u64>> {
+        let (&start, &end) = self.ranges.range(..range.end).next_back()?;
+        if end > range.start {
+            Some(start..end)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `range` lies entirely within one of the ranges in the set.
+    pub(crate) fn contains(&self, range: &Range<u64>) -> bool {
+        match self.ranges.range(..=range.start).next_back() {
+            Some((_, &end)) => end >= range.end,
+            None => false,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Adds `range` to the set, merging it with any ranges it touches.
+    pub(crate) fn insert(&mut self, range: Range<u64>) {
+        let mut start = range.start;
+        let mut end = range.end;
+        if let Some((&before_start, &before_end)) = self.ranges.range(..=start).next_back() {
+            if before_end >= start {
+                start = before_start;
+                end = end.max(before_end);
+            }
+        }
+        let touching: Vec<u64> = self.ranges.range(start..=end).map(|(&start, _)| start).collect();
+        for touching_start in touching {
+            if let Some(touching_end) = self.ranges.remove(&touching_start) {
+                end = end.max(touching_end);
+            }
+        }
+        self.ranges.insert(start, end);
+    }
+
+    /// The parts of `range` which aren't in the set, in order.
+    #[cfg(feature = "std")]
+    pub(crate) fn uncovered(&self, range: Range<u64>) -> Vec<Range<u64>> {
+        let mut gaps = Vec::new();
+        let mut start = range.start;
+        if let Some((_, &end)) = self.ranges.range(..=start).next_back() {
+            start = start.max(end);
+        }
+        for (&covered_start, &covered_end) in self.ranges.range(range.start..range.end) {
+            if covered_start > start {
+                gaps.push(start..covered_start);
+            }
+            start = start.max(covered_end);
+        }
+        if start < range.end {
+            gaps.push(start..range.end);
+        }
+        gaps
+    }
+
+    /// Rebuilds a set from its ranges, as given by `iter()`. Fails if any of them is empty.
+    pub(crate) fn from_ranges(ranges: Vec<Range<u64>>) -> Result<Self, &'static str> {
+        let mut extents = Extents::default();
+        for range in ranges {
+            if range.is_empty() {
+                return Err("extent is empty");
+            }
+            extents.insert(range);
+        }
+        Ok(extents)
+    }
+
+    /// The set with each range moved by `delta`, or `None` if one would be moved below zero or past `u64::MAX`.
+    pub(crate) fn translated(&self, delta: i64) -> Option<Self> {
+        let ranges = self.ranges.iter()
+            .map(|(&start, &end)| Some((start.checked_add_signed(delta)?, end.checked_add_signed(delta)?)))
+            .collect::<Option<_>>()?;
+        Some(Extents { ranges })
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.ranges.iter().map(|(&start, &end)| start..end)
+    }
+}
@@ -0,0 +1,324 @@
+use core::fmt::{self, Display};
+#[cfg(feature = "std")]
+use std::io::{Seek, Write};
+use crate::io::{ErrorKind, SeekFrom};
+use crate::slice::SliceCursor;
+use crate::{fitting_len, seek_position, OverflowPolicy};
+
+/// An operation stored by `YadonFixed`. Data is stored as the offset of its first byte in the data arena.
+#[derive(Debug, Clone, Copy)]
+enum FixedOperation {
+    Write { data: usize, len: usize },
+    Seek(SeekFrom, u64),
+    Flush,
+    Fill { byte: u8, len: u64 },
+    Repeat { pattern: usize, pattern_len: usize, count: u64 },
+}
+
+/// Stores write and seek operations to be laid down later, as `Yadon` does, in inline buffers rather than on the heap:
+/// up to `DATA` bytes of written data, and up to `OPS` operations. Recording fails with `FixedError::DataFull` or
+/// `FixedError::OperationsFull` once either runs out, without recording anything, and otherwise returns exactly what
+/// `Yadon` would, including how writes are clamped to `length`. Only writes, fills, repeated writes, seeks and flushes
+/// can be recorded.
+/// # Example
+/// ```
+/// use yadon::YadonFixed;
+/// use yadon::io::SeekFrom;
+/// let mut yadon = YadonFixed::<16, 4>::new(Some(0), Some(8));
+/// assert_eq!(yadon.seek(SeekFrom::Start(4)).unwrap(), 4);
+/// assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+/// assert_eq!(yadon.seek(SeekFrom::End(-6)).unwrap(), 2);
+/// assert_eq!(yadon.write(&[4, 5]).unwrap(), 2);
+///
+/// let mut target = [0u8; 8];
+/// yadon.materialize_into(&mut target).unwrap();
+/// assert_eq!(target, [0, 0, 4, 5, 1, 2, 3, 0]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct YadonFixed<const DATA: usize, const OPS: usize> {
+    /// The data of the stored writes, back to back.
+    data: [u8; DATA],
+    /// Number of bytes of `data` in use.
+    data_len: usize,
+    /// Stored operations. Only the first `operations_len` are in use.
+    operations: [FixedOperation; OPS],
+    operations_len: usize,
+    /// Virtual position to use for emulating the return values of another Write + Seek
+    virtual_position: Option<u64>,
+    /// If set, used to set the initial virtual cursor position, as with `Yadon::start`.
+    pub start: Option<u64>,
+    /// If set, used to emulate cursor position for `SeekFrom::End` operations, and writes are clamped to it, as with
+    /// `Yadon::length`.
+    pub length: Option<u64>,
+    /// What to do with writes which extend past `length`.
+    overflow_policy: OverflowPolicy,
+}
+
+impl<const DATA: usize, const OPS: usize> YadonFixed<DATA, OPS> {
+    /// Constructs an empty recorder with optional `start` position and `length`, as `Yadon::new()` does.
+    pub const fn new(start: Option<u64>, length: Option<u64>) -> Self {
+        YadonFixed {
+            data: [0; DATA],
+            data_len: 0,
+            operations: [FixedOperation::Flush; OPS],
+            operations_len: 0,
+            virtual_position: None,
+            start,
+            length,
+            overflow_policy: OverflowPolicy::Truncate,
+        }
+    }
+
+    /// Sets what to do with writes which extend past `length`, when constructing.
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Sets what to do with writes which extend past `length`. Only affects writes recorded afterwards.
+    pub fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    /// What is done with writes which extend past `length`.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// Number of stored operations.
+    pub fn len(&self) -> usize {
+        self.operations_len
+    }
+
+    /// Whether no operations have been stored.
+    pub fn is_empty(&self) -> bool {
+        self.operations_len == 0
+    }
+
+    /// Number of bytes of the data arena in use.
+    pub fn data_len(&self) -> usize {
+        self.data_len
+    }
+
+    /// Records writing `buf`, returning how many bytes of it would be written, after clamping to the length, as
+    /// `Yadon::write()` does.
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, FixedError> {
+        let len = self.fitting_len(buf.len() as u64)? as usize;
+        self.reserve(1, len)?;
+        let data = self.store(&buf[0..len]);
+        self.push(FixedOperation::Write { data, len });
+        self.advance(len as u64);
+        Ok(len)
+    }
+
+    /// Records writing `len` copies of `byte`, as `Yadon::fill()` does. No data is stored.
+    pub fn fill(&mut self, byte: u8, len: u64) -> Result<u64, FixedError> {
+        let len = self.fitting_len(len)?;
+        self.reserve(1, 0)?;
+        self.push(FixedOperation::Fill { byte, len });
+        self.advance(len);
+        Ok(len)
+    }
+
+    /// Records writing `pattern` `count` times, as `Yadon::write_repeated()` does, storing the pattern once. If the
+    /// length cuts off the final repetition, the partial repetition takes a second operation slot.
+    pub fn write_repeated(&mut self, pattern: &[u8], count: u64) -> Result<u64, FixedError> {
+        let total_len = (pattern.len() as u64).checked_mul(count).ok_or(FixedError::Io(ErrorKind::InvalidInput))?;
+        let len = self.fitting_len(total_len)?;
+        if len == 0 {
+            self.reserve(1, 0)?;
+            self.push(FixedOperation::Write { data: self.data_len, len: 0 });
+            return Ok(0);
+        }
+
+        let whole_repetitions = len / pattern.len() as u64;
+        let remainder = (len % pattern.len() as u64) as usize;
+        let stored_len = if whole_repetitions > 0 { pattern.len() } else { remainder };
+        self.reserve(usize::from(whole_repetitions > 0) + usize::from(remainder > 0), stored_len)?;
+        // The partial repetition is a prefix of the pattern, so it shares the pattern's data.
+        let data = self.store(&pattern[0..stored_len]);
+        if whole_repetitions > 0 {
+            self.push(FixedOperation::Repeat { pattern: data, pattern_len: pattern.len(), count: whole_repetitions });
+        }
+        if remainder > 0 {
+            self.push(FixedOperation::Write { data, len: remainder });
+        }
+        self.advance(len);
+        Ok(len)
+    }
+
+    /// Records seeking to `pos`, returning the resulting virtual position, as `Yadon::seek()` does.
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64, FixedError> {
+        let resulting_position = seek_position(self.virtual_position, self.start, self.length, pos).map_err(FixedError::Io)?;
+        self.reserve(1, 0)?;
+        self.push(FixedOperation::Seek(pos, resulting_position));
+        self.virtual_position = Some(resulting_position);
+        Ok(resulting_position)
+    }
+
+    /// Records a flush, as `Yadon::flush()` does. Laying the operations down into a slice doesn't need flushing, but
+    /// it takes an operation slot, so that the operations line up with those `Yadon` would record.
+    pub fn flush(&mut self) -> Result<(), FixedError> {
+        self.reserve(1, 0)?;
+        self.push(FixedOperation::Flush);
+        Ok(())
+    }
+
+    /// Lays the stored operations down onto `buf`, as `Yadon::materialize_into_slice()` does: the slice never grows, so
+    /// writes past its end are cut short, and a write or seek whose result differs from what was recorded fails.
+    /// Returns the number of bytes written.
+    pub fn materialize_into(&self, buf: &mut [u8]) -> Result<usize, FixedError> {
+        let mut cursor = SliceCursor::new(buf);
+        if let Some(start) = self.start {
+            cursor.seek(SeekFrom::Start(start));
+        }
+        let mut total_bytes_written = 0;
+        for (op_index, operation) in self.operations[0..self.operations_len].iter().enumerate() {
+            let (expected, bytes_written) = match *operation {
+                FixedOperation::Write { data, len } => (len, cursor.write_pattern(&self.data[data..data + len], len as u64)),
+                FixedOperation::Fill { byte, len } => (len as usize, cursor.write_pattern(&[byte], len)),
+                FixedOperation::Repeat { pattern, pattern_len, count } => {
+                    let expected = (pattern_len as u64).checked_mul(count).ok_or(FixedError::Io(ErrorKind::InvalidInput))? as usize;
+                    (expected, cursor.write_pattern(&self.data[pattern..pattern + pattern_len], expected as u64))
+                },
+                FixedOperation::Seek(pos, expected) => {
+                    let actual = cursor.seek(pos).ok_or(FixedError::Io(ErrorKind::InvalidInput))?;
+                    if actual != expected {
+                        return Err(FixedError::SeekDiverged { op_index, expected, actual });
+                    }
+                    continue;
+                },
+                FixedOperation::Flush => continue,
+            };
+            if bytes_written != expected {
+                return Err(FixedError::WriteDiverged { op_index, expected, actual: bytes_written });
+            }
+            total_bytes_written += bytes_written;
+        }
+        Ok(total_bytes_written)
+    }
+
+    /// How many bytes of a write of `len` bytes are recorded, as `Yadon` clamps writes.
+    fn fitting_len(&self, len: u64) -> Result<u64, FixedError> {
+        fitting_len(self.virtual_position.or(self.start), self.length, len, self.overflow_policy).map_err(FixedError::Io)
+    }
+
+    /// Fails unless there's room for `operations` more operations and `data` more bytes of data.
+    fn reserve(&self, operations: usize, data: usize) -> Result<(), FixedError> {
+        if OPS - self.operations_len < operations {
+            return Err(FixedError::OperationsFull);
+        }
+        if DATA - self.data_len < data {
+            return Err(FixedError::DataFull { needed: data, available: DATA - self.data_len });
+        }
+        Ok(())
+    }
+
+    /// Copies `data` into the arena, which must have room for it, returning where it starts.
+    fn store(&mut self, data: &[u8]) -> usize {
+        let start = self.data_len;
+        self.data[start..start + data.len()].copy_from_slice(data);
+        self.data_len += data.len();
+        start
+    }
+
+    /// Stores an operation in the next slot, which must be free.
+    fn push(&mut self, operation: FixedOperation) {
+        self.operations[self.operations_len] = operation;
+        self.operations_len += 1;
+    }
+
+    /// Moves the virtual position forward past a write of `len` bytes which fits.
+    fn advance(&mut self, len: u64) {
+        self.virtual_position = Some(self.virtual_position.or(self.start).unwrap_or(0) + len);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const DATA: usize, const OPS: usize> Write for YadonFixed<DATA, OPS> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(YadonFixed::write(self, buf)?)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(YadonFixed::flush(self)?)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<const DATA: usize, const OPS: usize> Seek for YadonFixed<DATA, OPS> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        Ok(YadonFixed::seek(self, pos)?)
+    }
+}
+
+/// Errors that may occur while recording into a `YadonFixed`, or laying its operations down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FixedError {
+    /// Recording failed as it would have with `Yadon`, whose error would have been of this kind, or a seek being laid
+    /// down would have moved to a negative or overflowing position.
+    Io(ErrorKind),
+    /// The data arena doesn't have room for the data being recorded. Nothing was recorded.
+    DataFull {
+        /// Number of bytes of data which the operation would store.
+        needed: usize,
+        /// Number of bytes of the arena which are free.
+        available: usize,
+    },
+    /// Every operation slot is in use. Nothing was recorded.
+    OperationsFull,
+    /// A seek being laid down moved somewhere other than it did when it was recorded.
+    SeekDiverged {
+        /// Index of the seek among the stored operations.
+        op_index: usize,
+        /// The position the seek moved to when it was recorded.
+        expected: u64,
+        /// The position it moved to.
+        actual: u64,
+    },
+    /// A write being laid down wrote a different number of bytes than it did when it was recorded.
+    WriteDiverged {
+        /// Index of the write among the stored operations.
+        op_index: usize,
+        /// The number of bytes it wrote when it was recorded.
+        expected: usize,
+        /// The number of bytes it wrote.
+        actual: usize,
+    },
+}
+
+impl Display for FixedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixedError::Io(kind) => write!(f, "recording failed: {}", kind),
+            FixedError::DataFull { needed, available } => {
+                write!(f, "recorder is full: {} bytes of data don't fit in the {} bytes free", needed, available)
+            },
+            FixedError::OperationsFull => write!(f, "recorder is full: every operation slot is in use"),
+            FixedError::SeekDiverged { op_index, expected, actual } => {
+                write!(f, "seek position diverged: expected {}, got {} (operation {})", expected, actual, op_index)
+            },
+            FixedError::WriteDiverged { op_index, expected, actual } => {
+                write!(f, "number of bytes written diverged: expected {}, got {} (operation {})", expected, actual, op_index)
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FixedError {}
+
+#[cfg(feature = "std")]
+impl From<FixedError> for std::io::Error {
+    /// Unwraps `FixedError::Io`, and wraps any other error so that it can be recovered with `io::Error::get_ref()`
+    /// and `downcast_ref::<FixedError>()`.
+    fn from(error: FixedError) -> Self {
+        let kind = match error {
+            FixedError::Io(kind) => return kind.into(),
+            FixedError::DataFull { .. } | FixedError::OperationsFull => ErrorKind::OutOfMemory,
+            FixedError::SeekDiverged { .. } | FixedError::WriteDiverged { .. } => ErrorKind::InvalidData,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
@@ -0,0 +1,193 @@
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use crate::extent::Extents;
+use crate::io::SeekFrom;
+use crate::label::Labels;
+#[cfg(feature = "std")]
+use crate::ApplyError;
+use crate::{ComposeError, WriteOperation, Yadon};
+
+impl Yadon {
+    /// Starts a child recording on top of this one, for speculative edits which can be discarded by dropping the child,
+    /// or kept with `flatten()`. The child starts where this recording's virtual position is, so that applying it after
+    /// this one carries on from there, and takes on its length and recording settings, such as `deny_overwrite` and the
+    /// ranges written so far. The child keeps this recording as its parent, without copying its operations, so that
+    /// queries such as `touched_ranges()` see through to it; the parent can't be recorded into while it's shared.
+    /// If the virtual position isn't known, as after a deferred seek from the end, the child has no `start`, so it
+    /// carries on from wherever applying this recording left the target. The parent isn't encoded by `to_bytes()` or
+    /// serde, so flatten the child before sending it elsewhere.
+    /// # Example
+    /// ```
+    /// # extern crate alloc;
+    /// use alloc::sync::Arc;
+    /// use yadon::Yadon;
+    /// let mut base = Yadon::new(Some(0), Some(8));
+    /// base.write(b"base").unwrap();
+    /// let base = Arc::new(base);
+    ///
+    /// let mut child = base.fork();
+    /// child.write(b"more").unwrap();
+    ///
+    /// let flattened = Yadon::flatten(&base, &child).unwrap();
+    /// let mut target = [0u8; 8];
+    /// flattened.materialize_into_slice(&mut target).unwrap();
+    /// assert_eq!(&target, b"basemore");
+    /// ```
+    pub fn fork(self: &Arc<Self>) -> Yadon {
+        let start = match self.end_unresolved {
+            true => None,
+            false => self.virtual_position.or(self.start),
+        };
+        Yadon {
+            virtual_position: start,
+            defer_end_seeks: self.defer_end_seeks,
+            append_only: self.append_only,
+            deny_overwrite: self.deny_overwrite,
+            written_extents: self.written_extents.clone(),
+            reserved_regions: self.reserved_regions.clone(),
+            written_end: self.written_end,
+            overflow_policy: self.overflow_policy,
+            length_mode: self.length_mode,
+            gap_fill: self.gap_fill,
+            parent: Some(self.clone()),
+            ..Yadon::new(start, self.length)
+        }
+    }
+
+    /// The recording this one was forked from by `fork()`, if it was.
+    pub fn parent(&self) -> Option<&Arc<Yadon>> {
+        self.parent.as_ref()
+    }
+
+    /// Makes one recording whose effect is that of applying `parent`, then `child`, which should have been forked from
+    /// it by `fork()`. If `parent` was itself forked, what it was forked from is flattened in too. The operations are
+    /// copied, with a seek to the `start` of each recording after the first, if it has one, between them, along with
+    /// their labels. The flattened recording has the `start`, base checksums, apply limit and generation stamp of the
+    /// first recording, and the length and recording settings of `child`, so that recording can carry on into it; base
+    /// checksums required by the recordings after the first are dropped, as they don't hold of the target before the
+    /// first is applied. Fails with `ComposeError::UnsupportedOperation` if any of the operations is a
+    /// `WriteOperation::Custom`, which can't be copied, with `ComposeError::Inconsistent` if `child` doesn't carry on
+    /// from where `parent` leaves the virtual position, or with `ComposeError::InStore` if the operations of any of the
+    /// recordings are in the store given to `with_store()`.
+    pub fn flatten(parent: &Yadon, child: &Yadon) -> Result<Yadon, ComposeError> {
+        let mut layers = parent.layers();
+        layers.push(child);
+        Yadon::concatenate(&layers).map_err(|error| match error {
+            ConcatenateError::Uncopyable(name) => ComposeError::UnsupportedOperation(name),
+            ConcatenateError::Inconsistent(reason) => ComposeError::Inconsistent(reason),
+            ConcatenateError::InStore => ComposeError::InStore,
+        })
+    }
+
+    /// Copies the operations of each of `layers` in turn into one recording, with a seek to the `start` of each after
+    /// the first, if it has one, between them, along with their labels and where they were recorded from. The
+    /// recording has the `start`, base checksums, apply limit and generation stamp of the first, and the length and
+    /// recording settings of the last. Fails if an operation can't be copied, the operations don't agree with one
+    /// another, or any of them are in a store.
+    pub(crate) fn concatenate(layers: &[&Yadon]) -> Result<Yadon, ConcatenateError> {
+        if layers.iter().any(|layer| layer.in_store()) {
+            return Err(ConcatenateError::InStore);
+        }
+        let (first, last) = (layers[0], layers[layers.len() - 1]);
+        let mut concatenated = Yadon {
+            defer_end_seeks: last.defer_end_seeks,
+            append_only: last.append_only,
+            deny_overwrite: last.deny_overwrite,
+            written_extents: last.written_extents.clone(),
+            reserved_regions: last.reserved_regions.clone(),
+            overflow_policy: last.overflow_policy,
+            length_mode: last.length_mode,
+            base_checksums: first.base_checksums.clone(),
+            max_applies: first.max_applies,
+            generation_stamp: first.generation_stamp,
+            gap_fill: last.gap_fill,
+            ..Yadon::new(first.start, last.length)
+        };
+        let mut runs: Vec<(String, Range<usize>)> = Vec::new();
+        for (i, layer) in layers.iter().enumerate() {
+            if let (true, Some(start)) = (i > 0, layer.start) {
+                concatenated.operations.push(WriteOperation::Seek(SeekFrom::Start(start), start));
+                #[cfg(feature = "track-callers")]
+                concatenated.locations.push(None);
+            }
+            let offset = concatenated.operations.len();
+            for operation in &layer.operations {
+                let copy = operation.try_copy().ok_or(ConcatenateError::Uncopyable(operation.name()))?;
+                concatenated.operations.push(copy);
+            }
+            #[cfg(feature = "track-callers")]
+            concatenated.locations.extend_from_slice(&layer.locations);
+            runs.extend(layer.labels.iter().map(|(label, operations)| {
+                (label.to_string(), operations.start + offset..operations.end + offset)
+            }));
+        }
+        let stack = last.labels.stack().map(ToString::to_string).collect();
+        concatenated.labels = Labels::from_parts(stack, runs, concatenated.operations.len())
+            .expect("runs of each recording are in order and within it");
+        concatenated.resimulate().map_err(ConcatenateError::Inconsistent)?;
+        Ok(concatenated)
+    }
+
+    /// This recording and those it was forked from, the one forked from first.
+    fn layers(&self) -> Vec<&Yadon> {
+        let mut layers = Vec::new();
+        let mut layer = Some(self);
+        while let Some(recording) = layer {
+            layers.push(recording);
+            layer = recording.parent.as_deref();
+        }
+        layers.reverse();
+        layers
+    }
+}
+
+#[cfg(feature = "std")]
+impl Yadon {
+    /// The ranges which applying the stored operations writes to, after those of the recording this one was forked
+    /// from, if it was, in order of offset, with touching ranges merged. Fails as `apply_range()` does if there's
+    /// anything other than writes, seeks and flushes, in this recording or one it was forked from.
+    pub fn touched_ranges(&self) -> Result<Vec<Range<u64>>, ApplyError> {
+        let mut touched = Extents::default();
+        for layer in self.layers() {
+            for (_, extent) in layer.write_extents()? {
+                touched.insert(extent);
+            }
+        }
+        Ok(touched.iter().collect())
+    }
+
+    /// Reads what applying the stored operations, after those of the recording this one was forked from, if it was,
+    /// would leave at `offset` into `buf`, for the bytes they write. Bytes they don't write are left as they are in
+    /// `buf`. Returns the ranges which were read, in order of offset, as `touched_ranges()` gives them. Fails as
+    /// `touched_ranges()` does.
+    pub fn read_pending_at(&self, offset: u64, buf: &mut [u8]) -> Result<Vec<Range<u64>>, ApplyError> {
+        let wanted = offset..offset.saturating_add(buf.len() as u64);
+        let mut read = Extents::default();
+        for layer in self.layers() {
+            for (index, extent) in layer.write_extents()? {
+                let overlap = extent.start.max(wanted.start)..extent.end.min(wanted.end);
+                if overlap.is_empty() {
+                    continue;
+                }
+                let into = &mut buf[(overlap.start - offset) as usize..(overlap.end - offset) as usize];
+                crate::apply::recorded_bytes(&layer.operations[index], overlap.start - extent.start, into);
+                read.insert(overlap);
+            }
+        }
+        Ok(read.iter().collect())
+    }
+}
+
+/// Why `Yadon::concatenate()` failed.
+#[derive(Debug)]
+pub(crate) enum ConcatenateError {
+    /// An operation with this name can't be copied.
+    Uncopyable(&'static str),
+    /// The operations don't agree with one another.
+    Inconsistent(String),
+    /// The operations of one of the recordings are in the store given to `with_store()`.
+    InStore,
+}
@@ -0,0 +1,81 @@
+use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::io::{Seek, Write};
+#[cfg(feature = "std")]
+use crate::{ApplyHandle, ApplyOptions};
+use crate::Yadon;
+
+/// A recording which is done with, which can be cloned cheaply and shared between threads to be applied many times.
+/// Clones share the stored operations, and count towards the same `max_applies()`. Everything a `Yadon` offers which
+/// doesn't record, such as `apply()`, `materialize()` or `to_bytes()`, is available through `Deref`; recording isn't,
+/// until the log is thawed back into a `Yadon` with `thaw()`.
+/// # Example
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use yadon::Yadon;
+/// use std::io::Write;
+/// let mut yadon = Yadon::new(Some(0), Some(4));
+/// yadon.write_all(b"abcd").unwrap();
+/// let log = yadon.freeze();
+///
+/// let threads: Vec<_> = (0..2).map(|_| {
+///     let log = log.clone();
+///     std::thread::spawn(move || log.materialize(None).unwrap())
+/// }).collect();
+/// for thread in threads {
+///     assert_eq!(thread.join().unwrap(), b"abcd");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplayLog {
+    yadon: Arc<Yadon>,
+}
+
+impl Yadon {
+    /// Freezes the recording into a `ReplayLog`, to be shared and applied without copying the stored operations.
+    pub fn freeze(self) -> ReplayLog {
+        ReplayLog { yadon: Arc::new(self) }
+    }
+}
+
+impl ReplayLog {
+    /// Thaws the log back into a `Yadon`, so that more can be recorded into it. The stored operations are only copied if
+    /// there are other clones of the log, so the recording carries on from where it was frozen, with the operations,
+    /// labels and recording settings it had, and how many times it has been applied; a store given to
+    /// `Yadon::with_store()`, and the recording it was forked from, aren't copied. If there are other clones, and there's
+    /// a `WriteOperation::Custom`, which can't be copied, the log is given back instead.
+    pub fn thaw(self) -> Result<Yadon, ReplayLog> {
+        let shared = match Arc::try_unwrap(self.yadon) {
+            Ok(yadon) => return Ok(yadon),
+            Err(shared) => shared,
+        };
+        match Yadon::concatenate(&[&shared]) {
+            Ok(copy) => Ok(Yadon {
+                virtual_position: shared.virtual_position,
+                end_unresolved: shared.end_unresolved,
+                written_end: shared.written_end,
+                applied: AtomicU64::new(shared.applied.load(Ordering::Relaxed)),
+                ..copy
+            }),
+            Err(_) => Err(ReplayLog { yadon: shared }),
+        }
+    }
+
+    /// Applies the stored operations on `target` from a new thread, as `Yadon::apply_in_background()` does, sharing
+    /// them with the thread.
+    #[cfg(feature = "std")]
+    pub fn apply_in_background<T>(&self, target: T, options: ApplyOptions) -> ApplyHandle
+    where T: Write + Seek + Send + 'static {
+        self.yadon.clone().apply_in_background(target, options)
+    }
+}
+
+impl Deref for ReplayLog {
+    type Target = Yadon;
+
+    fn deref(&self) -> &Yadon {
+        &self.yadon
+    }
+}
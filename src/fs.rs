@@ -0,0 +1,244 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::{ApplyError, ApplyOptions, ApplyReport, CheckPolicy, SetLen, SyncTarget, WriteAt, WriteOperation, Yadon};
+use crate::apply::{recorded_bytes, FILL_CHUNK_SIZE};
+use crate::positional::Positioned;
+
+/// Options for `Yadon::apply_atomic()`.
+#[derive(Debug, Clone)]
+pub struct AtomicOptions {
+    /// How the stored operations are applied to the temporary file.
+    pub apply: ApplyOptions,
+    /// Whether the temporary file starts as a copy of the original. If the stored operations rewrite the whole file,
+    /// the copy can be skipped, so that the temporary file starts empty.
+    pub copy_original: bool,
+}
+
+impl Default for AtomicOptions {
+    fn default() -> Self {
+        AtomicOptions {
+            apply: ApplyOptions::default(),
+            copy_original: true,
+        }
+    }
+}
+
+/// How many times to pick another name if a temporary file already exists.
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+/// Counts temporary files created by this process, to tell their names apart.
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary file next to the file it replaces, which is removed when dropped unless it has been persisted.
+struct TempFile {
+    path: PathBuf,
+    file: File,
+    persisted: bool,
+}
+
+impl TempFile {
+    fn create_next_to(original: &Path) -> std::io::Result<Self> {
+        let file_name = original.file_name()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "path does not name a file"))?;
+        let mut attempts = 0;
+        loop {
+            let mut temp_name = std::ffi::OsString::from(".");
+            temp_name.push(file_name);
+            temp_name.push(format!(".yadon-{}-{}.tmp", std::process::id(), TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)));
+            let path = original.with_file_name(temp_name);
+            match OpenOptions::new().read(true).write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok(TempFile { path, file, persisted: false }),
+                Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists && attempts < TEMP_FILE_ATTEMPTS => attempts += 1,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Renames the temporary file over `original`, then syncs the directory on platforms where that makes the rename
+    /// durable.
+    fn persist(mut self, original: &Path) -> std::io::Result<()> {
+        std::fs::rename(&self.path, original)?;
+        self.persisted = true;
+        #[cfg(unix)]
+        {
+            let dir = match original.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Yadon {
+    /// Applies the stored operations to the file at `path` so that, even if applying fails or the system crashes
+    /// partway through, the file is either left as it was or has every operation applied. The operations are applied to
+    /// a temporary file in the same directory, which is synced and then renamed over the original. The temporary file
+    /// gets the permissions of the original, and is removed if anything fails. The file must already exist.
+    pub fn apply_atomic(&self, path: &Path, options: &AtomicOptions) -> Result<ApplyReport, ApplyError> {
+        let permissions = std::fs::metadata(path)?.permissions();
+        let mut temp = TempFile::create_next_to(path)?;
+        if options.copy_original {
+            std::io::copy(&mut File::open(path)?, &mut temp.file)?;
+            temp.file.seek(SeekFrom::Start(0))?;
+        }
+        let report = self.apply_report_all(&mut temp.file, &options.apply)?;
+        temp.file.sync_all()?;
+        temp.file.set_permissions(permissions)?;
+        temp.persist(path)?;
+        Ok(report)
+    }
+}
+
+/// Writes a shared `File` at explicit offsets (`pwrite` on Unix), never using its cursor.
+#[cfg(any(unix, windows))]
+impl WriteAt for &File {
+    fn write_at(&mut self, pos: u64, buf: &[u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let written = std::os::unix::fs::FileExt::write_at(*self, buf, pos)?;
+        #[cfg(windows)]
+        let written = std::os::windows::fs::FileExt::seek_write(*self, buf, pos)?;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> std::io::Result<Option<u64>> {
+        Ok(Some(self.metadata()?.len()))
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl<'a, 'f> Read for Positioned<'a, &'f File> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let bytes_read = std::os::unix::fs::FileExt::read_at(*self.target, buf, self.position)?;
+        #[cfg(windows)]
+        let bytes_read = std::os::windows::fs::FileExt::seek_read(*self.target, buf, self.position)?;
+        self.position += bytes_read as u64;
+        Ok(bytes_read)
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl<'a, 'f> SetLen for Positioned<'a, &'f File> {
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        self.target.set_len(len)
+    }
+}
+
+#[cfg(any(unix, windows))]
+impl<'a, 'f> SyncTarget for Positioned<'a, &'f File> {
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.target.sync_data()
+    }
+}
+
+impl Yadon {
+    /// Applies the stored operations to a file with positional writes (`pwrite` on Unix), so that the file's shared
+    /// cursor is left alone, and other threads can keep using it. Seeks are only tracked, and `SeekFrom::End` seeks
+    /// resolve against the file's length from its metadata. If `start` isn't set, applying starts at offset 0. Short
+    /// writes are handled as `apply_with_policy()` handles them, and every kind of operation is supported, as with
+    /// `apply_atomic()`. On Windows, each positional write moves the file's cursor, so it's only left alone on Unix.
+    #[cfg(any(unix, windows))]
+    pub fn apply_positional(&self, mut file: &File, check_policy: CheckPolicy) -> Result<usize, ApplyError> {
+        let options = ApplyOptions {
+            check_policy,
+            ..Default::default()
+        };
+        self.apply_report_all(&mut Positioned::new(&mut file, None), &options).map(|report| report.bytes_written)
+    }
+    /// Applies the stored operations to a file from `threads` threads at once, with positional writes as
+    /// `apply_positional()` does, returning the number of bytes written. The writes are sorted by offset and split
+    /// into `threads` runs of about the same number of bytes, each written by its own thread. Seeks are taken to end
+    /// where they did while recording, and aren't checked against the file. Once a write fails, the other threads stop
+    /// before their next write, and the first error is returned.
+    ///
+    /// This only helps when the writes are independent, so if any of them overlap, or there's anything other than
+    /// writes, seeks and flushes, or base checksums or a generation stamp need checking, the stored operations are
+    /// applied by `apply_positional()` instead, with `CheckPolicy::Strict`.
+    #[cfg(any(unix, windows))]
+    pub fn apply_parallel(&self, file: &File, threads: usize) -> Result<usize, ApplyError> {
+        let writes = match self.offset_order() {
+            Ok(writes) if self.base_checksums.is_empty() && self.generation_stamp.is_none() => writes,
+            _ => return self.apply_positional(file, CheckPolicy::Strict),
+        };
+        self.check_can_apply()?;
+        let total: u64 = writes.iter().map(|&(index, _)| self.operations[index].expected_bytes_written()).sum();
+        let per_thread = total / threads.max(1) as u64 + 1;
+        let mut runs = vec![];
+        let mut run_start = 0;
+        let mut run_bytes = 0;
+        for (i, &(index, _)) in writes.iter().enumerate() {
+            run_bytes += self.operations[index].expected_bytes_written();
+            if run_bytes >= per_thread {
+                runs.push(&writes[run_start..=i]);
+                run_start = i + 1;
+                run_bytes = 0;
+            }
+        }
+        runs.push(&writes[run_start..]);
+
+        let failed = AtomicBool::new(false);
+        let results: Vec<std::io::Result<()>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = runs.into_iter().map(|run| {
+                let failed = &failed;
+                scope.spawn(move || {
+                    let written = self.write_run(file, run, failed);
+                    if written.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    written
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().expect("apply thread panicked")).collect()
+        });
+        for result in results {
+            result?;
+        }
+        self.count_apply();
+        Ok(total as usize)
+    }
+
+    /// Writes a run of writes for `apply_parallel()`, stopping early if another thread has `failed`.
+    #[cfg(any(unix, windows))]
+    fn write_run(&self, mut file: &File, run: &[(usize, u64)], failed: &AtomicBool) -> std::io::Result<()> {
+        let mut target = Positioned::new(&mut file, None);
+        let mut buf = vec![];
+        for &(index, offset) in run {
+            if failed.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let operation = &self.operations[index];
+            target.position = offset;
+            match operation {
+                WriteOperation::Write(data, _) => target.write_all(data)?,
+                _ => {
+                    let len = operation.expected_bytes_written();
+                    buf.resize(len.min(FILL_CHUNK_SIZE as u64) as usize, 0);
+                    let mut written = 0;
+                    while written < len {
+                        let chunk = &mut buf[0..(len - written).min(FILL_CHUNK_SIZE as u64) as usize];
+                        recorded_bytes(operation, written, chunk);
+                        target.write_all(chunk)?;
+                        written += chunk.len() as u64;
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+}
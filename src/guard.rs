@@ -0,0 +1,106 @@
+use core::ops::{Deref, DerefMut};
+use std::io::{Seek, Write};
+use crate::{ApplyError, Yadon};
+
+/// When an `ApplyGuard` which wasn't committed applies the recording as it's dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GuardPolicy {
+    /// Apply unless the guard is dropped while unwinding from a panic, so that a scope which didn't finish leaves the
+    /// target untouched.
+    #[default]
+    ApplyUnlessPanicking,
+    /// Apply even while unwinding from a panic, so that whatever was recorded before the panic lands.
+    Apply,
+    /// Never apply, so that only `commit()` does.
+    Discard,
+}
+
+/// Applies a recording to a target when it goes out of scope, returned by `Yadon::guard()`. It derefs to the `Yadon`,
+/// so that writes and seeks can be recorded through it. `commit()` applies the recording and returns the outcome, and
+/// is the way to find out whether applying succeeded; dropping the guard without committing applies it as its
+/// `GuardPolicy` says, and stashes any error in the `Yadon`, to be had with `Yadon::take_guard_error()`. Either way,
+/// the recording is applied at most once.
+/// # Example
+/// ```
+/// use yadon::{GuardPolicy, Yadon};
+/// use std::io::{Cursor, Write};
+/// let mut yadon = Yadon::new(Some(0), Some(4));
+/// let mut target = Cursor::new(vec![0u8; 4]);
+/// {
+///     let mut guard = yadon.guard(&mut target, GuardPolicy::default());
+///     guard.write_all(b"yado").unwrap();
+/// }
+/// assert!(yadon.take_guard_error().is_none());
+/// assert_eq!(target.get_ref(), b"yado");
+/// ```
+#[derive(Debug)]
+pub struct ApplyGuard<'a, T> where T: Write + Seek {
+    yadon: &'a mut Yadon,
+    target: &'a mut T,
+    policy: GuardPolicy,
+    /// Whether the recording has been applied, or was about to be, so mustn't be again.
+    done: bool,
+}
+
+impl<'a, T> ApplyGuard<'a, T> where T: Write + Seek {
+    /// Applies the recording to the target, checking return values as `apply()` does, and returns the number of bytes
+    /// written.
+    pub fn commit(mut self) -> Result<usize, ApplyError> {
+        self.done = true;
+        self.yadon.apply(self.target, true)
+    }
+
+    /// Applies the recording as `commit()` does, panicking if that fails. The recording isn't applied again as the
+    /// guard is dropped while unwinding, whatever its policy.
+    pub fn commit_or_panic(self) -> usize {
+        match self.commit() {
+            Ok(written) => written,
+            Err(error) => panic!("applying the recording failed: {}", error),
+        }
+    }
+}
+
+impl<T> Deref for ApplyGuard<'_, T> where T: Write + Seek {
+    type Target = Yadon;
+
+    fn deref(&self) -> &Yadon {
+        self.yadon
+    }
+}
+
+impl<T> DerefMut for ApplyGuard<'_, T> where T: Write + Seek {
+    fn deref_mut(&mut self) -> &mut Yadon {
+        self.yadon
+    }
+}
+
+impl<T> Drop for ApplyGuard<'_, T> where T: Write + Seek {
+    fn drop(&mut self) {
+        let apply = match self.policy {
+            GuardPolicy::ApplyUnlessPanicking => !std::thread::panicking(),
+            GuardPolicy::Apply => true,
+            GuardPolicy::Discard => false,
+        };
+        if self.done || !apply {
+            return;
+        }
+        self.done = true;
+        if let Err(error) = self.yadon.apply(self.target, true) {
+            self.yadon.guard_error = Some(error);
+        }
+    }
+}
+
+impl Yadon {
+    /// Returns a guard which records into this `Yadon`, and applies everything it holds to `target` when the guard is
+    /// committed, or as it's dropped, as `policy` says.
+    pub fn guard<'a, T>(&'a mut self, target: &'a mut T, policy: GuardPolicy) -> ApplyGuard<'a, T> where T: Write + Seek {
+        ApplyGuard { yadon: self, target, policy, done: false }
+    }
+
+    /// Takes the error from the last time an `ApplyGuard` failed to apply as it was dropped, if there's one which
+    /// hasn't been taken yet.
+    pub fn take_guard_error(&mut self) -> Option<ApplyError> {
+        self.guard_error.take()
+    }
+}
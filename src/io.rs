@@ -0,0 +1,105 @@
+//! The parts of `std::io` which recording needs. With the `std` feature, these are `std::io`'s own types. Without it,
+//! they're stand-ins with the same shape, so that code recording into a `Yadon` reads the same either way. Only
+//! `SeekFrom` and `ErrorKind` are available without the `alloc` feature.
+
+#[cfg(feature = "std")]
+pub use std::io::{Error, ErrorKind, Result, SeekFrom};
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::{ErrorKind, SeekFrom};
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+pub use self::core_io::{Error, Result};
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    #[cfg(feature = "alloc")]
+    use alloc::string::String;
+    use core::fmt::{self, Display};
+
+    /// Where to seek to, as with `std::io::SeekFrom`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum SeekFrom {
+        /// This many bytes from the start.
+        Start(u64),
+        /// This many bytes from the end, which may be negative.
+        End(i64),
+        /// This many bytes from the current position, which may be negative.
+        Current(i64),
+    }
+
+    /// The kinds of error which recording can fail with, as with `std::io::ErrorKind`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #[non_exhaustive]
+    pub enum ErrorKind {
+        /// A parameter was incorrect, such as a seek to a negative position.
+        InvalidInput,
+        /// Data wasn't valid for the operation.
+        InvalidData,
+        /// The operation isn't supported, such as a seek relative to an unknown end.
+        Unsupported,
+        /// Data couldn't be written in full.
+        WriteZero,
+        /// Any other error.
+        Other,
+    }
+
+    impl ErrorKind {
+        fn description(self) -> &'static str {
+            match self {
+                ErrorKind::InvalidInput => "invalid input parameter",
+                ErrorKind::InvalidData => "invalid data",
+                ErrorKind::Unsupported => "unsupported",
+                ErrorKind::WriteZero => "write zero",
+                ErrorKind::Other => "other error",
+            }
+        }
+    }
+
+    impl Display for ErrorKind {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.description())
+        }
+    }
+
+    /// An error from recording, as with `std::io::Error`.
+    #[cfg(feature = "alloc")]
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+        message: Option<String>,
+    }
+
+    #[cfg(feature = "alloc")]
+    impl Error {
+        /// Constructs an error of the given kind, with a message.
+        pub fn new<M>(kind: ErrorKind, message: M) -> Self where M: Into<String> {
+            Error { kind, message: Some(message.into()) }
+        }
+
+        /// The kind of error.
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Error { kind, message: None }
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    impl Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.message {
+                Some(message) => f.write_str(message),
+                None => Display::fmt(&self.kind, f),
+            }
+        }
+    }
+
+    /// The result of recording, as with `std::io::Result`.
+    #[cfg(feature = "alloc")]
+    pub type Result<T> = core::result::Result<T, Error>;
+}
@@ -0,0 +1,158 @@
+//! Exporting the stored writes as an IPS patch, the format many ROM patching tools use, and recording the writes of
+//! one. A patch is `PATCH`, then any
+//! number of records, then `EOF`. Each record is a 3 byte big-endian offset, then a 2 byte big-endian length followed
+//! by that many bytes, or, for an RLE record, a length of 0 followed by a 2 byte big-endian count and the byte to
+//! write that many times.
+
+use std::io::{SeekFrom, Write};
+use crate::{IpsError, Yadon};
+
+/// What every IPS patch starts with.
+const HEADER: &[u8; 5] = b"PATCH";
+/// What every IPS patch ends with.
+const FOOTER: &[u8; 3] = b"EOF";
+/// The offset whose encoding reads as `FOOTER`, so that no record can start there.
+const EOF_OFFSET: u64 = 0x454f46;
+/// One past the largest offset an IPS patch can address.
+const OFFSET_LIMIT: u64 = 1 << 24;
+/// The most bytes one record can write.
+const MAX_RECORD_LEN: usize = 0xffff;
+/// The shortest run of one byte which is written as an RLE record, which takes 8 bytes, rather than as part of a
+/// normal record.
+const RLE_MIN_RUN: usize = 8;
+
+impl Yadon {
+    /// Exports the stored writes as an IPS patch, which any IPS patcher can apply. The writes are resolved as
+    /// `to_patches()` does, so overlapping writes resolve as they would when applied in order. Runs of the same byte are
+    /// written as RLE records, and no record holds more than 65535 bytes. As with `ApplyOrder::Offset`, only writes,
+    /// seeks and flushes can be exported, and every write needs a known offset. The generation stamp, if there is one,
+    /// is exported as a write, but neither it nor any base checksums are checked by the patcher. Bytes which aren't
+    /// written are left as the patched file has them, so `gap_fill()` isn't used. Fails with
+    /// `IpsError::OffsetTooLarge` if anything is written past the first 16 MiB, which is all an IPS patch can address,
+    /// and with `IpsError::EofOffset` if a write starts at 0x454f46 without the byte before it being written, as no
+    /// record can start there.
+    pub fn to_ips(&self) -> Result<Vec<u8>, IpsError> {
+        // Checked before resolving the bytes, which could take far more memory than any IPS patch.
+        let writes = self.stamped_write_extents()?;
+        if let Some(end) = writes.iter().map(|(_, extent)| extent.end).max().filter(|&end| end > OFFSET_LIMIT) {
+            return Err(IpsError::OffsetTooLarge(end - 1));
+        }
+        let mut patch = HEADER.to_vec();
+        for (offset, data) in self.to_patches()? {
+            put_records(&mut patch, offset, &data)?;
+        }
+        patch.extend_from_slice(FOOTER);
+        Ok(patch)
+    }
+
+    /// Records the writes of the IPS patch `patch`: each record becomes a seek from the start, followed by a write, or
+    /// by a fill for an RLE record. `length` is the length of the file being patched, if it's known, and a record which
+    /// writes past it fails with `IpsError::Invalid`. The patch is read strictly: it must start with `PATCH`, every
+    /// record must be complete, and nothing may follow `EOF` except the 3 byte big-endian length which some patchers
+    /// resize the file to, usually to truncate it, which is recorded as `set_len()`. RLE records of no bytes are rejected too.
+    pub fn from_ips(patch: &[u8], length: Option<u64>) -> Result<Yadon, IpsError> {
+        if !patch.starts_with(HEADER) {
+            return Err(IpsError::BadMagic);
+        }
+        let mut yadon = Yadon::new(Some(0), length);
+        let mut offset = HEADER.len();
+        loop {
+            let start = offset;
+            let header = take(patch, start, offset, 3)?;
+            if header == FOOTER {
+                offset += FOOTER.len();
+                break;
+            }
+            let position = u64::from(u32::from_be_bytes([0, header[0], header[1], header[2]]));
+            let len = take(patch, start, offset + 3, 2)?;
+            let len = u16::from_be_bytes([len[0], len[1]]);
+            let (fill, data) = if len == 0 {
+                let rle = take(patch, start, offset + 5, 3)?;
+                offset += 8;
+                (Some((rle[2], u16::from_be_bytes([rle[0], rle[1]]))), &[][..])
+            } else {
+                let data = take(patch, start, offset + 5, usize::from(len))?;
+                offset += 5 + data.len();
+                (None, data)
+            };
+            let written_len = fill.map_or(data.len() as u64, |(_, count)| u64::from(count));
+            if written_len == 0 {
+                return Err(IpsError::Invalid { offset: start, reason: "RLE record writes nothing" });
+            }
+            if length.is_some_and(|length| position + written_len > length) {
+                return Err(IpsError::Invalid { offset: start, reason: "record writes past the length" });
+            }
+            yadon.seek(SeekFrom::Start(position)).expect("seeking from the start can't fail");
+            match fill {
+                Some((byte, count)) => yadon.fill(byte, u64::from(count)).map(|_| ()),
+                None => yadon.write_all(data),
+            }.expect("writes within the length can't fail");
+        }
+        match patch.len() - offset {
+            0 => {},
+            3 => {
+                let len = &patch[offset..];
+                yadon.set_len(u64::from(u32::from_be_bytes([0, len[0], len[1], len[2]]))).expect("resizing can't fail");
+            },
+            _ => return Err(IpsError::TrailingBytes { offset }),
+        }
+        Ok(yadon)
+    }
+}
+
+/// The `len` bytes of `patch` from `offset`, which are part of the record starting at `record`.
+fn take(patch: &[u8], record: usize, offset: usize, len: usize) -> Result<&[u8], IpsError> {
+    patch.get(offset..offset + len).ok_or(IpsError::Truncated { offset: record })
+}
+
+/// Appends the records which write `data` at `offset`. A record which would start at `EOF_OFFSET` is started a byte
+/// earlier instead, by shortening the record before it, which fails if `data` itself starts there.
+fn put_records(patch: &mut Vec<u8>, offset: u64, data: &[u8]) -> Result<(), IpsError> {
+    if offset == EOF_OFFSET {
+        return Err(IpsError::EofOffset);
+    }
+    let mut pos = 0;
+    while pos < data.len() {
+        let run = run_len(&data[pos..]);
+        let (mut len, mut rle) = if run >= RLE_MIN_RUN {
+            (run, true)
+        } else {
+            // Everything up to the next run which is long enough for an RLE record.
+            let mut len = run;
+            while pos + len < data.len() && len < MAX_RECORD_LEN {
+                let next = run_len(&data[pos + len..]);
+                if next >= RLE_MIN_RUN {
+                    break;
+                }
+                len += next;
+            }
+            (len.min(MAX_RECORD_LEN), false)
+        };
+        let record_offset = offset + pos as u64;
+        if record_offset + len as u64 == EOF_OFFSET && pos + len < data.len() {
+            // A one byte record is lengthened instead, so the next record starts a byte later.
+            if len > 1 {
+                len -= 1;
+            } else {
+                len = 2;
+                rle = false;
+            }
+        }
+        patch.extend_from_slice(&record_offset.to_be_bytes()[5..]);
+        if rle {
+            patch.extend_from_slice(&[0, 0]);
+            patch.extend_from_slice(&(len as u16).to_be_bytes());
+            patch.push(data[pos]);
+        } else {
+            patch.extend_from_slice(&(len as u16).to_be_bytes());
+            patch.extend_from_slice(&data[pos..pos + len]);
+        }
+        pos += len;
+    }
+    Ok(())
+}
+
+/// How many times `data` repeats its first byte from the start, up to as many as one record can write.
+fn run_len(data: &[u8]) -> usize {
+    data.iter().take(MAX_RECORD_LEN).take_while(|&&byte| byte == data[0]).count()
+}
@@ -0,0 +1,349 @@
+use core::convert::{TryFrom, TryInto};
+use core::ops::Deref;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use crate::crc::crc32;
+use crate::wire::{decode_operation, put_operation};
+use crate::{WriteOperation, Yadon};
+
+/// Identifies a journal: the magic bytes `YJNL`, then the format version, which is 1.
+const JOURNAL_MAGIC: &[u8; 5] = b"YJNL\x01";
+/// Offset of the byte which is 1 once the journal has been marked complete, and 0 until then.
+const COMPLETE_OFFSET: u64 = JOURNAL_MAGIC.len() as u64;
+/// Length of the header before the first record.
+const HEADER_LEN: u64 = COMPLETE_OFFSET + 1;
+/// Length of the part of each record before its payload: the payload's length, then its CRC-32.
+const RECORD_HEADER_LEN: usize = 8;
+
+/// How often a `JournaledYadon` syncs its journal to storage, so that recorded operations survive a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JournalSync {
+    /// After every operation.
+    #[default]
+    EveryOperation,
+    /// After every this many operations, so that up to this many may be lost.
+    Every(usize),
+    /// Only when a flush is recorded.
+    OnFlush,
+}
+
+/// Records operations as a `Yadon` does, appending each to a journal file as it's recorded, so that the recording
+/// survives the process crashing before it's applied. `JournaledYadon::recover()` reads the journal back. It derefs to
+/// the `Yadon`, so that it can be applied; record through its `Write` and `Seek` impls, or through `record()`.
+///
+/// After the header, which is the magic bytes `YJNL`, the format version 1, and a byte which is 1 once the journal is
+/// marked complete, the journal is a series of records, each the length of its payload and the CRC-32 of the payload,
+/// as 4 little-endian bytes each, then the payload. The first payload is the recording as it was created, encoded by
+/// `Yadon::to_bytes()`, and each after it is an operation, encoded as the `wire` module describes. Custom operations
+/// can't be journaled, and labels pushed after the journal is created aren't kept.
+/// # Example
+/// ```
+/// use yadon::{JournaledYadon, Yadon};
+/// use std::io::{Cursor, Write};
+/// # let path = std::env::temp_dir().join(format!("yadon-journal-doctest-{}", std::process::id()));
+/// let mut journaled = JournaledYadon::create_from(&path, Yadon::new(Some(0), Some(8))).unwrap();
+/// journaled.write_all(b"journal").unwrap();
+/// drop(journaled);
+///
+/// // After a crash, carry on from the journal.
+/// let mut recovered = JournaledYadon::recover(&path).unwrap();
+/// let mut target = Cursor::new(vec![0u8; 8]);
+/// recovered.apply(&mut target, true).unwrap();
+/// recovered.mark_complete().unwrap();
+/// assert_eq!(target.get_ref(), b"journal\0");
+/// # recovered.delete().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct JournaledYadon {
+    yadon: Yadon,
+    path: PathBuf,
+    pub(crate) file: File,
+    sync: JournalSync,
+    /// Number of the recording's operations which have been appended to the journal.
+    journaled: usize,
+    /// Number of operations appended since the journal was last synced.
+    unsynced: usize,
+    /// Offset of the end of the last record appended whole.
+    end: u64,
+    /// Whether appending a record failed partway, leaving some of it after `end`.
+    torn: bool,
+    complete: bool,
+}
+
+impl JournaledYadon {
+    /// Creates a journal at `path` for a recording made with `Yadon::default()`, replacing whatever is there.
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<JournaledYadon> {
+        JournaledYadon::create_from(path, Yadon::default())
+    }
+
+    /// Creates a journal at `path` to carry on `yadon` in, replacing whatever is there. The journal is synced before
+    /// this returns. Fails with `ErrorKind::InvalidInput` if `yadon` holds a custom operation, or its operations are in
+    /// the store given to `Yadon::with_store()`.
+    pub fn create_from(path: impl AsRef<Path>, mut yadon: Yadon) -> std::io::Result<JournaledYadon> {
+        if yadon.store.is_some() {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "operations in a store can't be journaled"));
+        }
+        let path = path.as_ref().to_path_buf();
+        let mut initial = Vec::new();
+        yadon.to_writer(&mut initial)?;
+        let mut header = JOURNAL_MAGIC.to_vec();
+        header.push(0);
+        put_record(&mut header, &initial)?;
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path)?;
+        file.write_all(&header)?;
+        file.sync_all()?;
+        let journaled = yadon.operations.len();
+        yadon.refuse_custom = true;
+        Ok(JournaledYadon {
+            yadon,
+            path,
+            file,
+            sync: JournalSync::default(),
+            journaled,
+            unsynced: 0,
+            end: header.len() as u64,
+            torn: false,
+            complete: false,
+        })
+    }
+
+    /// Reads the journal at `path` back, to apply it or carry on recording. Each journaled operation is recorded again,
+    /// so the recording is left as it was when that operation was journaled. If the last record was cut short, or its
+    /// CRC-32 doesn't match, as when the process crashed partway through appending it, it's truncated from the journal,
+    /// losing only that operation. Fails with `ErrorKind::InvalidData` if anything else is wrong with the journal, such
+    /// as a record before the last not matching its CRC-32. A record whose length was corrupted to run past the end of
+    /// the journal can't be told from one cut short, so is truncated along with everything after it.
+    pub fn recover(path: impl AsRef<Path>) -> std::io::Result<JournaledYadon> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).write(true).open(&path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        if contents.len() < HEADER_LEN as usize || &contents[0..JOURNAL_MAGIC.len()] != JOURNAL_MAGIC {
+            return Err(invalid("file isn't a journal"));
+        }
+        let complete = match contents[COMPLETE_OFFSET as usize] {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("journal's completion flag is neither clear nor set")),
+        };
+
+        let mut records = Vec::new();
+        let mut offset = HEADER_LEN as usize;
+        while let Some(header) = contents.get(offset..offset + RECORD_HEADER_LEN) {
+            let len = u32::from_le_bytes(header[0..4].try_into().expect("4 bytes")) as usize;
+            let crc = u32::from_le_bytes(header[4..8].try_into().expect("4 bytes"));
+            let end = offset + RECORD_HEADER_LEN + len;
+            match contents.get(offset + RECORD_HEADER_LEN..end) {
+                Some(payload) if crc32(payload) == crc => records.push(payload),
+                Some(_) if end < contents.len() => return Err(invalid("a record before the last doesn't match its CRC-32")),
+                _ => break,
+            }
+            offset = end;
+        }
+
+        let (initial, operations) = records.split_first().ok_or_else(|| invalid("journal holds no recording"))?;
+        let mut yadon = Yadon::from_bytes(initial).map_err(|error| invalid_because(error.to_string()))?;
+        for payload in operations {
+            let operation = decode_operation(payload).map_err(|error| invalid_because(error.to_string()))?;
+            replay(&mut yadon, &operation)?;
+        }
+        if offset < contents.len() {
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::Start(offset as u64))?;
+        let journaled = yadon.operations.len();
+        yadon.refuse_custom = true;
+        Ok(JournaledYadon {
+            yadon,
+            path,
+            file,
+            sync: JournalSync::default(),
+            journaled,
+            unsynced: 0,
+            end: offset as u64,
+            torn: false,
+            complete,
+        })
+    }
+
+    /// Sets how often the journal is synced to storage.
+    pub fn with_sync(mut self, sync: JournalSync) -> Self {
+        self.sync = sync;
+        self
+    }
+
+    /// Where the journal is.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the journal has been marked complete, as after applying it.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Calls `f` to record operations into the `Yadon`, then appends whatever it recorded to the journal, syncing it
+    /// as the `JournalSync` says. If appending fails, the operations stay recorded, and are appended along with the next
+    /// ones, after cutting off whatever of the failed record was written; a `JournalSync::OnFlush` sync which fails is
+    /// tried again at the next flush. Custom operations can't be journaled, so recording one fails without recording
+    /// it. Fails with `ErrorKind::InvalidInput`, without calling `f`, if the journal has been marked complete, and for
+    /// an operation too large for a record, which can't be appended.
+    pub fn record<R>(&mut self, f: impl FnOnce(&mut Yadon) -> R) -> std::io::Result<R> {
+        if self.complete {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "journal has been marked complete"));
+        }
+        let result = f(&mut self.yadon);
+        self.append_pending()?;
+        Ok(result)
+    }
+
+    /// Appends the operations which haven't been journaled yet, each as one write.
+    fn append_pending(&mut self) -> std::io::Result<()> {
+        if self.torn {
+            self.cut_torn()?;
+        }
+        let mut flushed = false;
+        while let Some(operation) = self.yadon.operations.get(self.journaled) {
+            let mut payload = Vec::new();
+            put_operation(&mut payload, operation);
+            let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+            put_record(&mut record, &payload)?;
+            flushed |= matches!(operation, WriteOperation::Flush);
+            if let Err(error) = self.file.write_all(&record) {
+                self.torn = true;
+                // If the torn record can't be cut off now, it's cut off before anything more is appended.
+                drop(self.cut_torn());
+                return Err(error);
+            }
+            self.end += record.len() as u64;
+            self.journaled += 1;
+            self.unsynced += 1;
+        }
+        let due = match self.sync {
+            JournalSync::EveryOperation => self.unsynced > 0,
+            JournalSync::Every(operations) => self.unsynced >= operations.max(1),
+            JournalSync::OnFlush => flushed,
+        };
+        if due {
+            self.file.sync_data()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    /// Marks the journal complete, as after applying it successfully, and syncs it, so that `recover()` tells that it
+    /// needn't be applied again. Nothing more can be recorded after.
+    pub fn mark_complete(&mut self) -> std::io::Result<()> {
+        if self.torn {
+            self.cut_torn()?;
+        }
+        self.file.seek(SeekFrom::Start(COMPLETE_OFFSET))?;
+        self.file.write_all(&[1])?;
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.sync_all()?;
+        self.complete = true;
+        Ok(())
+    }
+
+    /// Deletes the journal, returning the recording.
+    pub fn delete(mut self) -> std::io::Result<Yadon> {
+        drop(self.file);
+        std::fs::remove_file(&self.path)?;
+        self.yadon.refuse_custom = false;
+        Ok(self.yadon)
+    }
+
+    /// Unwraps the recording, leaving the journal as it is.
+    pub fn into_inner(mut self) -> Yadon {
+        self.yadon.refuse_custom = false;
+        self.yadon
+    }
+
+    /// Cuts off whatever was written of a record which failed to append, so that the next is appended after the last
+    /// whole one.
+    fn cut_torn(&mut self) -> std::io::Result<()> {
+        self.file.set_len(self.end)?;
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.torn = false;
+        Ok(())
+    }
+}
+
+impl Deref for JournaledYadon {
+    type Target = Yadon;
+
+    fn deref(&self) -> &Yadon {
+        &self.yadon
+    }
+}
+
+impl Write for JournaledYadon {
+    /// Records the write, as `Yadon` does, and journals it. If journaling fails, the write is still recorded, and the
+    /// error is returned.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.record(|yadon| yadon.write(buf))?
+    }
+
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.record(|yadon| yadon.flush())?
+    }
+}
+
+impl Seek for JournaledYadon {
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.record(|yadon| yadon.seek(pos))?
+    }
+}
+
+/// Records `operation` into `yadon` again, through the method which recorded it, so that everything recording it
+/// changed, such as the virtual position and the written extents, is changed again. Fails with
+/// `ErrorKind::InvalidData` if recording it fails, or records something else.
+#[cfg_attr(feature = "track-callers", track_caller)]
+fn replay(yadon: &mut Yadon, operation: &WriteOperation) -> std::io::Result<()> {
+    let count = yadon.operations.len();
+    let agrees = match operation {
+        WriteOperation::Write(data, len) => yadon.write(data).map(|written| written == *len),
+        WriteOperation::Seek(pos, expected_position) => yadon.seek(*pos).map(|position| position == *expected_position),
+        WriteOperation::DeferredSeek(pos) => yadon.seek(*pos).map(|_| true),
+        WriteOperation::Flush => yadon.flush().map(|_| true),
+        WriteOperation::Fill { byte, len } => yadon.fill(*byte, *len).map(|written| written == *len),
+        WriteOperation::Repeat { pattern, count } => {
+            yadon.write_repeated(pattern, *count).map(|written| Some(written) == (pattern.len() as u64).checked_mul(*count))
+        },
+        WriteOperation::SetLen(len) => yadon.set_len(*len).map(|_| true),
+        WriteOperation::Sync => yadon.sync_barrier().map(|_| true),
+        WriteOperation::CopyWithin { src, dst, len } => yadon.copy_within(*src, *dst, *len).map(|copied| copied == *len),
+        WriteOperation::AssertBytes { offset, expected } => yadon.assert_bytes_at(*offset, expected).map(|_| true),
+        WriteOperation::Custom(..) => Ok(false),
+    };
+    let recorded = yadon.operations.get(count).filter(|_| yadon.operations.len() == count + 1);
+    match agrees {
+        Ok(true) if recorded.map(WriteOperation::name) == Some(operation.name()) => Ok(()),
+        Ok(_) => Err(invalid_because(format!("journaled operation {} doesn't record as it did", count))),
+        Err(error) => Err(invalid_because(format!("journaled operation {} can't be recorded again: {}", count, error))),
+    }
+}
+
+/// Appends a record holding `payload`. Fails with `ErrorKind::InvalidInput` if `payload` is too long for its length to
+/// fit in the record.
+fn put_record(out: &mut Vec<u8>, payload: &[u8]) -> std::io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "record is too large to journal"))?;
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(&crc32(payload).to_le_bytes());
+    out.extend_from_slice(payload);
+    Ok(())
+}
+
+fn invalid(reason: &'static str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, reason)
+}
+
+fn invalid_because(reason: String) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, reason)
+}
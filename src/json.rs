@@ -0,0 +1,323 @@
+//! A human-readable JSON rendering of `Yadon`, behind the `json` feature, for looking through a recording and editing
+//! it by hand. Unlike the `serde` impls, the shape is meant to be read: byte strings are hex, broken into lines, and
+//! each operation is an object tagged with its kind.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+use crate::extent::Extents;
+use crate::io::SeekFrom;
+use crate::label::Labels;
+use crate::{LengthMode, OverflowPolicy, WriteOperation, Yadon};
+
+/// How many bytes of hex go on each line.
+const HEX_LINE_BYTES: usize = 32;
+
+/// Options for `Yadon::to_json_pretty_with_options()`.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    /// If set, no more than this many bytes of each write, repeated pattern or expected precondition are rendered. The
+    /// rest is left out, and the operation gets an `omitted_bytes` field saying how many were. A rendering with bytes
+    /// left out can't be read back.
+    pub data_preview_limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document {
+    start: Option<u64>,
+    length: Option<u64>,
+    #[serde(default)]
+    defer_end_seeks: bool,
+    #[serde(default)]
+    append_only: bool,
+    #[serde(default)]
+    deny_overwrite: bool,
+    #[serde(default)]
+    overflow_policy: OverflowPolicy,
+    #[serde(default)]
+    length_mode: LengthMode,
+    #[serde(default)]
+    gap_fill: u8,
+    #[serde(default)]
+    applied: u64,
+    #[serde(default)]
+    max_applies: Option<u64>,
+    #[serde(default)]
+    generation_stamp: Option<Stamp>,
+    #[serde(default)]
+    base_checksums: Vec<Checksum>,
+    #[serde(default)]
+    written_extents: Vec<Range<u64>>,
+    #[serde(default)]
+    reserved_regions: Vec<Range<u64>>,
+    #[serde(default)]
+    label_stack: Vec<String>,
+    #[serde(default)]
+    labels: Vec<LabelRun>,
+    operations: Vec<Op>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Stamp {
+    offset: u64,
+    generation: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Checksum {
+    range: Range<u64>,
+    crc32: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LabelRun {
+    label: String,
+    operations: Range<usize>,
+}
+
+/// A seek position, tagged with where it's relative to.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Pos {
+    Start(u64),
+    End(i64),
+    Current(i64),
+}
+
+impl From<SeekFrom> for Pos {
+    fn from(pos: SeekFrom) -> Self {
+        match pos {
+            SeekFrom::Start(offset) => Pos::Start(offset),
+            SeekFrom::End(offset) => Pos::End(offset),
+            SeekFrom::Current(offset) => Pos::Current(offset),
+        }
+    }
+}
+
+impl From<Pos> for SeekFrom {
+    fn from(pos: Pos) -> Self {
+        match pos {
+            Pos::Start(offset) => SeekFrom::Start(offset),
+            Pos::End(offset) => SeekFrom::End(offset),
+            Pos::Current(offset) => SeekFrom::Current(offset),
+        }
+    }
+}
+
+/// Bytes as lines of hex, and how many were left out after them.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Hex {
+    #[serde(rename = "hex")]
+    lines: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    omitted_bytes: Option<usize>,
+}
+
+/// One operation. The expected length of a write and the expected position of a seek may be left out when reading, and
+/// are then found by simulating the operations before them.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+enum Op {
+    Write {
+        #[serde(default)]
+        len: Option<usize>,
+        data: Hex,
+    },
+    Seek {
+        pos: Pos,
+        #[serde(default)]
+        expected: Option<u64>,
+    },
+    DeferredSeek {
+        pos: Pos,
+    },
+    Flush,
+    Fill {
+        byte: u8,
+        len: u64,
+    },
+    Repeat {
+        count: u64,
+        pattern: Hex,
+    },
+    SetLen {
+        len: u64,
+    },
+    Sync,
+    CopyWithin {
+        src: u64,
+        dst: u64,
+        len: u64,
+    },
+    AssertBytes {
+        offset: u64,
+        expected: Hex,
+    },
+    /// Only ever written, as what a custom operation does can't be read back.
+    Custom {
+        debug: String,
+        position: u64,
+        bytes_written: u64,
+    },
+}
+
+impl Hex {
+    fn new(bytes: &[u8], options: &JsonOptions) -> Self {
+        let shown = options.data_preview_limit.map_or(bytes.len(), |limit| limit.min(bytes.len()));
+        let lines = bytes[..shown].chunks(HEX_LINE_BYTES).map(|line| {
+            line.iter().map(|byte| format!("{:02x}", byte)).collect()
+        }).collect();
+        let omitted_bytes = Some(bytes.len() - shown).filter(|&omitted| omitted > 0);
+        Hex { lines, omitted_bytes }
+    }
+
+    /// Decodes the lines. Whitespace within them is ignored.
+    fn decode(self, index: usize) -> Result<Vec<u8>, String> {
+        if let Some(omitted) = self.omitted_bytes {
+            return Err(format!("operation {} has {} bytes left out, so can't be read back", index, omitted));
+        }
+        let digits = self.lines.iter()
+            .flat_map(|line| line.chars())
+            .filter(|c| !c.is_ascii_whitespace())
+            .map(|c| c.to_digit(16).map(|digit| digit as u8).ok_or_else(|| format!("operation {} has {:?} in its hex", index, c)))
+            .collect::<Result<Vec<u8>, String>>()?;
+        if digits.len() % 2 != 0 {
+            return Err(format!("operation {} has an odd number of hex digits", index));
+        }
+        Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+    }
+}
+
+/// Renders one operation for `Yadon::to_json_pretty_with_options()`.
+fn render_operation(operation: &WriteOperation, options: &JsonOptions) -> Op {
+    match operation {
+        WriteOperation::Write(data, len) => Op::Write { len: Some(*len), data: Hex::new(data, options) },
+        WriteOperation::Seek(pos, expected) => Op::Seek { pos: (*pos).into(), expected: Some(*expected) },
+        WriteOperation::DeferredSeek(pos) => Op::DeferredSeek { pos: (*pos).into() },
+        WriteOperation::Flush => Op::Flush,
+        WriteOperation::Fill { byte, len } => Op::Fill { byte: *byte, len: *len },
+        WriteOperation::Repeat { pattern, count } => Op::Repeat { count: *count, pattern: Hex::new(pattern, options) },
+        WriteOperation::SetLen(len) => Op::SetLen { len: *len },
+        WriteOperation::Sync => Op::Sync,
+        WriteOperation::CopyWithin { src, dst, len } => Op::CopyWithin { src: *src, dst: *dst, len: *len },
+        WriteOperation::AssertBytes { offset, expected } => {
+            Op::AssertBytes { offset: *offset, expected: Hex::new(expected, options) }
+        },
+        #[cfg(feature = "std")]
+        WriteOperation::Custom(op, result) => Op::Custom {
+            debug: format!("{:?}", op),
+            position: result.position,
+            bytes_written: result.bytes_written,
+        },
+    }
+}
+
+impl Yadon {
+    /// Renders the recording as indented JSON, for looking through it or editing it by hand. Byte strings are written
+    /// as lines of hex, and each operation as an object whose `op` field names its kind. Unlike serializing with serde,
+    /// this doesn't fail for a `WriteOperation::Custom`, which is rendered with its `Debug` output, but such a rendering
+    /// can't be read back. Operations in the store given to `with_store()` are read back from it. Panics if the store
+    /// can't be read.
+    pub fn to_json_pretty(&self) -> String {
+        self.to_json_pretty_with_options(&JsonOptions::default())
+    }
+
+    /// Renders the recording as `to_json_pretty()` does, with finer control over what's rendered.
+    pub fn to_json_pretty_with_options(&self, options: &JsonOptions) -> String {
+        let mut operations = Vec::new();
+        let rendered = self.try_for_each_recorded(|_, operation| {
+            operations.push(render_operation(operation, options));
+            Ok(())
+        }, |error| error);
+        if let Err(error) = rendered {
+            panic!("operations in the store can't be read back: {}", error);
+        }
+        let document = Document {
+            start: self.start,
+            length: self.length,
+            defer_end_seeks: self.defer_end_seeks,
+            append_only: self.append_only,
+            deny_overwrite: self.deny_overwrite,
+            overflow_policy: self.overflow_policy,
+            length_mode: self.length_mode,
+            gap_fill: self.gap_fill,
+            applied: self.applied.load(Ordering::Relaxed),
+            max_applies: self.max_applies,
+            generation_stamp: self.generation_stamp.map(|(offset, generation)| Stamp { offset, generation }),
+            base_checksums: self.base_checksums.iter().map(|(range, crc32)| Checksum { range: range.clone(), crc32: *crc32 }).collect(),
+            written_extents: self.written_extents.iter().collect(),
+            reserved_regions: self.reserved_regions.iter().collect(),
+            label_stack: self.labels.stack().map(ToString::to_string).collect(),
+            labels: self.labels.iter().map(|(label, operations)| LabelRun { label: label.to_string(), operations }).collect(),
+            operations,
+        };
+        serde_json::to_string_pretty(&document).expect("rendering a recording shouldn't fail")
+    }
+
+    /// Reads a recording rendered by `to_json_pretty()`, which recording can carry on from. Only `start`, `length` and
+    /// `operations` are needed; anything else left out takes its default. Unknown fields are rejected, so that typos
+    /// don't go unnoticed. As when deserializing with serde, the operations are simulated to find the virtual position
+    /// and checked to agree with one another, and any expected write length or seek position which was left out is
+    /// filled in from the simulation. Fails for a rendering with bytes left out by `JsonOptions::data_preview_limit`,
+    /// or with a custom operation.
+    pub fn from_json(json: &str) -> Result<Yadon, serde_json::Error> {
+        let document: Document = serde_json::from_str(json)?;
+        Yadon::from_document(document).map_err(serde::de::Error::custom)
+    }
+
+    fn from_document(document: Document) -> Result<Yadon, String> {
+        let mut fill_in = Vec::with_capacity(document.operations.len());
+        let mut operations = Vec::with_capacity(document.operations.len());
+        for (index, op) in document.operations.into_iter().enumerate() {
+            let (operation, missing) = match op {
+                Op::Write { len, data } => {
+                    let data = data.decode(index)?;
+                    (WriteOperation::Write(data, len.unwrap_or(0)), len.is_none())
+                },
+                Op::Seek { pos, expected } => (WriteOperation::Seek(pos.into(), expected.unwrap_or(0)), expected.is_none()),
+                Op::DeferredSeek { pos } => (WriteOperation::DeferredSeek(pos.into()), false),
+                Op::Flush => (WriteOperation::Flush, false),
+                Op::Fill { byte, len } => (WriteOperation::Fill { byte, len }, false),
+                Op::Repeat { count, pattern } => (WriteOperation::Repeat { pattern: pattern.decode(index)?, count }, false),
+                Op::SetLen { len } => (WriteOperation::SetLen(len), false),
+                Op::Sync => (WriteOperation::Sync, false),
+                Op::CopyWithin { src, dst, len } => (WriteOperation::CopyWithin { src, dst, len }, false),
+                Op::AssertBytes { offset, expected } => {
+                    (WriteOperation::AssertBytes { offset, expected: expected.decode(index)? }, false)
+                },
+                Op::Custom { .. } => return Err(format!("operation {} is a custom operation, which can't be read back", index)),
+            };
+            operations.push(operation);
+            fill_in.push(missing);
+        }
+        let label_runs = document.labels.into_iter().map(|run| (run.label, run.operations)).collect();
+        let labels = Labels::from_parts(document.label_stack, label_runs, operations.len())?;
+        let mut yadon = Yadon {
+            operations,
+            defer_end_seeks: document.defer_end_seeks,
+            append_only: document.append_only,
+            deny_overwrite: document.deny_overwrite,
+            written_extents: Extents::from_ranges(document.written_extents)?,
+            reserved_regions: Extents::from_ranges(document.reserved_regions)?,
+            overflow_policy: document.overflow_policy,
+            length_mode: document.length_mode,
+            labels,
+            base_checksums: document.base_checksums.into_iter().map(|checksum| (checksum.range, checksum.crc32)).collect(),
+            applied: AtomicU64::new(document.applied),
+            max_applies: document.max_applies,
+            generation_stamp: document.generation_stamp.map(|stamp| (stamp.offset, stamp.generation)),
+            gap_fill: document.gap_fill,
+            ..Yadon::new(document.start, document.length)
+        };
+        yadon.resimulate_filling(|index| fill_in[index]).map_err(|(_, message)| message)?;
+        Ok(yadon)
+    }
+}
@@ -0,0 +1,96 @@
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// Bookkeeping for the labels attached to operations. Labels are interned, and stored as runs of operation indices,
+/// so labelling an operation doesn't allocate.
+#[derive(Debug, Default)]
+pub(crate) struct Labels {
+    /// Every label which has been pushed so far.
+    interned: BTreeSet<Arc<str>>,
+    /// Labels which are currently pushed. The innermost one applies to new operations.
+    stack: Vec<Arc<str>>,
+    /// Ranges of operation indices which carry a label, in order.
+    runs: Vec<LabelRun>,
+}
+
+#[derive(Debug)]
+struct LabelRun {
+    label: Arc<str>,
+    operations: Range<usize>,
+}
+
+impl Labels {
+    pub(crate) fn push(&mut self, label: &str) {
+        let label = match self.interned.get(label) {
+            Some(label) => label.clone(),
+            None => {
+                let label: Arc<str> = label.into();
+                self.interned.insert(label.clone());
+                label
+            }
+        };
+        self.stack.push(label);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Arc<str>> {
+        self.stack.pop()
+    }
+
+    /// Attaches the current label, if there is one, to the operation at `index`.
+    pub(crate) fn record(&mut self, index: usize) {
+        let label = match self.stack.last() {
+            Some(label) => label,
+            None => return,
+        };
+        match self.runs.last_mut() {
+            Some(run) if Arc::ptr_eq(&run.label, label) && run.operations.end == index => {
+                run.operations.end = index + 1;
+            },
+            _ => self.runs.push(LabelRun {
+                label: label.clone(),
+                operations: index..index + 1,
+            }),
+        }
+    }
+
+    pub(crate) fn label_of(&self, index: usize) -> Option<&Arc<str>> {
+        let run = self.runs.partition_point(|run| run.operations.end <= index);
+        self.runs.get(run)
+            .filter(|run| run.operations.contains(&index))
+            .map(|run| &run.label)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, Range<usize>)> {
+        self.runs.iter().map(|run| (&*run.label, run.operations.clone()))
+    }
+
+    /// The labels which are currently pushed, outermost first.
+    pub(crate) fn stack(&self) -> impl Iterator<Item = &str> {
+        self.stack.iter().map(|label| &**label)
+    }
+
+    /// Rebuilds the labels from the ones currently pushed and the runs of operations they're attached to, as given by
+    /// `stack()` and `iter()`. Fails unless the runs are in order, don't overlap, and lie within `operations`
+    /// operations.
+    pub(crate) fn from_parts(stack: Vec<String>, runs: Vec<(String, Range<usize>)>, operations: usize) -> Result<Self, &'static str> {
+        let mut labels = Labels::default();
+        for (label, operations_range) in runs {
+            if operations_range.is_empty() || operations_range.end > operations {
+                return Err("label run is empty or past the last operation");
+            }
+            if labels.runs.last().is_some_and(|run| run.operations.end > operations_range.start) {
+                return Err("label runs are out of order or overlap");
+            }
+            labels.push(&label);
+            let label = labels.stack.pop().expect("label was just pushed");
+            labels.runs.push(LabelRun { label, operations: operations_range });
+        }
+        for label in stack {
+            labels.push(&label);
+        }
+        Ok(labels)
+    }
+}
@@ -1,27 +1,190 @@
-use thiserror::Error;
-use std::io::{Seek, SeekFrom, Write};
-use std::fmt::Debug;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[derive(Debug, Default)]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String, sync::Arc, vec, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::ops::Range;
+#[cfg(feature = "alloc")]
+use core::panic::Location;
+#[cfg(feature = "alloc")]
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::io::{IoSlice, Read, Seek, Write};
+use crate::io::{ErrorKind, SeekFrom};
+#[cfg(feature = "alloc")]
+use crate::operation::repeated_len;
+
+#[cfg(feature = "std")]
+mod apply;
+#[cfg(feature = "std")]
+mod async_apply;
+#[cfg(feature = "std")]
+mod background;
+#[cfg(feature = "std")]
+mod bps;
+#[cfg(feature = "alloc")]
+mod check;
+#[cfg(feature = "alloc")]
+mod compose;
+#[cfg(feature = "std")]
+mod crc;
+#[cfg(feature = "std")]
+mod deferred;
+#[cfg(feature = "alloc")]
+mod diff;
+#[cfg(feature = "alloc")]
+mod display;
+#[cfg(feature = "std")]
+mod dry_run;
+#[cfg(feature = "embedded-io")]
+pub mod embedded;
+#[cfg(feature = "alloc")]
+mod error;
+#[cfg(feature = "alloc")]
+mod extent;
+mod fixed;
+#[cfg(feature = "alloc")]
+mod fork;
+#[cfg(feature = "alloc")]
+mod freeze;
+#[cfg(feature = "fs")]
+mod fs;
+pub mod io;
+#[cfg(feature = "std")]
+mod guard;
+#[cfg(feature = "std")]
+mod ips;
+#[cfg(feature = "fs")]
+mod journal;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "alloc")]
+mod label;
+#[cfg(feature = "std")]
+mod limited;
+#[cfg(feature = "std")]
+mod mapping;
+#[cfg(feature = "alloc")]
+mod merge;
+#[cfg(feature = "alloc")]
+mod operation;
+#[cfg(feature = "std")]
+mod patches;
+#[cfg(feature = "std")]
+mod positional;
+mod preview;
+#[cfg(feature = "alloc")]
+mod replay;
+#[cfg(feature = "alloc")]
+mod script;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "std")]
+mod session;
+#[cfg(feature = "std")]
+mod ups;
+mod slice;
+#[cfg(feature = "alloc")]
+mod split;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(feature = "std")]
+mod tee;
+#[cfg(feature = "alloc")]
+mod translate;
+#[cfg(feature = "std")]
+mod window;
+#[cfg(feature = "alloc")]
+pub mod wire;
+
+#[cfg(feature = "std")]
+pub use apply::{ApplyHook, ApplyOptions, ApplyOrder, ApplyReport, ApplySummary, BitViolation, BlockReport, DetectReport, DiffReport, FlushPolicy, HookBreak, MappedReport, OpAction, OpOutcome, Progress, RangeReport, ResizedOp, SetLen, ShortWrite, SyncFallback, SyncTarget};
+#[cfg(feature = "std")]
+pub use async_apply::AsyncWriteSeek;
+#[cfg(feature = "std")]
+pub use background::ApplyHandle;
+#[cfg(feature = "alloc")]
+pub use check::CheckPolicy;
+#[cfg(feature = "std")]
+pub use deferred::DeferredWriter;
+#[cfg(feature = "alloc")]
+pub use diff::DiffOptions;
+#[cfg(feature = "std")]
+pub use dry_run::{DivergingSeek, DryRunReport, TruncatedWrite, ValidationReport};
+#[cfg(feature = "alloc")]
+pub use error::{ApplyError, BpsError, ChecksumMismatch, ComposeError, Confusion, DecodeError, DiffError, Divergence, DivergenceKind, DryRunError, IpsError, MaterializeError, MergeError, PatchError, ScriptError, SessionError, TranslateError, UpsError};
+pub use fixed::{FixedError, YadonFixed};
+#[cfg(feature = "alloc")]
+pub use freeze::ReplayLog;
+#[cfg(feature = "fs")]
+pub use fs::AtomicOptions;
+#[cfg(feature = "std")]
+pub use guard::{ApplyGuard, GuardPolicy};
+#[cfg(feature = "fs")]
+pub use journal::{JournalSync, JournaledYadon};
+#[cfg(feature = "json")]
+pub use json::JsonOptions;
+#[cfg(feature = "std")]
+pub use limited::LimitedTarget;
+#[cfg(feature = "std")]
+pub use mapping::{MapFlush, MappedTarget};
+#[cfg(feature = "alloc")]
+pub use operation::{SimResult, WriteOperation};
+#[cfg(feature = "std")]
+pub use operation::{ApplyOp, ApplyOutcome, WriteSeek};
+#[cfg(feature = "std")]
+pub use positional::WriteAt;
+pub use preview::HexPreview;
+#[cfg(feature = "std")]
+pub use session::{ApplySession, SessionState};
+#[cfg(feature = "std")]
+pub use store::{OpFile, OpSink, OpStore};
+#[cfg(feature = "std")]
+pub use tee::YadonTee;
+#[cfg(feature = "std")]
+pub use window::YadonWindow;
+
+#[cfg(feature = "alloc")]
+#[derive(Default)]
 /// Stores write and seek operations to be replayed later.
 /// # Example
 /// ```
 /// use yadon::Yadon;
-/// use std::io::{Cursor, Write, Seek, SeekFrom};
-/// let mut target = vec![0u8; 8];
-/// let mut yadon = Yadon::new(Some(0), Some(target.len() as u64));
+/// use yadon::io::SeekFrom;
+/// let mut yadon = Yadon::new(Some(0), Some(8));
 /// assert_eq!(yadon.seek(SeekFrom::Start(4)).unwrap(), 4);
 /// assert_eq!(yadon.write(&[1,2,3]).unwrap(), 3);
 /// assert_eq!(yadon.seek(SeekFrom::Current(0)).unwrap(), 7);
 /// assert_eq!(yadon.seek(SeekFrom::End(-6)).unwrap(), 2);
 /// assert_eq!(yadon.write(&[4,5]).unwrap(), 2);
 /// assert_eq!(yadon.seek(SeekFrom::Current(0)).unwrap(), 4);
-/// 
-/// let mut target_writer = Cursor::new(&mut target);
-/// // Apply the stored operations to our target writer.
-/// // Pass `true` so the return values of the seeks and writes are compared with the simulated values.
-/// yadon.apply(&mut target_writer, true).unwrap();
-/// assert_eq!(target, &[0, 0, 4, 5, 1, 2, 3, 0]);
+///
+/// // Lay the stored writes down into a buffer.
+/// // The return values of the seeks and writes are compared with the simulated values.
+/// let mut target = [0u8; 8];
+/// yadon.materialize_into_slice(&mut target).unwrap();
+/// assert_eq!(target, [0, 0, 4, 5, 1, 2, 3, 0]);
+/// ```
+/// With the `std` feature, `Yadon` is a `Write + Seek` itself, and can be applied to any other.
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use yadon::Yadon;
+/// use std::io::{Write, Seek, SeekFrom};
+/// let mut yadon = Yadon::new(Some(0), Some(8));
+/// yadon.seek(SeekFrom::Start(4)).unwrap();
+/// yadon.write_all(&[1, 2, 3]).unwrap();
+///
+/// // Lay the stored writes down into a fresh buffer of `length` bytes.
+/// let target = yadon.materialize(None).unwrap();
+/// assert_eq!(target, &[0, 0, 0, 0, 1, 2, 3, 0]);
+///
+/// // Or apply them to any other `Write + Seek`.
+/// let mut target = vec![0u8; 8];
+/// yadon.apply(&mut std::io::Cursor::new(&mut target), true).unwrap();
+/// assert_eq!(target, &[0, 0, 0, 0, 1, 2, 3, 0]);
 /// ```
 /// # Remarks
 /// * If a start position is set when apply() is called, the target will seek to the start position.
@@ -33,43 +196,64 @@ pub struct Yadon {
     virtual_position: Option<u64>,
     /// If set, used to set the initial virtual cursor position. `apply()` will seek to this position before applying.
     pub start: Option<u64>,
-    /// If set, used to emulate cursor position for SeekFrom::End operations. If not set, seeks involving SeekFrom::End will fail, returning `Err(std::io::ErrorKind::Unsupported)`
+    /// If set, used to emulate cursor position for SeekFrom::End operations. If not set, seeks involving SeekFrom::End will fail, returning `Err(io::ErrorKind::Unsupported)`
+    /// With `LengthMode::Growable`, writes past the end extend it.
     pub length: Option<u64>,
+    /// If set, and `length` is not, `SeekFrom::End` seeks are recorded without knowing where the end is, and resolved
+    /// against the target's actual end during `apply()`. Until the next `SeekFrom::Start` seek, positions returned by
+    /// the recorder are as if the target only contained what has been recorded so far, and aren't checked during
+    /// `apply()`.
+    pub defer_end_seeks: bool,
+    /// If set, seeks which would move the virtual position backwards fail with `ErrorKind::InvalidInput`, and aren't
+    /// recorded, so the operations can be applied without random access.
+    pub append_only: bool,
+    /// If set, writes which would touch any byte that has already been written fail with `ErrorKind::InvalidInput`,
+    /// and aren't recorded. Zero-length writes are also rejected. Only writes recorded while this is set are tracked.
+    pub deny_overwrite: bool,
+    /// The ranges written so far, while `deny_overwrite` is set.
+    written_extents: extent::Extents,
+    /// The ranges which writes are restricted to, if any have been reserved.
+    reserved_regions: extent::Extents,
+    /// Whether the virtual position is relative to an end which isn't known yet.
+    end_unresolved: bool,
+    /// The furthest position written to so far.
+    written_end: u64,
+    /// What to do with writes which extend past `length`.
+    overflow_policy: OverflowPolicy,
+    /// Whether writes past `length` extend it.
+    length_mode: LengthMode,
+    /// Where each of the stored operations was recorded from, by index.
+    #[cfg(feature = "track-callers")]
+    locations: Vec<Option<&'static Location<'static>>>,
+    /// Labels attached to the stored operations.
+    labels: label::Labels,
+    /// Ranges of the target which must have these CRC-32s before anything is applied.
+    base_checksums: Vec<(Range<u64>, u32)>,
+    /// Number of times the stored operations have been applied successfully.
+    applied: AtomicU64,
+    /// If set, applying fails once the stored operations have been applied this many times.
+    max_applies: Option<u64>,
+    /// If set, the offset of a generation stamp in the target, and the generation which applying stamps there.
+    generation_stamp: Option<(u64, u64)>,
+    /// The byte which `materialize()` fills unwritten gaps with, where there's no base.
+    gap_fill: u8,
+    /// Why an `ApplyGuard` last failed to apply as it was dropped, until it's taken.
+    #[cfg(feature = "std")]
+    guard_error: Option<ApplyError>,
+    /// Where operations are stored instead of `operations`, if somewhere else has been given.
+    #[cfg(feature = "std")]
+    store: Option<Box<dyn OpStore + Send + Sync>>,
+    /// Whether storing an operation in `store` has failed, which stops anything more being recorded.
+    #[cfg(feature = "std")]
+    store_failed: bool,
+    /// Whether custom operations are refused, as they are while journaled, since they can't be.
+    #[cfg(feature = "fs")]
+    refuse_custom: bool,
+    /// The recording this one was forked from, if it was.
+    parent: Option<Arc<Yadon>>,
 }
 
-/// Errors that may occur while applying `Yadon`.
-#[derive(Error, Debug)]
-pub enum ApplyError {
-    /// IO error while trying to replay operations.
-    #[error("io error while trying to replay operations")]
-    Io(#[from] std::io::Error),
-    /// Seek position diverged while trying to replay operations.
-    #[error("seek position diverged while trying to replay operations")]
-    SeekDiverged(Confusion<u64>),
-    /// Number of bytes written diverged while trying to replay operations.
-    #[error("number of bytes written diverged while trying to replay operations")]
-    NumBytesWrittenDiverge(Confusion<usize>)
-}
-
-/// During apply, there was divergence between the expected return value of an operation, and its result.
-#[derive(Debug)]
-pub struct Confusion<T>
-where T: Debug {
-    /// The value which we returned when the operation was first simulated.
-    pub expected: T,
-    /// The value which was returned when trying to apply this operation to another Write + Seek.
-    pub actual: T,
-}
-
-#[derive(Debug)]
-/// Write + Seek operations which were called on Yadon
-pub enum WriteOperation {
-    /// Write something, and check that the number of bytes written matches.
-    Write(Vec<u8>, usize),
-    /// Seek somewhere, and check that the resulting position matches.
-    Seek(SeekFrom, u64)
-}
-
+#[cfg(feature = "alloc")]
 impl Yadon {
     /// Constructs an instance of `Yadon` with optional `start` position and `length`, which, if set, should match
     /// whatever you plan to apply `Yadon` to later.
@@ -79,132 +263,807 @@ impl Yadon {
             virtual_position: None,
             start,
             length,
+            defer_end_seeks: false,
+            append_only: false,
+            deny_overwrite: false,
+            written_extents: Default::default(),
+            reserved_regions: Default::default(),
+            end_unresolved: false,
+            written_end: 0,
+            overflow_policy: OverflowPolicy::Truncate,
+            length_mode: LengthMode::Fixed,
+            #[cfg(feature = "track-callers")]
+            locations: vec![],
+            labels: Default::default(),
+            base_checksums: vec![],
+            applied: AtomicU64::new(0),
+            max_applies: None,
+            generation_stamp: None,
+            gap_fill: 0,
+            #[cfg(feature = "std")]
+            guard_error: None,
+            #[cfg(feature = "std")]
+            store: None,
+            #[cfg(feature = "std")]
+            store_failed: false,
+            #[cfg(feature = "fs")]
+            refuse_custom: false,
+            parent: None,
         }
     }
 
-    /// Applies the stored operations on a target writer. Operations are not consumed, and may be replayed again.
-    /// If a `start` position was specified, this will seek to that position before applying.
-    /// If `check_return_values` is set, the result of each seek / write will be compared to the
-    /// simulated return value, and the apply will fail if it is different.
-    pub fn apply<T>(&self, target: &mut T, check_return_values: bool) -> Result<usize, ApplyError> where T: Write + Seek {
-        if let Some(start) = self.start {
-            let seek_pos = target.seek(SeekFrom::Start(start))?;
-            if check_return_values && seek_pos != start {
-                // Something is wrong with the seek.
-                return Err(ApplyError::SeekDiverged(Confusion {
-                    expected: start,
-                    actual: seek_pos
-                }));
-            }
-        }
-        let mut total_bytes_written: usize = 0;
-        for operation in &self.operations {
-            match operation {
-                WriteOperation::Write(data, expected_bytes_written) => {
-                    let bytes_written = target.write(data)?;
-                    if check_return_values && *expected_bytes_written != bytes_written {
-                        return Err(ApplyError::NumBytesWrittenDiverge(Confusion{
-                            expected: *expected_bytes_written,
-                            actual: bytes_written
-                        }));
-                    }
-                    total_bytes_written += bytes_written;
-                },
-                WriteOperation::Seek(pos, expected_position) => {
-                    let new_position = target.seek(*pos)?;
-                    if check_return_values && new_position != *expected_position {
-                        return Err(ApplyError::SeekDiverged(Confusion{
-                            expected: *expected_position,
-                            actual: new_position
-                        }));
-                    }
-                }
+    /// Constructs an instance of `Yadon` like `new()`, which only allows moving forwards. See `append_only`.
+    pub fn new_append_only(start: Option<u64>, length: Option<u64>) -> Self {
+        Yadon {
+            append_only: true,
+            ..Yadon::new(start, length)
+        }
+    }
+
+    /// The ranges which have been written to while `deny_overwrite` was set, in order. Touching ranges are merged.
+    pub fn written_extents(&self) -> impl Iterator<Item = Range<u64>> + '_ {
+        self.written_extents.iter()
+    }
+
+    /// Restricts writes to `region`, and any other reserved regions. Once a region has been reserved, writes which aren't
+    /// entirely within a reserved region fail with `ErrorKind::InvalidInput`, and aren't recorded. Seeks are not
+    /// restricted. Touching regions are merged, so a write may span them.
+    pub fn reserve_region(&mut self, region: Range<u64>) {
+        if !region.is_empty() {
+            self.reserved_regions.insert(region);
+        }
+    }
+
+    /// Sets what to do with writes which extend past `length`, when constructing.
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Sets what to do with writes which extend past `length`. Only affects writes recorded afterwards.
+    pub fn set_overflow_policy(&mut self, overflow_policy: OverflowPolicy) {
+        self.overflow_policy = overflow_policy;
+    }
+
+    /// What is done with writes which extend past `length`.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// Sets whether writes past `length` extend it, when constructing.
+    pub fn with_length_mode(mut self, length_mode: LengthMode) -> Self {
+        self.length_mode = length_mode;
+        self
+    }
+
+    /// Sets whether writes past `length` extend it. Only affects writes recorded afterwards.
+    pub fn set_length_mode(&mut self, length_mode: LengthMode) {
+        self.length_mode = length_mode;
+    }
+
+    /// Whether writes past `length` extend it.
+    pub fn length_mode(&self) -> LengthMode {
+        self.length_mode
+    }
+
+    /// Sets how many times the stored operations may be applied, when constructing. See `set_max_applies()`.
+    pub fn with_max_applies(mut self, max_applies: Option<u64>) -> Self {
+        self.max_applies = max_applies;
+        self
+    }
+
+    /// Sets how many times the stored operations may be applied. Once `applied_count()` reaches it, applying fails
+    /// with `ApplyError::ApplyLimitReached` without touching the target.
+    pub fn set_max_applies(&mut self, max_applies: Option<u64>) {
+        self.max_applies = max_applies;
+    }
+
+    /// How many times the stored operations may be applied, if limited.
+    pub fn max_applies(&self) -> Option<u64> {
+        self.max_applies
+    }
+
+    /// Makes applying stamp `generation` into the target, as a little-endian `u64` at `offset`, so that applying the
+    /// same patch again can be detected. Before anything is applied, the stamp already in the target is read, and
+    /// applying fails with `ApplyError::GenerationNotNewer` unless it's older than `generation`; a target too short to
+    /// hold a stamp counts as generation 0. Once every operation has been applied, the new stamp is written before the
+    /// final flush, leaving the target positioned where the last operation did. The target must be readable, as with
+    /// `apply_readable()`, and with a fixed `length`, the stamp must fit within it.
+    pub fn with_generation_stamp(mut self, offset: u64, generation: u64) -> Self {
+        self.generation_stamp = Some((offset, generation));
+        self
+    }
+
+    /// Sets the byte which `materialize()` fills unwritten gaps with, where there's no base, when constructing.
+    pub fn with_gap_fill(mut self, gap_fill: u8) -> Self {
+        self.gap_fill = gap_fill;
+        self
+    }
+
+    /// Sets the byte which `materialize()` fills unwritten gaps with, where there's no base.
+    pub fn set_gap_fill(&mut self, gap_fill: u8) {
+        self.gap_fill = gap_fill;
+    }
+
+    /// The byte which `materialize()` fills unwritten gaps with, where there's no base. Defaults to 0.
+    pub fn gap_fill(&self) -> u8 {
+        self.gap_fill
+    }
+
+    /// Number of times the stored operations have been applied, by any of the `apply` methods. Only applies which
+    /// succeed are counted, even if a failed apply wrote to the target before failing. Resuming an `ApplySession`
+    /// counts once it finishes successfully.
+    pub fn applied_count(&self) -> u64 {
+        self.applied.load(Ordering::Relaxed)
+    }
+
+    /// The location in the source code which recorded the operation at `index`, if the `track-callers` feature is
+    /// enabled. Operations pushed onto `operations` directly have no location.
+    pub fn location_of(&self, index: usize) -> Option<&'static Location<'static>> {
+        #[cfg(feature = "track-callers")]
+        return self.locations.get(index).copied().flatten();
+        #[cfg(not(feature = "track-callers"))]
+        {
+            let _ = index;
+            None
+        }
+    }
+
+    /// Attaches `label` to every operation recorded until the matching `pop_label()`. Labels nest, and the innermost
+    /// one is used. They only affect diagnostics: errors from `apply()` include the label of the offending operation.
+    pub fn push_label(&mut self, label: &str) {
+        self.labels.push(label);
+    }
+
+    /// Stops attaching the most recently pushed label, returning it.
+    pub fn pop_label(&mut self) -> Option<Arc<str>> {
+        self.labels.pop()
+    }
+
+    /// Calls `f`, attaching `label` to every operation it records.
+    pub fn labeled_scope<R>(&mut self, label: &str, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.push_label(label);
+        let result = f(self);
+        self.pop_label();
+        result
+    }
+
+    /// The label attached to the operation at `index`, if any.
+    pub fn label_of(&self, index: usize) -> Option<&Arc<str>> {
+        self.labels.label_of(index)
+    }
+
+    /// Each label, along with the range of operation indices it was attached to, in order. A label appears once for
+    /// each contiguous run of operations recorded under it.
+    pub fn labels(&self) -> impl Iterator<Item = (&str, Range<usize>)> {
+        self.labels.iter()
+    }
+
+    /// Records writing `len` copies of `byte`, as if `write()` was called with a buffer of that size, but only the
+    /// byte and count are stored. Returns the number of bytes which would be written, after clamping to the length.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn fill(&mut self, byte: u8, len: u64) -> io::Result<u64> {
+        let len = self.advance_for_write(len)?;
+        self.push_operation(WriteOperation::Fill { byte, len })?;
+        Ok(len)
+    }
+
+    /// Records writing `pattern` `count` times, as if `write()` was called with the expanded buffer, but the pattern is
+    /// only stored once. Returns the number of bytes which would be written, after clamping to the length.
+    /// If the length cuts off the final repetition, the partial repetition is stored as a separate `Write`.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn write_repeated(&mut self, pattern: &[u8], count: u64) -> io::Result<u64> {
+        let total_len = repeated_len(pattern, count)?;
+        let len = self.advance_for_write(total_len)?;
+        if len == 0 {
+            self.push_operation(WriteOperation::Write(vec![], 0))?;
+            return Ok(0);
+        }
+
+        let whole_repetitions = len / pattern.len() as u64;
+        let remainder = (len % pattern.len() as u64) as usize;
+        if whole_repetitions > 0 {
+            self.push_operation(WriteOperation::Repeat { pattern: pattern.into(), count: whole_repetitions })?;
+        }
+        if remainder > 0 {
+            self.push_operation(WriteOperation::Write(pattern[0..remainder].into(), remainder))?;
+        }
+        Ok(len)
+    }
+
+    /// Records writing what `reader` holds, up to `limit` bytes if it's given, as `record_from_reader_chunked()` does
+    /// with chunks of 1 MiB.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn record_from_reader<R>(&mut self, reader: R, limit: Option<u64>) -> io::Result<u64> where R: Read {
+        self.record_from_reader_chunked(reader, limit, READ_CHUNK_SIZE)
+    }
+
+    /// Records writing what `reader` holds, up to `limit` bytes if it's given, as one write for each `chunk_size` bytes
+    /// read. Each chunk is read straight into the buffer of its write, rather than through an intermediate buffer. The
+    /// reader is read until it ends, `limit` bytes have been recorded, or there's no room left before the end of a
+    /// fixed length, whatever the overflow policy, so that nothing is read which can't be recorded. Returns the number
+    /// of bytes recorded. Fails with `ErrorKind::InvalidInput` if `chunk_size` is 0, and with the error from reading if
+    /// reading fails, keeping the chunks recorded before it.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn record_from_reader_chunked<R>(&mut self, reader: R, limit: Option<u64>, chunk_size: usize) -> io::Result<u64>
+    where R: Read {
+        if chunk_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "chunk size must be at least 1"));
+        }
+        let mut reader = reader;
+        let mut recorded = 0;
+        loop {
+            let length = match self.length_mode {
+                LengthMode::Fixed => self.length,
+                LengthMode::Growable => None,
+            };
+            let room = fitting_len(self.virtual_position.or(self.start), length, chunk_size as u64, OverflowPolicy::Truncate)
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "write would overflow the position"))?;
+            let want = limit.map_or(room, |limit| room.min(limit - recorded));
+            if want == 0 {
+                return Ok(recorded);
+            }
+            let mut data = Vec::with_capacity(want as usize);
+            (&mut reader).take(want).read_to_end(&mut data)?;
+            if data.is_empty() {
+                return Ok(recorded);
+            }
+            let len = self.advance_for_write(data.len() as u64)?;
+            recorded += len;
+            self.push_operation(WriteOperation::Write(data, len as usize))?;
+            if len < want {
+                return Ok(recorded);
             }
         }
-        target.flush()?;
-        Ok(total_bytes_written)
     }
-}
 
-impl Write for Yadon {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        if let (None, Some(start), Some(_)) = (self.virtual_position, self.start, self.length) {
-            // If the start position is specified and this is the first operation, and we're doing length
-            // emulation, the virtual position must be initialized.
-            self.virtual_position = Some(start);
+    /// Records resizing the target to `len` bytes, which also sets the emulated `length`. The virtual position is left
+    /// where it was, even if it is now past the end.
+    /// This can only be applied using `apply_with_setlen()`.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.length = Some(len);
+        self.push_operation(WriteOperation::SetLen(len))?;
+        Ok(())
+    }
+
+    /// Records a durability barrier: when applied, everything written before it must reach storage before anything
+    /// after it is written. This can be applied using `apply_durable()`, or `ApplyOptions::sync_fallback` can allow it
+    /// to be applied as a flush.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn sync_barrier(&mut self) -> io::Result<()> {
+        self.push_operation(WriteOperation::Sync)?;
+        Ok(())
+    }
+
+    /// Records copying `len` bytes within the target from `src` to `dst`, without needing to know what those bytes are
+    /// yet. This is treated as a write of `len` bytes at `dst`: afterwards, the virtual position is just after the copied
+    /// bytes, and `len` is clamped to the length. Overlapping ranges are copied as if through an intermediate buffer,
+    /// like `slice::copy_within`. Returns the number of bytes which would be copied.
+    /// This can only be applied using `apply_readable()`.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn copy_within(&mut self, src: u64, dst: u64, len: u64) -> io::Result<u64> {
+        if let Some(length) = self.length {
+            let copy_len = len.min(length.saturating_sub(dst));
+            if src.checked_add(copy_len).is_none_or(|src_end| src_end > length) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "copy source extends past the end"));
+            }
+        } else if dst.checked_add(len).is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "write would overflow the position"));
         }
+        self.check_forwards(dst)?;
+        // The copy is written from `dst`, but if it's rejected, the position is left where it was.
+        let moved_from = (self.virtual_position, self.end_unresolved);
+        self.virtual_position = Some(dst);
+        self.end_unresolved = false;
+        let len = match self.advance_for_write(len) {
+            Ok(len) => len,
+            Err(error) => {
+                (self.virtual_position, self.end_unresolved) = moved_from;
+                return Err(error);
+            },
+        };
+        self.push_operation(WriteOperation::CopyWithin { src, dst, len })?;
+        Ok(len)
+    }
 
-        let buf = match self.length {
-            Some(max_length) => { // Emulate writing into something with a max length
-                let available_space = match self.virtual_position {
-                    Some(current_position) => (max_length - current_position) as usize,
-                    None => max_length as usize
-                };
+    /// Records a precondition: when applied, the target must contain `expected` at `offset`, or the apply fails with
+    /// `ApplyError::PreconditionFailed`. The virtual position is not moved.
+    /// This can only be applied using `apply_readable()`.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn assert_bytes_at(&mut self, offset: u64, expected: &[u8]) -> io::Result<()> {
+        if let Some(length) = self.length {
+            if offset.checked_add(expected.len() as u64).is_none_or(|end| end > length) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "asserted bytes extend past the end"));
+            }
+        }
+        self.push_operation(WriteOperation::AssertBytes { offset, expected: expected.into() })?;
+        Ok(())
+    }
 
-                if buf.len() > available_space {
-                    &buf[0..available_space]
-                } else {
-                    buf
+    /// Requires `range` of the target to have the CRC-32 `crc32` before anything is applied, so that the stored
+    /// operations are only applied to the image they were recorded against. Can be called again to require more ranges.
+    /// An end of `u64::MAX` stands for the end of the target: `length` if it's set, or else wherever the target ends.
+    /// Use `base_checksum()` to compute the checksum for a range of the base image. Applying fails with
+    /// `ApplyError::BaseChecksumMismatch` if any range differs, and with `ApplyError::UnsupportedOperation` if the
+    /// target can't be read from, as with `apply_readable()`.
+    pub fn require_base_checksum(&mut self, range: Range<u64>, crc32: u32) {
+        self.base_checksums.push((range, crc32));
+    }
+
+    /// Records a user-defined operation, which is simulated immediately to find out how it moves the virtual position.
+    /// Returns the result of the simulation, which `apply()` will check the operation's outcome against.
+    /// Fails with `ErrorKind::Unsupported` while the position depends on a deferred `SeekFrom::End` seek. Fails with
+    /// `ErrorKind::InvalidInput`, recording nothing, if the operation would move backwards in append-only mode, or
+    /// would write anything while there are reserved regions or overwrites are denied, as where it writes isn't known,
+    /// or while recording into a `JournaledYadon`, as custom operations can't be journaled.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn record_custom<O>(&mut self, operation: O) -> io::Result<SimResult> where O: ApplyOp + 'static {
+        if self.end_unresolved {
+            // The operation can't be simulated without knowing where it will start.
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "position depends on a deferred end seek"));
+        }
+        #[cfg(feature = "fs")]
+        if self.refuse_custom {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "custom operations can't be journaled"));
+        }
+        let position = self.virtual_position.or(self.start).unwrap_or(0);
+        let result = operation.simulate(position, self.length);
+        self.check_forwards(result.position)?;
+        if result.bytes_written > 0 && (self.deny_overwrite || !self.reserved_regions.is_empty()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "custom operation writes somewhere which can't be checked against the reserved regions or earlier writes",
+            ));
+        }
+        self.push_operation(WriteOperation::Custom(Box::new(operation), result))?;
+        self.virtual_position = Some(result.position);
+        Ok(result)
+    }
+
+    /// Stores an operation, along with its label and where it was recorded from if the `track-callers` feature is
+    /// enabled. Fails with the error from the store given to `with_store()` if it can't store the operation, and from
+    /// then on with `ErrorKind::Other`, as what's stored no longer matches what was recorded. A custom operation which
+    /// the store refuses with `ErrorKind::InvalidInput` wasn't stored at all, so recording can carry on after it.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn push_operation(&mut self, operation: WriteOperation) -> io::Result<()> {
+        let index = self.operation_count();
+        #[cfg(feature = "std")]
+        let operation = match &mut self.store {
+            Some(_) if self.store_failed => {
+                return Err(io::Error::other("an earlier operation couldn't be stored"));
+            },
+            Some(store) => {
+                let custom = matches!(operation, WriteOperation::Custom(..));
+                match store.append(operation) {
+                    Ok(()) => None,
+                    Err(error) => {
+                        self.store_failed = !custom || error.kind() != io::ErrorKind::InvalidInput;
+                        return Err(error);
+                    },
                 }
             },
-            None => {
-                buf
-            }
+            None => Some(operation),
         };
+        #[cfg(not(feature = "std"))]
+        let operation = Some(operation);
+        #[cfg(feature = "track-callers")]
+        {
+            self.locations.resize(index, None);
+            self.locations.push(Some(Location::caller()));
+        }
+        self.labels.record(index);
+        self.operations.extend(operation);
+        Ok(())
+    }
 
+    /// Number of operations recorded, whether they're in `operations` or the store given to `with_store()`.
+    fn operation_count(&self) -> usize {
+        #[cfg(feature = "std")]
+        if let Some(store) = &self.store {
+            return store.len();
+        }
+        self.operations.len()
+    }
 
-        self.virtual_position = match self.virtual_position {
-            Some(current_position) => Some(current_position + buf.len() as u64),
-            None => Some(buf.len() as u64)
-        };
+    /// Whether the recorded operations are in the store given to `with_store()`, rather than in `operations`.
+    fn in_store(&self) -> bool {
+        #[cfg(feature = "std")]
+        if self.store.is_some() {
+            return true;
+        }
+        false
+    }
 
-        self.operations.push(WriteOperation::Write(buf.into(), buf.len()));
-        Ok(buf.len())
+    /// Calls `f` with the index of each recorded operation and the operation, in order, whether they're in `operations`
+    /// or the store given to `with_store()`, stopping at the first error `f` returns. An error reading the store is
+    /// turned into one of `f`'s by `store_error`.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    fn try_for_each_recorded<E>(
+        &self,
+        mut f: impl FnMut(usize, &WriteOperation) -> Result<(), E>,
+        store_error: impl FnOnce(ApplyError) -> E,
+    ) -> Result<(), E> {
+        #[cfg(feature = "std")]
+        if let Some(store) = &self.store {
+            let mut failed = None;
+            let read = store.for_each_operation(&mut |index, operation| f(index, operation).map_err(|error| {
+                failed = Some(error);
+                // Only stops the store reading; `f`'s own error is returned.
+                ApplyError::InStore
+            }));
+            return match (failed, read) {
+                (Some(error), _) => Err(error),
+                (None, Err(error)) => Err(store_error(error)),
+                (None, Ok(())) => Ok(()),
+            };
+        }
+        self.operations.iter().enumerate().try_for_each(|(index, operation)| f(index, operation))
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
+    /// Fails if `append_only` is set and moving to `position` would go backwards.
+    fn check_forwards(&self, position: u64) -> io::Result<()> {
+        if self.append_only && position < self.virtual_position.or(self.start).unwrap_or(0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek backwards in append-only mode"));
+        }
         Ok(())
     }
-}
 
-impl Seek for Yadon {
-    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+    /// Moves the virtual position forward for a write of `len` bytes, returning how many of them fit. Fails without
+    /// moving if the position after the write wouldn't fit in a `u64`, if the write doesn't fit and the overflow
+    /// policy is `OverflowPolicy::Error`, if the write is outside the reserved regions, or if the write is an overwrite
+    /// and `deny_overwrite` is set.
+    fn advance_for_write(&mut self, len: u64) -> io::Result<u64> {
+        // If the start position is specified and this is the first operation, the virtual position must be
+        // initialized.
+        let current_position = self.virtual_position.or(self.start);
 
-        match (self.virtual_position, pos, self.start, self.length) {
-            (_, SeekFrom::Start(from_start), _, _) => {
-                self.virtual_position = Some(from_start);
-            }
-            (None, SeekFrom::Current(from_current), Some(start_position), _) => {
-                self.virtual_position = Some((start_position as i64 + from_current) as u64);
-            }
-            (_, SeekFrom::End(from_end), _, Some(length)) => {
-                self.virtual_position = Some((length as i64 + from_end) as u64);
-            }
-            (Some(current_pos), SeekFrom::Current(from_current), _, _) => {
-                self.virtual_position = Some((current_pos as i64 + from_current) as u64)
-            }
-            (_, SeekFrom::End(_), _, None) => {
-                self.virtual_position = None; // This will return an ErrorKind::Unsupported.
+        let length = match self.length_mode {
+            LengthMode::Fixed => self.length,
+            LengthMode::Growable => None,
+        };
+        let len = fitting_len(current_position, length, len, self.overflow_policy).map_err(|kind| match kind {
+            ErrorKind::WriteZero => io::Error::new(kind, "write extends past the end"),
+            _ => io::Error::new(kind, "write would overflow the position"),
+        })?;
+        let new_position = current_position.unwrap_or(0) + len;
+        let written = current_position.unwrap_or(0)..new_position;
+        if !written.is_empty() && !self.reserved_regions.is_empty() && !self.reserved_regions.contains(&written) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("write to {:?} is outside the reserved regions", written),
+            ));
+        }
+        if self.deny_overwrite {
+            if written.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "zero-length write when overwrites are denied",
+                ));
             }
-            (None, SeekFrom::Current(from_current), None, _) => {
-                self.virtual_position = Some(from_current as u64); // If a start waas not specified, assume we're at position 0.
+            if let Some(conflict) = self.written_extents.overlapping(&written) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("write to {:?} overlaps previously written {:?}", written, conflict),
+                ));
             }
+            self.written_extents.insert(written);
         }
+        self.virtual_position = Some(new_position);
+        self.written_end = self.written_end.max(new_position);
+        if let (Some(length), LengthMode::Growable) = (self.length, self.length_mode) {
+            self.length = Some(length.max(new_position));
+        }
+        Ok(len)
+    }
 
-        match self.virtual_position {
-            Some(resulting_position) => {
-                self.operations.push(WriteOperation::Seek(pos, resulting_position));
-                Ok(resulting_position)
-            },
-            None => Err(std::io::ErrorKind::Unsupported.into()),
+    /// Finds where recording the stored operations left the virtual position, whether it depends on a deferred end
+    /// seek, and the furthest position written to, as recording them would have. Fails if an operation doesn't agree
+    /// with where the ones before it left the position.
+    pub(crate) fn resimulate(&mut self) -> Result<(), String> {
+        self.resimulate_filling(|_| false).map_err(|(_, message)| message)
+    }
+
+    /// Resimulates the stored operations as `resimulate()` does, except that the expected length of each write and the
+    /// expected position of each seek for which `fill_in` returns true are set to what the simulation gives, rather
+    /// than checked. Fails if one of those is a seek from the end, whose expected position can't be found again. The
+    /// index of the operation which failed is given with the error.
+    pub(crate) fn resimulate_filling(&mut self, fill_in: impl Fn(usize) -> bool) -> Result<(), (usize, String)> {
+        #[cfg(feature = "std")]
+        if self.store.is_some() {
+            return Err((0, "operations in a store can't be resimulated".into()));
+        }
+        let mut position = None;
+        let mut end_unresolved = false;
+        let mut written_end = 0;
+        let overflow = |index: usize| (index, format!("operation {} would overflow the position", index));
+        let start = self.start;
+        for (index, operation) in self.operations.iter_mut().enumerate() {
+            let current = position.or(start).unwrap_or(0);
+            let (write_start, len) = match operation {
+                WriteOperation::Write(data, len) => {
+                    if fill_in(index) {
+                        *len = data.len();
+                    }
+                    if *len != data.len() {
+                        return Err((index, format!("operation {} expects to write {} of its {} bytes", index, len, data.len())));
+                    }
+                    (current, *len as u64)
+                },
+                WriteOperation::Fill { len, .. } => (current, *len),
+                WriteOperation::Repeat { pattern, count } => {
+                    (current, (pattern.len() as u64).checked_mul(*count).ok_or_else(|| overflow(index))?)
+                },
+                WriteOperation::CopyWithin { dst, len, .. } => {
+                    end_unresolved = false;
+                    (*dst, *len)
+                },
+                WriteOperation::Seek(pos, expected_position) => {
+                    if fill_in(index) {
+                        *expected_position = match pos {
+                            SeekFrom::Start(offset) => *offset,
+                            SeekFrom::Current(offset) => current.checked_add_signed(*offset).ok_or_else(|| overflow(index))?,
+                            SeekFrom::End(_) => {
+                                return Err((index, format!("operation {} seeks from the end, so needs its expected position", index)));
+                            },
+                        };
+                    }
+                    let consistent = match pos {
+                        SeekFrom::Start(offset) => offset == expected_position,
+                        SeekFrom::Current(offset) => current.checked_add_signed(*offset) == Some(*expected_position),
+                        SeekFrom::End(_) => true,
+                    };
+                    if !consistent {
+                        return Err((index, format!(
+                            "operation {} expects {:?} to end up at {}, which it can't from {}",
+                            index, pos, expected_position, current,
+                        )));
+                    }
+                    if let SeekFrom::Start(_) = pos {
+                        end_unresolved = false;
+                    }
+                    position = Some(*expected_position);
+                    continue;
+                },
+                WriteOperation::DeferredSeek(pos) => {
+                    let (base, offset) = match pos {
+                        SeekFrom::End(offset) => (written_end, *offset),
+                        SeekFrom::Current(offset) => (current, *offset),
+                        SeekFrom::Start(_) => return Err((index, format!("operation {} is a deferred seek from the start", index))),
+                    };
+                    position = Some(base.checked_add_signed(offset).ok_or_else(|| overflow(index))?);
+                    end_unresolved = true;
+                    continue;
+                },
+                #[cfg(feature = "std")]
+                WriteOperation::Custom(_, result) => {
+                    position = Some(result.position);
+                    continue;
+                },
+                WriteOperation::Flush
+                | WriteOperation::SetLen(_)
+                | WriteOperation::Sync
+                | WriteOperation::AssertBytes { .. } => continue,
+            };
+            let end = write_start.checked_add(len).ok_or_else(|| overflow(index))?;
+            position = Some(end);
+            written_end = written_end.max(end);
+        }
+        self.virtual_position = position;
+        self.end_unresolved = end_unresolved;
+        self.written_end = written_end;
+        Ok(())
+    }
+}
+
+/// What `Yadon` does with a write which extends past its `length`, or a `LimitedTarget` with one which extends past the
+/// end of its region.
+/// # Example
+#[cfg_attr(feature = "alloc", doc = "```")]
+#[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+/// use yadon::{OverflowPolicy, Yadon};
+/// use yadon::io::ErrorKind;
+/// let mut yadon = Yadon::new(Some(0), Some(4));
+/// // By default, the write is cut short, like writing to a `Cursor<&mut [u8]>`.
+/// assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+/// assert_eq!(yadon.write(&[4, 5, 6]).unwrap(), 1);
+///
+/// let mut yadon = Yadon::new(Some(0), Some(4)).with_overflow_policy(OverflowPolicy::Error);
+/// assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+/// // Nothing is recorded for a write which doesn't fit.
+/// assert_eq!(yadon.write(&[4, 5, 6]).unwrap_err().kind(), ErrorKind::WriteZero);
+/// assert_eq!(yadon.operations.len(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OverflowPolicy {
+    /// Write as much as fits, and return the short count.
+    #[default]
+    Truncate,
+    /// Fail with `ErrorKind::WriteZero`, without recording anything.
+    Error,
+}
+
+/// Whether `Yadon` treats its `length` as fixed, or as the initial length of something which grows when written past
+/// the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LengthMode {
+    /// Writes past the length are handled according to the `OverflowPolicy`, like writing to a `Cursor<&mut [u8]>`.
+    #[default]
+    Fixed,
+    /// Writes past the length are recorded in full, and extend the length, like writing to a `Cursor<Vec<u8>>`.
+    /// `SeekFrom::End` seeks are resolved against the extended length.
+    Growable,
+}
+
+#[cfg(feature = "alloc")]
+impl Yadon {
+    /// Records writing `buf`, returning how many bytes of it would be written, after clamping to the length. This is
+    /// what `Write::write()` records, so it can be used without the `std` feature.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.advance_for_write(buf.len() as u64)? as usize;
+        let buf = &buf[0..len];
+        self.push_operation(WriteOperation::Write(buf.into(), buf.len()))?;
+        Ok(buf.len())
+    }
+
+    /// Records a flush, as `Write::flush()` does.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.push_operation(WriteOperation::Flush)?;
+        Ok(())
+    }
+
+    /// Records seeking to `pos`, returning the resulting virtual position, as `Seek::seek()` does.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let deferred_base = match pos {
+            SeekFrom::End(offset) if self.defer_end_seeks && self.length.is_none() => Some((self.written_end, offset)),
+            SeekFrom::Current(offset) if self.end_unresolved => Some((self.virtual_position.unwrap_or(0), offset)),
+            _ => None,
+        };
+        if let Some((base, offset)) = deferred_base {
+            let resulting_position = offset_position(base, offset)?;
+            self.check_forwards(resulting_position)?;
+            self.end_unresolved = true;
+            self.virtual_position = Some(resulting_position);
+            self.push_operation(WriteOperation::DeferredSeek(pos))?;
+            return Ok(resulting_position);
+        }
+
+        let resulting_position = seek_position(self.virtual_position, self.start, self.length, pos).map_err(|kind| match kind {
+            ErrorKind::InvalidInput => invalid_seek(),
+            // Nothing to resolve the seek against, so leave the state as it was.
+            _ => io::Error::from(kind),
+        })?;
+        self.check_forwards(resulting_position)?;
+
+        if let SeekFrom::Start(_) = pos {
+            self.end_unresolved = false;
+        }
+        self.virtual_position = Some(resulting_position);
+        self.push_operation(WriteOperation::Seek(pos, resulting_position))?;
+        Ok(resulting_position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Write for Yadon {
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Yadon::write(self, buf)
+    }
+
+    /// Records the slices as a single write of their concatenated contents, clamped to the length as a whole.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+        let total_len: usize = bufs.iter().map(|buf| buf.len()).sum();
+        let len = self.advance_for_write(total_len as u64)? as usize;
+        let mut data = Vec::with_capacity(len);
+        for buf in bufs {
+            let remaining = len - data.len();
+            data.extend_from_slice(&buf[0..buf.len().min(remaining)]);
         }
+        self.push_operation(WriteOperation::Write(data, len))?;
+        Ok(len)
+    }
+
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn flush(&mut self) -> std::io::Result<()> {
+        Yadon::flush(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Seek for Yadon {
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        Yadon::seek(self, pos)
+    }
+}
+
+/// Resolves a seek of `offset` bytes from `base`, failing like `Cursor` does if the result would be negative or
+/// wouldn't fit in a `u64`.
+#[cfg(feature = "alloc")]
+fn offset_position(base: u64, offset: i64) -> io::Result<u64> {
+    base.checked_add_signed(offset).ok_or_else(invalid_seek)
+}
+
+/// The error `Cursor` fails with for a seek to a negative or overflowing position.
+#[cfg(feature = "alloc")]
+fn invalid_seek() -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+}
+
+/// Where seeking to `pos` moves a recorder's virtual position, given where it is, if it has moved yet, and its `start`
+/// and `length`. Fails with `ErrorKind::Unsupported` for a `SeekFrom::End` seek without a length, and with
+/// `ErrorKind::InvalidInput` if the result would be negative or wouldn't fit in a `u64`.
+fn seek_position(position: Option<u64>, start: Option<u64>, length: Option<u64>, pos: SeekFrom) -> Result<u64, ErrorKind> {
+    let (base, offset) = match (position, pos, start, length) {
+        (_, SeekFrom::Start(from_start), _, _) => return Ok(from_start),
+        (None, SeekFrom::Current(from_current), Some(start_position), _) => (start_position, from_current),
+        (_, SeekFrom::End(from_end), _, Some(length)) => (length, from_end),
+        (Some(current_pos), SeekFrom::Current(from_current), _, _) => (current_pos, from_current),
+        (_, SeekFrom::End(_), _, None) => return Err(ErrorKind::Unsupported),
+        // If a start was not specified, assume we're at position 0.
+        (None, SeekFrom::Current(from_current), None, _) => (0, from_current),
+    };
+    base.checked_add_signed(offset).ok_or(ErrorKind::InvalidInput)
+}
+
+/// How many bytes `Yadon::record_from_reader()` reads into each write.
+#[cfg(feature = "std")]
+const READ_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// How many bytes of a write of `len` bytes at `position` are recorded, clamping them to `length` if it's set. Fails
+/// with `ErrorKind::WriteZero` if they don't all fit and the overflow policy is `OverflowPolicy::Error`, and with
+/// `ErrorKind::InvalidInput` if the position after the write wouldn't fit in a `u64`.
+fn fitting_len(position: Option<u64>, length: Option<u64>, len: u64, overflow_policy: OverflowPolicy) -> Result<u64, ErrorKind> {
+    let len = match length {
+        Some(max_length) => { // Emulate writing into something with a max length
+            let available_space = match position {
+                // Nothing fits if the position is at or past the end.
+                Some(position) => max_length.saturating_sub(position),
+                None => max_length
+            };
+
+            if len > available_space {
+                if overflow_policy == OverflowPolicy::Error {
+                    return Err(ErrorKind::WriteZero);
+                }
+                available_space
+            } else {
+                len
+            }
+        },
+        None => len,
+    };
+    match position.unwrap_or(0).checked_add(len) {
+        Some(_) => Ok(len),
+        None => Err(ErrorKind::InvalidInput),
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
-    use std::io::{Cursor, Seek, SeekFrom, Write};
-    use crate::{ApplyError, Yadon};
+    use std::collections::hash_map::DefaultHasher;
+    use std::convert::TryFrom;
+    use std::hash::{Hash, Hasher};
+    use std::io::{Cursor, IoSlice, Read, Seek, SeekFrom, Write};
+    use std::ops::ControlFlow;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use crate::{ApplyError, ApplyHook, ApplyOp, ApplyOptions, AsyncWriteSeek, ApplyOrder, ApplyOutcome, BitViolation, BlockReport, BpsError, CheckPolicy, ComposeError, DecodeError, DeferredWriter, DetectReport, DiffError, DiffOptions, DiffReport, Divergence, DivergenceKind, DivergingSeek, DryRunError, FixedError, FlushPolicy, GuardPolicy, HexPreview, HookBreak, IpsError, LengthMode, LimitedTarget, MapFlush, MappedTarget, MaterializeError, MergeError, OpAction, OpFile, OpOutcome, OpSink, OpStore, OverflowPolicy, PatchError, Progress, RangeReport, ResizedOp, SessionState, SetLen, ShortWrite, SimResult, SyncFallback, SyncTarget, TranslateError, TruncatedWrite, UpsError, ValidationReport, WriteAt, WriteOperation, WriteSeek, Yadon, YadonFixed, YadonTee};
+    use crate::apply::FILL_CHUNK_SIZE;
+    #[cfg(feature = "embedded-io")]
+    use crate::embedded;
+    use proptest::prelude::*;
 
     #[test]
     fn delayed_write() {
@@ -226,9 +1085,9 @@ mod tests {
     fn start_and_end() {
         let mut yadon = Yadon::new(Some(1), Some(4));
         assert_eq!(yadon.seek(SeekFrom::Current(2)).unwrap(), 3);
-        yadon.write(&[1]).unwrap();
+        assert_eq!(yadon.write(&[1]).unwrap(), 1);
         assert_eq!(yadon.seek(SeekFrom::End(-3)).unwrap(), 1);
-        yadon.write(&[2]).unwrap();
+        assert_eq!(yadon.write(&[2]).unwrap(), 1);
 
         let mut target = vec![0u8; 4];
         let mut target_writer = Cursor::new(&mut target);
@@ -239,73 +1098,691 @@ mod tests {
     #[test]
     fn unspecified_length_end_seek_fails() {
         let mut yadon = Yadon::new(None, None);
-        assert_eq!(yadon.seek(SeekFrom::End(-3)).map_err(|e| e.kind()), Err(std::io::ErrorKind::Unsupported.into()));
+        assert_eq!(yadon.seek(SeekFrom::End(-3)).map_err(|e| e.kind()), Err(std::io::ErrorKind::Unsupported));
     }
 
     #[test]
-    fn unspecified_start_current_seek_assumes_0() {
-        let mut yadon = Yadon::new(None, None);
-        assert_eq!(yadon.seek(SeekFrom::Current(2)).unwrap(), 2);
+    fn negative_seeks_rejected() {
+        let mut now = Cursor::new(vec![0u8; 8]);
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1, 2, 3]).unwrap(), 3);
+        for pos in [SeekFrom::Current(-10), SeekFrom::End(-9)] {
+            let now_error = now.seek(pos).unwrap_err();
+            let yadon_error = yadon.seek(pos).unwrap_err();
+            assert_eq!(yadon_error.kind(), now_error.kind());
+            assert_eq!(yadon_error.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(yadon.operations.len(), 1);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap(), 3);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-8)).unwrap(), 0);
     }
 
     #[test]
-    fn too_big_write() {
-        let mut now_target = [0u8; 32];
-        let mut now = Cursor::new(&mut now_target[..]);
-        now.seek(SeekFrom::Start(1)).unwrap();
-        let mut yadon = Yadon::new(Some(1), Some(32));
-        // Too big since we start at position 1
-        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1u8; 64]).unwrap(), 31);
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-16)).unwrap();
-        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[2u8; 17]).unwrap(), 16);
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(31)).unwrap();
-        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[3u8; 2]).unwrap(), 1);
-        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[4u8; 2]).unwrap(), 0);
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(0)).unwrap();
-        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[5u8; 2]).unwrap(), 0);
-        assert_multi_write(&mut now, &mut yadon, &[1]).unwrap();
-        assert_multi_write(&mut now, &mut yadon, &[1]).unwrap();
+    fn overflowing_seeks_rejected() {
+        let mut now = Cursor::new(Vec::<u8>::new());
+        let mut yadon = Yadon::new(None, None);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(u64::MAX - 4)).unwrap(), u64::MAX - 4);
+        assert_eq!(now.seek(SeekFrom::Current(10)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.seek(SeekFrom::Current(10)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.write(&[0; 5]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.operations.len(), 1);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(4)).unwrap(), u64::MAX);
 
-        println!("{:?}", yadon);
-        let mut later_target = vec![0u8; 32]; // Using a vec here because it can grow if we've made a mistake
-        let mut later_writer = Cursor::new(&mut later_target[..]);
-        yadon.apply(&mut later_writer, true).unwrap();
-        assert_eq!(&later_target, &now_target);
+        let mut yadon = Yadon::new(Some(0), Some(u64::MAX));
+        assert_eq!(yadon.seek(SeekFrom::End(1)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.seek(SeekFrom::End(i64::MIN)).unwrap(), u64::MAX - (1 << 63));
     }
 
-    #[test]
-    fn return_values() {
-        let mut now_target = [0u8; 128];
-        let mut now = Cursor::new(&mut now_target[..]);
-        now.seek(SeekFrom::Start(27)).unwrap();
-        let mut yadon = Yadon::new(Some(27), Some(128));
-
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap();
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(4)).unwrap();
-        assert_multi_write(&mut now, &mut yadon, &[1,2,3,4,5]).unwrap();
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(-2)).unwrap();
-        assert_multi_write(&mut now, &mut yadon, &[1,2,3,4,5]).unwrap();
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(27)).unwrap();
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(2)).unwrap();
-        assert_multi_write(&mut now, &mut yadon, &[1,2]).unwrap();
-        assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-12)).unwrap();
-        assert_multi_write(&mut now, &mut yadon, &[12; 14]).unwrap();
+    /// Deltas which are either small, or anywhere in the range of `i64`.
+    fn seek_delta() -> impl Strategy<Value = i64> {
+        prop_oneof![-16i64..16, any::<i64>()]
+    }
 
-        let mut later_target = vec![0u8; 128]; // Using a vec here because it can grow if we've made a mistake
-        let mut later_writer = Cursor::new(&mut later_target[..]);
-        yadon.apply(&mut later_writer, true).unwrap();
-        assert_eq!(&later_target, &now_target);
+    /// Positions which are either near the start, near the end of the range of `u64`, or anywhere in between.
+    fn seek_base() -> impl Strategy<Value = u64> {
+        prop_oneof![0u64..32, (u64::MAX - 32)..=u64::MAX, any::<u64>()]
     }
 
-    #[test]
-    fn failed_apply_write_end() {
-        // mismatched sizes between yadon and the target
-        let mut yadon = Yadon::new(Some(1), Some(4));
-        assert_eq!(yadon.seek(SeekFrom::End(-3)).unwrap(), 1);
-        yadon.write(&[2]).unwrap();
+    proptest! {
+        #[test]
+        fn current_seeks_match_cursor(start in seek_base(), deltas in proptest::collection::vec(seek_delta(), 1..8)) {
+            let mut reference = Cursor::new(Vec::<u8>::new());
+            let mut yadon = Yadon::new(None, None);
+            prop_assert_eq!(yadon.seek(SeekFrom::Start(start)).unwrap(), reference.seek(SeekFrom::Start(start)).unwrap());
+            for delta in deltas {
+                let expected = reference.seek(SeekFrom::Current(delta)).map_err(|e| e.kind());
+                prop_assert_eq!(yadon.seek(SeekFrom::Current(delta)).map_err(|e| e.kind()), expected);
+            }
+        }
 
-        let mut target = vec![0u8; 8];
-        let mut target_writer = Cursor::new(&mut target);
+        #[test]
+        fn end_seeks_match_model(length in seek_base(), delta in seek_delta()) {
+            let mut yadon = Yadon::new(Some(0), Some(length));
+            let position = length as i128 + delta as i128;
+            let expected = match u64::try_from(position) {
+                Ok(position) => Ok(position),
+                Err(_) => Err(std::io::ErrorKind::InvalidInput),
+            };
+            prop_assert_eq!(yadon.seek(SeekFrom::End(delta)).map_err(|e| e.kind()), expected);
+        }
+
+        #[test]
+        fn diff_only_matches_apply(
+            original in proptest::collection::vec(0u8..3, 0..48),
+            writes in proptest::collection::vec((0u64..64, proptest::collection::vec(0u8..3, 0..16), any::<bool>()), 1..8),
+        ) {
+            let mut yadon = Yadon::new(Some(0), None);
+            for (offset, data, fill) in &writes {
+                yadon.seek(SeekFrom::Start(*offset)).unwrap();
+                if *fill {
+                    yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).unwrap();
+                } else {
+                    yadon.write_all(data).unwrap();
+                }
+            }
+            let mut expected = Cursor::new(original.clone());
+            let bytes_written = yadon.apply_with_options(&mut expected, &ApplyOptions::default()).unwrap();
+            let mut target = Cursor::new(original);
+            let report = yadon.apply_diff_only(&mut target, &ApplyOptions::default()).unwrap();
+            prop_assert_eq!(target.get_ref(), expected.get_ref());
+            prop_assert_eq!(target.position(), expected.position());
+            prop_assert_eq!(report.bytes_written + report.bytes_unchanged, bytes_written as u64);
+        }
+
+        #[test]
+        fn blockwise_matches_apply(
+            original in proptest::collection::vec(0u8..3, 0..48),
+            writes in proptest::collection::vec((0u64..64, proptest::collection::vec(0u8..3, 0..16), any::<bool>()), 1..8),
+            block_size in 1u64..12,
+        ) {
+            let mut yadon = Yadon::new(Some(0), None);
+            for (offset, data, fill) in &writes {
+                yadon.seek(SeekFrom::Start(*offset)).unwrap();
+                if *fill {
+                    yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).unwrap();
+                } else {
+                    yadon.write_all(data).unwrap();
+                }
+            }
+            let mut expected = Cursor::new(original.clone());
+            yadon.apply_with_options(&mut expected, &ApplyOptions::default()).unwrap();
+            let mut target = Cursor::new(original);
+            let report = yadon.apply_blockwise(&mut target, block_size).unwrap();
+            prop_assert_eq!(target.get_ref(), expected.get_ref());
+            prop_assert_eq!(report.blocks_written, yadon.dirty_blocks(block_size).unwrap().len() as u64);
+        }
+
+        #[test]
+        fn ips_matches_materialize(
+            original in proptest::collection::vec(0u8..3, 96),
+            writes in proptest::collection::vec((0u64..64, proptest::collection::vec(0u8..3, 0..24), any::<bool>()), 1..8),
+        ) {
+            let mut yadon = Yadon::new(Some(0), None);
+            for (offset, data, fill) in &writes {
+                yadon.seek(SeekFrom::Start(*offset)).unwrap();
+                if *fill {
+                    yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).unwrap();
+                } else {
+                    yadon.write_all(data).unwrap();
+                }
+            }
+            let expected = yadon.materialize(Some(&original)).unwrap();
+            prop_assert_eq!(apply_ips(original, &yadon.to_ips().unwrap()), expected);
+        }
+
+        #[test]
+        fn patches_round_trip(
+            original in proptest::collection::vec(any::<u8>(), 96),
+            writes in proptest::collection::vec((0u64..64, proptest::collection::vec(0u8..3, 0..24), 0u8..3), 1..8),
+        ) {
+            // Overlapping writes, sometimes seeking relative to where the last one ended, and rewriting a pattern.
+            let mut yadon = Yadon::new(Some(0), Some(96));
+            for (offset, data, kind) in &writes {
+                let position = yadon.stream_position().unwrap();
+                yadon.seek(SeekFrom::Current(*offset as i64 - position as i64)).unwrap();
+                match kind {
+                    0 => yadon.write_all(data).unwrap(),
+                    1 => drop(yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).unwrap()),
+                    _ => drop(yadon.write_repeated(&[1, 2], data.len() as u64 / 2).unwrap()),
+                }
+            }
+            let patches = yadon.to_patches().unwrap();
+            for pair in patches.windows(2) {
+                prop_assert!(pair[0].0 + (pair[0].1.len() as u64) < pair[1].0);
+            }
+            prop_assert!(patches.iter().all(|(_, data)| !data.is_empty()));
+            let expected = yadon.materialize(Some(&original)).unwrap();
+            let round_tripped = Yadon::from_patches(patches.clone(), Some(0), Some(96)).unwrap();
+            prop_assert_eq!(&round_tripped.materialize(Some(&original)).unwrap(), &expected);
+            // The patches don't overlap, so they can be applied in any order.
+            let reversed = Yadon::from_patches(patches.into_iter().rev(), Some(0), Some(96)).unwrap();
+            prop_assert_eq!(&reversed.materialize(Some(&original)).unwrap(), &expected);
+        }
+
+        #[test]
+        fn ips_import_matches_patcher(
+            original in proptest::collection::vec(any::<u8>(), 0..64),
+            records in proptest::collection::vec((0u32..80, proptest::collection::vec(any::<u8>(), 1..12), any::<bool>()), 0..8),
+            truncate in proptest::option::of(0u32..96),
+        ) {
+            let mut patch = b"PATCH".to_vec();
+            for (offset, data, rle) in &records {
+                patch.extend_from_slice(&offset.to_be_bytes()[1..]);
+                if *rle {
+                    patch.extend_from_slice(&[0, 0, 0, data.len() as u8, data[0]]);
+                } else {
+                    patch.extend_from_slice(&(data.len() as u16).to_be_bytes());
+                    patch.extend_from_slice(data);
+                }
+            }
+            patch.extend_from_slice(b"EOF");
+            if let Some(truncate) = truncate {
+                patch.extend_from_slice(&truncate.to_be_bytes()[1..]);
+            }
+            let yadon = Yadon::from_ips(&patch, None).unwrap();
+            let mut target = Cursor::new(original.clone());
+            yadon.apply_with_setlen(&mut target, &ApplyOptions::default()).unwrap();
+            prop_assert_eq!(target.into_inner(), apply_ips(original, &patch));
+        }
+
+        #[test]
+        fn bps_round_trip(
+            source in proptest::collection::vec(0u8..3, 0..48),
+            writes in proptest::collection::vec((0u64..64, proptest::collection::vec(0u8..3, 0..16), any::<bool>()), 0..8),
+            len in proptest::option::of(0u64..80),
+        ) {
+            let mut yadon = Yadon::new(Some(0), None);
+            for (offset, data, fill) in &writes {
+                yadon.seek(SeekFrom::Start(*offset)).unwrap();
+                if *fill {
+                    yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).unwrap();
+                } else {
+                    yadon.write_all(data).unwrap();
+                }
+            }
+            if let Some(len) = len {
+                yadon.set_len(len).unwrap();
+            }
+            let expected = yadon.materialize(Some(&source)).unwrap();
+            let patch = yadon.to_bps(&source).unwrap();
+            prop_assert_eq!(&apply_bps(&source, &patch), &expected);
+            prop_assert_eq!(&Yadon::from_bps(&patch, &source).unwrap().materialize(Some(&source)).unwrap(), &expected);
+        }
+
+        #[test]
+        fn diff_round_trip(
+            old in proptest::collection::vec(0u8..3, 0..48),
+            new in proptest::collection::vec(0u8..3, 0..48),
+            join_gap in 0usize..6,
+            growable: bool,
+        ) {
+            let length_mode = if growable { LengthMode::Growable } else { LengthMode::Fixed };
+            let options = DiffOptions { join_gap, length_mode, allow_set_len: true };
+            let yadon = Yadon::from_diff(&old, &new, options).unwrap();
+            prop_assert_eq!(yadon.length, Some(new.len() as u64));
+            prop_assert_eq!(&yadon.materialize(Some(&old)).unwrap(), &new);
+            if new.len() >= old.len() {
+                let mut target = Cursor::new(old.clone());
+                yadon.apply(&mut target, true).unwrap();
+                prop_assert_eq!(&target.into_inner(), &new);
+            }
+            // Every write starts and ends on a byte which differs, and is separated from the next by at least
+            // `join_gap` identical bytes.
+            let differs = |i: u64| old.get(i as usize) != new.get(i as usize);
+            let mut last_end = None;
+            for pair in yadon.operations.chunks(2) {
+                let (start, len) = match pair {
+                    [WriteOperation::Seek(SeekFrom::Start(start), _), WriteOperation::Write(data, _)] => (*start, data.len() as u64),
+                    _ => continue,
+                };
+                prop_assert!(differs(start) && differs(start + len - 1));
+                if let Some(last_end) = last_end {
+                    prop_assert!(start - last_end >= join_gap as u64);
+                }
+                last_end = Some(start + len);
+            }
+        }
+
+        #[test]
+        fn ups_import_matches_patcher(
+            source in proptest::collection::vec(0u8..3, 0..48),
+            target in proptest::collection::vec(0u8..3, 0..48),
+        ) {
+            let patch = make_ups(&source, &target);
+            prop_assert_eq!(&apply_ups(&source, &patch), &target);
+            let yadon = Yadon::from_ups(&patch, &source).unwrap();
+            prop_assert_eq!(yadon.length, Some(target.len() as u64));
+            prop_assert_eq!(&yadon.materialize(Some(&source)).unwrap(), &target);
+        }
+
+        #[test]
+        fn streaming_matches_apply(
+            original in proptest::collection::vec(0u8..3, 0..48),
+            writes in proptest::collection::vec((0u64..64, proptest::collection::vec(0u8..3, 0..16), 0u8..3), 1..8),
+            total_len in 0usize..96,
+        ) {
+            let mut yadon = Yadon::new(Some(0), None);
+            for (offset, data, kind) in &writes {
+                yadon.seek(SeekFrom::Start(*offset)).unwrap();
+                match kind {
+                    0 => yadon.write_all(data).map(|_| ()).unwrap(),
+                    1 => yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).map(|_| ()).unwrap(),
+                    _ => yadon.write_repeated(&data[0..data.len().min(3)], 2).map(|_| ()).unwrap(),
+                }
+            }
+            let mut expected = Cursor::new(original.clone());
+            yadon.apply_with_options(&mut expected, &ApplyOptions::default()).unwrap();
+            let mut expected = expected.into_inner();
+            expected.resize(total_len, 0);
+            let mut streamed = Vec::new();
+            yadon.apply_streaming(&mut streamed, Some(&original[..]), total_len as u64, 0).unwrap();
+            prop_assert_eq!(streamed, expected);
+        }
+
+        #[test]
+        fn materialized_into_slice_matches_apply(
+            base in proptest::collection::vec(any::<u8>(), 0..48),
+            start in proptest::option::of(0u64..64),
+            length in 0u64..64,
+            operations in proptest::collection::vec((0u8..6, -16i64..64, proptest::collection::vec(any::<u8>(), 0..16)), 0..12),
+        ) {
+            let mut yadon = Yadon::new(start, Some(length));
+            for (kind, offset, data) in &operations {
+                let _ = match kind {
+                    0 => yadon.write(data).map(|_| ()),
+                    1 => yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).map(|_| ()),
+                    2 => yadon.write_repeated(&data[0..data.len().min(3)], 3).map(|_| ()),
+                    3 => yadon.seek(SeekFrom::Start(offset.unsigned_abs())).map(|_| ()),
+                    4 => yadon.seek(SeekFrom::End(*offset)).map(|_| ()),
+                    _ => yadon.seek(SeekFrom::Current(*offset)).map(|_| ()),
+                };
+            }
+            let mut applied = base.clone();
+            let expected = yadon.apply_with_policy(&mut Cursor::new(&mut applied[..]), CheckPolicy::Strict).map_err(|error| error.to_string());
+            let mut materialized = base.clone();
+            let actual = yadon.materialize_into_slice(&mut materialized).map_err(|error| error.to_string());
+            prop_assert_eq!(actual, expected);
+            prop_assert_eq!(materialized, applied);
+        }
+
+        #[test]
+        fn fixed_recorder_matches_yadon(
+            base in proptest::collection::vec(any::<u8>(), 0..48),
+            start in proptest::option::of(0u64..64),
+            length in proptest::option::of(0u64..64),
+            overflow_error in any::<bool>(),
+            operations in proptest::collection::vec((0u8..7, -16i64..64, proptest::collection::vec(any::<u8>(), 0..16)), 0..12),
+        ) {
+            fn record<R>(recorder: &mut R, operations: &[(u8, i64, Vec<u8>)]) -> Vec<Result<u64, std::io::ErrorKind>>
+            where R: Recorder {
+                operations.iter().map(|(kind, offset, data)| match kind {
+                    0 => recorder.record_write(data),
+                    1 => recorder.record_fill(data.first().copied().unwrap_or(0), data.len() as u64),
+                    2 => recorder.record_repeated(&data[0..data.len().min(3)], offset.unsigned_abs() % 4),
+                    3 => recorder.record_seek(SeekFrom::Start(offset.unsigned_abs())),
+                    4 => recorder.record_seek(SeekFrom::End(*offset)),
+                    5 => recorder.record_seek(SeekFrom::Current(*offset)),
+                    _ => recorder.record_flush(),
+                }).collect()
+            }
+
+            let overflow_policy = if overflow_error { OverflowPolicy::Error } else { OverflowPolicy::Truncate };
+            let mut yadon = Yadon::new_recorder(start, length, overflow_policy);
+            // Enough room for every operation to record in full, with a repeat taking two slots.
+            let mut fixed = YadonFixed::<192, 24>::new_recorder(start, length, overflow_policy);
+            prop_assert_eq!(record(&mut fixed, &operations), record(&mut yadon, &operations));
+            prop_assert_eq!(fixed.len(), yadon.operations.len());
+            let mut heap_target = base.clone();
+            let mut fixed_target = base.clone();
+            prop_assert_eq!(fixed.lay_down(&mut fixed_target), yadon.lay_down(&mut heap_target));
+            prop_assert_eq!(fixed_target, heap_target);
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn serde_restores_recording_state(
+            start in proptest::option::of(0u64..64),
+            length in proptest::option::of(0u64..64),
+            growable in any::<bool>(),
+            defer_end_seeks in any::<bool>(),
+            operations in proptest::collection::vec((0u8..8, -16i64..64, proptest::collection::vec(any::<u8>(), 0..16)), 0..12),
+        ) {
+            let length_mode = if growable { LengthMode::Growable } else { LengthMode::Fixed };
+            let mut yadon = Yadon::new(start, length).with_length_mode(length_mode);
+            yadon.defer_end_seeks = defer_end_seeks;
+            for (kind, offset, data) in &operations {
+                let _ = match kind {
+                    0 => yadon.write(data).map(|_| ()),
+                    1 => yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).map(|_| ()),
+                    2 => yadon.write_repeated(&data[0..data.len().min(3)], 3).map(|_| ()),
+                    3 => yadon.seek(SeekFrom::Start(offset.unsigned_abs())).map(|_| ()),
+                    4 => yadon.seek(SeekFrom::End(*offset)).map(|_| ()),
+                    5 => yadon.seek(SeekFrom::Current(*offset)).map(|_| ()),
+                    6 => yadon.copy_within(offset.unsigned_abs() % 8, data.len() as u64, 4).map(|_| ()),
+                    _ => yadon.set_len(offset.unsigned_abs()),
+                };
+            }
+            let loaded: Yadon = serde_json::from_str(&serde_json::to_string(&yadon).unwrap()).unwrap();
+            prop_assert_eq!(
+                (loaded.virtual_position, loaded.end_unresolved, loaded.written_end),
+                (yadon.virtual_position, yadon.end_unresolved, yadon.written_end),
+            );
+        }
+
+        #[test]
+        fn decoding_never_panics(bytes in proptest::collection::vec(any::<u8>(), 0..64), header in any::<bool>()) {
+            let mut input = if header { b"YADN\x01".to_vec() } else { vec![] };
+            input.extend(bytes);
+            let _ = Yadon::from_bytes(&input);
+        }
+
+        #[test]
+        fn wire_restores_recording_state(
+            start in proptest::option::of(0u64..64),
+            length in proptest::option::of(0u64..64),
+            defer_end_seeks in any::<bool>(),
+            operations in proptest::collection::vec((0u8..6, -16i64..64, proptest::collection::vec(any::<u8>(), 0..16)), 0..12),
+        ) {
+            let mut yadon = Yadon::new(start, length);
+            yadon.defer_end_seeks = defer_end_seeks;
+            for (kind, offset, data) in &operations {
+                let _ = match kind {
+                    0 => yadon.write(data).map(|_| ()),
+                    1 => yadon.fill(data.first().copied().unwrap_or(0), data.len() as u64).map(|_| ()),
+                    2 => yadon.write_repeated(&data[0..data.len().min(3)], 3).map(|_| ()),
+                    3 => yadon.seek(SeekFrom::Start(offset.unsigned_abs())).map(|_| ()),
+                    4 => yadon.seek(SeekFrom::End(*offset)).map(|_| ()),
+                    _ => yadon.seek(SeekFrom::Current(*offset)).map(|_| ()),
+                };
+            }
+            let encoded = yadon.to_bytes();
+            let decoded = Yadon::from_bytes(&encoded).unwrap();
+            prop_assert_eq!(decoded.to_bytes(), encoded);
+            prop_assert_eq!(
+                (decoded.virtual_position, decoded.end_unresolved, decoded.written_end),
+                (yadon.virtual_position, yadon.end_unresolved, yadon.written_end),
+            );
+        }
+
+        #[test]
+        fn writes_near_the_top_match_model(start in seek_base(), len in 0usize..64) {
+            let mut yadon = Yadon::new(Some(start), None);
+            let expected = match start.checked_add(len as u64) {
+                Some(_) => Ok(len),
+                None => Err(std::io::ErrorKind::InvalidInput),
+            };
+            prop_assert_eq!(yadon.write(&vec![1; len]).map_err(|e| e.kind()), expected);
+        }
+    }
+
+    #[test]
+    fn overflow_policy_error() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        yadon.set_overflow_policy(OverflowPolicy::Error);
+        assert_eq!(yadon.write(&[1; 6]).unwrap(), 6);
+        assert_eq!(yadon.fill(2, 3).unwrap_err().kind(), std::io::ErrorKind::WriteZero);
+        assert_eq!(yadon.write_repeated(&[3, 4], 2).unwrap_err().kind(), std::io::ErrorKind::WriteZero);
+        assert_eq!(yadon.stream_position().unwrap(), 6);
+        assert_eq!(yadon.write_repeated(&[3, 4], 1).unwrap(), 2);
+        assert_eq!(yadon.write(&[]).unwrap(), 0);
+        assert_eq!(yadon.operations.len(), 4);
+
+        let mut target = [0u8; 8];
+        yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap();
+        assert_eq!(target, [1, 1, 1, 1, 1, 1, 3, 4]);
+    }
+
+    #[test]
+    fn growable_length() {
+        let mut now = Cursor::new(vec![0u8; 4]);
+        let mut yadon = Yadon::new(Some(0), Some(4)).with_length_mode(LengthMode::Growable);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1, 2, 3, 4]).unwrap(), 4);
+        assert_eq!(yadon.length, Some(6));
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-1)).unwrap(), 5);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[5, 6]).unwrap(), 2);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(9)).unwrap(), 9);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[7]).unwrap(), 1);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(0)).unwrap(), 10);
+
+        let mut target = Cursor::new(vec![0u8; 4]);
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.into_inner(), now.into_inner());
+
+        let mut target = [0u8; 4];
+        match yadon.apply(&mut Cursor::new(&mut target[..]), true) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.expected, 4);
+                assert_eq!(diff.actual, 2);
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn append_only_rejects_backwards_seeks() {
+        let mut yadon = Yadon::new_append_only(Some(2), Some(16));
+        assert_eq!(yadon.seek(SeekFrom::Start(1)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::Current(-1)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.seek(SeekFrom::End(-12)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.copy_within(0, 4, 1).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(yadon.operations.len(), 1);
+        assert_eq!(yadon.stream_position().unwrap(), 5);
+        assert_eq!(yadon.seek(SeekFrom::Start(6)).unwrap(), 6);
+        assert_eq!(yadon.write(&[4]).unwrap(), 1);
+        assert_eq!(yadon.seek(SeekFrom::End(-8)).unwrap(), 8);
+        assert_eq!(yadon.write(&[5]).unwrap(), 1);
+
+        let mut target = [0u8; 16];
+        yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap();
+        assert_eq!(&target[0..10], &[0, 0, 1, 2, 3, 0, 4, 0, 5, 0]);
+    }
+
+    #[test]
+    fn overwrites_denied() {
+        let mut yadon = Yadon::new(Some(0), Some(32));
+        yadon.deny_overwrite = true;
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        assert_eq!(yadon.fill(2, 4).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(16)).unwrap(), 16);
+        assert_eq!(yadon.write(&[3; 4]).unwrap(), 4);
+        assert_eq!(yadon.written_extents().collect::<Vec<_>>(), vec![0..8, 16..20]);
+
+        assert_eq!(yadon.seek(SeekFrom::Start(19)).unwrap(), 19);
+        let error = yadon.write(&[4; 2]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "write to 19..21 overlaps previously written 16..20");
+        assert_eq!(yadon.stream_position().unwrap(), 19);
+        assert_eq!(yadon.seek(SeekFrom::Start(6)).unwrap(), 6);
+        let error = yadon.write_repeated(&[5, 6], 2).unwrap_err();
+        assert_eq!(error.to_string(), "write to 6..10 overlaps previously written 0..8");
+        assert_eq!(yadon.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert!(yadon.write(&[1; 4]).is_err());
+        assert!(yadon.write(&[]).is_err());
+        assert_eq!(yadon.seek(SeekFrom::Start(8)).unwrap(), 8);
+        assert_eq!(yadon.write(&[7; 8]).unwrap(), 8);
+        assert_eq!(yadon.written_extents().collect::<Vec<_>>(), vec![0..20]);
+    }
+
+    #[test]
+    fn writes_restricted_to_reserved_regions() {
+        let mut yadon = Yadon::new(Some(0), Some(64));
+        yadon.reserve_region(8..12);
+        yadon.reserve_region(32..40);
+        let error = yadon.write(&[1; 4]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "write to 0..4 is outside the reserved regions");
+        assert_eq!(yadon.seek(SeekFrom::Start(8)).unwrap(), 8);
+        assert_eq!(yadon.write(&[2; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(30)).unwrap(), 30);
+        assert_eq!(yadon.fill(3, 4).unwrap_err().to_string(), "write to 30..34 is outside the reserved regions");
+        assert_eq!(yadon.seek(SeekFrom::Start(36)).unwrap(), 36);
+        assert_eq!(yadon.write_repeated(&[4, 5], 3).unwrap_err().to_string(), "write to 36..42 is outside the reserved regions");
+        assert_eq!(yadon.write_repeated(&[4, 5], 2).unwrap(), 4);
+        assert_eq!(yadon.operations.len(), 5);
+
+        let mut target = [0u8; 64];
+        yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap();
+        assert_eq!(&target[8..12], &[2; 4]);
+        assert_eq!(&target[32..40], &[0, 0, 0, 0, 4, 5, 4, 5]);
+    }
+
+    #[test]
+    fn write_after_seeking_past_end() {
+        let mut now_target = [0u8; 8];
+        let mut now = Cursor::new(&mut now_target[..]);
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(20)).unwrap(), 20);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1]).unwrap(), 0);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap(), 20);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-1)).unwrap(), 7);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[2, 3]).unwrap(), 1);
+
+        let mut target = [0u8; 8];
+        yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap();
+        assert_eq!(target, now_target);
+    }
+
+    #[test]
+    fn start_without_length_write_first() {
+        let mut now = Cursor::new(vec![0u8; 16]);
+        now.seek(SeekFrom::Start(10)).unwrap();
+        let mut yadon = Yadon::new(Some(10), None);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1, 2, 3]).unwrap(), 3);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(-1)).unwrap(), 12);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[4]).unwrap(), 1);
+
+        let mut target = Cursor::new(vec![0u8; 16]);
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.into_inner(), now.into_inner());
+    }
+
+    #[test]
+    fn start_without_length_seek_first() {
+        let mut now = Cursor::new(vec![0u8; 16]);
+        now.seek(SeekFrom::Start(10)).unwrap();
+        let mut yadon = Yadon::new(Some(10), None);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(2)).unwrap(), 12);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1, 2]).unwrap(), 2);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap(), 14);
+
+        let mut target = Cursor::new(vec![0u8; 16]);
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.into_inner(), now.into_inner());
+    }
+
+    #[test]
+    fn failed_end_seek_leaves_state() {
+        let mut now = Cursor::new(vec![0u8; 8]);
+        let mut yadon = Yadon::new(None, None);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).map_err(|e| e.kind()), Err(std::io::ErrorKind::Unsupported));
+        assert_eq!(yadon.operations.len(), 1);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap(), 3);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[4]).unwrap(), 1);
+
+        let mut target = Cursor::new(vec![0u8; 8]);
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.into_inner(), now.into_inner());
+    }
+
+    #[test]
+    fn deferred_end_seek_resolved_on_apply() {
+        #[derive(Debug)]
+        struct Nothing;
+
+        impl ApplyOp for Nothing {
+            fn simulate(&self, pos: u64, _len: Option<u64>) -> SimResult {
+                SimResult { position: pos, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written: 0 })
+            }
+        }
+
+        let mut yadon = Yadon::new(None, None);
+        yadon.defer_end_seeks = true;
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::End(0)).unwrap(), 2);
+        assert_eq!(yadon.write(&[3, 4, 5]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::Current(-1)).unwrap(), 4);
+        assert_eq!(yadon.write(&[6]).unwrap(), 1);
+        assert_eq!(yadon.record_custom(Nothing).map_err(|e| e.kind()).unwrap_err(), std::io::ErrorKind::Unsupported);
+        assert_eq!(yadon.seek(SeekFrom::Start(1)).unwrap(), 1);
+        assert_eq!(yadon.write(&[7]).unwrap(), 1);
+
+        // The target is longer than what was recorded, so the end seek lands further along than the recorder said.
+        let mut target = Cursor::new(vec![0u8; 6]);
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.into_inner(), &[1, 7, 0, 0, 0, 0, 3, 4, 6]);
+    }
+
+    #[test]
+    fn unspecified_start_current_seek_assumes_0() {
+        let mut yadon = Yadon::new(None, None);
+        assert_eq!(yadon.seek(SeekFrom::Current(2)).unwrap(), 2);
+    }
+
+    #[test]
+    fn too_big_write() {
+        let mut now_target = [0u8; 32];
+        let mut now = Cursor::new(&mut now_target[..]);
+        now.seek(SeekFrom::Start(1)).unwrap();
+        let mut yadon = Yadon::new(Some(1), Some(32));
+        // Too big since we start at position 1
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1u8; 64]).unwrap(), 31);
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-16)).unwrap();
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[2u8; 17]).unwrap(), 16);
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(31)).unwrap();
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[3u8; 2]).unwrap(), 1);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[4u8; 2]).unwrap(), 0);
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(0)).unwrap();
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[5u8; 2]).unwrap(), 0);
+        assert_multi_write(&mut now, &mut yadon, &[1]).unwrap();
+        assert_multi_write(&mut now, &mut yadon, &[1]).unwrap();
+
+        println!("{:?}", yadon);
+        let mut later_target = vec![0u8; 32]; // Using a vec here because it can grow if we've made a mistake
+        let mut later_writer = Cursor::new(&mut later_target[..]);
+        yadon.apply(&mut later_writer, true).unwrap();
+        assert_eq!(&later_target, &now_target);
+    }
+
+    #[test]
+    fn return_values() {
+        let mut now_target = [0u8; 128];
+        let mut now = Cursor::new(&mut now_target[..]);
+        now.seek(SeekFrom::Start(27)).unwrap();
+        let mut yadon = Yadon::new(Some(27), Some(128));
+
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap();
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(4)).unwrap();
+        assert_multi_write(&mut now, &mut yadon, &[1,2,3,4,5]).unwrap();
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(-2)).unwrap();
+        assert_multi_write(&mut now, &mut yadon, &[1,2,3,4,5]).unwrap();
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(27)).unwrap();
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(2)).unwrap();
+        assert_multi_write(&mut now, &mut yadon, &[1,2]).unwrap();
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-12)).unwrap();
+        assert_multi_write(&mut now, &mut yadon, &[12; 14]).unwrap();
+
+        let mut later_target = vec![0u8; 128]; // Using a vec here because it can grow if we've made a mistake
+        let mut later_writer = Cursor::new(&mut later_target[..]);
+        yadon.apply(&mut later_writer, true).unwrap();
+        assert_eq!(&later_target, &now_target);
+    }
+
+    #[test]
+    fn failed_apply_write_end() {
+        // mismatched sizes between yadon and the target
+        let mut yadon = Yadon::new(Some(1), Some(4));
+        assert_eq!(yadon.seek(SeekFrom::End(-3)).unwrap(), 1);
+        assert_eq!(yadon.write(&[2]).unwrap(), 1);
+
+        let mut target = vec![0u8; 8];
+        let mut target_writer = Cursor::new(&mut target);
         // we should be able to successfully apply this if check_return_values is false.
         yadon.apply(&mut target_writer, false).unwrap();
 
@@ -316,7 +1793,7 @@ mod tests {
                 assert_eq!(diff.actual, 5);
             },
             res => {
-                assert!(false, "Apply did not fail with a diverged seek: {:?}", res);
+                panic!("Apply did not fail with a diverged seek: {:?}", res);
             }
         }
     }
@@ -324,7 +1801,7 @@ mod tests {
     #[test]
     fn apply_smaller_than_target() {
         let mut yadon = Yadon::new(Some(1), Some(4));
-        yadon.write(&[0; 6]).unwrap();
+        assert_eq!(yadon.write(&[0; 6]).unwrap(), 3);
 
         let mut target = vec![0u8; 8];
         let mut target_writer = Cursor::new(&mut target);
@@ -335,7 +1812,7 @@ mod tests {
     fn failed_apply_write_too_much() {
         // mismatched sizes between yadon and the target
         let mut yadon = Yadon::new(Some(1), Some(8));
-        yadon.write(&[0; 6]).unwrap();
+        assert_eq!(yadon.write(&[0; 6]).unwrap(), 6);
 
         let mut target = [0u8; 4];
         let mut target_writer = Cursor::new(&mut target[..]);
@@ -350,7 +1827,7 @@ mod tests {
                 assert_eq!(diff.actual, 3);
             },
             res => {
-                assert!(false, "Apply did not fail with a diverged write: {:?}", res);
+                panic!("Apply did not fail with a diverged write: {:?}", res);
             }
         }
     }
@@ -359,8 +1836,4485 @@ mod tests {
     fn cannot_seek_end_without_length() {
         // mismatched sizes between yadon and the target
         let mut yadon = Yadon::new(Some(3), None);
-        yadon.write(&[0; 6]).unwrap();
-        assert_eq!(yadon.seek(SeekFrom::End(-2)).map_err(|e| e.kind()), Err(std::io::ErrorKind::Unsupported.into()));
+        assert_eq!(yadon.write(&[0; 6]).unwrap(), 6);
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).map_err(|e| e.kind()), Err(std::io::ErrorKind::Unsupported));
+    }
+
+    #[test]
+    fn flushes_replayed_in_order() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        yadon.flush().unwrap();
+        assert_eq!(yadon.seek(SeekFrom::Start(8)).unwrap(), 8);
+        yadon.flush().unwrap();
+        assert_eq!(yadon.write(&[3]).unwrap(), 1);
+        assert_eq!(yadon.seek(SeekFrom::Current(2)).unwrap(), 11);
+        assert_eq!(yadon.write(&[4]).unwrap(), 1);
+        yadon.flush().unwrap();
+
+        let mut target = EventLog::new(16);
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.events, vec![
+            Event::Seek(SeekFrom::Start(0)),
+            Event::Write(2),
+            Event::Flush,
+            Event::Seek(SeekFrom::Start(8)),
+            Event::Flush,
+            Event::Write(1),
+            Event::Seek(SeekFrom::Current(2)),
+            Event::Write(1),
+            Event::Flush,
+            Event::Flush, // final flush after all operations
+        ]);
+        assert_eq!(target.inner.get_ref(), &[1, 2, 0, 0, 0, 0, 0, 0, 3, 0, 0, 4, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn flushes_not_replayed_when_disabled() {
+        let mut yadon = Yadon::new(None, Some(16));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        yadon.flush().unwrap();
+        assert_eq!(yadon.write(&[3]).unwrap(), 1);
+        yadon.flush().unwrap();
+
+        let mut target = EventLog::new(16);
+        yadon.apply_with_options(&mut target, &ApplyOptions {
+            replay_flushes: false,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(target.events, vec![Event::Write(2), Event::Write(1), Event::Flush]);
+    }
+
+    #[test]
+    fn vectored_write_clamped_mid_slice() {
+        let mut now_target = [0u8; 8];
+        let mut now = Cursor::new(&mut now_target[..]);
+        now.seek(SeekFrom::Start(1)).unwrap();
+        let mut yadon = Yadon::new(Some(1), Some(8));
+        let bufs = [IoSlice::new(&[1, 2, 3]), IoSlice::new(&[4, 5, 6, 7, 8]), IoSlice::new(&[9])];
+        let now_written = now.write_vectored(&bufs).unwrap();
+        assert_eq!(yadon.write_vectored(&bufs).unwrap(), now_written);
+        assert_eq!(now_written, 7);
+        assert_eq!(yadon.operations.len(), 1);
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap();
+
+        let mut target = [0u8; 8];
+        yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap();
+        assert_eq!(target, now_target);
+    }
+
+    #[test]
+    fn contiguous_writes_vectored() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.write(&[3]).unwrap(), 1);
+        assert_eq!(yadon.write(&[4, 5, 6]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::Current(1)).unwrap(), 7);
+        assert_eq!(yadon.write(&[7]).unwrap(), 1);
+        assert_eq!(yadon.write(&[8]).unwrap(), 1);
+
+        let mut target = EventLog::new(16);
+        target.vectored = true;
+        assert_eq!(yadon.apply(&mut target, true).unwrap(), 8);
+        assert_eq!(target.events, vec![
+            Event::Seek(SeekFrom::Start(0)),
+            Event::WriteVectored(6),
+            Event::Seek(SeekFrom::Current(1)),
+            Event::WriteVectored(2),
+            Event::Flush,
+        ]);
+        assert_eq!(&target.inner.get_ref()[0..9], &[1, 2, 3, 4, 5, 6, 0, 7, 8]);
+
+        // Targets without vectored writes get one write per operation.
+        let mut target = EventLog::new(16);
+        assert_eq!(yadon.apply(&mut target, true).unwrap(), 8);
+        assert_eq!(target.events, vec![
+            Event::Seek(SeekFrom::Start(0)),
+            Event::Write(2),
+            Event::Write(1),
+            Event::Write(3),
+            Event::Seek(SeekFrom::Current(1)),
+            Event::Write(1),
+            Event::Write(1),
+            Event::Flush,
+        ]);
+        assert_eq!(&target.inner.get_ref()[0..9], &[1, 2, 3, 4, 5, 6, 0, 7, 8]);
+    }
+
+    #[test]
+    fn short_vectored_write_attributed() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        yadon.labeled_scope("first", |yadon| assert_eq!(yadon.write(&[1; 3]).unwrap(), 3));
+        yadon.labeled_scope("second", |yadon| assert_eq!(yadon.write(&[2; 3]).unwrap(), 3));
+        yadon.labeled_scope("third", |yadon| assert_eq!(yadon.write(&[3; 2]).unwrap(), 2));
+
+        let mut target = [0u8; 5];
+        match yadon.apply(&mut Cursor::new(&mut target[..]), true) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.expected, 3);
+                assert_eq!(diff.actual, 2);
+                assert_eq!(diff.label.as_deref(), Some("second"));
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+        assert_eq!(target, [1, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn writes_only_ignores_seek_positions() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).unwrap(), 6);
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+
+        // The target is longer than the recorder thought, so the end seek lands somewhere else.
+        let mut target = Cursor::new(vec![0u8; 10]);
+        match yadon.apply_with_policy(&mut target, CheckPolicy::Strict) {
+            Err(ApplyError::SeekDiverged(diff)) => {
+                assert_eq!(diff.expected, 6);
+                assert_eq!(diff.actual, 8);
+            },
+            res => panic!("Apply did not fail with a diverged seek: {:?}", res),
+        }
+
+        let mut target = Cursor::new(vec![0u8; 10]);
+        assert_eq!(yadon.apply_with_policy(&mut target, CheckPolicy::WritesOnly).unwrap(), 2);
+        assert_eq!(target.into_inner(), &[0, 0, 0, 0, 0, 0, 0, 0, 1, 2]);
+
+        // Seek checks still catch a too-short target, without failing on the short write.
+        let mut target = [0u8; 4];
+        match yadon.apply_with_policy(&mut Cursor::new(&mut target[..]), CheckPolicy::SeeksOnly) {
+            Err(ApplyError::SeekDiverged(diff)) => assert_eq!(diff.actual, 2),
+            res => panic!("Apply did not fail with a diverged seek: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn divergences_collected() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.write(&[1; 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).unwrap(), 6);
+        assert_eq!(yadon.write(&[2; 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::Start(3)).unwrap(), 3);
+        assert_eq!(yadon.write(&[3; 3]).unwrap(), 3);
+
+        let mut target = [0u8; 5];
+        let summary = yadon.apply_collect(&mut Cursor::new(&mut target[..])).unwrap();
+        assert!(!summary.is_clean());
+        assert_eq!(summary.bytes_written, 6);
+        assert_eq!(summary.diverged_operations(), 2);
+        assert_eq!(summary.divergences.len(), 2);
+        match &summary.divergences[0] {
+            Divergence { index: Some(1), kind: DivergenceKind::Seek(diff) } => {
+                assert_eq!(diff.expected, 6);
+                assert_eq!(diff.actual, 3);
+            },
+            divergence => panic!("Unexpected divergence: {:?}", divergence),
+        }
+        match &summary.divergences[1] {
+            Divergence { index: Some(4), kind: DivergenceKind::BytesWritten(diff) } => {
+                assert_eq!(diff.expected, 3);
+                assert_eq!(diff.actual, 2);
+            },
+            divergence => panic!("Unexpected divergence: {:?}", divergence),
+        }
+        assert_eq!(target, [1, 1, 0, 3, 3]);
+
+        let mut target = [0u8; 8];
+        let summary = yadon.apply_collect(&mut Cursor::new(&mut target[..])).unwrap();
+        assert!(summary.is_clean());
+        assert_eq!(summary.bytes_written, 7);
+    }
+
+    #[test]
+    fn report_counts_short_writes() {
+        let mut yadon = Yadon::new(Some(1), Some(8));
+        assert_eq!(yadon.write(&[1; 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::Current(1)).unwrap(), 4);
+        assert_eq!(yadon.write(&[2; 4]).unwrap(), 4);
+        yadon.flush().unwrap();
+
+        let mut target = [0u8; 6];
+        let report = yadon.apply_report(&mut Cursor::new(&mut target[..]), &ApplyOptions {
+            check_policy: CheckPolicy::None,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(report.ops_applied, 4);
+        assert_eq!(report.bytes_written, 4);
+        assert_eq!(report.seeks_issued, 2);
+        assert_eq!(report.final_position, 6);
+        assert!(report.to_string().starts_with("applied 4 operations (4 bytes written, 2 seeks), ending at position 6, in "));
+        assert_eq!(target, [0, 1, 1, 0, 2, 2]);
+    }
+
+    #[test]
+    fn zero_writes_skipped() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[0; 4]).unwrap(), 4);
+        assert_eq!(yadon.write(&[0, 0, 1]).unwrap(), 3);
+        assert_eq!(yadon.fill(0, 2).unwrap(), 2);
+        assert_eq!(yadon.write(&[2]).unwrap(), 1);
+
+        let mut target = EventLog::new(16);
+        let bytes_written = yadon.apply_with_options(&mut target, &ApplyOptions {
+            skip_zero_writes: true,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(bytes_written, 10);
+        assert_eq!(target.events, vec![
+            Event::Seek(SeekFrom::Start(0)),
+            Event::Seek(SeekFrom::Current(4)),
+            Event::Write(3),
+            Event::Seek(SeekFrom::Current(2)),
+            Event::Write(1),
+            Event::Flush,
+        ]);
+        assert_eq!(&target.inner.get_ref()[0..10], &[0, 0, 0, 0, 0, 0, 1, 0, 0, 2]);
+    }
+
+    #[test]
+    fn fill_applied_in_chunks() {
+        let len = 200_000u64;
+        let mut yadon = Yadon::new(Some(0), Some(len + 16));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.fill(0xFF, len).unwrap(), len);
+        assert_eq!(yadon.stream_position().unwrap(), len + 2);
+        assert_eq!(yadon.write(&[3]).unwrap(), 1);
+
+        let mut target = EventLog::new(len as usize + 16);
+        assert_eq!(yadon.apply(&mut target, true).unwrap(), len as usize + 3);
+        let fill_writes: Vec<usize> = target.events.iter().filter_map(|e| match e {
+            Event::Write(n) if *n > 2 => Some(*n),
+            _ => None
+        }).collect();
+        assert_eq!(fill_writes, vec![65536, 65536, 65536, 3392]);
+
+        let data = target.inner.get_ref();
+        assert_eq!(&data[0..2], &[1, 2]);
+        assert!(data[2..len as usize + 2].iter().all(|b| *b == 0xFF));
+        assert_eq!(&data[len as usize + 2..len as usize + 4], &[3, 0]);
+    }
+
+    #[test]
+    fn fill_clamped_like_write() {
+        let mut now_target = [0u8; 32];
+        let mut now = Cursor::new(&mut now_target[..]);
+        now.seek(SeekFrom::Start(4)).unwrap();
+        let mut yadon = Yadon::new(Some(4), Some(32));
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[9u8; 8]).unwrap(), 8);
+        assert_eq!(now.write(&[0xAA; 64]).unwrap(), 20);
+        assert_eq!(yadon.fill(0xAA, 64).unwrap(), 20);
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap();
+
+        let mut later_target = vec![0u8; 32];
+        let mut later_writer = Cursor::new(&mut later_target[..]);
+        assert_eq!(yadon.apply(&mut later_writer, true).unwrap(), 28);
+        assert_eq!(&later_target, &now_target);
+    }
+
+    #[test]
+    fn record_from_reader() {
+        /// Hands out at most 3 bytes per read, as `io::copy()` would see them.
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let len = buf.len().min(self.0.len()).min(3);
+                buf[0..len].copy_from_slice(&self.0[0..len]);
+                self.0 = &self.0[len..];
+                Ok(len)
+            }
+        }
+        let source: Vec<u8> = (0..40).collect();
+
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.record_from_reader(Trickle(&source), None).unwrap(), 40);
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [WriteOperation::Write(source.clone(), 40)]));
+
+        // Chunks are written whole, however little each read returns.
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.record_from_reader_chunked(Trickle(&source), Some(35), 16).unwrap(), 35);
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            WriteOperation::Write(source[0..16].to_vec(), 16),
+            WriteOperation::Write(source[16..32].to_vec(), 16),
+            WriteOperation::Write(source[32..35].to_vec(), 3),
+        ]));
+        assert_eq!(yadon.record_from_reader_chunked(&b""[..], Some(8), 16).unwrap(), 0);
+        assert_eq!(yadon.operations.len(), 3);
+
+        // Reading stops at the end of the length, leaving the rest unread.
+        let mut yadon = Yadon::new(Some(0), Some(24)).with_overflow_policy(OverflowPolicy::Error);
+        yadon.seek(SeekFrom::Start(4)).unwrap();
+        let mut reader = Trickle(&source);
+        assert_eq!(yadon.record_from_reader_chunked(&mut reader, None, 16).unwrap(), 20);
+        assert_eq!(reader.0, &source[20..]);
+        assert_eq!(yadon.record_from_reader(&mut reader, None).unwrap(), 0);
+        let mut target = [0xee; 24];
+        yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap();
+        assert_eq!(&target[0..4], &[0xee; 4]);
+        assert_eq!(&target[4..], &source[0..20]);
+
+        assert_eq!(yadon.record_from_reader_chunked(&b""[..], None, 0).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn tee_records_what_was_written() {
+        /// Takes at most 3 bytes per write.
+        struct Stingy(Cursor<Vec<u8>>);
+        impl Write for Stingy {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.write(&buf[0..buf.len().min(3)])
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        impl Seek for Stingy {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+
+        let base: Vec<u8> = (0..16).collect();
+        let mut primary = Stingy(Cursor::new(base.clone()));
+        primary.seek(SeekFrom::Start(2)).unwrap();
+        let mut tee = YadonTee::new(&mut primary).unwrap();
+        assert_eq!(tee.write(b"abcde").unwrap(), 3);
+        assert_eq!(tee.seek(SeekFrom::End(-2)).unwrap(), 14);
+        tee.write_all(b"yz!!").unwrap();
+        assert_eq!(tee.seek(SeekFrom::Current(-10)).unwrap(), 8);
+        assert_eq!(tee.write(b"").unwrap(), 0);
+        tee.flush().unwrap();
+        assert!(tee.seek(SeekFrom::Current(-20)).is_err());
+        let (_, yadon) = tee.into_parts();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            WriteOperation::Write(b"abc".to_vec(), 3),
+            WriteOperation::Seek(SeekFrom::End(-2), 14),
+            WriteOperation::Write(b"yz!".to_vec(), 3),
+            WriteOperation::Write(b"!".to_vec(), 1),
+            WriteOperation::Seek(SeekFrom::Current(-10), 8),
+            WriteOperation::Write(vec![], 0),
+            WriteOperation::Flush,
+        ]));
+
+        let mut replica = Cursor::new(base);
+        yadon.apply(&mut replica, true).unwrap();
+        assert_eq!(replica.position(), primary.0.position());
+        assert_eq!(replica.into_inner(), primary.0.into_inner());
+    }
+
+    #[test]
+    fn deferred_writer() {
+        let mut writer = DeferredWriter::new(Cursor::new(b"0123456789".to_vec())).unwrap();
+        writer.seek(SeekFrom::Start(2)).unwrap();
+        writer.write_all(b"ab").unwrap();
+        assert_eq!(writer.get_ref().get_ref(), b"0123456789");
+        writer.flush().unwrap();
+        assert_eq!(writer.get_ref().get_ref(), b"01ab456789");
+        assert!(writer.pending().operations.is_empty());
+
+        // Recording carries on from where the flush left the target.
+        writer.write_all(b"cd").unwrap();
+        assert_eq!(writer.seek(SeekFrom::End(-1)).unwrap(), 9);
+        writer.write_all(b"ef").unwrap();
+        let target = writer.commit().unwrap();
+        assert_eq!(target.get_ref(), b"01abcd678ef");
+
+        let mut writer = DeferredWriter::new(target).unwrap();
+        writer.write_all(b"gone").unwrap();
+        assert_eq!(writer.into_inner().get_ref(), b"01abcd678ef");
+
+        // A write which the target cuts short fails the flush, and is kept to be applied again.
+        let mut buffer = [0u8; 4];
+        let mut writer = DeferredWriter::new(Cursor::new(&mut buffer[..])).unwrap();
+        writer.write_all(b"too long").unwrap();
+        let error = writer.flush().unwrap_err();
+        assert!(matches!(error.get_ref().and_then(|error| error.downcast_ref::<ApplyError>()), Some(ApplyError::NumBytesWrittenDiverge(_))));
+        assert_eq!(writer.pending().operations.len(), 1);
+        assert!(writer.commit().is_err());
+        assert_eq!(&buffer, b"too ");
+    }
+
+    #[test]
+    fn apply_guard() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        let mut target = Cursor::new(vec![0u8; 4]);
+        let mut yadon = Yadon::new(Some(0), Some(4));
+        let mut guard = yadon.guard(&mut target, GuardPolicy::default());
+        guard.write_all(b"ab").unwrap();
+        assert_eq!(guard.commit().unwrap(), 2);
+        assert_eq!(target.get_ref(), b"ab\0\0");
+        assert_eq!(yadon.applied_count(), 1);
+
+        // Panicking partway through a scope leaves the target untouched, unless the policy says to apply anyway, in
+        // which case it's applied once.
+        for (policy, applied, contents) in [
+            (GuardPolicy::ApplyUnlessPanicking, 0, b"\0\0\0\0"),
+            (GuardPolicy::Apply, 1, b"cd\0\0"),
+            (GuardPolicy::Discard, 0, b"\0\0\0\0"),
+        ] {
+            let mut target = Cursor::new(vec![0u8; 4]);
+            let mut yadon = Yadon::new(Some(0), Some(4));
+            let panicked = catch_unwind(AssertUnwindSafe(|| {
+                let mut guard = yadon.guard(&mut target, policy);
+                guard.write_all(b"cd").unwrap();
+                panic!("scope failed");
+            }));
+            assert!(panicked.is_err());
+            assert_eq!(yadon.applied_count(), applied);
+            assert_eq!(target.get_ref(), contents);
+        }
+
+        // An error from applying on drop is stashed.
+        let mut short = [0u8; 2];
+        let mut target = Cursor::new(&mut short[..]);
+        let mut yadon = Yadon::new(Some(0), Some(4));
+        yadon.guard(&mut target, GuardPolicy::Apply).write_all(b"long").unwrap();
+        assert!(matches!(yadon.take_guard_error(), Some(ApplyError::NumBytesWrittenDiverge(_))));
+        assert!(yadon.take_guard_error().is_none());
+
+        // A failing `commit_or_panic()` doesn't apply again as the guard unwinds.
+        let mut target = Cursor::new(&mut short[..]);
+        let panicked = catch_unwind(AssertUnwindSafe(|| yadon.guard(&mut target, GuardPolicy::Apply).commit_or_panic()));
+        assert!(panicked.is_err());
+        assert!(yadon.take_guard_error().is_none());
+        assert_eq!(yadon.applied_count(), 0);
+    }
+
+    #[test]
+    fn apply_with_hook() {
+        struct Audit {
+            events: Vec<String>,
+            skip: usize,
+        }
+        impl ApplyHook for Audit {
+            fn before_op(&mut self, index: usize, operation: &WriteOperation) -> ControlFlow<()> {
+                self.events.push(format!("before {} {}", index, operation.name()));
+                if index == self.skip { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+            }
+
+            fn after_op(&mut self, index: usize, _operation: &WriteOperation, result: &Result<OpOutcome, ApplyError>) {
+                self.events.push(format!("after {} {:?}", index, result.as_ref().ok()));
+            }
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(12));
+        yadon.seek(SeekFrom::Start(2)).unwrap();
+        yadon.write_all(b"head").unwrap();
+        yadon.write_all(b"body").unwrap();
+        yadon.flush().unwrap();
+        let mut hook = Audit { events: vec![], skip: 1 };
+        let mut target = Cursor::new(vec![0u8; 12]);
+        let report = yadon.apply_with_hook(&mut target, &ApplyOptions::default(), &mut hook).unwrap();
+        assert_eq!(report.ops_skipped, vec![1]);
+        assert_eq!(report.ops_applied, 4);
+        assert_eq!(report.bytes_written, 4);
+        assert_eq!(target.get_ref(), b"\0\0\0\0\0\0body\0\0");
+        // The writes would be applied as one vectored write, but the hook is told about each.
+        assert_eq!(hook.events, vec![
+            "before 0 seek",
+            "after 0 Some(OpOutcome { bytes_written: 0, position: Some(2) })",
+            "before 1 write",
+            "before 2 write",
+            "after 2 Some(OpOutcome { bytes_written: 4, position: Some(10) })",
+            "before 3 flush",
+            "after 3 Some(OpOutcome { bytes_written: 0, position: Some(10) })",
+        ]);
+
+        // Skipping a seek still moves the target, so the writes after it land where they were recorded to.
+        let mut hook = Audit { events: vec![], skip: 0 };
+        let mut target = Cursor::new(vec![0u8; 12]);
+        yadon.apply_with_hook(&mut target, &ApplyOptions::default(), &mut hook).unwrap();
+        assert_eq!(target.get_ref(), b"\0\0headbody\0\0");
+
+        // The hook can stop applying instead.
+        let options = ApplyOptions { hook_break: HookBreak::Abort, ..ApplyOptions::default() };
+        let mut hook = Audit { events: vec![], skip: 2 };
+        let mut target = Cursor::new(vec![0u8; 12]);
+        assert!(matches!(
+            yadon.apply_with_hook(&mut target, &options, &mut hook),
+            Err(ApplyError::Cancelled { ops_applied: 2, bytes_written: 4 }),
+        ));
+        assert_eq!(target.get_ref(), b"\0\0head\0\0\0\0\0\0");
+        assert_eq!(hook.events.last().unwrap(), "before 2 write");
+
+        // An operation which fails is reported to the hook along with its error.
+        let mut hook = Audit { events: vec![], skip: usize::MAX };
+        let mut short = [0u8; 8];
+        let mut target = Cursor::new(&mut short[..]);
+        assert!(matches!(
+            yadon.apply_with_hook(&mut target, &ApplyOptions::default(), &mut hook),
+            Err(ApplyError::NumBytesWrittenDiverge { .. }),
+        ));
+        assert_eq!(hook.events.last().unwrap(), "after 2 None");
+    }
+
+    #[test]
+    fn apply_mapped() {
+        let mut yadon = Yadon::new(Some(0), Some(12));
+        yadon.write_all(b"OLD!").unwrap();
+        yadon.fill(0xee, 4).unwrap();
+        yadon.write_all(b"tail").unwrap();
+        yadon.flush().unwrap();
+        let recorded = format!("{:?}", yadon.operations);
+
+        // The magic is rewritten, and the fill into the deprecated region is dropped.
+        let mut target = Cursor::new(vec![0u8; 12]);
+        let report = yadon.apply_mapped(&mut target, &ApplyOptions::default(), |index, operation| match (index, operation) {
+            (0, WriteOperation::Write(..)) => OpAction::Replace(WriteOperation::Write(b"NEW!".to_vec(), 4)),
+            (_, WriteOperation::Fill { .. }) => OpAction::Skip,
+            _ => OpAction::Keep,
+        }).unwrap();
+        assert_eq!(target.get_ref(), b"NEW!\0\0\0\0tail");
+        assert_eq!(report.report.ops_skipped, vec![1]);
+        assert_eq!(report.report.bytes_written, 8);
+        assert_eq!(report.ops_replaced, vec![0]);
+        assert!(report.resized.is_empty());
+        assert_eq!(format!("{:?}", yadon.operations), recorded);
+
+        // A replacement which writes a different number of bytes is flagged, and checked against its own length.
+        let mut target = Cursor::new(vec![0u8; 12]);
+        let report = yadon.apply_mapped(&mut target, &ApplyOptions::default(), |index, _| match index {
+            0 => OpAction::Replace(WriteOperation::Write(b"NEW".to_vec(), 3)),
+            _ => OpAction::Keep,
+        }).unwrap();
+        assert_eq!(report.resized, vec![ResizedOp { index: 0, original: 4, replacement: 3 }]);
+        assert_eq!(target.get_ref(), b"NEW\xee\xee\xee\xeetail\0");
+
+        // A replacement the target can't apply fails.
+        let mut target = Cursor::new(vec![0u8; 12]);
+        assert!(matches!(
+            yadon.apply_mapped(&mut target, &ApplyOptions::default(), |index, _| match index {
+                3 => OpAction::Replace(WriteOperation::SetLen(4)),
+                _ => OpAction::Keep,
+            }),
+            Err(ApplyError::UnsupportedOperation("set_len")),
+        ));
+    }
+
+    #[test]
+    fn fork_and_flatten() {
+        let mut base = Yadon::new(Some(2), Some(16));
+        base.push_label("header");
+        base.write_all(b"base").unwrap();
+        base.pop_label();
+        base.fill(0xbb, 2).unwrap();
+        let base = Arc::new(base);
+
+        let mut child = base.fork();
+        assert_eq!(child.start, Some(8));
+        assert!(Arc::ptr_eq(child.parent().unwrap(), &base));
+        child.write_all(b"kid").unwrap();
+        assert_eq!(child.seek(SeekFrom::End(-4)).unwrap(), 12);
+        child.push_label("footer");
+        child.write_all(b"tail").unwrap();
+        // Queries see the parent's writes, with the child's over them.
+        assert_eq!(child.touched_ranges().unwrap(), vec![2..11, 12..16]);
+        let mut pending = [0u8; 8];
+        assert_eq!(child.read_pending_at(4, &mut pending).unwrap(), vec![4..11]);
+        assert_eq!(&pending, b"se\xbb\xbbkid\0");
+
+        // A child of the child sees through both.
+        let child = Arc::new(child);
+        let mut grandchild = child.fork();
+        assert_eq!(grandchild.start, Some(16));
+        grandchild.seek(SeekFrom::Start(0)).unwrap();
+        grandchild.write_all(b"BA").unwrap();
+        assert_eq!(grandchild.touched_ranges().unwrap(), vec![0..11, 12..16]);
+
+        // Flattening has the same effect as applying each in turn.
+        let mut sequential = Cursor::new(vec![0x11u8; 16]);
+        base.apply(&mut sequential, true).unwrap();
+        child.apply(&mut sequential, true).unwrap();
+        grandchild.apply(&mut sequential, true).unwrap();
+        let flattened = Yadon::flatten(&child, &grandchild).unwrap();
+        let mut target = Cursor::new(vec![0x11u8; 16]);
+        flattened.apply(&mut target, true).unwrap();
+        assert_eq!(target.get_ref(), sequential.get_ref());
+        assert_eq!(target.get_ref(), b"BAbase\xbb\xbbkid\x11tail");
+        assert_eq!(flattened.labels().collect::<Vec<_>>(), vec![("header", 0..1), ("footer", 5..6)]);
+        assert!(flattened.parent().is_none());
+        assert_eq!(flattened.operations.len(), base.operations.len() + child.operations.len() + grandchild.operations.len() + 2);
+
+        // Recording carries on into the flattened recording where the child left off.
+        let mut flattened = flattened;
+        assert_eq!(flattened.stream_position().unwrap(), 2);
+
+        // Without a known position, the child carries on from wherever the parent leaves the target.
+        let unpositioned = Arc::new(Yadon::new(None, None));
+        let mut child = unpositioned.fork();
+        assert_eq!(child.start, None);
+        child.write_all(b"x").unwrap();
+        let flattened = Yadon::flatten(&unpositioned, &child).unwrap();
+        assert_eq!(format!("{:?}", flattened.operations), "[Write([120], 1)]");
+
+        // A child which doesn't carry on from its parent, or an operation which can't be copied, fails.
+        let mut relative = Yadon::new(None, None);
+        relative.seek(SeekFrom::Current(1)).unwrap();
+        assert!(matches!(Yadon::flatten(&base, &relative), Err(ComposeError::Inconsistent(_))));
+        #[derive(Debug)]
+        struct Nothing;
+        impl ApplyOp for Nothing {
+            fn simulate(&self, pos: u64, _: Option<u64>) -> SimResult {
+                SimResult { position: pos, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written: 0 })
+            }
+        }
+        let mut child = base.fork();
+        child.record_custom(Nothing).unwrap();
+        assert_eq!(Yadon::flatten(&base, &child).unwrap_err(), ComposeError::UnsupportedOperation("custom"));
+    }
+
+    #[test]
+    fn compose() {
+        let mut first = Yadon::new(Some(0), Some(16));
+        first.write_all(b"first").unwrap();
+        first.seek(SeekFrom::End(-3)).unwrap();
+        first.write_repeated(b"ab", 1).unwrap();
+        let mut second = Yadon::new(Some(4), Some(16));
+        second.fill(0xcc, 3).unwrap();
+        second.seek(SeekFrom::End(-1)).unwrap();
+        second.write_all(b"!").unwrap();
+        let mut unpositioned = Yadon::new(None, None);
+        unpositioned.write_all(b"??").unwrap();
+
+        // Composing has the same effect as applying one after the other.
+        for (first, second) in [(&first, &second), (&second, &first), (&first, &unpositioned)] {
+            let mut sequential = Cursor::new(vec![0x11u8; 16]);
+            first.apply(&mut sequential, true).unwrap();
+            second.apply(&mut sequential, true).unwrap();
+            let composed = first.compose(second).unwrap();
+            let mut target = Cursor::new(vec![0x11u8; 16]);
+            composed.apply(&mut target, true).unwrap();
+            assert_eq!(target.get_ref(), sequential.get_ref());
+        }
+        assert_eq!(first.compose(&second).unwrap().materialize(Some(&[0x11; 16])).unwrap(), b"firs\xcc\xcc\xcc\x11\x11\x11\x11\x11\x11ab!");
+
+        // Applying onto a `Yadon` of the same length records the same, including resizing and barriers.
+        let mut recorded = Yadon::new(None, Some(16));
+        first.apply(&mut recorded, true).unwrap();
+        second.apply(&mut recorded, true).unwrap();
+        let mut resized = Yadon::new(Some(0), Some(16));
+        resized.set_len(4).unwrap();
+        let mut synced = Yadon::new(None, Some(4));
+        synced.sync_barrier().unwrap();
+        resized.apply_with_setlen(&mut recorded, &ApplyOptions::default()).unwrap();
+        synced.apply_durable(&mut recorded, &ApplyOptions::default()).unwrap();
+        assert!(recorded.operations.iter().any(|operation| matches!(operation, WriteOperation::SetLen(4))));
+        assert!(recorded.operations.iter().any(|operation| matches!(operation, WriteOperation::Sync)));
+        let composed = first.compose(&second).unwrap().compose(&resized).unwrap().compose(&synced).unwrap();
+        let options = ApplyOptions { sync_fallback: SyncFallback::Flush, ..ApplyOptions::default() };
+        let mut expected = Cursor::new(vec![0x11u8; 16]);
+        composed.apply_with_setlen(&mut expected, &options).unwrap();
+        let mut target = Cursor::new(vec![0x11u8; 16]);
+        recorded.apply_with_setlen(&mut target, &options).unwrap();
+        assert_eq!(target.get_ref(), expected.get_ref());
+        assert_eq!(target.get_ref(), b"firs");
+        assert_eq!(resized.compose(&second).unwrap_err(), ComposeError::LengthMismatch { first: 4, second: 16 });
+
+        // Recordings which can't be of the same target, or can't be composed in order, aren't.
+        let stamped = Yadon::new(Some(0), Some(16)).with_generation_stamp(0, 1);
+        assert_eq!(stamped.compose(&second).unwrap_err(), ComposeError::GenerationStamp);
+        assert!(second.compose(&stamped).unwrap().generation_stamp.is_some());
+        let mut checked = Yadon::new(Some(0), Some(16));
+        checked.require_base_checksum(0..4, 0);
+        assert_eq!(first.compose(&checked).unwrap_err(), ComposeError::BaseChecksums);
+        let mut relative = Yadon::new(None, None);
+        relative.seek(SeekFrom::Current(1)).unwrap();
+        assert!(matches!(first.compose(&relative), Err(ComposeError::Inconsistent(_))));
+    }
+
+    #[test]
+    fn merge() {
+        let mut header = Yadon::new(Some(0), Some(24)).with_generation_stamp(16, 2);
+        header.write_all(b"head").unwrap();
+        header.require_base_checksum(0..4, 0x2144df1c);
+        let mut trailer = Yadon::new(Some(10), Some(24));
+        trailer.write_all(b"xx").unwrap();
+        trailer.seek(SeekFrom::Current(-2)).unwrap();
+        trailer.write_all(b"tail").unwrap();
+        let mut relative = Yadon::new(None, Some(24));
+        relative.seek(SeekFrom::Current(1)).unwrap();
+        relative.write_all(b"+").unwrap();
+
+        // Each recording starts where it was recorded to, and one without a `start` carries on from the last.
+        let mut extended = Yadon::new(Some(0), Some(24));
+        extended.extend_from(&header).unwrap();
+        extended.extend_from(&trailer).unwrap();
+        extended.extend_from(&relative).unwrap();
+        assert!(matches!(extended.operations[2], WriteOperation::Seek(SeekFrom::Start(10), 10)));
+        assert!(matches!(extended.operations.last(), Some(WriteOperation::Write(_, 1))));
+        assert!(matches!(extended.operations[6], WriteOperation::Seek(SeekFrom::Current(1), 15)));
+        assert_eq!(extended.generation_stamp, Some((16, 2)));
+        assert_eq!(extended.base_checksums.len(), 1);
+        let merged = header.merge(trailer).unwrap().merge(relative).unwrap();
+        assert_eq!(format!("{:?}", merged.operations), format!("{:?}", &extended.operations[1..]));
+        assert_eq!(merged.materialize(Some(&[0; 24])).unwrap(), b"head\0\0\0\0\0\0tail\0+\x02\0\0\0\0\0\0\0");
+
+        // Recordings which can't be of the same target aren't merged, and a failed extend changes nothing.
+        let short = Yadon::new(Some(0), Some(8));
+        assert_eq!(extended.extend_from(&short).unwrap_err(), MergeError::LengthMismatch { first: 24, second: 8 });
+        let restamped = Yadon::new(Some(0), Some(24)).with_generation_stamp(16, 3);
+        assert_eq!(
+            extended.extend_from(&restamped).unwrap_err(),
+            MergeError::GenerationStampMismatch { first: (16, 2), second: (16, 3) },
+        );
+        let mut corrupt = Yadon::new(None, Some(24));
+        corrupt.operations.push(WriteOperation::Write(vec![1, 2], 3));
+        assert!(matches!(extended.extend_from(&corrupt), Err(MergeError::Inconsistent(_))));
+        assert_eq!(format!("{:?}", merged.operations), format!("{:?}", &extended.operations[1..]));
+    }
+
+    #[test]
+    fn apply_at() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        yadon.write_all(b"ab").unwrap();
+        yadon.seek(SeekFrom::End(-3)).unwrap();
+        yadon.write_all(b"cd").unwrap();
+        yadon.seek(SeekFrom::Current(-4)).unwrap();
+        yadon.write_all(b"e").unwrap();
+        let mut reference = Cursor::new(vec![b'.'; 8]);
+        yadon.apply(&mut reference, true).unwrap();
+
+        // Each slot of the archive is patched as a copy of the slot on its own would be, leaving the rest as it was.
+        let mut archive = Cursor::new(vec![b'.'; 32]);
+        for base_offset in [8, 20] {
+            assert_eq!(yadon.apply_at(&mut archive, base_offset, &ApplyOptions::default()).unwrap(), 5);
+        }
+        let expected = [&[b'.'; 8][..], reference.get_ref(), &[b'.'; 4], reference.get_ref(), &[b'.'; 4]].concat();
+        assert_eq!(archive.get_ref(), &expected);
+        assert!(matches!(yadon.operations[1], WriteOperation::Seek(SeekFrom::End(-3), 5)));
+
+        // Seeks from the end of a slot whose end isn't known are rejected before anything is applied.
+        let mut growable = Yadon::new(Some(0), Some(4)).with_length_mode(LengthMode::Growable);
+        growable.write_all(b"x").unwrap();
+        growable.seek(SeekFrom::End(0)).unwrap();
+        assert!(matches!(
+            growable.apply_at(&mut archive, 8, &ApplyOptions::default()),
+            Err(ApplyError::UnsupportedOperation(_)),
+        ));
+        assert_eq!(archive.get_ref(), &expected);
+
+        // Without a start, the recording begins wherever the target is, rather than at the start of the slot.
+        let mut unanchored = Yadon::new(None, Some(8));
+        unanchored.write_all(b"xyz").unwrap();
+        archive.seek(SeekFrom::Start(10)).unwrap();
+        assert_eq!(unanchored.apply_at(&mut archive, 8, &ApplyOptions::default()).unwrap(), 3);
+        assert_eq!(&archive.get_ref()[8..16], b"abxyzcd.");
+        assert_eq!(archive.position(), 13);
+        archive.seek(SeekFrom::Start(4)).unwrap();
+        assert_eq!(
+            unanchored.apply_at(&mut archive, 8, &ApplyOptions::default()).unwrap_err().to_string(),
+            ApplyError::from(std::io::Error::new(std::io::ErrorKind::InvalidInput, "target is before the base offset")).to_string(),
+        );
+        assert_eq!(archive.position(), 4);
+    }
+
+    #[test]
+    fn window() {
+        let mut yadon = Yadon::new(Some(0), Some(24));
+        yadon.write_all(b"HDR").unwrap();
+        {
+            let mut window = yadon.window(8, 8);
+            window.write_all(b"abc").unwrap();
+            window.seek(SeekFrom::End(-2)).unwrap();
+            assert_eq!(window.write(b"xyz").unwrap(), 2);
+            assert!(window.seek(SeekFrom::Current(-9)).is_err());
+            {
+                // A window on a window is cut short at the end of the outer one.
+                let mut inner = window.window(4, 8);
+                assert_eq!(inner.len(), 4);
+                inner.seek(SeekFrom::Start(1)).unwrap();
+                inner.write_all(b"in").unwrap();
+                assert_eq!(inner.stream_position().unwrap(), 3);
+            }
+            // The outer window carries on from where it was, not where the inner one left the recording.
+            assert_eq!(window.write(b"!").unwrap(), 0);
+            window.seek(SeekFrom::Start(3)).unwrap();
+            window.write_all(b"d").unwrap();
+        }
+        yadon.seek(SeekFrom::Start(16)).unwrap();
+        yadon.write_all(b"tail").unwrap();
+        assert_eq!(yadon.materialize(Some(&[b'.'; 24])).unwrap(), b"HDR.....abcd.inytail....");
+
+        // The recording stays consistent, so applying it checks out.
+        let consistent = format!("{:?}", yadon.operations);
+        yadon.resimulate().unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), consistent);
+        let mut target = Cursor::new(vec![b'.'; 24]);
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.get_ref(), b"HDR.....abcd.inytail....");
+    }
+
+    #[test]
+    fn limited_target() {
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.write_all(b"in").unwrap();
+        yadon.seek(SeekFrom::Start(2)).unwrap();
+        yadon.write_all(b"side").unwrap();
+        let mut file = Cursor::new(vec![b'.'; 16]);
+        file.set_position(1);
+        {
+            let mut region = LimitedTarget::new(&mut file, 4, 6).restoring_position().unwrap();
+            yadon.apply(&mut region, true).unwrap();
+            assert_eq!(region.seek(SeekFrom::End(-1)).unwrap(), 5);
+            assert!(region.seek(SeekFrom::Current(-6)).is_err());
+        }
+        assert_eq!(file.get_ref(), b"....inside......");
+        assert_eq!(file.position(), 1);
+
+        // Writing past the end of the region is cut short, or fails, and never lands outside it.
+        let mut escaping = Yadon::new(Some(0), None);
+        escaping.seek(SeekFrom::Start(4)).unwrap();
+        escaping.write_all(b"escape").unwrap();
+        let mut file = Cursor::new(vec![b'.'; 16]);
+        assert!(matches!(
+            escaping.apply(&mut LimitedTarget::new(&mut file, 4, 6), true),
+            Err(ApplyError::NumBytesWrittenDiverge(_)),
+        ));
+        assert_eq!(file.get_ref(), b"........es......");
+        let mut file = Cursor::new(vec![b'.'; 16]);
+        let mut region = LimitedTarget::new(&mut file, 4, 6).with_overflow_policy(OverflowPolicy::Error);
+        match escaping.apply(&mut region, false) {
+            Err(ApplyError::Io(error)) => assert_eq!(error.kind(), std::io::ErrorKind::WriteZero),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(region.get_ref().get_ref(), &[b'.'; 16]);
+    }
+
+    #[test]
+    fn replay_log() {
+        let mut yadon = Yadon::new(Some(0), Some(8)).with_max_applies(Some(3));
+        yadon.write_all(b"frozen").unwrap();
+        let log = yadon.freeze();
+
+        // Clones share the operations and the apply limit, and can be applied from several threads at once.
+        let shared = log.clone();
+        assert!(std::ptr::eq(&log.operations[0], &shared.operations[0]));
+        std::thread::scope(|scope| {
+            for log in [&log, &shared] {
+                scope.spawn(move || {
+                    let mut target = Cursor::new(vec![0u8; 8]);
+                    log.apply(&mut target, true).unwrap();
+                    assert_eq!(&target.get_ref()[..6], b"frozen");
+                });
+            }
+        });
+        let handle = shared.apply_in_background(Cursor::new(vec![0u8; 8]), ApplyOptions::default());
+        assert_eq!(handle.join().unwrap().ops_applied, 1);
+        assert!(matches!(log.apply(&mut Cursor::new(vec![0u8; 8]), true), Err(ApplyError::ApplyLimitReached { .. })));
+
+        // Thawing a shared log copies it, carrying on from where it was frozen.
+        let mut thawed = log.thaw().unwrap();
+        thawed.write_all(b"!!").unwrap();
+        assert_eq!(thawed.applied_count(), 3);
+        assert_eq!(thawed.with_max_applies(None).materialize(None).unwrap(), b"frozen!!");
+        assert_eq!(shared.operations.len(), 1);
+        let unshared = shared.thaw().unwrap();
+        assert_eq!(unshared.operations.len(), 1);
+
+        #[derive(Debug)]
+        struct Nothing;
+        impl ApplyOp for Nothing {
+            fn simulate(&self, pos: u64, _: Option<u64>) -> SimResult {
+                SimResult { position: pos, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written: 0 })
+            }
+        }
+        let mut custom = Yadon::new(Some(0), None);
+        custom.record_custom(Nothing).unwrap();
+        let custom = custom.freeze();
+        let clone = custom.clone();
+        let custom = custom.thaw().unwrap_err();
+        drop(clone);
+        assert!(custom.thaw().is_ok());
+    }
+
+    #[test]
+    fn split_off() {
+        let mut yadon = Yadon::new(Some(2), Some(32)).with_generation_stamp(24, u64::MAX);
+        yadon.write_all(b"one").unwrap();
+        yadon.push_label("phase");
+        yadon.seek(SeekFrom::Current(2)).unwrap();
+        yadon.write_all(b"two").unwrap();
+        yadon.seek(SeekFrom::End(-12)).unwrap();
+        yadon.fill(b'!', 2).unwrap();
+        yadon.pop_label();
+        yadon.copy_within(2, 12, 2).unwrap();
+        let whole = |yadon: &Yadon| {
+            let mut target = Cursor::new(vec![b'.'; 32]);
+            yadon.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+            target.into_inner()
+        };
+        let expected = whole(&yadon);
+
+        // Applying the head, then the tail, to one buffer does what applying the whole did, wherever it's split.
+        let count = yadon.operations.len();
+        for index in 0..=count {
+            let mut head = yadon.translated(0).unwrap();
+            let tail = head.split_off(index);
+            assert_eq!((head.operations.len(), tail.operations.len()), (index, count - index));
+            let mut target = Cursor::new(vec![b'.'; 32]);
+            head.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+            tail.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+            assert_eq!(target.into_inner(), expected);
+        }
+
+        let tail = yadon.split_off(2);
+        assert_eq!(tail.start, Some(7));
+        assert_eq!((yadon.generation_stamp, tail.generation_stamp), (None, Some((24, u64::MAX))));
+        assert_eq!(yadon.labels().collect::<Vec<_>>(), vec![("phase", 1..2)]);
+        assert_eq!(tail.labels().collect::<Vec<_>>(), vec![("phase", 0..3)]);
+        let mut rejoined = yadon.compose(&tail).unwrap();
+        rejoined.write_all(b"?").unwrap();
+        assert_eq!(&whole(&rejoined)[12..16], b"on?.");
+
+        // Splitting past the end, or operations in a store, panics rather than splitting what isn't there.
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        assert!(catch_unwind(AssertUnwindSafe(|| rejoined.split_off(rejoined.operations.len() + 1))).is_err());
+        let mut stored = Yadon::new(Some(0), None).with_store(Vec::new()).unwrap();
+        stored.write_all(b"ab").unwrap();
+        stored.write_all(b"cd").unwrap();
+        for index in 0..=2 {
+            assert!(catch_unwind(AssertUnwindSafe(|| stored.split_off(index))).is_err());
+        }
+        assert_eq!(stored.operation_count(), 2);
+    }
+
+    #[test]
+    fn translate() {
+        let rom: Vec<u8> = (0..32).collect();
+        let mut yadon = Yadon::new(Some(0), Some(32)).with_generation_stamp(24, u64::MAX);
+        let crc32 = yadon.base_checksum(&mut Cursor::new(&rom), 0..4).unwrap();
+        yadon.require_base_checksum(0..4, crc32);
+        yadon.assert_bytes_at(8, &[8, 9]).unwrap();
+        yadon.write_all(b"ab").unwrap();
+        yadon.seek(SeekFrom::Start(10)).unwrap();
+        yadon.write_all(b"cd").unwrap();
+        yadon.seek(SeekFrom::Current(2)).unwrap();
+        yadon.write_all(b"ef").unwrap();
+        yadon.seek(SeekFrom::End(-4)).unwrap();
+        yadon.write_all(b"gh").unwrap();
+        yadon.copy_within(0, 20, 4).unwrap();
+        let mut reference = Cursor::new(rom.clone());
+        yadon.apply_readable(&mut reference, &ApplyOptions::default()).unwrap();
+
+        // Applied to a copy with a header before it, the translated recording leaves what was after the header as the
+        // recording leaves the copy without one.
+        let translated = yadon.translated(512).unwrap();
+        assert_eq!(translated.start, Some(512));
+        assert_eq!(translated.length, Some(544));
+        assert_eq!((translated.base_checksums[0].0.clone(), translated.generation_stamp), (512..516, Some((536, u64::MAX))));
+        let mut headered = Cursor::new([vec![0xffu8; 512], rom].concat());
+        translated.apply_readable(&mut headered, &ApplyOptions::default()).unwrap();
+        assert_eq!(&headered.get_ref()[..512], &[0xff; 512][..]);
+        assert_eq!(&headered.get_ref()[512..], &reference.get_ref()[..]);
+        yadon.translate(512).unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", translated.operations));
+        yadon.translate(-512).unwrap();
+        assert_eq!(yadon.start, Some(0));
+
+        // Landing below zero is an error naming the operation, and leaves the recording as it was.
+        let mut unstarted = Yadon::new(None, None);
+        unstarted.seek(SeekFrom::Start(100)).unwrap();
+        unstarted.seek(SeekFrom::Start(4)).unwrap();
+        assert_eq!(unstarted.translate(-8), Err(TranslateError::OutOfRange { index: 1 }));
+        assert!(matches!(unstarted.operations[0], WriteOperation::Seek(SeekFrom::Start(100), 100)));
+        assert_eq!(yadon.translate(-1), Err(TranslateError::FieldOutOfRange("start")));
+    }
+
+    #[test]
+    fn failed_apply_fill_too_much() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.fill(0, 8).unwrap(), 8);
+
+        let mut target = [0u8; 5];
+        let mut target_writer = Cursor::new(&mut target[..]);
+        match yadon.apply(&mut target_writer, true) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.expected, 8);
+                assert_eq!(diff.actual, 5);
+            },
+            res => {
+                panic!("Apply did not fail with a diverged write: {:?}", res);
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_stores_pattern_once() {
+        let pattern = [0xDE, 0xAD, 0xBE, 0xEF, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+        let mut yadon = Yadon::new(Some(0), Some(16 * 4096));
+        assert_eq!(yadon.write_repeated(&pattern, 4096).unwrap(), 16 * 4096);
+        assert_eq!(yadon.operations.len(), 1);
+
+        let mut target = EventLog::new(16 * 4096);
+        assert_eq!(yadon.apply(&mut target, true).unwrap(), 16 * 4096);
+        assert!(target.events.iter().all(|e| !matches!(e, Event::Write(n) if *n > FILL_CHUNK_SIZE)));
+        assert_eq!(target.inner.get_ref(), &pattern.repeat(4096));
+    }
+
+    #[test]
+    fn repeat_clamped_mid_repetition() {
+        let pattern = [1, 2, 3, 4, 5];
+        let mut now_target = [0u8; 24];
+        let mut now = Cursor::new(&mut now_target[..]);
+        now.seek(SeekFrom::Start(2)).unwrap();
+        let mut yadon = Yadon::new(Some(2), Some(24));
+        // 22 bytes of space: 4 whole repetitions, and 2 bytes of the fifth.
+        assert_eq!(now.write(&pattern.repeat(10)).unwrap(), 22);
+        assert_eq!(yadon.write_repeated(&pattern, 10).unwrap(), 22);
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap();
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[6]).unwrap(), 0);
+
+        let mut later_target = vec![0u8; 24];
+        let mut later_writer = Cursor::new(&mut later_target[..]);
+        assert_eq!(yadon.apply(&mut later_writer, true).unwrap(), 22);
+        assert_eq!(&later_target, &now_target);
+        assert_eq!(&later_target[20..], &[4, 5, 1, 2]);
+    }
+
+    #[test]
+    fn overflowing_repeat_rejected() {
+        // Only a hand-built recording, or one read back from a store, can hold a repeat this long.
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.operations.push(WriteOperation::Repeat { pattern: vec![1, 2], count: u64::MAX });
+        let invalid_input = |result: Result<usize, ApplyError>| match result {
+            Err(ApplyError::Io(error)) => error.kind() == std::io::ErrorKind::InvalidInput,
+            _ => false,
+        };
+        assert!(invalid_input(yadon.apply(&mut Cursor::new(vec![]), true)));
+        assert!(invalid_input(yadon.apply_to_slice(&mut [0; 4], CheckPolicy::None)));
+        let store = std::mem::take(&mut yadon.operations);
+        assert!(invalid_input(yadon.apply_store(&store, &mut Cursor::new(vec![]), &ApplyOptions::default())));
+        yadon.operations = store;
+        assert!(matches!(yadon.dirty_blocks(16), Err(ApplyError::Io(_))));
+        assert!(yadon.to_string().contains("repeat"));
+    }
+
+    #[test]
+    fn failed_apply_repeat_too_much() {
+        let mut yadon = Yadon::new(Some(0), Some(12));
+        assert_eq!(yadon.write_repeated(&[1, 2, 3], 4).unwrap(), 12);
+
+        let mut target = [0u8; 7];
+        let mut target_writer = Cursor::new(&mut target[..]);
+        match yadon.apply(&mut target_writer, true) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.expected, 12);
+                assert_eq!(diff.actual, 7);
+            },
+            res => {
+                panic!("Apply did not fail with a diverged write: {:?}", res);
+            }
+        }
+        assert_eq!(target, [1, 2, 3, 1, 2, 3, 1]);
+    }
+
+    #[test]
+    fn set_len_truncate_and_extend() {
+        let mut now = Cursor::new(vec![0u8; 16]);
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_multi_seek(&mut now, &mut yadon, SeekFrom::Start(12)).unwrap();
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[1, 2]).unwrap(), 2);
+
+        // Truncate below the current position; the position stays where it was.
+        now.set_len(8).unwrap();
+        yadon.set_len(8).unwrap();
+        assert_eq!(yadon.length, Some(8));
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::Current(0)).unwrap(), 14);
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(0)).unwrap(), 8);
+
+        // Extend past the old length, and seek relative to the new end.
+        now.set_len(24).unwrap();
+        yadon.set_len(24).unwrap();
+        assert_eq!(assert_multi_seek(&mut now, &mut yadon, SeekFrom::End(-3)).unwrap(), 21);
+        assert_eq!(assert_multi_write(&mut now, &mut yadon, &[3, 4, 5]).unwrap(), 3);
+
+        let mut later = Cursor::new(vec![0u8; 16]);
+        assert_eq!(yadon.apply_with_setlen(&mut later, &ApplyOptions::default()).unwrap(), 5);
+        assert_eq!(later.get_ref(), now.get_ref());
+        assert_eq!(later.get_ref().len(), 24);
+    }
+
+    #[test]
+    fn set_len_unsupported_by_plain_apply() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        yadon.set_len(4).unwrap();
+
+        let mut target = vec![0u8; 8];
+        let mut target_writer = Cursor::new(&mut target);
+        match yadon.apply(&mut target_writer, true) {
+            Err(ApplyError::UnsupportedOperation(name)) => assert_eq!(name, "set_len"),
+            res => panic!("Apply did not fail with an unsupported operation: {:?}", res),
+        }
+        // Nothing should have been written.
+        assert_eq!(target, vec![0u8; 8]);
+    }
+
+    #[test]
+    fn sync_barriers_replayed_in_order() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[0xAA; 4]).unwrap(), 4); // header
+        yadon.sync_barrier().unwrap();
+        assert_eq!(yadon.write(&[0xBB; 2]).unwrap(), 2); // commit record
+        yadon.sync_barrier().unwrap();
+
+        let mut target = EventLog::new(16);
+        yadon.apply_durable(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.events, vec![
+            Event::Seek(SeekFrom::Start(0)),
+            Event::Write(4),
+            Event::Sync,
+            Event::Write(2),
+            Event::Sync,
+            Event::Flush,
+        ]);
+    }
+
+    #[test]
+    fn sync_barrier_fallback() {
+        let mut yadon = Yadon::new(None, Some(16));
+        assert_eq!(yadon.write(&[1]).unwrap(), 1);
+        yadon.sync_barrier().unwrap();
+        assert_eq!(yadon.write(&[2]).unwrap(), 1);
+
+        let mut target = EventLog::new(16);
+        match yadon.apply(&mut target, true) {
+            Err(ApplyError::UnsupportedOperation(name)) => assert_eq!(name, "sync"),
+            res => panic!("Apply did not fail with an unsupported operation: {:?}", res),
+        }
+        assert!(target.events.is_empty());
+
+        yadon.apply_with_options(&mut target, &ApplyOptions {
+            sync_fallback: SyncFallback::Flush,
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(target.events, vec![Event::Write(1), Event::Flush, Event::Write(1), Event::Flush]);
+    }
+
+    #[test]
+    fn sync_barrier_on_file() {
+        let path = std::env::temp_dir().join(format!("yadon-sync-barrier-{}", std::process::id()));
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        yadon.sync_barrier().unwrap();
+        assert_eq!(yadon.write(&[4]).unwrap(), 1);
+        yadon.apply_durable(&mut file, &ApplyOptions::default()).unwrap();
+        drop(file);
+        assert_eq!(std::fs::read(&path).unwrap(), vec![1, 2, 3, 4]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn op_file_store() {
+        let record = |yadon: &mut Yadon| {
+            yadon.seek(SeekFrom::Start(2)).unwrap();
+            yadon.write_all(b"stored").unwrap();
+            yadon.fill(0xff, 3).unwrap();
+            yadon.seek(SeekFrom::End(-2)).unwrap();
+            yadon.write_all(b"!!").unwrap();
+            yadon.flush().unwrap();
+        };
+        let mut in_memory = Yadon::new(Some(0), Some(16));
+        record(&mut in_memory);
+        let expected = in_memory.materialize(None).unwrap();
+        let mut target = Cursor::new(vec![0u8; 16]);
+        assert_eq!(in_memory.apply_store(&in_memory.operations, &mut target, &ApplyOptions::default()).unwrap(), 11);
+        assert_eq!(target.get_ref(), &expected);
+
+        let path = std::env::temp_dir().join(format!("yadon-op-file-{}", std::process::id()));
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        yadon.seek(SeekFrom::Start(0)).unwrap();
+        let mut yadon = yadon.with_store(OpFile::create(&path).unwrap()).unwrap();
+        record(&mut yadon);
+        assert!(yadon.operations.is_empty());
+        assert_eq!(yadon.store().unwrap().len(), 7);
+        let mut target = Cursor::new(vec![0u8; 16]);
+        assert_eq!(yadon.apply_store(yadon.store().unwrap(), &mut target, &ApplyOptions::default()).unwrap(), 11);
+        assert_eq!(target.get_ref(), &expected);
+
+        // The file can be opened again, and applied from as it is.
+        let reopened = OpFile::open(&path).unwrap();
+        assert_eq!(reopened.len(), 7);
+        let mut operations = vec![];
+        reopened.for_each_operation(&mut |index, operation| {
+            operations.push(format!("{} {:?}", index, operation));
+            Ok(())
+        }).unwrap();
+        assert_eq!(operations[1], "1 Seek(Start(2), 2)");
+        let mut target = Cursor::new(vec![0u8; 16]);
+        in_memory.apply_store(&reopened, &mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.get_ref(), &expected);
+
+        // Operations the target can't apply are found before anything is written.
+        let mut resized = Yadon::new(Some(0), None).with_store(OpFile::create(&path).unwrap()).unwrap();
+        resized.write_all(b"abc").unwrap();
+        resized.set_len(2).unwrap();
+        let mut target = Cursor::new(vec![0u8; 4]);
+        assert!(matches!(
+            resized.apply_store(resized.store().unwrap(), &mut target, &ApplyOptions::default()),
+            Err(ApplyError::UnsupportedOperation("set_len")),
+        ));
+        assert_eq!(target.get_ref(), &[0; 4]);
+
+        // A custom operation the file refuses isn't recorded, and recording carries on after it.
+        #[derive(Debug)]
+        struct Skip;
+        impl ApplyOp for Skip {
+            fn simulate(&self, pos: u64, _: Option<u64>) -> SimResult {
+                SimResult { position: pos + 1, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.seek(SeekFrom::Current(1))?, bytes_written: 0 })
+            }
+        }
+        resized.seek(SeekFrom::Start(1)).unwrap();
+        let stored = resized.store().unwrap().len();
+        assert_eq!(resized.record_custom(Skip).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(resized.stream_position().unwrap(), 1);
+        assert_eq!(resized.store().unwrap().len(), stored + 1);
+
+        // A record cut short is found on opening.
+        let contents = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &contents[0..contents.len() - 1]).unwrap();
+        assert_eq!(OpFile::open(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn recording_into_store() {
+        /// Stores operations until it's full.
+        struct Full(Vec<WriteOperation>, usize);
+        impl OpSink for Full {
+            fn append(&mut self, operation: WriteOperation) -> std::io::Result<()> {
+                if self.0.len() == self.1 {
+                    return Err(std::io::Error::new(std::io::ErrorKind::StorageFull, "store is full"));
+                }
+                self.0.push(operation);
+                Ok(())
+            }
+
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+        }
+        impl OpStore for Full {
+            fn for_each_operation(&self, f: &mut dyn FnMut(usize, &WriteOperation) -> Result<(), ApplyError>) -> Result<(), ApplyError> {
+                self.0.for_each_operation(f)
+            }
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(4)).with_store(Full(vec![], 2)).unwrap();
+        yadon.write_all(b"ab").unwrap();
+        yadon.seek(SeekFrom::Start(3)).unwrap();
+        assert_eq!(yadon.write(b"c").unwrap_err().kind(), std::io::ErrorKind::StorageFull);
+        assert_eq!(yadon.flush().unwrap_err().kind(), std::io::ErrorKind::Other);
+        assert_eq!(yadon.store().unwrap().len(), 2);
+
+        // What reads `operations` fails rather than seeing none, and encoding reads the store back.
+        assert!(matches!(yadon.materialize(None), Err(MaterializeError::Apply(ApplyError::InStore))));
+        assert!(matches!(yadon.apply(&mut Cursor::new(vec![0u8; 4]), true), Err(ApplyError::InStore)));
+        assert!(matches!(yadon.apply_to_slice(&mut [0; 4], CheckPolicy::Strict), Err(ApplyError::InStore)));
+        assert!(matches!(yadon.apply_at(&mut Cursor::new(vec![0u8; 8]), 4, &ApplyOptions::default()), Err(ApplyError::InStore)));
+        let mut decoded = Yadon::from_bytes(&yadon.to_bytes()).unwrap();
+        assert_eq!(decoded.materialize(None).unwrap(), b"ab\0\0");
+        let mut target = Cursor::new(vec![0u8; 4]);
+        yadon.apply_store(yadon.store().unwrap(), &mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.get_ref(), b"ab\0\0");
+
+        // Rendering reads the store back too.
+        assert_eq!(yadon.to_string(), decoded.to_string());
+        let (mut script, mut decoded_script) = (String::new(), String::new());
+        yadon.to_script(&mut script).unwrap();
+        decoded.to_script(&mut decoded_script).unwrap();
+        assert_eq!(script, decoded_script);
+        #[cfg(feature = "json")]
+        {
+            assert_eq!(yadon.to_json_pretty(), decoded.to_json_pretty());
+            assert_eq!(serde_json::to_string(&yadon).unwrap(), serde_json::to_string(&decoded).unwrap());
+        }
+
+        // What needs the operations in `operations` fails.
+        assert_eq!(yadon.dry_run(4).unwrap_err(), DryRunError::InStore);
+        assert!(matches!(yadon.validate_against(&mut Cursor::new(vec![0u8; 4])), Err(ApplyError::InStore)));
+        assert_eq!(yadon.compose(&decoded).unwrap_err(), ComposeError::InStore);
+        assert_eq!(decoded.compose(&yadon).unwrap_err(), ComposeError::InStore);
+        assert_eq!(Yadon::flatten(&decoded, &yadon).unwrap_err(), ComposeError::InStore);
+        assert_eq!(decoded.extend_from(&yadon).unwrap_err(), MergeError::InStore);
+        assert_eq!(yadon.translate(1).unwrap_err(), TranslateError::InStore);
+        assert_eq!(yadon.translated(1).unwrap_err(), TranslateError::InStore);
+
+        // Encoding reads the store once, and fails for what can't be encoded without writing anything.
+        #[derive(Debug)]
+        struct Nothing;
+        impl ApplyOp for Nothing {
+            fn simulate(&self, pos: u64, _: Option<u64>) -> SimResult {
+                SimResult { position: pos, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written: 0 })
+            }
+        }
+        let mut custom = Yadon::new(Some(0), None).with_store(Vec::new()).unwrap();
+        custom.record_custom(Nothing).unwrap();
+        let mut encoded = vec![];
+        assert_eq!(custom.to_writer(&mut encoded).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert!(encoded.is_empty());
+    }
+
+    #[test]
+    fn copy_within_overlapping() {
+        let original: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let mut expected = original.clone();
+        expected.copy_within(1000..201_000, 50_000);
+        expected[250_000] = 0xEE;
+        expected.copy_within(60_000..160_000, 10);
+        expected[100_010] = 0xEF;
+
+        let mut yadon = Yadon::new(Some(0), Some(original.len() as u64));
+        assert_eq!(yadon.copy_within(1000, 50_000, 200_000).unwrap(), 200_000);
+        assert_eq!(yadon.write(&[0xEE]).unwrap(), 1);
+        assert_eq!(yadon.copy_within(60_000, 10, 100_000).unwrap(), 100_000);
+        assert_eq!(yadon.stream_position().unwrap(), 100_010);
+        assert_eq!(yadon.write(&[0xEF]).unwrap(), 1);
+
+        let mut target = Cursor::new(original);
+        assert_eq!(yadon.apply_readable(&mut target, &ApplyOptions::default()).unwrap(), 300_002);
+        assert!(target.get_ref() == &expected);
+    }
+
+    #[test]
+    fn copy_within_clamped_to_length() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.write(&[1, 2, 3, 4]).unwrap(), 4);
+        assert_eq!(yadon.copy_within(0, 6, 4).unwrap(), 2);
+        assert_eq!(yadon.stream_position().unwrap(), 8);
+        assert_eq!(yadon.copy_within(4, 0, 8).map_err(|e| e.kind()), Err(std::io::ErrorKind::InvalidInput));
+
+        let mut target = Cursor::new(vec![0u8; 8]);
+        yadon.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.get_ref(), &[1, 2, 3, 4, 0, 0, 1, 2]);
+
+        match yadon.apply(&mut target, true) {
+            Err(ApplyError::UnsupportedOperation(name)) => assert_eq!(name, "copy_within"),
+            res => panic!("Apply did not fail with an unsupported operation: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn rejected_copy_within_leaves_position() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        yadon.reserve_region(0..4);
+        yadon.write_all(b"ab").unwrap();
+        assert_eq!(yadon.copy_within(0, 10, 2).map_err(|e| e.kind()), Err(std::io::ErrorKind::InvalidInput));
+        assert_eq!(yadon.stream_position().unwrap(), 2);
+        yadon.write_all(b"cd").unwrap();
+        assert_eq!(yadon.materialize(Some(&[0; 16])).unwrap()[..4], b"abcd"[..]);
+    }
+
+    #[test]
+    fn assert_bytes_before_patch() {
+        let rom = vec![0x10, 0x20, 0x30, 0x40, 0x50, 0x60];
+        let mut yadon = Yadon::new(Some(0), Some(6));
+        assert_eq!(yadon.seek(SeekFrom::Start(2)).unwrap(), 2);
+        yadon.assert_bytes_at(1, &[0x20, 0x30, 0x40]).unwrap();
+        assert_eq!(yadon.stream_position().unwrap(), 2);
+        assert_eq!(yadon.write(&[0xFF]).unwrap(), 1);
+        assert_eq!(yadon.assert_bytes_at(4, &[0; 4]).map_err(|e| e.kind()), Err(std::io::ErrorKind::InvalidInput));
+
+        let mut target = Cursor::new(rom.clone());
+        yadon.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.get_ref(), &[0x10, 0x20, 0xFF, 0x40, 0x50, 0x60]);
+
+        // The wrong revision fails at the first differing byte.
+        let mut target = Cursor::new(vec![0x10, 0x20, 0x31, 0x41, 0x50, 0x60]);
+        match yadon.apply_readable(&mut target, &ApplyOptions::default()) {
+            Err(ApplyError::PreconditionFailed { offset, expected, actual, .. }) => {
+                assert_eq!(offset, 2);
+                assert_eq!(expected, 0x30);
+                assert_eq!(actual, Some(0x31));
+            },
+            res => panic!("Apply did not fail with a precondition failure: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn assert_bytes_checked_first() {
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        yadon.assert_bytes_at(4, &[5, 6, 7]).unwrap();
+        assert_eq!(yadon.write(&[3]).unwrap(), 1);
+
+        let mut target = Cursor::new(vec![0, 0, 0, 0, 5, 6]);
+        target.seek(SeekFrom::Start(1)).unwrap();
+        match yadon.check_preconditions(&mut target) {
+            Err(ApplyError::PreconditionFailed { offset, expected, actual, .. }) => {
+                assert_eq!(offset, 6);
+                assert_eq!(expected, 7);
+                assert_eq!(actual, None);
+            },
+            res => panic!("Check did not fail with a precondition failure: {:?}", res),
+        }
+
+        let options = ApplyOptions {
+            check_preconditions_first: true,
+            ..Default::default()
+        };
+        assert!(matches!(yadon.apply_readable(&mut target, &options), Err(ApplyError::PreconditionFailed { .. })));
+        assert_eq!(target.get_ref(), &[0, 0, 0, 0, 5, 6]);
+
+        target.get_mut().push(7);
+        target.seek(SeekFrom::Start(1)).unwrap();
+        yadon.check_preconditions(&mut target).unwrap();
+        assert_eq!(target.stream_position().unwrap(), 1);
+        assert_eq!(yadon.apply_readable(&mut target, &options).unwrap(), 3);
+        assert_eq!(target.get_ref(), &[1, 2, 3, 0, 5, 6, 7]);
+    }
+
+    #[test]
+    fn custom_operation_checked() {
+        /// Writes a byte at a fixed offset, leaving the position after it.
+        #[derive(Debug)]
+        struct Poke(u64, u8);
+
+        impl ApplyOp for Poke {
+            fn simulate(&self, _pos: u64, _len: Option<u64>) -> SimResult {
+                SimResult { position: self.0 + 1, bytes_written: 1 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                target.seek(SeekFrom::Start(self.0))?;
+                let bytes_written = target.write(&[self.1])? as u64;
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written })
+            }
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.record_custom(Poke(6, 0xAB)).unwrap(), SimResult { position: 7, bytes_written: 1 });
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 1);
+        assert!(format!("{:?}", yadon.operations[0]).contains("Poke(6, 171)"));
+
+        let mut target = [0u8; 8];
+        assert_eq!(yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap(), 2);
+        assert_eq!(target, [0, 0, 0, 0, 0, 0, 0xAB, 1]);
+
+        let mut target = [0u8; 4];
+        match yadon.apply(&mut Cursor::new(&mut target[..]), true) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.expected, 1);
+                assert_eq!(diff.actual, 0);
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+
+        // The recording's restrictions apply to custom operations too.
+        let mut append_only = Yadon::new_append_only(Some(0), Some(8));
+        append_only.write_all(&[0; 7]).unwrap();
+        assert_eq!(append_only.record_custom(Poke(2, 0xAB)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        let mut reserved = Yadon::new(Some(0), Some(8));
+        reserved.reserve_region(6..7);
+        assert_eq!(reserved.record_custom(Poke(6, 0xAB)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        let mut denied = Yadon::new(Some(0), Some(8));
+        denied.deny_overwrite = true;
+        assert_eq!(denied.record_custom(Poke(6, 0xAB)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        for rejected in [&append_only, &reserved, &denied] {
+            assert!(!rejected.operations.iter().any(|operation| matches!(operation, WriteOperation::Custom(..))));
+        }
+        assert_eq!(append_only.stream_position().unwrap(), 7);
+    }
+
+    #[test]
+    fn redundant_seeks_elided() {
+        let mut yadon = Yadon::new(None, Some(16));
+        assert_eq!(yadon.stream_position().unwrap(), 0);
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        assert_eq!(yadon.stream_position().unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(8)).unwrap(), 8);
+        assert_eq!(yadon.write(&[2; 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::Current(-2)).unwrap(), 8);
+        assert_eq!(yadon.seek(SeekFrom::End(-8)).unwrap(), 8);
+        assert_eq!(yadon.stream_position().unwrap(), 8);
+
+        let options = ApplyOptions {
+            elide_seeks: true,
+            ..Default::default()
+        };
+        let mut target = EventLog::new(16);
+        let report = yadon.apply_report(&mut target, &options).unwrap();
+        assert_eq!(report.seeks_elided, 3);
+        assert_eq!(report.seeks_issued, 4);
+        assert!(report.to_string().starts_with("applied 9 operations (6 bytes written, 4 seeks, 3 elided), ending at position 8"));
+        let seeks: Vec<SeekFrom> = target.events.iter().filter_map(|event| match event {
+            Event::Seek(pos) => Some(*pos),
+            _ => None,
+        }).collect();
+        // The position isn't known until the first seek. The last seek is `apply_report()` finding the final position.
+        assert_eq!(seeks, vec![
+            SeekFrom::Current(0), SeekFrom::Start(8), SeekFrom::Current(-2), SeekFrom::End(-8), SeekFrom::Current(0),
+        ]);
+        assert_eq!(&target.inner.get_ref()[0..10], &[1, 1, 1, 1, 0, 0, 0, 0, 2, 2]);
+    }
+
+    #[cfg(feature = "track-callers")]
+    #[test]
+    fn divergence_reports_location() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        let write_line = line!() + 1;
+        assert_eq!(yadon.write(&[3; 6]).unwrap(), 6);
+        assert_eq!(yadon.location_of(1).unwrap().line(), write_line);
+        assert_eq!(yadon.location_of(1).unwrap().file(), file!());
+        assert!(format!("{:?}", yadon).contains(&format!("line: {}", write_line)));
+
+        let mut target = [0u8; 4];
+        match yadon.apply(&mut Cursor::new(&mut target[..]), true) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.location.unwrap().line(), write_line);
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+    }
+
+    /// A recording with an operation of every kind which can be serialized, and every option set.
+    fn serializable_recording() -> Yadon {
+        let mut yadon = Yadon::new(Some(2), None)
+            .with_overflow_policy(OverflowPolicy::Error)
+            .with_length_mode(LengthMode::Growable)
+            .with_max_applies(Some(3))
+            .with_generation_stamp(100, 7)
+            .with_gap_fill(0xee);
+        yadon.defer_end_seeks = true;
+        yadon.require_base_checksum(0..8, 0x1234);
+        yadon.reserve_region(0..128);
+        yadon.deny_overwrite = true;
+        yadon.push_label("header");
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.fill(4, 3).unwrap(), 3);
+        assert_eq!(yadon.write_repeated(&[5, 6], 2).unwrap(), 4);
+        yadon.pop_label();
+        yadon.deny_overwrite = false;
+        assert_eq!(yadon.seek(SeekFrom::End(-4)).unwrap(), 8);
+        assert_eq!(yadon.write(&[7]).unwrap(), 1);
+        assert_eq!(yadon.seek(SeekFrom::Current(1)).unwrap(), 10);
+        assert_eq!(yadon.seek(SeekFrom::Start(16)).unwrap(), 16);
+        yadon.flush().unwrap();
+        assert_eq!(yadon.copy_within(2, 20, 4).unwrap(), 4);
+        yadon.assert_bytes_at(0, &[0]).unwrap();
+        yadon.sync_barrier().unwrap();
+        yadon.set_len(70).unwrap();
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).unwrap(), 68);
+        yadon.push_label("tail");
+        assert_eq!(yadon.write(&[9]).unwrap(), 1);
+        yadon
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let json = serde_json::to_string(&serializable_recording()).unwrap();
+        let mut cbor = Vec::new();
+        ciborium::into_writer(&serializable_recording(), &mut cbor).unwrap();
+        let from_json: Yadon = serde_json::from_str(&json).unwrap();
+        let from_cbor: Yadon = ciborium::from_reader(&cbor[..]).unwrap();
+
+        for mut loaded in [from_json, from_cbor] {
+            let mut original = serializable_recording();
+            assert_eq!(format!("{:?}", loaded.operations), format!("{:?}", original.operations));
+            assert_eq!((loaded.start, loaded.length), (Some(2), Some(70)));
+            assert_eq!((loaded.defer_end_seeks, loaded.append_only, loaded.deny_overwrite), (true, false, false));
+            assert_eq!(loaded.written_extents().collect::<Vec<_>>(), vec![2..12]);
+            assert_eq!(loaded.overflow_policy(), OverflowPolicy::Error);
+            assert_eq!(loaded.length_mode(), LengthMode::Growable);
+            assert_eq!(loaded.max_applies(), Some(3));
+            assert_eq!(loaded.generation_stamp, Some((100, 7)));
+            assert_eq!(loaded.base_checksums, vec![(0..8, 0x1234)]);
+            assert_eq!(loaded.gap_fill(), 0xee);
+            assert_eq!(loaded.labels().collect::<Vec<_>>(), original.labels().collect::<Vec<_>>());
+            assert_eq!(loaded.location_of(0), None);
+            assert_eq!(
+                (loaded.virtual_position, loaded.end_unresolved, loaded.written_end),
+                (original.virtual_position, original.end_unresolved, original.written_end),
+            );
+
+            // Recording carries on as it would have, under the label which was still pushed.
+            assert_eq!(loaded.write(&[8, 8]).unwrap(), original.write(&[8, 8]).unwrap());
+            assert_eq!(loaded.stream_position().unwrap(), 71);
+            assert_eq!(loaded.label_of(loaded.operations.len() - 2).map(|label| &**label), Some("tail"));
+            loaded.seek(SeekFrom::Start(127)).unwrap();
+            assert_eq!(loaded.write(&[1, 2]).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_inconsistent_recordings() {
+        let mut yadon = Yadon::new(Some(4), None);
+        yadon.operations.push(crate::WriteOperation::Seek(SeekFrom::Current(2), 5));
+        let json = serde_json::to_string(&yadon).unwrap();
+        let error = serde_json::from_str::<Yadon>(&json).unwrap_err();
+        assert!(error.to_string().starts_with("operation 0 expects Current(2) to end up at 5, which it can't from 4"));
+
+        let mut yadon = Yadon::new(Some(4), None);
+        yadon.operations.push(crate::WriteOperation::Write(vec![1, 2], 3));
+        let json = serde_json::to_string(&yadon).unwrap();
+        assert!(serde_json::from_str::<Yadon>(&json).unwrap_err().to_string().starts_with("operation 0 expects to write 3"));
+
+        // Custom operations can't be serialized.
+        #[derive(Debug)]
+        struct Nothing;
+        impl ApplyOp for Nothing {
+            fn simulate(&self, pos: u64, _: Option<u64>) -> SimResult {
+                SimResult { position: pos, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written: 0 })
+            }
+        }
+        let mut yadon = Yadon::new(Some(4), None);
+        yadon.record_custom(Nothing).unwrap();
+        assert!(serde_json::to_string(&yadon).is_err());
+    }
+
+    #[test]
+    fn wire_format_is_pinned() {
+        let mut yadon = Yadon::new(Some(2), None);
+        yadon.require_base_checksum(0..4, 0x1122_3344);
+        yadon.labeled_scope("hdr", |yadon| yadon.write(&[1, 2, 3])).unwrap();
+        assert_eq!(yadon.seek(SeekFrom::Current(-1)).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(300)).unwrap(), 300);
+        assert_eq!(yadon.fill(0xaa, 2).unwrap(), 2);
+        yadon.flush().unwrap();
+        let encoded: &[u8] = &[
+            b'Y', b'A', b'D', b'N', 1,
+            1, 2, // start
+            0, // length
+            0, 0, 0, 0, 0, // flags, gap fill, apply count, apply limit, generation stamp
+            1, 0, 4, 0x44, 0x33, 0x22, 0x11, // base checksums
+            0, 0, // written extents, reserved regions
+            0, 1, 3, b'h', b'd', b'r', 0, 1, // labels
+            5, // operations
+            1, 4, 3, 1, 2, 3,
+            2, 3, 2, 1, 4,
+            2, 5, 0, 0xac, 0x02, 0xac, 0x02,
+            5, 2, 0xaa, 2,
+            4, 0,
+        ];
+        assert_eq!(yadon.to_bytes(), encoded);
+        let decoded = Yadon::from_bytes(encoded).unwrap();
+        assert_eq!(format!("{:?}", decoded.operations), format!("{:?}", yadon.operations));
+        assert_eq!(decoded.to_bytes(), encoded);
+    }
+
+    #[test]
+    fn wire_round_trip() {
+        let mut original = serializable_recording();
+        let mut encoded = Vec::new();
+        original.to_writer(&mut encoded).unwrap();
+        assert_eq!(encoded, original.to_bytes());
+        let mut decoded = Yadon::from_reader(&encoded[..]).unwrap();
+        assert_eq!(format!("{:?}", decoded.operations), format!("{:?}", original.operations));
+        assert_eq!((decoded.start, decoded.length), (Some(2), Some(70)));
+        assert_eq!((decoded.defer_end_seeks, decoded.append_only, decoded.deny_overwrite), (true, false, false));
+        assert_eq!(decoded.written_extents().collect::<Vec<_>>(), vec![2..12]);
+        assert_eq!(decoded.overflow_policy(), OverflowPolicy::Error);
+        assert_eq!(decoded.length_mode(), LengthMode::Growable);
+        assert_eq!(decoded.max_applies(), Some(3));
+        assert_eq!(decoded.generation_stamp, Some((100, 7)));
+        assert_eq!(decoded.base_checksums, vec![(0..8, 0x1234)]);
+        assert_eq!(decoded.gap_fill(), 0xee);
+        assert_eq!(decoded.labels().collect::<Vec<_>>(), original.labels().collect::<Vec<_>>());
+        assert_eq!(
+            (decoded.virtual_position, decoded.end_unresolved, decoded.written_end),
+            (original.virtual_position, original.end_unresolved, original.written_end),
+        );
+        assert_eq!(decoded.write(&[8]).unwrap(), original.write(&[8]).unwrap());
+        assert_eq!(decoded.pop_label(), original.pop_label());
+        assert_eq!(decoded.to_bytes(), original.to_bytes());
+    }
+
+    #[test]
+    fn wire_rejects_bad_input() {
+        let encoded = serializable_recording().to_bytes();
+        for len in 0..encoded.len() {
+            assert!(Yadon::from_bytes(&encoded[0..len]).is_err(), "decoded {} of {} bytes", len, encoded.len());
+        }
+        assert!(matches!(Yadon::from_bytes(b"YODN\x01"), Err(DecodeError::BadMagic)));
+        assert!(matches!(Yadon::from_bytes(b"YADN\x02"), Err(DecodeError::UnsupportedVersion(2))));
+        assert!(matches!(Yadon::from_bytes(b"YADN\x01\x01"), Err(DecodeError::Truncated { offset: 6 })));
+        assert!(matches!(
+            Yadon::from_bytes(b"YADN\x01\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\x02"),
+            Err(DecodeError::Invalid { offset: 6, .. }),
+        ));
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(Yadon::from_bytes(&trailing), Err(DecodeError::TrailingBytes { offset }) if offset == encoded.len()));
+
+        // The last operation is a one byte write, taking four bytes with its tag and length.
+        let mut unknown = encoded.clone();
+        let tag_offset = encoded.len() - 4;
+        unknown[tag_offset] = 0x7f;
+        assert!(matches!(Yadon::from_bytes(&unknown), Err(DecodeError::UnknownTag { tag: 0x7f, offset }) if offset == tag_offset));
+
+        let mut inconsistent = Yadon::new(Some(4), None);
+        inconsistent.operations.push(crate::WriteOperation::Write(vec![1, 2], 3));
+        assert!(matches!(Yadon::from_bytes(&inconsistent.to_bytes()), Err(DecodeError::Inconsistent(_))));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip() {
+        let mut original = serializable_recording();
+        let json = original.to_json_pretty();
+        assert!(json.contains("\"op\": \"write\""));
+        assert!(json.contains("\"current\": 1"));
+        let mut loaded = Yadon::from_json(&json).unwrap();
+        assert_eq!(format!("{:?}", loaded.operations), format!("{:?}", original.operations));
+        assert_eq!((loaded.start, loaded.length), (Some(2), Some(70)));
+        assert_eq!((loaded.defer_end_seeks, loaded.append_only, loaded.deny_overwrite), (true, false, false));
+        assert_eq!(loaded.written_extents().collect::<Vec<_>>(), vec![2..12]);
+        assert_eq!(loaded.reserved_regions.iter().collect::<Vec<_>>(), vec![0..128]);
+        assert_eq!(loaded.overflow_policy(), OverflowPolicy::Error);
+        assert_eq!(loaded.length_mode(), LengthMode::Growable);
+        assert_eq!(loaded.max_applies(), Some(3));
+        assert_eq!(loaded.generation_stamp, Some((100, 7)));
+        assert_eq!(loaded.base_checksums, vec![(0..8, 0x1234)]);
+        assert_eq!(loaded.gap_fill(), 0xee);
+        assert_eq!(loaded.labels().collect::<Vec<_>>(), original.labels().collect::<Vec<_>>());
+        assert_eq!(
+            (loaded.virtual_position, loaded.end_unresolved, loaded.written_end),
+            (original.virtual_position, original.end_unresolved, original.written_end),
+        );
+        assert_eq!(loaded.write(&[8]).unwrap(), original.write(&[8]).unwrap());
+        assert_eq!(loaded.pop_label(), original.pop_label());
+        assert_eq!(loaded.to_json_pretty(), original.to_json_pretty());
+
+        // Long data is broken into lines, and can be cut short with a marker, which can't be read back.
+        let mut long = Yadon::new(Some(0), None);
+        long.write(&[0xab; 100]).unwrap();
+        let lines = ["ab".repeat(32), "ab".repeat(32), "ab".repeat(32), "ab".repeat(4)];
+        let json = long.to_json_pretty();
+        assert!(lines.iter().all(|line| json.contains(&format!("\"{}\"", line))));
+        assert_eq!(format!("{:?}", Yadon::from_json(&json).unwrap().operations), format!("{:?}", long.operations));
+        let preview = long.to_json_pretty_with_options(&crate::JsonOptions { data_preview_limit: Some(40) });
+        assert!(preview.contains(&format!("\"{}\"", "ab".repeat(8))));
+        assert_eq!(preview.matches(&"ab".repeat(32)).count(), 1);
+        assert!(preview.contains("\"omitted_bytes\": 60"));
+        assert!(Yadon::from_json(&preview).unwrap_err().to_string().contains("60 bytes left out"));
+    }
+
+    #[test]
+    fn script_round_trip() {
+        let mut original = serializable_recording();
+        original.write(b"say \"hi\" \\").unwrap();
+        let mut script = String::new();
+        original.to_script(&mut script).unwrap();
+        assert!(script.contains("\ndeferred_seek current 0x1\n"));
+        assert!(script.contains("\nwrite hex \"01 02 03\"\n"));
+        assert!(script.contains("\nwrite \"say \\\"hi\\\" \\\\\"\n"));
+        assert!(script.contains("\nlabel \"header\"\n"));
+        let mut loaded = Yadon::from_script(&script).unwrap();
+        assert_eq!(format!("{:?}", loaded.operations), format!("{:?}", original.operations));
+        assert_eq!((loaded.start, loaded.length), (Some(2), Some(79)));
+        assert_eq!((loaded.defer_end_seeks, loaded.append_only, loaded.deny_overwrite), (true, false, false));
+        assert_eq!(loaded.written_extents().collect::<Vec<_>>(), vec![2..12]);
+        assert_eq!(loaded.overflow_policy(), OverflowPolicy::Error);
+        assert_eq!(loaded.length_mode(), LengthMode::Growable);
+        assert_eq!(loaded.max_applies(), Some(3));
+        assert_eq!(loaded.generation_stamp, Some((100, 7)));
+        assert_eq!(loaded.base_checksums, vec![(0..8, 0x1234)]);
+        assert_eq!(loaded.gap_fill(), 0xee);
+        assert_eq!(loaded.labels().collect::<Vec<_>>(), original.labels().collect::<Vec<_>>());
+        assert_eq!(
+            (loaded.virtual_position, loaded.end_unresolved, loaded.written_end),
+            (original.virtual_position, original.end_unresolved, original.written_end),
+        );
+        assert_eq!(loaded.write(&[8]).unwrap(), original.write(&[8]).unwrap());
+        assert_eq!(loaded.pop_label(), original.pop_label());
+        let (mut reloaded, mut expected) = (String::new(), String::new());
+        loaded.to_script(&mut reloaded).unwrap();
+        original.to_script(&mut expected).unwrap();
+        assert_eq!(reloaded, expected);
+
+        // Written by hand, with comments, and with the expected results left to the simulation.
+        let script = "
+            # A header, then a table.
+            start 4   # where the cursor starts
+            length 0x40
+            seek start 0x10
+            write hex \"DE AD be ef\"
+            seek current -2
+            write \"\\x00#\\t\"
+            seek end -0x8 at 0x38
+            fill 0xff 4
+            repeat 2 hex \"0102\"
+            copy 0x10 0x20 3
+            assert 0 \"\"
+            set_len 0x30
+            sync
+            flush
+        ";
+        let yadon = Yadon::from_script(script).unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            WriteOperation::Seek(SeekFrom::Start(16), 16),
+            WriteOperation::Write(vec![0xde, 0xad, 0xbe, 0xef], 4),
+            WriteOperation::Seek(SeekFrom::Current(-2), 18),
+            WriteOperation::Write(vec![0, b'#', b'\t'], 3),
+            WriteOperation::Seek(SeekFrom::End(-8), 56),
+            WriteOperation::Fill { byte: 0xff, len: 4 },
+            WriteOperation::Repeat { pattern: vec![1, 2], count: 2 },
+            WriteOperation::CopyWithin { src: 16, dst: 32, len: 3 },
+            WriteOperation::AssertBytes { offset: 0, expected: vec![] },
+            WriteOperation::SetLen(48),
+            WriteOperation::Sync,
+            WriteOperation::Flush,
+        ]));
+        assert_eq!(yadon.virtual_position, Some(35));
+        assert_eq!((yadon.start, yadon.length), (Some(4), Some(64)));
+    }
+
+    #[test]
+    fn script_errors() {
+        let error = |script: &str| {
+            let error = Yadon::from_script(script).unwrap_err();
+            (error.line, error.column, error.message)
+        };
+        assert_eq!(error("flush\n  frobnicate 3"), (2, 3, "unknown directive".to_string()));
+        assert_eq!(error("seek sideways 3"), (1, 6, "expected `start`, `current` or `end`".to_string()));
+        assert_eq!(error("seek end -4"), (1, 12, "expected `at`".to_string()));
+        assert_eq!(error("fill 0x100 2"), (1, 6, "expected a byte".to_string()));
+        assert_eq!(error("fill 1 2 3"), (1, 10, "unexpected extra token".to_string()));
+        assert_eq!(error("write \"abc"), (1, 7, "string isn't closed".to_string()));
+        assert_eq!(error("write \"a\\q\""), (1, 9, "unknown escape".to_string()));
+        assert_eq!(error("write hex \"abc\""), (1, 11, "odd number of hex digits".to_string()));
+        assert_eq!(error("written 4..4"), (1, 9, "range is empty".to_string()));
+        // Problems the simulation finds are reported where the operation starts.
+        assert_eq!(
+            error("start 0\nwrite \"ab\"\n    seek current -3"),
+            (3, 5, "operation 1 would overflow the position".to_string()),
+        );
+        assert_eq!(
+            Yadon::from_script("start 0\n\tseek current -1").unwrap_err().to_string(),
+            "line 2, column 2: operation 0 would overflow the position",
+        );
+
+        #[derive(Debug)]
+        struct Nothing;
+        impl ApplyOp for Nothing {
+            fn simulate(&self, pos: u64, _: Option<u64>) -> SimResult {
+                SimResult { position: pos, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written: 0 })
+            }
+        }
+        let mut yadon = Yadon::new(Some(4), None);
+        yadon.record_custom(Nothing).unwrap();
+        let mut script = String::new();
+        yadon.to_script(&mut script).unwrap();
+        assert_eq!(script, "start 0x4\ncustom \"Nothing\" # ends at 0x4, having written 0\n");
+        assert_eq!(error(&script), (2, 1, "custom operations can't be read back".to_string()));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_fills_in_expectations() {
+        let json = r#"{
+            "start": 4,
+            "length": null,
+            "operations": [
+                { "op": "write", "data": { "hex": ["01 02", "0304"] } },
+                { "op": "seek", "pos": { "current": -2 } },
+                { "op": "seek", "pos": { "start": 1 } },
+                { "op": "fill", "byte": 255, "len": 2 }
+            ]
+        }"#;
+        let mut yadon = Yadon::from_json(json).unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            crate::WriteOperation::Write(vec![1, 2, 3, 4], 4),
+            crate::WriteOperation::Seek(SeekFrom::Current(-2), 6),
+            crate::WriteOperation::Seek(SeekFrom::Start(1), 1),
+            crate::WriteOperation::Fill { byte: 0xff, len: 2 },
+        ]));
+        assert_eq!(yadon.stream_position().unwrap(), 3);
+
+        let rejected = [
+            (json.replace(r#""pos": { "start": 1 } }"#, r#""pos": { "start": 1 }, "expected": 2 }"#), "expects"),
+            (json.replace(r#""current": -2 }"#, r#""end": -2 }"#), "needs its expected position"),
+            (json.replace("0304", "030"), "odd number of hex digits"),
+            (json.replace("0304", "03xy"), "in its hex"),
+            (json.replace(r#""byte""#, r#""bite""#), "unknown field"),
+        ];
+        for (json, reason) in &rejected {
+            let error = Yadon::from_json(json).unwrap_err().to_string();
+            assert!(error.contains(reason), "{:?} doesn't say {:?}", error, reason);
+        }
+    }
+
+    /// Applies an IPS patch to `image`, written from the format's description rather than sharing anything with
+    /// `Yadon::to_ips()` or `Yadon::from_ips()`, growing the image as needed and resizing it if the patch says to, as
+    /// patchers do.
+    fn apply_ips(mut image: Vec<u8>, patch: &[u8]) -> Vec<u8> {
+        assert_eq!(&patch[0..5], b"PATCH");
+        let mut rest = &patch[5..];
+        while &rest[0..3] != b"EOF" {
+            let offset = u32::from_be_bytes([0, rest[0], rest[1], rest[2]]) as usize;
+            let len = u16::from_be_bytes([rest[3], rest[4]]) as usize;
+            let (data, record_len) = if len == 0 {
+                let count = u16::from_be_bytes([rest[5], rest[6]]) as usize;
+                assert!(count > 0, "empty RLE record at {:#x}", offset);
+                (vec![rest[7]; count], 8)
+            } else {
+                (rest[5..5 + len].to_vec(), 5 + len)
+            };
+            if image.len() < offset + data.len() {
+                image.resize(offset + data.len(), 0);
+            }
+            image[offset..offset + data.len()].copy_from_slice(&data);
+            rest = &rest[record_len..];
+        }
+        match &rest[3..] {
+            [] => {},
+            &[a, b, c] => image.resize(u32::from_be_bytes([0, a, b, c]) as usize, 0),
+            _ => panic!("bytes after EOF"),
+        }
+        image
+    }
+
+    #[test]
+    fn ips_export() {
+        // Overlapping writes, a long run, and a write longer than a record can hold.
+        let mut yadon = Yadon::new(Some(0x100), None);
+        yadon.write_all(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        yadon.seek(SeekFrom::Start(0x104)).unwrap();
+        yadon.fill(0xaa, 300).unwrap();
+        yadon.seek(SeekFrom::Start(0x1000)).unwrap();
+        let long: Vec<u8> = (0..70_000u32).map(|i| (i % 251) as u8).collect();
+        yadon.write_all(&long).unwrap();
+        let patch = yadon.to_ips().unwrap();
+        let mut expected = b"PATCH\x00\x01\x00\x00\x04\x01\x02\x03\x04\x00\x01\x04\x00\x00\x01\x2c\xaa".to_vec();
+        expected.extend_from_slice(b"\x00\x10\x00\xff\xff");
+        expected.extend_from_slice(&long[..0xffff]);
+        expected.extend_from_slice(&[0x01, 0x0f, 0xff, 0x11, 0x71]);
+        expected.extend_from_slice(&long[0xffff..]);
+        expected.extend_from_slice(b"EOF");
+        assert_eq!(patch, expected);
+        let base = vec![0x55; 0x20000];
+        assert_eq!(apply_ips(base.clone(), &patch), yadon.materialize(Some(&base)).unwrap());
+
+        // A record is never started where its offset would read as EOF.
+        let mut yadon = Yadon::new(Some(0x454f40), None);
+        yadon.write_all(&[1, 2, 3, 4, 5, 6]).unwrap();
+        yadon.fill(0xbb, 20).unwrap();
+        yadon.seek(SeekFrom::Start(0x454f45)).unwrap();
+        yadon.write_all(&[0xcc]).unwrap();
+        let patch = yadon.to_ips().unwrap();
+        assert!(!patch[5..patch.len() - 3].windows(3).any(|window| window == b"EOF"));
+        let base = vec![0; 0x454f60];
+        assert_eq!(apply_ips(base.clone(), &patch), yadon.materialize(Some(&base)).unwrap());
+        let mut yadon = Yadon::new(Some(0x454f46), None);
+        yadon.write_all(&[1]).unwrap();
+        assert!(matches!(yadon.to_ips(), Err(IpsError::EofOffset)));
+
+        let mut yadon = Yadon::new(Some(0xfffffe), None);
+        yadon.write_all(&[1, 2, 3]).unwrap();
+        assert!(matches!(yadon.to_ips(), Err(IpsError::OffsetTooLarge(0x1000000))));
+        yadon.set_len(4).unwrap();
+        assert!(matches!(yadon.to_ips(), Err(IpsError::Apply(ApplyError::OrderDependent("set_len")))));
+    }
+
+    #[test]
+    fn ips_import() {
+        let patch = b"PATCH\x00\x00\x02\x00\x03\x01\x02\x03\x00\x00\x08\x00\x00\x00\x04\xffEOF";
+        let yadon = Yadon::from_ips(patch, Some(16)).unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            crate::WriteOperation::Seek(SeekFrom::Start(2), 2),
+            crate::WriteOperation::Write(vec![1, 2, 3], 3),
+            crate::WriteOperation::Seek(SeekFrom::Start(8), 8),
+            crate::WriteOperation::Fill { byte: 0xff, len: 4 },
+        ]));
+        assert_eq!(Yadon::from_ips(&yadon.to_ips().unwrap(), None).unwrap().to_ips().unwrap(), yadon.to_ips().unwrap());
+
+        assert!(matches!(Yadon::from_ips(b"PATCK\x00\x00\x00EOF", None), Err(IpsError::BadMagic)));
+        for len in 5..patch.len() {
+            let error = Yadon::from_ips(&patch[0..len], None).unwrap_err();
+            let record = if len < 13 { 5 } else if len < 21 { 13 } else { 21 };
+            assert!(matches!(error, IpsError::Truncated { offset } if offset == record), "{} bytes gave {:?}", len, error);
+        }
+        let mut trailing = patch.to_vec();
+        trailing.extend_from_slice(&[0, 0]);
+        assert!(matches!(Yadon::from_ips(&trailing, None), Err(IpsError::TrailingBytes { offset: 24 })));
+        trailing.extend_from_slice(&[0, 0]);
+        assert!(matches!(Yadon::from_ips(&trailing, None), Err(IpsError::TrailingBytes { offset: 24 })));
+        assert!(matches!(Yadon::from_ips(patch, Some(11)), Err(IpsError::Invalid { offset: 13, .. })));
+        assert!(matches!(
+            Yadon::from_ips(b"PATCH\x00\x00\x00\x00\x00\x00\x00\x01EOF", None),
+            Err(IpsError::Invalid { offset: 5, .. }),
+        ));
+
+        // The length a patch truncates to is recorded as a resize.
+        let mut truncating = patch.to_vec();
+        truncating.extend_from_slice(&[0, 0, 10]);
+        let yadon = Yadon::from_ips(&truncating, Some(16)).unwrap();
+        assert!(matches!(yadon.operations.last(), Some(crate::WriteOperation::SetLen(10))));
+        let mut target = Cursor::new(vec![0x55; 16]);
+        yadon.apply_with_setlen(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.into_inner(), apply_ips(vec![0x55; 16], &truncating));
+    }
+
+    /// Applies a BPS patch to `source`, written from the format's description rather than sharing anything with
+    /// `Yadon::to_bps()` or `Yadon::from_bps()`. Checksums aren't checked.
+    fn apply_bps(source: &[u8], patch: &[u8]) -> Vec<u8> {
+        fn varint(patch: &[u8], at: &mut usize) -> u64 {
+            let (mut value, mut shift) = (0, 1);
+            loop {
+                let byte = patch[*at];
+                *at += 1;
+                value += (byte & 0x7f) as u64 * shift;
+                if byte & 0x80 != 0 {
+                    return value;
+                }
+                shift <<= 7;
+                value += shift;
+            }
+        }
+        fn relative(patch: &[u8], at: &mut usize) -> i64 {
+            let delta = varint(patch, at);
+            (if delta & 1 == 1 { -1 } else { 1 }) * (delta >> 1) as i64
+        }
+        assert_eq!(&patch[0..4], b"BPS1");
+        let mut at = 4;
+        assert_eq!(varint(patch, &mut at), source.len() as u64);
+        let target_len = varint(patch, &mut at) as usize;
+        at += varint(patch, &mut at) as usize;
+        let mut target = Vec::new();
+        let (mut source_relative, mut target_relative) = (0i64, 0i64);
+        while at < patch.len() - 12 {
+            let action = varint(patch, &mut at);
+            let len = (action >> 2) + 1;
+            match action & 3 {
+                0 => (0..len).for_each(|_| target.push(source[target.len()])),
+                1 => {
+                    target.extend_from_slice(&patch[at..at + len as usize]);
+                    at += len as usize;
+                },
+                2 => {
+                    source_relative += relative(patch, &mut at);
+                    for _ in 0..len {
+                        target.push(source[source_relative as usize]);
+                        source_relative += 1;
+                    }
+                },
+                _ => {
+                    target_relative += relative(patch, &mut at);
+                    for _ in 0..len {
+                        target.push(target[target_relative as usize]);
+                        target_relative += 1;
+                    }
+                },
+            }
+        }
+        assert_eq!(target.len(), target_len);
+        target
+    }
+
+    #[test]
+    fn bps_import() {
+        // Made with every kind of action, metadata, and a longer target, by an encoder written from the format's
+        // description, with the CRC-32s from zlib.
+        let source: Vec<u8> = (0..48u32).map(|i| ((i * 7 + 3) % 256) as u8).collect();
+        let patch = b"\x42\x50\x53\x31\xb0\xb8\x84\x3c\x78\x2f\x3e\x9c\x91\x68\x65\x6c\x6c\x6f\x96\xd0\xa7\x90\xa8\x8e\xd9\xad\
+            \xee\xee\xee\xee\xee\xee\xee\xee\xee\xee\xee\xee\x45\xdc\x72\x45\x34\xba\x04\x2d\x37\x41\xf3\xe7";
+        let target = b"\x03\x0a\x11\x18\x1f\x26\x2d\x34\x68\x65\x6c\x6c\x6f\x1b\x22\x29\x30\x37\x3e\x68\x65\x6c\x6c\x6f\x1b\x22\
+            \x29\x30\x37\xce\xd5\xdc\xe3\xea\xf1\xf8\xff\x06\x0d\x14\x11\x18\x1f\x26\xee\xee\xee\xee\xee\xee\xee\xee\xee\xee\xee\xee";
+        assert_eq!(apply_bps(&source, patch), target);
+
+        let yadon = Yadon::from_bps(patch, &source).unwrap();
+        assert_eq!(yadon.length, Some(56));
+        assert!(matches!(yadon.operations[0], crate::WriteOperation::SetLen(56)));
+        assert_eq!(yadon.base_checksums, vec![(0..48, 0x4572dc45)]);
+        assert_eq!(yadon.materialize(Some(&source)).unwrap(), target);
+        let mut wrong_source = source.clone();
+        wrong_source[0] ^= 1;
+        assert!(matches!(yadon.materialize(Some(&wrong_source)), Err(MaterializeError::Apply(ApplyError::BaseChecksumMismatch(_)))));
+
+        // Exporting the recording again gives a patch which patchers give the same target from.
+        let exported = yadon.to_bps(&source).unwrap();
+        assert_eq!(apply_bps(&source, &exported), target);
+        assert_eq!(Yadon::from_bps(&exported, &source).unwrap().materialize(Some(&source)).unwrap(), target);
+
+        assert!(matches!(Yadon::from_bps(b"UPS1", &source), Err(BpsError::BadMagic)));
+        assert!(matches!(Yadon::from_bps(b"BPS1\x80", &source), Err(BpsError::Truncated { offset: 4 })));
+        let mut corrupt = patch.to_vec();
+        corrupt[14] ^= 1;
+        assert!(matches!(Yadon::from_bps(&corrupt, &source), Err(BpsError::PatchChecksum { expected: 0xe7f34137, .. })));
+        assert!(matches!(Yadon::from_bps(patch, &source[1..]), Err(BpsError::SourceSize { expected: 48, actual: 47 })));
+        assert!(matches!(
+            Yadon::from_bps(patch, &wrong_source),
+            Err(BpsError::SourceChecksum { expected: 0x4572dc45, .. }),
+        ));
+    }
+
+    #[test]
+    fn patches() {
+        let mut yadon = Yadon::new(Some(0), Some(32)).with_generation_stamp(24, 6);
+        yadon.write_all(b"abcdef").unwrap();
+        yadon.seek(SeekFrom::Start(2)).unwrap();
+        yadon.fill(b'x', 2).unwrap();
+        yadon.seek(SeekFrom::Start(6)).unwrap();
+        yadon.write_all(b"gh").unwrap();
+        yadon.seek(SeekFrom::Start(12)).unwrap();
+        yadon.write_all(b"ij").unwrap();
+        yadon.seek(SeekFrom::Start(11)).unwrap();
+        yadon.write_all(b"k").unwrap();
+        yadon.flush().unwrap();
+        assert_eq!(yadon.to_patches().unwrap(), vec![
+            (0, b"abxxefgh".to_vec()),
+            (11, b"kij".to_vec()),
+            (24, 6u64.to_le_bytes().to_vec()),
+        ]);
+
+        let yadon = Yadon::from_patches(vec![(4, b"ab".to_vec()), (0, b"cdef".to_vec()), (5, vec![])], Some(0), Some(8)).unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            WriteOperation::Seek(SeekFrom::Start(4), 4),
+            WriteOperation::Write(b"ab".to_vec(), 2),
+            WriteOperation::Seek(SeekFrom::Start(0), 0),
+            WriteOperation::Write(b"cdef".to_vec(), 4),
+        ]));
+        assert_eq!(yadon.materialize(None).unwrap(), b"cdefab\0\0");
+        assert_eq!(yadon.to_patches().unwrap(), vec![(0, b"cdefab".to_vec())]);
+
+        assert_eq!(
+            Yadon::from_patches(vec![(0, vec![1; 4]), (6, vec![2; 3])], Some(0), Some(8)).unwrap_err(),
+            PatchError::OutOfBounds { index: 1, end: 9, length: 8 },
+        );
+        assert_eq!(Yadon::from_patches(vec![(u64::MAX, vec![1])], None, None).unwrap_err(), PatchError::Overflow { index: 0 });
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.set_len(4).unwrap();
+        assert!(matches!(yadon.to_patches(), Err(ApplyError::OrderDependent(_))));
+    }
+
+    #[test]
+    fn bounded_debug_and_display() {
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.write_all(&vec![0xab; 1 << 20]).unwrap();
+        yadon.seek(SeekFrom::Start(2)).unwrap();
+        yadon.write_repeated(b"xy", 3).unwrap();
+        yadon.copy_within(0, 9, 2).unwrap();
+        yadon.assert_bytes_at(4, &[1, 2, 3]).unwrap();
+        yadon.set_len(12).unwrap();
+        let debug = format!("{:?}", yadon);
+        assert!(debug.len() < 2000);
+        assert!(debug.contains(&format!("Write([{}, ... +1048544 bytes], 1048576)", ["171"; 32].join(", "))));
+        assert!(format!("{:.2?}", yadon).contains("Repeat { pattern: [120, 121], count: 3 }"));
+        assert!(format!("{:.1?}", yadon).contains("Repeat { pattern: [120, ... +1 bytes], count: 3 }"));
+        assert!(format!("{:.1?}", yadon).contains("AssertBytes { offset: 4, expected: [1, ... +2 bytes] }"));
+        assert!(format!("{:#.1?}", yadon).contains("                171,\n                ... +1048575 bytes,\n"));
+
+        assert_eq!(yadon.to_string(), "    \
+                0 write         0x0..0x100000        1048576 bytes  ab ab ab ab ab ab ab ab ...
+    1 seek          -> 0x2
+    2 repeat        0x2..0x8                 6 bytes  78 79 78 79 78 79
+    3 copy_within   0x9..0xb                 2 bytes  from 0x0..0x2
+    4 assert_bytes  0x4..0x7                 3 bytes  01 02 03
+    5 set_len       -> 0xc
+");
+        let mut deferred = Yadon::new(Some(0), None);
+        deferred.defer_end_seeks = true;
+        deferred.write_all(&[0; 4]).unwrap();
+        deferred.seek(SeekFrom::End(-2)).unwrap();
+        deferred.write_all(&[1]).unwrap();
+        deferred.seek(SeekFrom::Start(1)).unwrap();
+        deferred.write_all(&[2]).unwrap();
+        assert_eq!(format!("{:.0}", deferred), "    \
+                0 write         0x0..0x4                 4 bytes  ...
+    1 deferred_seek -> End(-2)
+    2 write         ?                        1 bytes  ...
+    3 seek          -> 0x1
+    4 write         0x1..0x2                 1 bytes  ...
+");
+
+        // Later writes win, as they would when applied.
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.fill(b'a', 20).unwrap();
+        yadon.seek(SeekFrom::Start(3)).unwrap();
+        yadon.write_all(b"BC").unwrap();
+        assert_eq!(yadon.hexdump(2..18).unwrap(), "\
+00000000        61 42 43 61 61 61  61 61 61 61 61 61 61 61  |  aBCaaaaaaaaaaa|
+00000010  61 61                                             |aa              |
+");
+        assert_eq!(yadon.hexdump(40..50).unwrap(), "");
+        yadon.set_len(4).unwrap();
+        assert!(matches!(yadon.hexdump(0..4), Err(ApplyError::OrderDependent("set_len"))));
+    }
+
+    #[test]
+    fn hex_previews() {
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.write_all(&vec![0xab; 1 << 20]).unwrap();
+        yadon.write_repeated(b"xy", 3).unwrap();
+        yadon.assert_bytes_at(4, &[1]).unwrap();
+        yadon.write(&[]).unwrap();
+        yadon.flush().unwrap();
+        let previews: Vec<_> = yadon.operations.iter().map(|operation| operation.preview().map(|preview| preview.to_string())).collect();
+        assert_eq!(previews, [
+            Some(format!("{} ... (1048576 bytes)", ["ab"; 16].join(" "))),
+            Some("78 79 (2 bytes)".to_string()),
+            Some("01 (1 byte)".to_string()),
+            Some("(0 bytes)".to_string()),
+            None,
+        ]);
+
+        let preview = yadon.operations[0].preview().unwrap();
+        assert_eq!((preview.head(), preview.len(), preview.is_truncated()), (&[0xab; 16][..], 1 << 20, true));
+        assert_eq!(format!("{:?}", preview), preview.to_string());
+        let preview = HexPreview::new(&[7; 16]);
+        assert_eq!((preview.head().len(), preview.is_truncated(), preview.is_empty()), (16, false, false));
+        assert_eq!(preview.to_string(), format!("{} (16 bytes)", ["07"; 16].join(" ")));
+        assert_eq!(HexPreview::new(&[7; 17]).head(), preview.head());
+        assert!(HexPreview::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn diff() {
+        let old = b"abcdefghijklmnop";
+        let new = b"aXcdeYYhijklmnZpQR";
+        let yadon = Yadon::from_diff(old, new, DiffOptions::default()).unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            WriteOperation::Seek(SeekFrom::Start(1), 1),
+            WriteOperation::Write(b"X".to_vec(), 1),
+            WriteOperation::Seek(SeekFrom::Start(5), 5),
+            WriteOperation::Write(b"YY".to_vec(), 2),
+            WriteOperation::Seek(SeekFrom::Start(14), 14),
+            WriteOperation::Write(b"Z".to_vec(), 1),
+            WriteOperation::Seek(SeekFrom::Start(16), 16),
+            WriteOperation::Write(b"QR".to_vec(), 2),
+        ]));
+        assert_eq!(yadon.length, Some(18));
+        assert_eq!(yadon.length_mode(), LengthMode::Fixed);
+
+        // Gaps of fewer than 4 identical bytes are written through.
+        let options = DiffOptions { join_gap: 4, length_mode: LengthMode::Growable, ..Default::default() };
+        let yadon = Yadon::from_diff(old, new, options).unwrap();
+        assert_eq!(format!("{:?}", yadon.operations), format!("{:?}", [
+            WriteOperation::Seek(SeekFrom::Start(1), 1),
+            WriteOperation::Write(b"XcdeYY".to_vec(), 6),
+            WriteOperation::Seek(SeekFrom::Start(14), 14),
+            WriteOperation::Write(b"ZpQR".to_vec(), 4),
+        ]));
+        assert_eq!(yadon.length, Some(18));
+        assert_eq!(yadon.length_mode(), LengthMode::Growable);
+        let mut target = Cursor::new(old.to_vec());
+        yadon.apply(&mut target, true).unwrap();
+        assert_eq!(target.into_inner(), new);
+
+        assert_eq!(Yadon::from_diff(old, old, DiffOptions::default()).unwrap().operations.len(), 0);
+        assert_eq!(
+            Yadon::from_diff(new, old, DiffOptions::default()).unwrap_err(),
+            DiffError::Shrinks { old_len: 18, new_len: 16 },
+        );
+        let yadon = Yadon::from_diff(new, old, DiffOptions { allow_set_len: true, ..Default::default() }).unwrap();
+        assert!(matches!(yadon.operations[0], WriteOperation::SetLen(16)));
+        assert_eq!(yadon.materialize(Some(new)).unwrap(), old);
+    }
+
+    /// Reads a varint as BPS and UPS patches encode them, written from the format's description.
+    fn test_varint(patch: &[u8], at: &mut usize) -> u64 {
+        let (mut value, mut shift) = (0, 1);
+        loop {
+            let byte = patch[*at];
+            *at += 1;
+            value += (byte & 0x7f) as u64 * shift;
+            if byte & 0x80 != 0 {
+                return value;
+            }
+            shift <<= 7;
+            value += shift;
+        }
+    }
+
+    /// Makes a UPS patch from `source` to `target`, with the CRC-32s left as 0.
+    fn make_ups(source: &[u8], target: &[u8]) -> Vec<u8> {
+        fn varint(out: &mut Vec<u8>, mut value: u64) {
+            loop {
+                let bits = (value & 0x7f) as u8;
+                value >>= 7;
+                if value == 0 {
+                    out.push(0x80 | bits);
+                    return;
+                }
+                out.push(bits);
+                value -= 1;
+            }
+        }
+        let input = |i: usize| source.get(i).copied().unwrap_or(0);
+        let mut patch = b"UPS1".to_vec();
+        varint(&mut patch, source.len() as u64);
+        varint(&mut patch, target.len() as u64);
+        let (mut last, mut i) = (0, 0);
+        while i < target.len() {
+            if input(i) == target[i] {
+                i += 1;
+                continue;
+            }
+            varint(&mut patch, (i - last) as u64);
+            while i < target.len() && input(i) != target[i] {
+                patch.push(input(i) ^ target[i]);
+                i += 1;
+            }
+            patch.push(0);
+            i += 1;
+            last = i;
+        }
+        let crc32 = |data: &[u8]| {
+            let mut crc = !0u32;
+            for byte in data {
+                crc ^= *byte as u32;
+                for _ in 0..8 {
+                    crc = if crc & 1 == 1 { crc >> 1 ^ 0xedb88320 } else { crc >> 1 };
+                }
+            }
+            !crc
+        };
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        patch.extend_from_slice(&crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    /// Applies a UPS patch to `source`, written from the format's description rather than sharing anything with
+    /// `Yadon::from_ups()`. Checksums aren't checked.
+    fn apply_ups(source: &[u8], patch: &[u8]) -> Vec<u8> {
+        assert_eq!(&patch[0..4], b"UPS1");
+        let mut at = 4;
+        assert_eq!(test_varint(patch, &mut at), source.len() as u64);
+        let len = test_varint(patch, &mut at) as usize;
+        let mut target: Vec<u8> = (0..len).map(|i| source.get(i).copied().unwrap_or(0)).collect();
+        let mut position = 0;
+        while at < patch.len() - 12 {
+            position += test_varint(patch, &mut at) as usize;
+            loop {
+                let byte = patch[at];
+                at += 1;
+                if position < len {
+                    target[position] ^= byte;
+                }
+                position += 1;
+                if byte == 0 {
+                    break;
+                }
+            }
+        }
+        target
+    }
+
+    #[test]
+    fn ups_import() {
+        // Made with hunks at the start, middle and end of a longer output, by an encoder written from the format's
+        // description, with the CRC-32s from zlib.
+        let source: Vec<u8> = (0..40u32).map(|i| ((i * 11 + 5) % 256) as u8).collect();
+        let patch = b"\x55\x50\x53\x31\xa8\xb0\x83\x26\x9b\x87\x00\x8d\x96\x00\x90\x91\x85\x38\x39\x3a\x3b\x3c\x3d\x00\xbc\x3b\
+            \x4b\x5f\xb2\x4a\x08\x7e\xef\xf6\xb1\x95";
+        let target = b"\x05\x10\x1b\x00\xaa\xbb\x47\x52\x5d\x68\x73\x7e\x89\x94\x9f\xaa\xb5\xc0\xcb\xd6\x77\xec\xf7\x02\x0d\x18\
+            \x23\x2e\x39\x44\x4f\x5a\x65\x70\x7b\x86\x91\x9c\x36\x37\x38\x39\x3a\x3b\x3c\x3d\x00\x00";
+        assert_eq!(apply_ups(&source, patch), target);
+        assert_eq!(make_ups(&source, target), patch);
+
+        let yadon = Yadon::from_ups(patch, &source).unwrap();
+        assert_eq!(yadon.length, Some(48));
+        assert_eq!(yadon.base_checksums, vec![(0..40, 0x5f4b3bbc)]);
+        assert_eq!(format!("{:?}", &yadon.operations[..3]), format!("{:?}", [
+            crate::WriteOperation::SetLen(48),
+            crate::WriteOperation::Seek(SeekFrom::Start(3), 3),
+            crate::WriteOperation::Write(vec![0x00, 0xaa, 0xbb], 3),
+        ]));
+        assert_eq!(yadon.materialize(Some(&source)).unwrap(), target);
+
+        assert_eq!(Yadon::from_ups(b"BPS1", &source).unwrap_err(), UpsError::BadMagic);
+        assert_eq!(Yadon::from_ups(b"UPS1\x80", &source).unwrap_err(), UpsError::Truncated { offset: 4 });
+        let mut corrupt = patch.to_vec();
+        corrupt[12] ^= 1;
+        assert!(matches!(Yadon::from_ups(&corrupt, &source).unwrap_err(), UpsError::PatchChecksum { expected: 0x95b1f6ef, .. }));
+        assert_eq!(Yadon::from_ups(patch, &source[1..]).unwrap_err(), UpsError::InputSize { expected: 40, actual: 39 });
+        let mut wrong_source = source.clone();
+        wrong_source[0] ^= 1;
+        assert!(matches!(
+            Yadon::from_ups(patch, &wrong_source).unwrap_err(),
+            UpsError::InputChecksum { expected: 0x5f4b3bbc, .. },
+        ));
+        let yadon = Yadon::from_ups(&make_ups(&[0; 4], &[1, 0, 0, 2]), &[0; 4]).unwrap();
+        assert_eq!(yadon.operations.len(), 4);
+
+        // Patches whose checksums are right, but whose hunks aren't.
+        let valid_crc = |body: &[u8]| {
+            let mut patch = make_ups(&[0; 4], &[0; 4]);
+            patch.splice(6..6, body.iter().copied());
+            let len = patch.len();
+            let crc = crate::crc::crc32(&patch[..len - 4]);
+            patch[len - 4..].copy_from_slice(&crc.to_le_bytes());
+            Yadon::from_ups(&patch, &[0; 4]).unwrap_err()
+        };
+        assert_eq!(valid_crc(&[0x80, 0x01]), UpsError::Truncated { offset: 6 });
+        assert!(matches!(valid_crc(&[0x83, 0x01, 0x01, 0x00]), UpsError::Invalid { offset: 8, .. }));
+        assert!(matches!(valid_crc(&[0x80, 0x01, 0x00]), UpsError::OutputChecksum { .. }));
+    }
+
+    #[test]
+    fn divergence_context() {
+        let mut yadon = Yadon::new(Some(2), Some(10));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::Current(1)).unwrap(), 5);
+        assert_eq!(yadon.write(&[3; 5]).unwrap(), 5);
+
+        let mut target = [0u8; 8];
+        match yadon.apply(&mut Cursor::new(&mut target[..]), true) {
+            Err(error @ ApplyError::NumBytesWrittenDiverge(_)) => {
+                assert!(error.to_string().starts_with(
+                    "number of bytes written diverged while trying to replay operations: \
+                     expected 5, got 3 (operation 2 at offset 5, writing 5 bytes",
+                ));
+                match error {
+                    ApplyError::NumBytesWrittenDiverge(diff) => {
+                        assert_eq!(diff.op_index, Some(2));
+                        assert_eq!(diff.offset, Some(5));
+                        assert_eq!(diff.len, Some(5));
+                    },
+                    _ => unreachable!(),
+                }
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn apply_error_into_io_error() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.write(&[1; 6]).unwrap(), 6);
+
+        let mut target = [0u8; 4];
+        let error: std::io::Error = yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap_err().into();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        match error.get_ref().and_then(|error| error.downcast_ref::<ApplyError>()) {
+            Some(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.expected, 6);
+                assert_eq!(diff.actual, 4);
+            },
+            res => panic!("std::io::Error did not wrap a diverged write: {:?}", res),
+        }
+
+        let original = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "gone");
+        let error: std::io::Error = ApplyError::Io(original).into();
+        assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe);
+        assert_eq!(error.to_string(), "gone");
+    }
+
+    #[test]
+    fn interrupted_calls_retried() {
+        let mut yadon = Yadon::new(Some(1), Some(8));
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::Current(1)).unwrap(), 5);
+        assert_eq!(yadon.write(&[4, 5]).unwrap(), 2);
+
+        let mut target = EventLog::new(8);
+        target.interruptions = 2;
+        target.interruptions_left = 2;
+        assert_eq!(yadon.apply(&mut target, true).unwrap(), 5);
+        assert_eq!(target.inner.get_ref(), &[0, 1, 2, 3, 0, 4, 5, 0]);
+        let interrupted = target.events.iter().filter(|event| matches!(event, Event::Interrupted)).count();
+        assert_eq!(interrupted, 2 * 4);
+
+        // Reads for preconditions and copies are retried too, rather than leaving the target partly applied.
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        yadon.write_all(&[1, 2, 3]).unwrap();
+        yadon.assert_bytes_at(6, &[0, 0]).unwrap();
+        yadon.copy_within(0, 5, 3).unwrap();
+        let mut target = EventLog::new(8);
+        target.interruptions = 1;
+        target.interruptions_left = 1;
+        yadon.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.inner.get_ref(), &[1, 2, 3, 0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn short_writes_retried() {
+        let mut yadon = Yadon::new(Some(0), Some(64));
+        assert_eq!(yadon.write(&[1; 20]).unwrap(), 20);
+        assert_eq!(yadon.write(&[2; 3]).unwrap(), 3);
+        yadon.fill(3, 16).unwrap();
+        yadon.write_repeated(&[4, 5, 6], 5).unwrap();
+
+        let mut target = EventLog::new(64);
+        target.max_write = Some(7);
+        let options = ApplyOptions {
+            vectored_writes: false,
+            ..ApplyOptions::default()
+        };
+        match yadon.apply_with_options(&mut target, &options) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.op_index, Some(0));
+                assert_eq!((diff.expected, diff.actual), (20, 7));
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+
+        let mut target = EventLog::new(64);
+        target.max_write = Some(7);
+        let options = ApplyOptions {
+            short_write_behavior: ShortWrite::Retry,
+            ..ApplyOptions::default()
+        };
+        assert_eq!(yadon.apply_with_options(&mut target, &options).unwrap(), 54);
+        let mut expected = vec![1; 20];
+        expected.extend_from_slice(&[2; 3]);
+        expected.extend_from_slice(&[3; 16]);
+        expected.extend_from_slice(&[4, 5, 6].repeat(5));
+        expected.resize(64, 0);
+        assert_eq!(target.inner.get_ref(), &expected);
+        assert!(target.events.iter().all(|event| match event {
+            Event::Write(written) => *written <= 7,
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn writes_split_at_max_write_size() {
+        let mut yadon = Yadon::new(Some(0), Some(48));
+        assert_eq!(yadon.write(&[1; 12]).unwrap(), 12);
+        assert_eq!(yadon.write(&[2; 3]).unwrap(), 3);
+        assert_eq!(yadon.write(&[3; 4]).unwrap(), 4);
+        yadon.fill(4, 11).unwrap();
+        yadon.write_repeated(&[5, 6, 7], 4).unwrap();
+        let mut expected = vec![1; 12];
+        expected.extend_from_slice(&[2; 3]);
+        expected.extend_from_slice(&[3; 4]);
+        expected.extend_from_slice(&[4; 11]);
+        expected.extend_from_slice(&[5, 6, 7].repeat(4));
+        expected.resize(48, 0);
+
+        let options = ApplyOptions {
+            max_write_size: Some(5),
+            ..ApplyOptions::default()
+        };
+        for vectored in [false, true] {
+            let mut target = EventLog::new(48);
+            target.vectored = vectored;
+            assert_eq!(yadon.apply_with_options(&mut target, &options).unwrap(), 42);
+            assert_eq!(target.inner.get_ref(), &expected);
+            let writes: Vec<usize> = target.events.iter().filter_map(|event| match event {
+                Event::Write(written) | Event::WriteVectored(written) => Some(*written),
+                _ => None,
+            }).collect();
+            assert!(writes.iter().all(|written| *written <= 5));
+            assert_eq!(writes.len(), 11);
+        }
+    }
+
+    #[test]
+    fn coalesced_writes() {
+        let mut yadon = Yadon::new(Some(0), Some(12));
+        for i in 0..3 {
+            assert_eq!(yadon.write(&[i + 1; 4]).unwrap(), 4);
+        }
+        let options = ApplyOptions {
+            coalesce_writes: true,
+            ..ApplyOptions::default()
+        };
+
+        let mut target = EventLog::new(12);
+        target.max_write = Some(7);
+        assert_eq!(yadon.apply_with_options(&mut target, &options).unwrap(), 12);
+        assert_eq!(target.inner.get_ref(), &[1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3]);
+        let writes: Vec<usize> = target.events.iter().filter_map(|event| match event {
+            Event::Write(written) => Some(*written),
+            _ => None,
+        }).collect();
+        assert_eq!(writes, vec![7, 1, 4]);
+
+        let mut target = [0u8; 10];
+        match yadon.apply_with_options(&mut Cursor::new(&mut target[..]), &options) {
+            Err(ApplyError::NumBytesWrittenDiverge(diff)) => {
+                assert_eq!(diff.op_index, Some(2));
+                assert_eq!((diff.expected, diff.actual), (4, 2));
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn apply_ordered_by_offset() {
+        let mut yadon = Yadon::new(Some(2), Some(16));
+        assert_eq!(yadon.seek(SeekFrom::Start(10)).unwrap(), 10);
+        assert_eq!(yadon.write(&[3; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(6)).unwrap(), 6);
+        yadon.fill(2, 4).unwrap();
+        yadon.flush().unwrap();
+        assert_eq!(yadon.seek(SeekFrom::End(-14)).unwrap(), 2);
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        let operations = format!("{:?}", yadon.operations);
+
+        let options = ApplyOptions {
+            order: ApplyOrder::Offset,
+            vectored_writes: false,
+            ..Default::default()
+        };
+        let mut target = EventLog::new(16);
+        assert_eq!(yadon.apply_with_options(&mut target, &options).unwrap(), 12);
+        assert_eq!(target.inner.get_ref(), &[0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 0, 0]);
+        let seeks: Vec<SeekFrom> = target.events.iter().filter_map(|event| match event {
+            Event::Seek(pos) => Some(*pos),
+            _ => None,
+        }).collect();
+        assert_eq!(seeks, vec![SeekFrom::Start(2), SeekFrom::Start(2), SeekFrom::Start(6), SeekFrom::Start(10)]);
+        assert_eq!(format!("{:?}", yadon.operations), operations);
+
+        // Elided seeks drop the ones which are already in place.
+        let mut target = EventLog::new(16);
+        let report = yadon.apply_report(&mut target, &ApplyOptions { elide_seeks: true, ..options.clone() }).unwrap();
+        assert_eq!((report.seeks_issued, report.seeks_elided), (1, 3));
+    }
+
+    #[test]
+    fn apply_ordered_by_offset_rejected() {
+        let options = ApplyOptions {
+            order: ApplyOrder::Offset,
+            ..Default::default()
+        };
+
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[1; 6]).unwrap(), 6);
+        assert_eq!(yadon.seek(SeekFrom::Start(8)).unwrap(), 8);
+        assert_eq!(yadon.write(&[2; 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(yadon.write(&[3; 2]).unwrap(), 2);
+        let mut target = EventLog::new(16);
+        match yadon.apply_with_options(&mut target, &options) {
+            Err(ApplyError::OverlappingWrites { first, second }) => {
+                assert_eq!(first, (0, 0..6));
+                assert_eq!(second, (4, 4..6));
+            },
+            res => panic!("Apply did not fail with overlapping writes: {:?}", res),
+        }
+        assert!(target.events.is_empty());
+
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        yadon.copy_within(0, 8, 4).unwrap();
+        let mut target = EventLog::new(16);
+        match yadon.apply_with_options(&mut target, &options) {
+            Err(ApplyError::OrderDependent(name)) => assert_eq!(name, "copy_within"),
+            res => panic!("Apply did not fail with an order dependent operation: {:?}", res),
+        }
+
+        let mut yadon = Yadon::new(None, Some(16));
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        match yadon.apply_with_options(&mut EventLog::new(16), &options) {
+            Err(ApplyError::UnresolvedOffset(index)) => assert_eq!(index, 0),
+            res => panic!("Apply did not fail with an unresolved offset: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn restore_position_after_apply() {
+        let mut yadon = Yadon::new(Some(8), Some(12));
+        assert_eq!(yadon.write(&[9; 2]).unwrap(), 2);
+        let options = ApplyOptions {
+            restore_position: true,
+            ..Default::default()
+        };
+
+        let mut target = Cursor::new(vec![0u8; 12]);
+        target.write_all(&[1; 3]).unwrap();
+        let report = yadon.apply_report(&mut target, &options).unwrap();
+        assert_eq!(report.bytes_written, 2);
+        assert_eq!(report.final_position, 10);
+        target.write_all(&[2; 3]).unwrap();
+        assert_eq!(target.get_ref(), &[1, 1, 1, 2, 2, 2, 0, 0, 9, 9, 0, 0]);
+
+        // The position is restored when applying fails partway, too.
+        assert_eq!(yadon.write(&[9; 4]).unwrap(), 2);
+        let mut target = [0u8; 9];
+        let mut target = Cursor::new(&mut target[..]);
+        target.seek(SeekFrom::Start(5)).unwrap();
+        assert!(matches!(yadon.apply_with_options(&mut target, &options), Err(ApplyError::NumBytesWrittenDiverge(_))));
+        assert_eq!(target.stream_position().unwrap(), 5);
+    }
+
+    #[test]
+    fn flush_policies() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        for i in 0..4 {
+            assert_eq!(yadon.write(&[i; 3]).unwrap(), 3);
+        }
+        let flushes = |flush_policy| {
+            let mut target = EventLog::new(16);
+            yadon.apply_with_options(&mut target, &ApplyOptions { flush_policy, ..Default::default() }).unwrap();
+            target.events.iter().map(|event| match event {
+                Event::Flush => 'F',
+                Event::Write(_) | Event::WriteVectored(_) => 'W',
+                _ => '-',
+            }).collect::<String>()
+        };
+        assert_eq!(flushes(FlushPolicy::Never), "-WWWW");
+        assert_eq!(flushes(FlushPolicy::AtEnd), "-WWWWF");
+        assert_eq!(flushes(FlushPolicy::EveryOp), "-WFWFWFWFF");
+        assert_eq!(flushes(FlushPolicy::EveryNBytes(5)), "-WWWWFF");
+
+        let options = ApplyOptions {
+            flush_policy: FlushPolicy::EveryNBytes(5),
+            vectored_writes: false,
+            ..Default::default()
+        };
+        let mut target = EventLog::new(16);
+        yadon.apply_with_options(&mut target, &options).unwrap();
+        assert_eq!(target.events.iter().filter(|event| matches!(event, Event::Flush)).count(), 3);
+
+        let mut target = EventLog::new(16);
+        target.failing_flush = true;
+        match yadon.apply_with_options(&mut target, &options) {
+            Err(error @ ApplyError::FlushFailed { .. }) => {
+                assert!(error.to_string().starts_with("flushing the target after operation 1 failed"));
+                match error {
+                    ApplyError::FlushFailed { op_index, source, .. } => {
+                        assert_eq!(op_index, 1);
+                        assert_eq!(source.kind(), std::io::ErrorKind::BrokenPipe);
+                    },
+                    _ => unreachable!(),
+                }
+            },
+            res => panic!("Apply did not fail with a failed flush: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn progress_reported() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[1; 3]).unwrap(), 3);
+        assert_eq!(yadon.write(&[2; 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::Current(1)).unwrap(), 6);
+        yadon.fill(3, 4).unwrap();
+        yadon.flush().unwrap();
+
+        let mut reports = vec![];
+        let mut target = EventLog::new(16);
+        target.vectored = true;
+        let bytes_written = yadon.apply_with_progress(&mut target, &ApplyOptions::default(), |progress| reports.push(progress)).unwrap();
+        assert_eq!(bytes_written, 9);
+        let completed: Vec<(usize, u64)> = reports.iter().map(|progress| (progress.ops_completed, progress.bytes_written)).collect();
+        assert_eq!(completed, vec![(1, 3), (2, 5), (3, 5), (4, 9), (5, 9)]);
+        assert!(reports.iter().all(|progress| *progress == Progress { total_ops: 5, total_bytes: 9, ..*progress }));
+    }
+
+    #[test]
+    fn apply_cancelled() {
+        let mut yadon = Yadon::new(Some(0), Some(64));
+        for i in 0..4 {
+            assert_eq!(yadon.write(&[i + 1; 3]).unwrap(), 3);
+        }
+        yadon.fill(5, 40).unwrap();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = ApplyOptions {
+            vectored_writes: false,
+            max_write_size: Some(16),
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+
+        let mut target = EventLog::new(16);
+        let cancelled = yadon.apply_with_progress(&mut target, &options, |progress| {
+            if progress.ops_completed == 2 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+        });
+        match cancelled {
+            Err(ApplyError::Cancelled { ops_applied, bytes_written }) => assert_eq!((ops_applied, bytes_written), (2, 6)),
+            res => panic!("Apply was not cancelled: {:?}", res),
+        }
+        assert_eq!(&target.inner.get_ref()[0..8], &[1, 1, 1, 2, 2, 2, 0, 0]);
+        assert!(matches!(target.events.last(), Some(Event::Flush)));
+
+        // Large operations are cancelled between chunks.
+        struct CancellingWriter(Cursor<Vec<u8>>, Arc<AtomicBool>);
+        impl Write for CancellingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                if buf.len() == 16 {
+                    self.1.store(true, Ordering::Relaxed);
+                }
+                self.0.write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        impl Seek for CancellingWriter {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.0.seek(pos)
+            }
+        }
+        cancel.store(false, Ordering::Relaxed);
+        let mut target = CancellingWriter(Cursor::new(vec![]), cancel.clone());
+        match yadon.apply_with_options(&mut target, &options) {
+            Err(ApplyError::Cancelled { ops_applied, bytes_written }) => assert_eq!((ops_applied, bytes_written), (4, 12)),
+            res => panic!("Apply was not cancelled: {:?}", res),
+        }
+        assert_eq!(target.0.get_ref().len(), 12 + 16);
+    }
+
+    #[test]
+    fn apply_rate_limited() {
+        thread_local! {
+            static SLEPT: std::cell::Cell<Duration> = const { std::cell::Cell::new(Duration::ZERO) };
+        }
+        fn fake_sleep(duration: Duration) {
+            SLEPT.with(|slept| slept.set(slept.get() + duration));
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(512));
+        assert_eq!(yadon.write(&[1; 250]).unwrap(), 250);
+        assert_eq!(yadon.seek(SeekFrom::Start(300)).unwrap(), 300);
+        yadon.fill(2, 100).unwrap();
+
+        let options = ApplyOptions {
+            rate_limit: Some(100),
+            rate_limit_sleep: fake_sleep,
+            ..Default::default()
+        };
+        let mut target = EventLog::new(512);
+        assert_eq!(yadon.apply_with_options(&mut target, &options).unwrap(), 350);
+        assert!(target.events.iter().all(|event| match event {
+            Event::Write(written) => *written <= 10,
+            _ => true,
+        }));
+        // The first chunk is written straight away, and each of the other 34 waits a tenth of a second.
+        let slept = SLEPT.with(|slept| slept.get());
+        assert!(slept > Duration::from_millis(3350) && slept < Duration::from_millis(3410), "slept for {:?}", slept);
+    }
+
+    #[test]
+    fn session_resumed() {
+        let mut yadon = Yadon::new(Some(1), Some(16));
+        assert_eq!(yadon.write(&[1; 3]).unwrap(), 3);
+        assert_eq!(yadon.write(&[2; 3]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::Current(2)).unwrap(), 9);
+        assert_eq!(yadon.write(&[3; 4]).unwrap(), 4);
+
+        let mut session = yadon.session(ApplyOptions::default());
+        let mut short = [0u8; 6];
+        let error = session.run(&mut Cursor::new(&mut short[..])).unwrap_err();
+        assert_eq!(error.ops_completed, 1);
+        assert!(matches!(error.error, ApplyError::NumBytesWrittenDiverge(_)));
+        assert_eq!(session.state(), SessionState { next_op: 1, position: Some(4) });
+        assert!(!session.is_finished());
+
+        let state = SessionState::from_bytes(&session.state().to_bytes()).unwrap();
+        let mut session = yadon.resume_session(ApplyOptions::default(), state);
+        let mut target = EventLog::new(16);
+        assert_eq!(session.resume(&mut target).unwrap(), 7);
+        assert!(session.is_finished());
+        assert_eq!(target.inner.get_ref(), &[0, 0, 0, 0, 2, 2, 2, 0, 0, 3, 3, 3, 3, 0, 0, 0]);
+        assert!(matches!(target.events[0], Event::Seek(SeekFrom::Start(4))));
+
+        assert_eq!(SessionState::from_bytes(&[0; 16]), None);
+        let unknown = SessionState { next_op: 2, position: None };
+        assert_eq!(SessionState::from_bytes(&unknown.to_bytes()), Some(unknown));
+        let error = yadon.resume_session(ApplyOptions::default(), unknown).resume(&mut EventLog::new(16)).unwrap_err();
+        assert!(matches!(error.error, ApplyError::UnresolvedOffset(2)));
+    }
+
+    #[test]
+    fn apply_range_slices_writes() {
+        let mut yadon = Yadon::new(Some(0), Some(32));
+        assert_eq!(yadon.write(&[1, 2, 3, 4, 5, 6]).unwrap(), 6);
+        assert_eq!(yadon.seek(SeekFrom::Start(20)).unwrap(), 20);
+        assert_eq!(yadon.write(&[9; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(8)).unwrap(), 8);
+        yadon.write_repeated(&[7, 8, 9], 3).unwrap();
+        yadon.fill(5, 4).unwrap();
+
+        let mut target = EventLog::new(32);
+        let report = yadon.apply_range(&mut target, 4..13, &ApplyOptions::default()).unwrap();
+        assert_eq!(report, RangeReport { bytes_applied: 7, bytes_skipped: 16 });
+        let mut expected = vec![0u8; 32];
+        expected[4..6].copy_from_slice(&[5, 6]);
+        expected[8..13].copy_from_slice(&[7, 8, 9, 7, 8]);
+        assert_eq!(target.inner.get_ref(), &expected);
+        let seeks: Vec<SeekFrom> = target.events.iter().filter_map(|event| match event {
+            Event::Seek(pos) => Some(*pos),
+            _ => None,
+        }).collect();
+        assert_eq!(seeks, vec![SeekFrom::Start(4), SeekFrom::Start(8)]);
+
+        // A repeated pattern cut into partway starts at the right phase.
+        let mut target = EventLog::new(32);
+        let report = yadon.apply_range(&mut target, 10..19, &ApplyOptions::default()).unwrap();
+        assert_eq!(report, RangeReport { bytes_applied: 9, bytes_skipped: 14 });
+        assert_eq!(&target.inner.get_ref()[8..20], &[0, 0, 9, 7, 8, 9, 7, 8, 9, 5, 5, 0]);
+
+        let mut target = EventLog::new(32);
+        let report = yadon.apply_range(&mut target, 26..32, &ApplyOptions::default()).unwrap();
+        assert_eq!(report, RangeReport { bytes_applied: 0, bytes_skipped: 23 });
+        assert!(target.events.iter().all(|event| matches!(event, Event::Flush)));
+    }
+
+    #[test]
+    fn validated_before_writing() {
+        fn hash(target: &Cursor<Vec<u8>>) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            target.get_ref().hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.write(&[1, 2, 3, 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).unwrap(), 6);
+        assert_eq!(yadon.write(&[9]).unwrap(), 1);
+        let options = ApplyOptions {
+            validate_first: true,
+            ..Default::default()
+        };
+
+        // The seek from the end diverges in a longer target.
+        let mut target = Cursor::new(vec![0xaa; 10]);
+        let before = hash(&target);
+        assert!(matches!(yadon.apply_with_options(&mut target, &options), Err(ApplyError::SeekDiverged(_))));
+        assert_eq!(hash(&target), before);
+        let mut unvalidated = Cursor::new(vec![0xaa; 10]);
+        assert!(yadon.apply_with_options(&mut unvalidated, &ApplyOptions::default()).is_err());
+        assert_ne!(hash(&unvalidated), before);
+
+        let mut target = Cursor::new(vec![0xaa; 6]);
+        let before = hash(&target);
+        match yadon.apply_with_options(&mut target, &options) {
+            Err(ApplyError::TargetTooShort { required, actual }) => assert_eq!((required, actual), (7, 6)),
+            res => panic!("Apply did not fail with a short target: {:?}", res),
+        }
+        assert_eq!(hash(&target), before);
+
+        let mut target = EventLog::new(8);
+        assert_eq!(yadon.apply_with_options(&mut target, &options).unwrap(), 5);
+        assert_eq!(target.inner.get_ref(), &[1, 2, 3, 4, 0, 0, 9, 0]);
+        let writes = target.events.iter().filter(|event| matches!(event, Event::Write(_) | Event::WriteVectored(_))).count();
+        assert_eq!(writes, 2);
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn applied_atomically() {
+        use crate::AtomicOptions;
+
+        let dir = std::env::temp_dir().join(format!("yadon-applied-atomically-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.bin");
+        let original = vec![0xaa; 10];
+        std::fs::write(&path, &original).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        // The seek from the end diverges, as the file is longer than recorded.
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.write(&[1, 2, 3, 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).unwrap(), 6);
+        assert_eq!(yadon.write(&[9]).unwrap(), 1);
+        assert!(matches!(yadon.apply_atomic(&path, &AtomicOptions::default()), Err(ApplyError::SeekDiverged(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), original);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        std::fs::write(&path, [0xaa; 8]).unwrap();
+        let report = yadon.apply_atomic(&path, &AtomicOptions::default()).unwrap();
+        assert_eq!(report.bytes_written, 5);
+        assert_eq!(std::fs::read(&path).unwrap(), &[1, 2, 3, 4, 0xaa, 0xaa, 9, 0xaa]);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        }
+
+        // Without the copy, the file ends up with only what was written.
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[5, 6, 7]).unwrap(), 3);
+        let options = AtomicOptions {
+            copy_original: false,
+            ..Default::default()
+        };
+        yadon.apply_atomic(&path, &options).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), &[5, 6, 7]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "fs")]
+    #[test]
+    fn journal_recovery() {
+        use crate::{JournalSync, JournaledYadon};
+
+        let path = std::env::temp_dir().join(format!("yadon-journal-{}", std::process::id()));
+        let mut yadon = Yadon::new(Some(0), Some(8)).with_length_mode(LengthMode::Growable);
+        yadon.deny_overwrite = true;
+        let mut journaled = JournaledYadon::create_from(&path, yadon).unwrap().with_sync(JournalSync::Every(2));
+        journaled.write_all(b"journal").unwrap();
+        journaled.seek(SeekFrom::Start(10)).unwrap();
+        journaled.record(|yadon| yadon.fill(0xff, 4)).unwrap().unwrap();
+        journaled.flush().unwrap();
+        journaled.record(|yadon| yadon.assert_bytes_at(0, b"j")).unwrap().unwrap();
+        let mut expected = Cursor::new(vec![]);
+        assert_eq!(journaled.apply_readable(&mut expected, &ApplyOptions::default()).unwrap(), 11);
+        let operations: Vec<String> = journaled.operations.iter().map(|operation| format!("{:?}", operation)).collect();
+        let contents = std::fs::read(&path).unwrap();
+        drop(journaled);
+
+        // However much of the journal made it to storage, what's recovered is the operations which were journaled in
+        // full, and recording carries on after them as it would have.
+        let mut recovered_lens = vec![];
+        for len in 0..=contents.len() {
+            std::fs::write(&path, &contents[..len]).unwrap();
+            let recovered = match JournaledYadon::recover(&path) {
+                Ok(recovered) => recovered,
+                Err(error) => {
+                    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+                    assert!(recovered_lens.is_empty(), "recovery failed with {} bytes of the journal", len);
+                    continue;
+                },
+            };
+            let recovered_operations: Vec<String> = recovered.operations.iter().map(|operation| format!("{:?}", operation)).collect();
+            assert_eq!(recovered_operations[..], operations[..recovered_operations.len()]);
+            assert!(std::fs::metadata(&path).unwrap().len() as usize <= len);
+            recovered_lens.push(recovered_operations.len());
+            if recovered_operations.len() == operations.len() {
+                // What was written is remembered, so overwriting it is still denied.
+                assert_eq!(recovered.written_extents().collect::<Vec<_>>(), vec![0..7, 10..14]);
+                assert_eq!(recovered.length, Some(14));
+                let mut target = Cursor::new(vec![]);
+                recovered.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+                assert_eq!(target.get_ref(), expected.get_ref());
+            }
+        }
+        assert_eq!(recovered_lens.first(), Some(&0));
+        assert_eq!(recovered_lens.last(), Some(&operations.len()));
+        assert!(recovered_lens.windows(2).all(|lens| lens[0] <= lens[1]));
+
+        // A record before the last which doesn't match its CRC-32 isn't mistaken for a torn write.
+        let mut corrupt = contents.clone();
+        let initial_len = u32::from_le_bytes([contents[6], contents[7], contents[8], contents[9]]) as usize;
+        corrupt[14 + initial_len - 1] ^= 1;
+        std::fs::write(&path, &corrupt).unwrap();
+        assert_eq!(JournaledYadon::recover(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+
+        // Recording carries on into the recovered journal, until it's marked complete.
+        std::fs::write(&path, &contents).unwrap();
+        let mut recovered = JournaledYadon::recover(&path).unwrap();
+        recovered.write_all(b"!").unwrap();
+        recovered.mark_complete().unwrap();
+        assert!(recovered.write(b"!").is_err());
+        drop(recovered);
+        let recovered = JournaledYadon::recover(&path).unwrap();
+        assert!(recovered.is_complete());
+        assert_eq!(recovered.operations.len(), operations.len() + 1);
+        recovered.delete().unwrap();
+        assert!(!path.exists());
+
+        // Custom operations are refused, rather than recorded and never journaled.
+        #[derive(Debug)]
+        struct Nothing;
+        impl ApplyOp for Nothing {
+            fn simulate(&self, pos: u64, _: Option<u64>) -> SimResult {
+                SimResult { position: pos, bytes_written: 0 }
+            }
+
+            fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+                Ok(ApplyOutcome { position: target.stream_position()?, bytes_written: 0 })
+            }
+        }
+        let mut journaled = JournaledYadon::create(&path).unwrap();
+        let refused = journaled.record(|yadon| yadon.record_custom(Nothing)).unwrap();
+        assert_eq!(refused.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert!(journaled.operations.is_empty());
+
+        // A record torn by a failed append is cut off before the next is appended, so nothing after it is lost.
+        let mut writable = std::mem::replace(&mut journaled.file, std::fs::File::open(&path).unwrap());
+        assert!(journaled.write_all(b"torn").is_err());
+        writable.write_all(&[0xff; 5]).unwrap();
+        journaled.file = writable;
+        journaled.write_all(b"!").unwrap();
+        let recovered = JournaledYadon::recover(&path).unwrap();
+        assert_eq!(recovered.operations.len(), 2);
+        assert_eq!(recovered.materialize(None).unwrap(), b"torn!");
+        let mut yadon = recovered.delete().unwrap();
+        yadon.record_custom(Nothing).unwrap();
+
+        // Nor can a recording whose operations are in a store be journaled.
+        let stored = Yadon::default().with_store(Vec::new()).unwrap();
+        assert_eq!(JournaledYadon::create_from(&path, stored).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn transactional_apply_rolled_back() {
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[1, 2, 3, 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(10)).unwrap(), 10);
+        yadon.fill(7, 6).unwrap();
+        yadon.write_repeated(&[8, 9], 3).unwrap();
+        yadon.copy_within(0, 18, 4).unwrap();
+        assert_eq!(yadon.write(&[5; 4]).unwrap(), 4);
+        assert_eq!(yadon.rollback_size(), 24);
+
+        let original: Vec<u8> = (0..26).collect();
+        let mut target = EventLog::new(0);
+        target.inner = Cursor::new(original.clone());
+        assert_eq!(yadon.apply_transactional(&mut target, &ApplyOptions::default()).unwrap(), 24);
+        let mut expected = original.clone();
+        expected[0..4].copy_from_slice(&[1, 2, 3, 4]);
+        expected[10..16].copy_from_slice(&[7; 6]);
+        expected[16..22].copy_from_slice(&[8, 9, 8, 9, 8, 9]);
+        expected[18..22].copy_from_slice(&[1, 2, 3, 4]);
+        expected[22..26].copy_from_slice(&[5; 4]);
+        assert_eq!(target.inner.get_ref(), &expected);
+        let writes = target.events.iter().filter(|event| matches!(event, Event::Write(_))).count();
+        assert_eq!(writes, 5);
+
+        for n in 0..writes {
+            let mut target = EventLog::new(0);
+            target.inner = Cursor::new(original.clone());
+            target.writes_before_failure = Some(n);
+            match yadon.apply_transactional(&mut target, &ApplyOptions::default()) {
+                Err(ApplyError::Io(error)) => assert_eq!(error.kind(), std::io::ErrorKind::BrokenPipe),
+                res => panic!("Apply did not fail on write {}: {:?}", n, res),
+            }
+            assert_eq!(target.inner.get_ref(), &original, "not restored after failing write {}", n);
+        }
+
+        // Flushing fails both at the end of applying and after rolling back.
+        let mut target = EventLog::new(0);
+        target.inner = Cursor::new(original.clone());
+        target.failing_flush = true;
+        match yadon.apply_transactional(&mut target, &ApplyOptions::default()) {
+            Err(ApplyError::RollbackFailed { error, rollback_error }) => {
+                assert!(matches!(*error, ApplyError::Io(_)));
+                assert_eq!(rollback_error.kind(), std::io::ErrorKind::BrokenPipe);
+            },
+            res => panic!("Apply did not fail to roll back: {:?}", res),
+        }
+        assert_eq!(target.inner.get_ref(), &original);
+    }
+
+    #[test]
+    fn undo_captured() {
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[1, 2, 3, 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(2)).unwrap(), 2);
+        yadon.fill(0, 4).unwrap();
+        yadon.copy_within(0, 8, 4).unwrap();
+        assert_eq!(yadon.seek(SeekFrom::Start(10)).unwrap(), 10);
+        yadon.write_repeated(&[7, 8], 2).unwrap();
+
+        let original: Vec<u8> = (10..22).collect();
+        let mut target = Cursor::new(original.clone());
+        let (report, undo) = yadon.apply_and_capture_undo(&mut target).unwrap();
+        assert_eq!(report.bytes_written, 16);
+        assert_eq!(target.get_ref(), &[1, 2, 0, 0, 0, 0, 16, 17, 1, 2, 7, 8, 7, 8]);
+        assert_eq!(target.stream_position().unwrap(), 14);
+
+        // Applying the undo log restores the target, truncating what was appended.
+        undo.apply_with_setlen(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.get_ref(), &original);
+
+        // Without extending the target, the undo log can be applied to a target which can't be resized.
+        let mut target = Cursor::new(vec![9u8; 16]);
+        let (_, undo) = yadon.apply_and_capture_undo(&mut target).unwrap();
+        undo.apply_with_options(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.get_ref(), &[9u8; 16]);
+    }
+
+    #[test]
+    fn diff_only_skips_unchanged() {
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[0, 1, 1, 0, 0, 2]).unwrap(), 6);
+        yadon.fill(3, 4).unwrap();
+
+        let mut target = EventLog::new(8);
+        target.inner.get_mut()[6] = 3;
+        let report = yadon.apply_diff_only(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(report, DiffReport { bytes_written: 6, bytes_unchanged: 4 });
+        assert_eq!(target.inner.get_ref(), &[0, 1, 1, 0, 0, 2, 3, 3, 3, 3]);
+        let writes: Vec<Event> = target.events.into_iter().filter(|event| matches!(event, Event::Write(_))).collect();
+        assert_eq!(writes, vec![Event::Write(2), Event::Write(1), Event::Write(3)]);
+    }
+
+    #[test]
+    fn nor_flash_only_clears_bits() {
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[0xf0, 0x0f]).unwrap(), 2);
+        yadon.seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(yadon.write(&[0x05]).unwrap(), 1);
+        let mut target = Cursor::new(vec![0xff; 4]);
+        assert_eq!(yadon.apply_nor_flash(&mut target, &ApplyOptions::default()).unwrap(), 3);
+        assert_eq!(target.get_ref(), &[0xf0, 0x05, 0xff, 0xff]);
+
+        // Setting a bit fails before anything of that write is written, but earlier writes stay applied.
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.fill(0x00, 2).unwrap();
+        assert_eq!(yadon.write(&[0x00, 0x70, 0x80]).unwrap(), 3);
+        let mut target = Cursor::new(vec![0xff, 0xff, 0x0f, 0x0f, 0xf0]);
+        assert!(matches!(
+            yadon.apply_nor_flash(&mut target, &ApplyOptions::default()),
+            Err(ApplyError::WouldSetBits { offset: 3, old: 0x0f, new: 0x70 }),
+        ));
+        assert_eq!(target.get_ref(), &[0x00, 0x00, 0x0f, 0x0f, 0xf0]);
+
+        // Bytes past the end are taken to be erased.
+        let mut target = Cursor::new(vec![0x00, 0x00, 0x00, 0xf0]);
+        assert_eq!(yadon.apply_nor_flash(&mut target, &ApplyOptions::default()).unwrap(), 5);
+        assert_eq!(target.get_ref(), &[0x00, 0x00, 0x00, 0x70, 0x80]);
+    }
+
+    #[test]
+    fn nor_flash_violations_are_all_found() {
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[0x0f, 0x3c, 0x01]).unwrap(), 3);
+        // The earlier write clears a bit this one would set.
+        yadon.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(yadon.write(&[0x1f]).unwrap(), 1);
+        yadon.copy_within(1, 4, 2).unwrap();
+        let mut target = Cursor::new(vec![0xff, 0x33, 0xff, 0xff, 0x0f]);
+        target.set_position(2);
+        let violations = yadon.nor_flash_violations(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(violations, vec![
+            BitViolation { offset: 1, old: 0x33, new: 0x3c },
+            BitViolation { offset: 0, old: 0x0f, new: 0x1f },
+            BitViolation { offset: 4, old: 0x0f, new: 0x3c },
+        ]);
+        assert_eq!(target.get_ref(), &[0xff, 0x33, 0xff, 0xff, 0x0f]);
+        assert_eq!(target.position(), 2);
+        assert_eq!(yadon.applied_count(), 0);
+
+        let mut yadon = Yadon::new(Some(0), None);
+        yadon.fill(0x00, 4).unwrap();
+        assert!(yadon.nor_flash_violations(&mut target, &ApplyOptions::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn blockwise_writes_whole_dirty_blocks() {
+        let mut yadon = Yadon::new(Some(0), None).with_gap_fill(0xff);
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        yadon.seek(SeekFrom::Start(6)).unwrap();
+        yadon.fill(9, 6).unwrap();
+        yadon.seek(SeekFrom::Start(17)).unwrap();
+        assert_eq!(yadon.write(&[7, 7, 7]).unwrap(), 3);
+        yadon.seek(SeekFrom::Start(22)).unwrap();
+        assert_eq!(yadon.write(&[6]).unwrap(), 1);
+        // Overwrites part of the first write, in a block which is already dirty.
+        yadon.seek(SeekFrom::Start(1)).unwrap();
+        assert_eq!(yadon.write(&[5]).unwrap(), 1);
+        assert_eq!(yadon.dirty_blocks(4).unwrap(), vec![0, 1, 2, 4, 5]);
+
+        let mut target = EventLog::new(18);
+        target.inner.get_mut()[12..18].copy_from_slice(&[3; 6]);
+        let report = yadon.apply_blockwise(&mut target, 4).unwrap();
+        assert_eq!(report, BlockReport { blocks_written: 5, bytes_written: 19 });
+        assert_eq!(target.inner.get_ref(), &[
+            1, 5, 0, 0, 0, 0, 9, 9, 9, 9, 9, 9, 3, 3, 3, 3, 3, 7, 7, 7, 0xff, 0xff, 6,
+        ]);
+        let writes: Vec<Event> = target.events.into_iter().filter(|event| matches!(event, Event::Write(_))).collect();
+        assert_eq!(writes, vec![Event::Write(4), Event::Write(4), Event::Write(4), Event::Write(4), Event::Write(3)]);
+
+        assert!(matches!(yadon.dirty_blocks(0), Err(ApplyError::Io(error)) if error.kind() == std::io::ErrorKind::InvalidInput));
+        yadon.seek(SeekFrom::Current(-1)).unwrap();
+        yadon.copy_within(0, 1, 1).unwrap();
+        assert!(matches!(yadon.dirty_blocks(4), Err(ApplyError::OrderDependent("copy_within"))));
+    }
+
+    #[test]
+    fn apply_verified_reads_back() {
+        let mut crc = crate::crc::Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xcbf4_3926);
+
+        let mut yadon = Yadon::new(Some(0), None);
+        assert_eq!(yadon.write(&[1, 2, 3, 4, 5, 6]).unwrap(), 6);
+        yadon.write_repeated(&[7, 8, 9], 2).unwrap();
+        assert_eq!(yadon.seek(SeekFrom::Start(4)).unwrap(), 4);
+        yadon.fill(10, 4).unwrap();
+        yadon.copy_within(0, 11, 2).unwrap();
+
+        let mut target = Cursor::new(vec![0u8; 14]);
+        assert_eq!(yadon.apply_verified(&mut target, &ApplyOptions::default()).unwrap(), 18);
+        assert_eq!(target.get_ref(), &[1, 2, 3, 4, 10, 10, 10, 10, 9, 7, 8, 1, 2, 0]);
+        assert_eq!(target.position(), 13);
+
+        // The torn ends of the first write and the repeat are overwritten later, but the end of the fill isn't.
+        let mut target = EventLog::new(16);
+        target.torn_writes = true;
+        match yadon.apply_verified(&mut target, &ApplyOptions::default()) {
+            Err(ApplyError::VerificationFailed { offset, len }) => assert_eq!((offset, len), (4, 4)),
+            res => panic!("Apply did not fail verification: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn base_checksums_required() {
+        let base: Vec<u8> = (0..32).collect();
+        let mut yadon = Yadon::new(Some(4), None);
+        assert_eq!(yadon.write(&[9; 4]).unwrap(), 4);
+        let header = yadon.base_checksum(&mut Cursor::new(&base), 0..4).unwrap();
+        let whole = yadon.base_checksum(&mut Cursor::new(&base), 0..u64::MAX).unwrap();
+        let mut crc = crate::crc::Crc32::new();
+        crc.update(&base);
+        assert_eq!(whole, crc.finish());
+        yadon.require_base_checksum(0..4, header);
+        yadon.require_base_checksum(0..u64::MAX, whole);
+
+        let mut target = Cursor::new(base.clone());
+        target.seek(SeekFrom::Start(20)).unwrap();
+        yadon.check_preconditions(&mut target).unwrap();
+        assert_eq!(target.position(), 20);
+        assert_eq!(yadon.apply_readable(&mut target, &ApplyOptions::default()).unwrap(), 4);
+        assert_eq!(&target.get_ref()[4..8], &[9; 4]);
+
+        // Applying again finds the whole image changed, but the header still as it was.
+        match yadon.apply_readable(&mut target, &ApplyOptions::default()) {
+            Err(ApplyError::BaseChecksumMismatch(mismatches)) => {
+                assert_eq!(mismatches.len(), 1);
+                assert_eq!((mismatches[0].range.clone(), mismatches[0].expected), (0..32, whole));
+                let error = ApplyError::BaseChecksumMismatch(mismatches);
+                assert!(error.to_string().starts_with("target isn't the expected base image: 0..32 has checksum 0x"));
+            },
+            res => panic!("Apply did not fail with a checksum mismatch: {:?}", res),
+        }
+
+        let mut target = Cursor::new(base);
+        assert!(matches!(yadon.apply_with_options(&mut target, &ApplyOptions::default()), Err(ApplyError::UnsupportedOperation(_))));
+        assert_eq!(&target.get_ref()[4..8], &[4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn dry_run_against_length() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::End(-4)).unwrap(), 12);
+        assert_eq!(yadon.write(&[2; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(8)).unwrap(), 8);
+        yadon.fill(3, 6).unwrap();
+
+        let report = yadon.dry_run(16).unwrap();
+        assert!(report.fits());
+        assert_eq!((report.bytes_written, report.max_offset), (14, 16));
+
+        let report = yadon.dry_run(10).unwrap();
+        assert!(!report.fits());
+        assert_eq!(report.bytes_written, 4 + 4 + 2);
+        assert_eq!(report.max_offset, 10);
+        assert_eq!(report.diverging_seeks, vec![DivergingSeek { op_index: 1, expected: 12, actual: 6 }]);
+        assert_eq!(report.truncated_writes, vec![TruncatedWrite { op_index: 4, offset: 8, len: 6, fits: 2 }]);
+
+        assert_eq!(yadon.dry_run(2).unwrap_err(), DryRunError::OutOfRange(1));
+
+        let mut grown = Yadon::new(Some(0), Some(16)).with_length_mode(LengthMode::Growable);
+        assert_eq!(grown.write(&[1; 20]).unwrap(), 20);
+        assert_eq!(grown.seek(SeekFrom::End(0)).unwrap(), 20);
+        let report = grown.dry_run(8).unwrap();
+        assert!(report.fits());
+        assert_eq!(report.max_offset, 20);
+    }
+
+    #[test]
+    fn validated_against_target() {
+        let mut yadon = Yadon::new(Some(0), Some(16));
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::End(-4)).unwrap(), 12);
+        assert_eq!(yadon.write(&[2; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Current(-8)).unwrap(), 8);
+        yadon.fill(3, 10).unwrap();
+
+        let mut target = EventLog::new(16);
+        target.inner.set_position(5);
+        let report = yadon.validate_against(&mut target).unwrap();
+        assert_eq!(report, ValidationReport {
+            target_len: 16,
+            seeks_issued: 3,
+            bytes_written: 16,
+            truncated_writes: vec![],
+        });
+        assert_eq!(target.inner.position(), 5);
+        assert_eq!(target.inner.get_ref(), &[0; 16]);
+        assert_eq!(target.events, vec![
+            Event::Seek(SeekFrom::Current(0)),
+            Event::Seek(SeekFrom::End(0)),
+            Event::Seek(SeekFrom::Start(0)),
+            Event::Seek(SeekFrom::Start(12)),
+            Event::Seek(SeekFrom::Start(8)),
+            Event::Seek(SeekFrom::Start(5)),
+        ]);
+
+        let mut target = Cursor::new(vec![0u8; 14]);
+        match yadon.validate_against(&mut target) {
+            Err(ApplyError::SeekDiverged(confusion)) => {
+                assert_eq!((confusion.op_index, confusion.expected, confusion.actual), (Some(1), 12, 10));
+            },
+            res => panic!("Validation did not fail with a divergence: {:?}", res),
+        }
+        assert_eq!(target.position(), 0);
+    }
+
+    #[test]
+    fn applies_counted_and_limited() {
+        let mut yadon = Yadon::new(Some(0), Some(4)).with_max_applies(Some(2));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.applied_count(), 0);
+
+        // A failed apply isn't counted.
+        assert!(yadon.apply(&mut Cursor::new(&mut [0u8; 1][..]), true).is_err());
+        assert_eq!(yadon.applied_count(), 0);
+        yadon.apply(&mut Cursor::new(vec![0u8; 4]), true).unwrap();
+        yadon.apply_range(&mut Cursor::new(vec![0u8; 4]), 0..4, &ApplyOptions::default()).unwrap();
+        assert_eq!(yadon.applied_count(), 2);
+
+        let mut target = Cursor::new(vec![0u8; 4]);
+        match yadon.apply(&mut target, true) {
+            Err(ApplyError::ApplyLimitReached { applied, max_applies }) => assert_eq!((applied, max_applies), (2, 2)),
+            res => panic!("Apply was not limited: {:?}", res),
+        }
+        assert_eq!(target.get_ref(), &[0; 4]);
+
+        yadon.set_max_applies(None);
+        assert_eq!(yadon.apply_once(&mut target, CheckPolicy::Strict).unwrap(), 2);
+        assert_eq!(target.get_ref(), &[1, 2, 0, 0]);
+    }
+
+    #[test]
+    fn generation_stamped() {
+        let mut yadon = Yadon::new(Some(0), Some(16)).with_generation_stamp(8, 2);
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+
+        let mut target = Cursor::new(vec![0u8; 16]);
+        assert_eq!(yadon.apply_readable(&mut target, &ApplyOptions::default()).unwrap(), 4);
+        assert_eq!(target.position(), 4);
+        assert_eq!(target.get_ref(), &[1, 1, 1, 1, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+
+        match yadon.apply_readable(&mut target, &ApplyOptions::default()) {
+            Err(ApplyError::GenerationNotNewer { stored, generation }) => assert_eq!((stored, generation), (2, 2)),
+            res => panic!("Apply did not fail with an old generation: {:?}", res),
+        }
+        assert!(matches!(yadon.apply(&mut Cursor::new(vec![0u8; 16]), true), Err(ApplyError::UnsupportedOperation(_))));
+
+        let mut newer = Yadon::new(Some(0), Some(16)).with_generation_stamp(8, 3);
+        assert_eq!(newer.write(&[5; 2]).unwrap(), 2);
+        newer.apply_readable(&mut target, &ApplyOptions::default()).unwrap();
+        assert_eq!(target.get_ref(), &[5, 5, 1, 1, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+
+        // A torn stamp is caught by verification.
+        let stamp_only = Yadon::new(Some(0), Some(16)).with_generation_stamp(8, 1 << 56);
+        let mut target = EventLog::new(16);
+        target.torn_writes = true;
+        match stamp_only.apply_verified(&mut target, &ApplyOptions::default()) {
+            Err(ApplyError::VerificationFailed { offset, len }) => assert_eq!((offset, len), (8, 8)),
+            res => panic!("Apply did not fail verification: {:?}", res),
+        }
+
+        let stamp_past_end = Yadon::new(Some(0), Some(12)).with_generation_stamp(8, 1);
+        assert!(matches!(stamp_past_end.apply_readable(&mut Cursor::new(vec![0u8; 16]), &ApplyOptions::default()), Err(ApplyError::Io(_))));
+    }
+
+    #[test]
+    fn applied_prefix_detected() {
+        let mut yadon = Yadon::new(Some(1), Some(16));
+        assert_eq!(yadon.write(&[1; 3]).unwrap(), 3);
+        assert_eq!(yadon.write(&[]).unwrap(), 0);
+        assert_eq!(yadon.write(&[2; 3]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::Current(2)).unwrap(), 9);
+        assert_eq!(yadon.write(&[3; 4]).unwrap(), 4);
+
+        let mut target = Cursor::new(vec![0, 1, 1, 1, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        target.set_position(5);
+        let report = yadon.detect_applied(&mut target).unwrap();
+        assert_eq!(report, DetectReport { ops_applied: 2, first_mismatch: Some(2), position: 4 });
+        assert_eq!(target.position(), 5);
+
+        let mut session = yadon.resume_session(ApplyOptions::default(), report.session_state());
+        assert_eq!(session.resume(&mut target).unwrap(), 7);
+        assert_eq!(target.get_ref(), &[0, 1, 1, 1, 2, 2, 2, 0, 0, 3, 3, 3, 3, 0, 0, 0]);
+        assert_eq!(yadon.detect_applied(&mut target).unwrap(), DetectReport { ops_applied: 5, first_mismatch: None, position: 13 });
+
+        let mut untouched = Cursor::new(vec![0u8; 16]);
+        assert_eq!(yadon.detect_applied(&mut untouched).unwrap().ops_applied, 0);
+        let mut short = Cursor::new(vec![1u8; 3]);
+        assert_eq!(yadon.detect_applied(&mut short).unwrap().first_mismatch, Some(0));
+    }
+
+    #[test]
+    fn streamed_without_seeking() {
+        let mut yadon = Yadon::new(Some(2), Some(12)).with_generation_stamp(12, 7);
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        assert_eq!(yadon.seek(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(yadon.fill(2, 4).unwrap(), 4);
+        yadon.flush().unwrap();
+
+        let mut out = Vec::new();
+        yadon.apply_streaming(&mut out, None::<&[u8]>, 24, 0xff).unwrap();
+        assert_eq!(out, [0xff, 0xff, 1, 1, 2, 2, 2, 2, 0xff, 0xff, 0xff, 0xff, 7, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(yadon.applied_count(), 1);
+
+        let mut out = Vec::new();
+        yadon.apply_streaming(&mut out, Some(&[9u8; 10][..]), 11, 0xff).unwrap();
+        assert_eq!(out, [9, 9, 1, 1, 2, 2, 2, 2, 9, 9, 0xff]);
+
+        assert_eq!(yadon.copy_within(0, 8, 2).unwrap(), 2);
+        assert!(matches!(yadon.apply_streaming(Vec::new(), None::<&[u8]>, 8, 0), Err(ApplyError::OrderDependent(_))));
+    }
+
+    /// Applies `yadon` to a slice of `len` bytes both directly and through a `Cursor`, checking they agree.
+    fn assert_slice_matches_cursor(yadon: &Yadon, len: usize, check_policy: CheckPolicy) -> Vec<u8> {
+        let mut expected = vec![0xaa; len];
+        let expected_res = yadon.apply_with_policy(&mut Cursor::new(&mut expected[..]), check_policy);
+        let mut target = vec![0xaa; len];
+        let res = yadon.apply_to_slice(&mut target, check_policy);
+        // IO errors are built differently, so only what they say is compared.
+        assert_eq!(res.map_err(|error| error.to_string()), expected_res.map_err(|error| error.to_string()));
+        assert_eq!(target, expected);
+        target
+    }
+
+    #[test]
+    fn applied_to_slice() {
+        let mut yadon = Yadon::new(Some(1), Some(10));
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).unwrap(), 8);
+        assert_eq!(yadon.write(&[4, 5]).unwrap(), 2);
+        assert_eq!(yadon.fill(6, 2).unwrap(), 0);
+        for policy in [CheckPolicy::Strict, CheckPolicy::SeeksOnly, CheckPolicy::WritesOnly, CheckPolicy::None] {
+            assert_slice_matches_cursor(&yadon, 10, policy);
+            assert_slice_matches_cursor(&yadon, 9, policy);
+            assert_slice_matches_cursor(&yadon, 12, policy);
+            assert_slice_matches_cursor(&yadon, 0, policy);
+        }
+        assert_eq!(assert_slice_matches_cursor(&yadon, 9, CheckPolicy::None), [0xaa, 1, 2, 3, 0xaa, 0xaa, 0xaa, 4, 5]);
+
+        let mut array = [0u8; 10];
+        assert_eq!(yadon.apply_to_array(&mut array, CheckPolicy::Strict).unwrap(), 5);
+        assert_eq!(array, [0, 1, 2, 3, 0, 0, 0, 0, 4, 5]);
+    }
+
+    #[test]
+    fn materialized() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        assert_eq!(yadon.seek(SeekFrom::Start(2)).unwrap(), 2);
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.copy_within(2, 5, 2).unwrap(), 2);
+        yadon.sync_barrier().unwrap();
+        assert_eq!(yadon.materialize(None).unwrap(), [0, 0, 1, 2, 0, 1, 2, 0]);
+        assert_eq!(yadon.materialize(Some(&[9; 12])).unwrap(), [9, 9, 1, 2, 9, 1, 2, 9]);
+        assert_eq!(yadon.materialize(Some(&[9; 3])).unwrap(), [9, 9, 1, 2, 0, 1, 2, 0]);
+
+        let mut buf = vec![7; 32];
+        let yadon = yadon.with_gap_fill(0xff);
+        yadon.materialize_into(&mut buf).unwrap();
+        assert_eq!(buf, [0xff, 0xff, 1, 2, 0xff, 1, 2, 0xff]);
+        assert!(buf.capacity() >= 32);
+
+        let mut unbounded = Yadon::new(Some(4), None);
+        assert_eq!(unbounded.write(&[3; 2]).unwrap(), 2);
+        assert_eq!(unbounded.materialize(None).unwrap(), [0, 0, 0, 0, 3, 3]);
+        assert_eq!(unbounded.materialize(Some(&[5; 8])).unwrap(), [5, 5, 5, 5, 3, 3, 5, 5]);
+
+        let mut precondition = Yadon::new(Some(0), Some(4));
+        precondition.assert_bytes_at(0, &[1]).unwrap();
+        assert!(matches!(precondition.materialize(None), Err(MaterializeError::Apply(ApplyError::PreconditionFailed { .. }))));
+        assert_eq!(precondition.materialize(Some(&[1])).unwrap(), [1, 0, 0, 0]);
+
+        assert!(matches!(Yadon::new(Some(0), Some(u64::MAX)).materialize(None), Err(MaterializeError::TooLarge(u64::MAX))));
+    }
+
+    struct Mapping {
+        bytes: Vec<u8>,
+        flushed: Vec<(usize, usize)>,
+    }
+
+    impl MappedTarget for Mapping {
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            &mut self.bytes
+        }
+
+        fn flush_range(&mut self, offset: usize, len: usize) -> std::io::Result<()> {
+            self.flushed.push((offset, len));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn applied_to_mapping() {
+        let mut yadon = Yadon::new(Some(1), Some(10));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.fill(3, 2).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::End(-2)).unwrap(), 8);
+        assert_eq!(yadon.write(&[4, 5]).unwrap(), 2);
+        yadon.flush().unwrap();
+        assert_eq!(yadon.seek(SeekFrom::Start(0)).unwrap(), 0);
+        assert_eq!(yadon.write(&[6]).unwrap(), 1);
+
+        let mut map = Mapping { bytes: vec![0; 10], flushed: vec![] };
+        assert_eq!(yadon.apply_to_mapping(&mut map, CheckPolicy::Strict, MapFlush::PerExtent).unwrap(), 7);
+        assert_eq!(map.bytes, [6, 1, 2, 3, 3, 0, 0, 0, 4, 5]);
+        assert_eq!(map.flushed, [(1, 4), (8, 2), (0, 1)]);
+
+        let mut map = Mapping { bytes: vec![0; 10], flushed: vec![] };
+        yadon.apply_to_mapping(&mut map, CheckPolicy::Strict, MapFlush::AtEnd).unwrap();
+        assert_eq!(map.flushed, [(0, 10)]);
+
+        for policy in [CheckPolicy::Strict, CheckPolicy::None] {
+            let mut map = Mapping { bytes: vec![0xaa; 9], flushed: vec![] };
+            let res = yadon.apply_to_mapping(&mut map, policy, MapFlush::Never);
+            let mut expected = vec![0xaa; 9];
+            assert_eq!(format!("{:?}", res), format!("{:?}", yadon.apply_to_slice(&mut expected, policy)));
+            assert_eq!(map.bytes, expected);
+            assert!(map.flushed.is_empty());
+        }
+    }
+
+    #[cfg(all(feature = "fs", unix))]
+    #[test]
+    fn applied_positionally() {
+        let path = std::env::temp_dir().join(format!("yadon-applied-positionally-{}", std::process::id()));
+        std::fs::write(&path, [0xaa; 8]).unwrap();
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        assert_eq!(file.seek(SeekFrom::Start(3)).unwrap(), 3);
+
+        let mut yadon = Yadon::new(None, Some(8));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::End(-1)).unwrap(), 7);
+        assert_eq!(yadon.write(&[3, 4]).unwrap(), 1);
+        assert_eq!(yadon.copy_within(0, 4, 2).unwrap(), 2);
+        assert_eq!(yadon.apply_positional(&file, CheckPolicy::Strict).unwrap(), 5);
+        assert_eq!(file.stream_position().unwrap(), 3);
+        assert_eq!(std::fs::read(&path).unwrap(), &[1, 2, 0xaa, 0xaa, 1, 2, 0xaa, 3]);
+
+        // The file has grown since recording, so the seek from its end diverges.
+        file.set_len(10).unwrap();
+        assert!(matches!(yadon.apply_positional(&file, CheckPolicy::Strict), Err(ApplyError::SeekDiverged(_))));
+        assert_eq!(file.stream_position().unwrap(), 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn applied_write_at() {
+        let mut yadon = Yadon::new(Some(2), Some(8));
+        assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::End(-1)).unwrap(), 7);
+        assert_eq!(yadon.write(&[3]).unwrap(), 1);
+        assert_eq!(yadon.seek(SeekFrom::Current(-6)).unwrap(), 2);
+        assert_eq!(yadon.fill(4, 3).unwrap(), 3);
+
+        for len in [0, 5, 8, 12] {
+            let mut expected = Cursor::new(vec![0xaa; len]);
+            let expected_res = yadon.apply(&mut expected, true);
+            let mut target = vec![0xaa; len];
+            let res = yadon.apply_write_at(&mut target, None);
+            assert_eq!(format!("{:?}", res), format!("{:?}", expected_res));
+            assert_eq!(&target, expected.get_ref());
+        }
+
+        let mut target = vec![0xaa; 4];
+        assert_eq!(yadon.apply_write_at(&mut target, Some(8)).unwrap(), 6);
+        assert_eq!(target, [0xaa, 0xaa, 4, 4, 4, 0, 0, 3]);
+
+        struct Unsized(Vec<u8>);
+        impl WriteAt for Unsized {
+            fn write_at(&mut self, pos: u64, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.write_at(pos, buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        match yadon.apply_write_at(&mut Unsized(vec![]), None) {
+            Err(ApplyError::Io(error)) => assert_eq!(error.kind(), std::io::ErrorKind::Unsupported),
+            res => panic!("Apply did not fail without a length: {:?}", res),
+        }
+    }
+
+    #[cfg(all(feature = "fs", unix))]
+    #[test]
+    fn applied_in_parallel() {
+        let path = std::env::temp_dir().join(format!("yadon-applied-in-parallel-{}", std::process::id()));
+        let file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        let mut yadon = Yadon::new(Some(0), None);
+        for i in 0..64u8 {
+            yadon.seek(SeekFrom::Start((63 - i as u64) * 1000)).unwrap();
+            match i % 3 {
+                0 => yadon.write_all(&[i; 700]).unwrap(),
+                1 => assert_eq!(yadon.fill(i, 900).unwrap(), 900),
+                _ => assert_eq!(yadon.write_repeated(&[i, 0xff], 300).unwrap(), 600),
+            }
+        }
+        let expected = yadon.materialize(None).unwrap();
+        for threads in [0, 1, 3, 8, 100] {
+            file.set_len(0).unwrap();
+            assert_eq!(yadon.apply_parallel(&file, threads).unwrap(), 22 * 700 + 21 * 900 + 21 * 600);
+            assert_eq!(std::fs::read(&path).unwrap(), expected);
+        }
+
+        // Overlapping writes are applied in order instead.
+        yadon.seek(SeekFrom::Start(10)).unwrap();
+        yadon.write_all(&[0xee; 4]).unwrap();
+        let expected = yadon.materialize(None).unwrap();
+        file.set_len(0).unwrap();
+        yadon.apply_parallel(&file, 4).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+        assert_eq!(yadon.applied_count(), 8);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn applied_to_every_target() {
+        let mut yadon = Yadon::new(Some(1), Some(8));
+        assert_eq!(yadon.write(&[1; 4]).unwrap(), 4);
+        yadon.flush().unwrap();
+
+        let mut targets = vec![EventLog::new(8), EventLog::new(8), EventLog::new(8)];
+        targets[1].writes_before_failure = Some(0);
+        let results = yadon.apply_all(&mut targets, &ApplyOptions::default(), false);
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], (0, Ok(ref report)) if report.bytes_written == 4 && report.final_position == 5));
+        assert!(matches!(results[1], (1, Err(ApplyError::Io(_)))));
+        assert!(matches!(results[2], (2, Ok(_))));
+        let mut single = EventLog::new(8);
+        yadon.apply_report(&mut single, &ApplyOptions::default()).unwrap();
+        for target in [&targets[0], &targets[2]] {
+            assert_eq!(target.inner.get_ref(), &[0, 1, 1, 1, 1, 0, 0, 0]);
+            assert_eq!(target.events, single.events);
+        }
+        assert_eq!(yadon.applied_count(), 3);
+
+        let mut targets = vec![EventLog::new(8), EventLog::new(8), EventLog::new(8)];
+        targets[1].writes_before_failure = Some(0);
+        let results = yadon.apply_all(&mut targets, &ApplyOptions::default(), true);
+        assert_eq!(results.len(), 2);
+        assert!(results[1].1.is_err());
+        assert!(targets[2].events.is_empty());
+    }
+
+    /// Target shared with the test, whose writes wait for `gate` until it's disconnected.
+    struct Gated {
+        inner: Arc<std::sync::Mutex<Cursor<Vec<u8>>>>,
+        gate: std::sync::mpsc::Receiver<()>,
+    }
+
+    impl Write for Gated {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let _ = self.gate.recv();
+            self.inner.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for Gated {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.inner.lock().unwrap().seek(pos)
+        }
+    }
+
+    #[test]
+    fn applied_in_background() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        for i in 0..4 {
+            assert_eq!(yadon.write(&[i; 2]).unwrap(), 2);
+        }
+        let yadon = Arc::new(yadon);
+
+        let inner = Arc::new(std::sync::Mutex::new(Cursor::new(vec![0u8; 8])));
+        let (open, gate) = std::sync::mpsc::channel();
+        let handle = yadon.clone().apply_in_background(Gated { inner: inner.clone(), gate }, ApplyOptions::default());
+        assert_eq!(handle.try_progress(), Progress { ops_completed: 0, total_ops: 4, bytes_written: 0, total_bytes: 8 });
+        assert!(!handle.is_finished());
+        open.send(()).unwrap();
+        while handle.try_progress().ops_completed < 1 {
+            std::thread::yield_now();
+        }
+        assert_eq!(handle.try_progress().bytes_written, 2);
+        drop(open);
+        let report = handle.join().unwrap();
+        assert_eq!((report.ops_applied, report.bytes_written), (4, 8));
+        assert_eq!(inner.lock().unwrap().get_ref(), &[0, 0, 1, 1, 2, 2, 3, 3]);
+
+        // Holding the target keeps the worker at its first seek, so it can't have got past checking for cancellation.
+        let (open, gate) = std::sync::mpsc::channel();
+        let held = inner.lock().unwrap();
+        let handle = yadon.clone().apply_in_background(Gated { inner: inner.clone(), gate }, ApplyOptions::default());
+        handle.cancel();
+        drop(held);
+        drop(open);
+        assert!(matches!(handle.join(), Err(ApplyError::Cancelled { .. })));
+
+        // A dropped handle leaves the worker to finish.
+        inner.lock().unwrap().get_mut().fill(0);
+        let (open, gate) = std::sync::mpsc::channel();
+        drop(yadon.clone().apply_in_background(Gated { inner: inner.clone(), gate }, ApplyOptions::default()));
+        drop(open);
+        while Arc::strong_count(&yadon) > 1 {
+            std::thread::yield_now();
+        }
+        assert_eq!(inner.lock().unwrap().get_ref(), &[0, 0, 1, 1, 2, 2, 3, 3]);
+        assert_eq!(yadon.applied_count(), 2);
+    }
+
+    /// Asynchronous target over a `Cursor`, which returns `Poll::Pending` before every call it completes.
+    struct AsyncCursor {
+        inner: Cursor<Vec<u8>>,
+        ready: bool,
+        max_write: Option<usize>,
+    }
+
+    impl AsyncCursor {
+        fn poll<R>(&mut self, cx: &mut std::task::Context<'_>, f: impl FnOnce(&mut Cursor<Vec<u8>>) -> R) -> std::task::Poll<R> {
+            self.ready = !self.ready;
+            if self.ready {
+                cx.waker().wake_by_ref();
+                return std::task::Poll::Pending;
+            }
+            std::task::Poll::Ready(f(&mut self.inner))
+        }
+    }
+
+    impl AsyncWriteSeek for AsyncCursor {
+        fn poll_write(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>, buf: &[u8]) -> std::task::Poll<std::io::Result<usize>> {
+            let buf = &buf[0..buf.len().min(self.max_write.unwrap_or(usize::MAX))];
+            self.poll(cx, |inner| inner.write(buf))
+        }
+
+        fn poll_flush(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<std::io::Result<()>> {
+            self.poll(cx, |inner| inner.flush())
+        }
+
+        fn poll_seek(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>, pos: SeekFrom) -> std::task::Poll<std::io::Result<u64>> {
+            self.poll(cx, |inner| inner.seek(pos))
+        }
+    }
+
+    /// Runs `future` to completion on this thread.
+    fn block_on<F>(future: F) -> F::Output where F: std::future::Future {
+        struct Noop;
+        impl std::task::Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+        let waker = std::task::Waker::from(Arc::new(Noop));
+        let mut cx = std::task::Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn applied_asynchronously() {
+        let mut yadon = Yadon::new(Some(1), Some(16));
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.fill(4, 2).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::End(-4)).unwrap(), 12);
+        assert_eq!(yadon.write_repeated(&[5, 6], 2).unwrap(), 4);
+        yadon.flush().unwrap();
+
+        for (len, max_write) in [(16, None), (14, None), (16, Some(2))] {
+            let mut expected = EventLog::new(len);
+            expected.max_write = max_write;
+            let expected_res = yadon.apply_with_policy(&mut expected, CheckPolicy::Strict);
+            let mut target = AsyncCursor { inner: Cursor::new(vec![0; len]), ready: false, max_write };
+            let res = block_on(yadon.apply_async(&mut target, CheckPolicy::Strict));
+            assert_eq!(format!("{:?}", res), format!("{:?}", expected_res));
+            assert_eq!(target.inner.get_ref(), expected.inner.get_ref());
+        }
+
+        let mut target = AsyncCursor { inner: Cursor::new(vec![0; 14]), ready: false, max_write: None };
+        assert_eq!(block_on(yadon.apply_async(&mut target, CheckPolicy::WritesOnly)).unwrap(), 9);
+
+        yadon.sync_barrier().unwrap();
+        assert!(matches!(block_on(yadon.apply_async(&mut target, CheckPolicy::Strict)), Err(ApplyError::UnsupportedOperation("sync"))));
+    }
+
+    #[test]
+    fn recorded_asynchronously() {
+        async fn serialize<W>(out: &mut W) -> std::io::Result<u64> where W: AsyncWriteSeek + Unpin {
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut *out).poll_write(cx, &[1, 2, 3])).await?;
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut *out).poll_seek(cx, SeekFrom::End(-1))).await?;
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut *out).poll_write(cx, &[4])).await?;
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut *out).poll_flush(cx)).await?;
+            std::future::poll_fn(|cx| std::pin::Pin::new(&mut *out).poll_seek(cx, SeekFrom::Current(0))).await
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(6));
+        assert_eq!(block_on(serialize(&mut yadon)).unwrap(), 6);
+        assert_eq!(yadon.operations.len(), 5);
+        assert_eq!(yadon.materialize(None).unwrap(), [1, 2, 3, 0, 0, 4]);
+
+        let mut unbounded = Yadon::new(Some(0), None);
+        assert_eq!(block_on(serialize(&mut unbounded)).unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+    }
+
+    /// Target with `embedded_io`'s shape, over a buffer which doesn't grow. Writes at the end fail with
+    /// `ErrorKind::WriteZero`, as `embedded_io` requires.
+    #[cfg(feature = "embedded-io")]
+    struct EmbeddedSlice {
+        data: Vec<u8>,
+        position: u64,
+        /// The most bytes a single write will accept, if limited.
+        max_write: Option<usize>,
+        /// Whether every other call fails with `ErrorKind::Interrupted`.
+        interrupting: bool,
+        interrupted: bool,
+        /// How far past where they should land seeks end up.
+        seek_skew: u64,
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl EmbeddedSlice {
+        fn new(len: usize) -> Self {
+            EmbeddedSlice { data: vec![0; len], position: 0, max_write: None, interrupting: false, interrupted: false, seek_skew: 0 }
+        }
+
+        fn interrupt(&mut self) -> Result<(), embedded::ErrorKind> {
+            self.interrupted = self.interrupting && !self.interrupted;
+            match self.interrupted {
+                true => Err(embedded::ErrorKind::Interrupted),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded::ErrorType for EmbeddedSlice {
+        type Error = embedded::ErrorKind;
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded::Write for EmbeddedSlice {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, embedded::ErrorKind> {
+            self.interrupt()?;
+            let position = (self.position as usize).min(self.data.len());
+            let len = buf.len().min(self.data.len() - position).min(self.max_write.unwrap_or(usize::MAX));
+            if len == 0 && !buf.is_empty() {
+                return Err(embedded::ErrorKind::WriteZero);
+            }
+            self.data[position..position + len].copy_from_slice(&buf[0..len]);
+            self.position += len as u64;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> Result<(), embedded::ErrorKind> {
+            self.interrupt()
+        }
+    }
+
+    #[cfg(feature = "embedded-io")]
+    impl embedded::Seek for EmbeddedSlice {
+        fn seek(&mut self, pos: embedded::SeekFrom) -> Result<u64, embedded::ErrorKind> {
+            self.interrupt()?;
+            let (base, offset) = match pos {
+                embedded::SeekFrom::Start(offset) => (offset, 0),
+                embedded::SeekFrom::End(offset) => (self.data.len() as u64, offset),
+                embedded::SeekFrom::Current(offset) => (self.position, offset),
+            };
+            self.position = base.checked_add_signed(offset).ok_or(embedded::ErrorKind::InvalidInput)? + self.seek_skew;
+            Ok(self.position)
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-io")]
+    fn applied_to_embedded_target() {
+        let mut yadon = Yadon::new(Some(1), Some(16));
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.fill(4, 2).unwrap(), 2);
+        assert_eq!(yadon.seek(SeekFrom::End(-4)).unwrap(), 12);
+        assert_eq!(yadon.write_repeated(&[5, 6], 2).unwrap(), 4);
+        yadon.flush().unwrap();
+
+        for (max_write, interrupting) in [(None, false), (Some(2), false), (None, true), (Some(3), true)] {
+            let mut expected = EventLog::new(16);
+            expected.max_write = max_write;
+            expected.interruptions = interrupting as usize;
+            let expected_res = yadon.apply_with_policy(&mut expected, CheckPolicy::Strict);
+            let mut target = EmbeddedSlice::new(16);
+            target.max_write = max_write;
+            target.interrupting = interrupting;
+            let res = yadon.apply_embedded(&mut target, CheckPolicy::Strict);
+            assert_eq!(format!("{:?}", res), format!("{:?}", expected_res));
+            assert_eq!(target.data, expected.inner.get_ref()[..]);
+        }
+        let applied = yadon.applied_count();
+        let mut target = EmbeddedSlice::new(16);
+        assert_eq!(yadon.apply_embedded(&mut &mut target, CheckPolicy::Strict).unwrap(), 9);
+        assert_eq!(yadon.applied_count(), applied + 1);
+        assert_eq!(target.data, yadon.materialize(None).unwrap());
+
+        // A write of which nothing fits is checked as a write of nothing, rather than failing the apply.
+        let mut target = EmbeddedSlice::new(1);
+        match yadon.apply_embedded(&mut target, CheckPolicy::Strict) {
+            Err(ApplyError::NumBytesWrittenDiverge(confusion)) => {
+                assert_eq!((confusion.expected, confusion.actual, confusion.op_index), (3, 0, Some(0)));
+            },
+            res => panic!("{:?}", res),
+        }
+        let mut target = EmbeddedSlice::new(3);
+        match yadon.apply_embedded(&mut target, CheckPolicy::Strict) {
+            Err(ApplyError::NumBytesWrittenDiverge(confusion)) => {
+                assert_eq!((confusion.expected, confusion.actual, confusion.op_index), (3, 2, Some(0)));
+            },
+            res => panic!("{:?}", res),
+        }
+        assert_eq!(target.data, [0, 1, 2]);
+        let mut target = EmbeddedSlice::new(4);
+        match yadon.apply_embedded(&mut target, CheckPolicy::Strict) {
+            Err(ApplyError::NumBytesWrittenDiverge(confusion)) => {
+                assert_eq!((confusion.expected, confusion.actual, confusion.op_index), (2, 0, Some(1)));
+            },
+            res => panic!("{:?}", res),
+        }
+        let mut target = EmbeddedSlice::new(4);
+        assert_eq!(yadon.apply_embedded(&mut target, CheckPolicy::None).unwrap(), 7);
+        assert_eq!(target.data, [5, 6, 5, 6]);
+
+        // Seeks are checked against where the target says they landed.
+        let mut target = EmbeddedSlice::new(14);
+        match yadon.apply_embedded(&mut target, CheckPolicy::Strict) {
+            Err(ApplyError::SeekDiverged(confusion)) => {
+                assert_eq!((confusion.expected, confusion.actual, confusion.op_index), (12, 10, Some(2)));
+            },
+            res => panic!("{:?}", res),
+        }
+        let mut target = EmbeddedSlice::new(16);
+        target.seek_skew = 1;
+        match yadon.apply_embedded(&mut target, CheckPolicy::Strict) {
+            Err(ApplyError::SeekDiverged(confusion)) => {
+                assert_eq!((confusion.expected, confusion.actual, confusion.op_index), (1, 2, None));
+            },
+            res => panic!("{:?}", res),
+        }
+        let mut target = EmbeddedSlice::new(16);
+        target.seek_skew = 1;
+        assert_eq!(yadon.apply_embedded(&mut target, CheckPolicy::None).unwrap(), 8);
+        assert_eq!(target.data, [0, 0, 1, 2, 3, 4, 4, 0, 0, 0, 0, 0, 0, 5, 6, 5]);
+
+        // Errors from the target are mapped onto `std::io`'s kinds.
+        let mut yadon = Yadon::new(None, None);
+        yadon.seek(SeekFrom::Start(2)).unwrap();
+        yadon.seek(SeekFrom::Current(-3)).unwrap_err();
+        yadon.operations.push(WriteOperation::Seek(SeekFrom::Current(-3), 0));
+        let mut target = EmbeddedSlice::new(4);
+        match yadon.apply_embedded(&mut target, CheckPolicy::Strict) {
+            Err(ApplyError::Io(error)) => assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput),
+            res => panic!("{:?}", res),
+        }
+        assert_eq!(yadon.applied_count(), 0);
+
+        let mut yadon = Yadon::new(None, None);
+        yadon.sync_barrier().unwrap();
+        let mut target = EmbeddedSlice::new(4);
+        assert!(matches!(yadon.apply_embedded(&mut target, CheckPolicy::Strict), Err(ApplyError::UnsupportedOperation("sync"))));
+        let mut yadon = Yadon::new(None, None).with_store(Vec::new()).unwrap();
+        yadon.write(&[1]).unwrap();
+        assert!(matches!(yadon.apply_embedded(&mut target, CheckPolicy::Strict), Err(ApplyError::InStore)));
+        assert_eq!(target.data, [0; 4]);
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-io")]
+    fn recorded_through_embedded_traits() {
+        fn serialize<W>(out: &mut W) -> Result<u64, W::Error> where W: embedded::Write + embedded::Seek {
+            assert_eq!(out.write(&[1, 2, 3])?, 3);
+            out.seek(embedded::SeekFrom::End(-1))?;
+            assert_eq!(out.write(&[4, 5])?, 1);
+            out.flush()?;
+            out.stream_position()
+        }
+
+        let mut yadon = Yadon::new(None, Some(4));
+        assert_eq!(serialize(&mut yadon).unwrap(), 4);
+        assert_eq!(yadon.materialize(None).unwrap(), [1, 2, 3, 4]);
+        let mut target = EmbeddedSlice::new(4);
+        assert_eq!(serialize(&mut target).unwrap(), 4);
+        assert_eq!(target.data, [1, 2, 3, 4]);
+
+        // Nothing fits at the end, so the write fails as `embedded_io` requires, without recording anything.
+        let ops = yadon.operations.len();
+        assert_eq!(embedded::Write::write(&mut yadon, &[6]), Err(embedded::ErrorKind::WriteZero));
+        assert_eq!(embedded::Write::write(&mut yadon, &[]), Ok(0));
+        assert_eq!(yadon.operations.len(), ops + 1);
+        assert_eq!(Yadon::write(&mut yadon, &[6]).unwrap(), 0);
+        yadon.set_overflow_policy(OverflowPolicy::Error);
+        assert_eq!(embedded::Write::write(&mut yadon, &[6]), Err(embedded::ErrorKind::WriteZero));
+
+        let mut yadon = Yadon::new(None, None);
+        assert_eq!(embedded::Seek::seek(&mut yadon, embedded::SeekFrom::End(0)), Err(embedded::ErrorKind::Unsupported));
+        assert_eq!(embedded::Seek::seek(&mut yadon, embedded::SeekFrom::Current(-1)), Err(embedded::ErrorKind::InvalidInput));
+        assert!(yadon.operations.is_empty());
+        assert_eq!(embedded::Write::write(&mut yadon, &[1]), Ok(1));
+        assert_eq!(embedded::Seek::stream_position(&mut yadon), Ok(1));
+        yadon.defer_end_seeks = true;
+        assert_eq!(embedded::Seek::seek(&mut yadon, embedded::SeekFrom::End(0)), Ok(1));
+        assert_eq!(SeekFrom::from(embedded::SeekFrom::Current(-2)), SeekFrom::Current(-2));
+        assert_eq!(embedded::SeekFrom::from(SeekFrom::End(3)), embedded::SeekFrom::End(3));
+    }
+
+    /// What laying recorded operations down into a slice came to, in terms which `Yadon` and `YadonFixed` share.
+    #[derive(Debug, PartialEq)]
+    enum LaidDown {
+        Written(usize),
+        SeekDiverged { op_index: usize, expected: u64, actual: u64 },
+        WriteDiverged { op_index: usize, expected: usize, actual: usize },
+        InvalidSeek,
+    }
+
+    /// Recording and laying down into a slice, which `Yadon` and `YadonFixed` must do identically.
+    trait Recorder {
+        fn new_recorder(start: Option<u64>, length: Option<u64>, overflow_policy: OverflowPolicy) -> Self;
+        fn record_write(&mut self, buf: &[u8]) -> Result<u64, std::io::ErrorKind>;
+        fn record_fill(&mut self, byte: u8, len: u64) -> Result<u64, std::io::ErrorKind>;
+        fn record_repeated(&mut self, pattern: &[u8], count: u64) -> Result<u64, std::io::ErrorKind>;
+        fn record_seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::ErrorKind>;
+        fn record_flush(&mut self) -> Result<u64, std::io::ErrorKind>;
+        fn lay_down(&self, buf: &mut [u8]) -> LaidDown;
+    }
+
+    impl Recorder for Yadon {
+        fn new_recorder(start: Option<u64>, length: Option<u64>, overflow_policy: OverflowPolicy) -> Self {
+            Yadon::new(start, length).with_overflow_policy(overflow_policy)
+        }
+
+        fn record_write(&mut self, buf: &[u8]) -> Result<u64, std::io::ErrorKind> {
+            self.write(buf).map(|len| len as u64).map_err(|error| error.kind())
+        }
+
+        fn record_fill(&mut self, byte: u8, len: u64) -> Result<u64, std::io::ErrorKind> {
+            self.fill(byte, len).map_err(|error| error.kind())
+        }
+
+        fn record_repeated(&mut self, pattern: &[u8], count: u64) -> Result<u64, std::io::ErrorKind> {
+            self.write_repeated(pattern, count).map_err(|error| error.kind())
+        }
+
+        fn record_seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::ErrorKind> {
+            self.seek(pos).map_err(|error| error.kind())
+        }
+
+        fn record_flush(&mut self) -> Result<u64, std::io::ErrorKind> {
+            self.flush().map(|_| 0).map_err(|error| error.kind())
+        }
+
+        fn lay_down(&self, buf: &mut [u8]) -> LaidDown {
+            match self.materialize_into_slice(buf) {
+                Ok(bytes_written) => LaidDown::Written(bytes_written),
+                Err(ApplyError::SeekDiverged(confusion)) => LaidDown::SeekDiverged {
+                    op_index: confusion.op_index.unwrap(),
+                    expected: confusion.expected,
+                    actual: confusion.actual,
+                },
+                Err(ApplyError::NumBytesWrittenDiverge(confusion)) => LaidDown::WriteDiverged {
+                    op_index: confusion.op_index.unwrap(),
+                    expected: confusion.expected,
+                    actual: confusion.actual,
+                },
+                Err(ApplyError::Io(error)) if error.kind() == std::io::ErrorKind::InvalidInput => LaidDown::InvalidSeek,
+                Err(error) => panic!("unexpected error: {}", error),
+            }
+        }
+    }
+
+    impl<const DATA: usize, const OPS: usize> Recorder for YadonFixed<DATA, OPS> {
+        fn new_recorder(start: Option<u64>, length: Option<u64>, overflow_policy: OverflowPolicy) -> Self {
+            YadonFixed::new(start, length).with_overflow_policy(overflow_policy)
+        }
+
+        fn record_write(&mut self, buf: &[u8]) -> Result<u64, std::io::ErrorKind> {
+            self.write(buf).map(|len| len as u64).map_err(fixed_error_kind)
+        }
+
+        fn record_fill(&mut self, byte: u8, len: u64) -> Result<u64, std::io::ErrorKind> {
+            self.fill(byte, len).map_err(fixed_error_kind)
+        }
+
+        fn record_repeated(&mut self, pattern: &[u8], count: u64) -> Result<u64, std::io::ErrorKind> {
+            self.write_repeated(pattern, count).map_err(fixed_error_kind)
+        }
+
+        fn record_seek(&mut self, pos: SeekFrom) -> Result<u64, std::io::ErrorKind> {
+            self.seek(pos).map_err(fixed_error_kind)
+        }
+
+        fn record_flush(&mut self) -> Result<u64, std::io::ErrorKind> {
+            self.flush().map(|_| 0).map_err(fixed_error_kind)
+        }
+
+        fn lay_down(&self, buf: &mut [u8]) -> LaidDown {
+            match self.materialize_into(buf) {
+                Ok(bytes_written) => LaidDown::Written(bytes_written),
+                Err(FixedError::SeekDiverged { op_index, expected, actual }) => LaidDown::SeekDiverged { op_index, expected, actual },
+                Err(FixedError::WriteDiverged { op_index, expected, actual }) => LaidDown::WriteDiverged { op_index, expected, actual },
+                Err(FixedError::Io(std::io::ErrorKind::InvalidInput)) => LaidDown::InvalidSeek,
+                Err(error) => panic!("unexpected error: {}", error),
+            }
+        }
+    }
+
+    fn fixed_error_kind(error: FixedError) -> std::io::ErrorKind {
+        match error {
+            FixedError::Io(kind) => kind,
+            error => panic!("unexpected error: {}", error),
+        }
+    }
+
+    /// Cases with known results, which both recorders must give.
+    fn recorder_suite<R>() where R: Recorder {
+        // Seeks relative to the start, the current position and the end, with writes in between.
+        let mut recorder = R::new_recorder(Some(0), Some(8), OverflowPolicy::Truncate);
+        assert_eq!(recorder.record_seek(SeekFrom::Start(4)), Ok(4));
+        assert_eq!(recorder.record_write(&[1, 2, 3]), Ok(3));
+        assert_eq!(recorder.record_seek(SeekFrom::Current(0)), Ok(7));
+        assert_eq!(recorder.record_seek(SeekFrom::End(-6)), Ok(2));
+        assert_eq!(recorder.record_write(&[4, 5]), Ok(2));
+        assert_eq!(recorder.record_flush(), Ok(0));
+        let mut target = [9u8; 8];
+        assert_eq!(recorder.lay_down(&mut target), LaidDown::Written(5));
+        assert_eq!(target, [9, 9, 4, 5, 1, 2, 3, 9]);
+
+        // Writes past the length are clamped, and fills and repeats are clamped like writes.
+        let mut recorder = R::new_recorder(Some(2), Some(8), OverflowPolicy::Truncate);
+        assert_eq!(recorder.record_repeated(&[1, 2], 2), Ok(4));
+        assert_eq!(recorder.record_repeated(&[3, 4, 5], 2), Ok(2));
+        assert_eq!(recorder.record_fill(6, 4), Ok(0));
+        assert_eq!(recorder.record_seek(SeekFrom::Start(0)), Ok(0));
+        assert_eq!(recorder.record_fill(7, 1), Ok(1));
+        let mut target = [0u8; 8];
+        assert_eq!(recorder.lay_down(&mut target), LaidDown::Written(7));
+        assert_eq!(target, [7, 0, 1, 2, 1, 2, 3, 4]);
+        // A shorter target cuts the writes short, so they diverge.
+        let mut target = [0u8; 5];
+        assert_eq!(recorder.lay_down(&mut target), LaidDown::WriteDiverged { op_index: 0, expected: 4, actual: 3 });
+
+        // Seeks which can't be resolved, or would be negative, fail without moving.
+        let mut recorder = R::new_recorder(None, None, OverflowPolicy::Error);
+        assert_eq!(recorder.record_seek(SeekFrom::End(0)), Err(std::io::ErrorKind::Unsupported));
+        assert_eq!(recorder.record_seek(SeekFrom::Current(-1)), Err(std::io::ErrorKind::InvalidInput));
+        assert_eq!(recorder.record_write(&[1]), Ok(1));
+        assert_eq!(recorder.record_seek(SeekFrom::Current(0)), Ok(1));
+        assert_eq!(recorder.record_seek(SeekFrom::Start(u64::MAX)), Ok(u64::MAX));
+        assert_eq!(recorder.record_write(&[1]), Err(std::io::ErrorKind::InvalidInput));
+        assert_eq!(recorder.record_repeated(&[1, 2], u64::MAX), Err(std::io::ErrorKind::InvalidInput));
+
+        // With `OverflowPolicy::Error`, writes which don't fit fail, and aren't recorded.
+        let mut recorder = R::new_recorder(Some(0), Some(4), OverflowPolicy::Error);
+        assert_eq!(recorder.record_write(&[1, 2, 3]), Ok(3));
+        assert_eq!(recorder.record_write(&[4, 5]), Err(std::io::ErrorKind::WriteZero));
+        assert_eq!(recorder.record_seek(SeekFrom::End(-2)), Ok(2));
+        let mut target = [0u8; 3];
+        assert_eq!(recorder.lay_down(&mut target), LaidDown::SeekDiverged { op_index: 1, expected: 2, actual: 1 });
+    }
+
+    #[test]
+    fn recorder_suite_on_yadon() {
+        recorder_suite::<Yadon>();
+    }
+
+    #[test]
+    fn recorder_suite_on_fixed() {
+        recorder_suite::<YadonFixed<64, 16>>();
+    }
+
+    #[test]
+    fn fixed_recorder_full() {
+        let mut yadon = YadonFixed::<4, 3>::new(Some(0), None);
+        assert_eq!(yadon.write(&[1, 2, 3]).unwrap(), 3);
+        assert_eq!(yadon.write(&[4, 5]).unwrap_err(), FixedError::DataFull { needed: 2, available: 1 });
+        // Fills store no data, and a partial repetition shares the pattern's data.
+        assert_eq!(yadon.fill(6, 2).unwrap(), 2);
+        assert_eq!(yadon.write_repeated(&[7], 1).unwrap(), 1);
+        assert_eq!((yadon.len(), yadon.data_len()), (3, 4));
+        assert_eq!(yadon.seek(SeekFrom::Start(0)).unwrap_err(), FixedError::OperationsFull);
+        assert_eq!(yadon.flush().unwrap_err(), FixedError::OperationsFull);
+        let error = Write::write(&mut yadon, &[8]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::OutOfMemory);
+        assert_eq!(error.get_ref().unwrap().downcast_ref::<FixedError>(), Some(&FixedError::OperationsFull));
+
+        // Nothing was recorded by the failed calls, so the position is where the last write left it.
+        let mut target = [0u8; 8];
+        assert_eq!(yadon.materialize_into(&mut target).unwrap(), 6);
+        assert_eq!(target, [1, 2, 3, 6, 6, 7, 0, 0]);
+
+        // A repeat which needs a second slot for its partial repetition fails as a whole.
+        let mut yadon = YadonFixed::<8, 1>::new(Some(0), Some(5));
+        assert_eq!(yadon.write_repeated(&[1, 2], 3).unwrap_err(), FixedError::OperationsFull);
+        assert_eq!(yadon.write_repeated(&[1, 2, 3, 4, 5], 1).unwrap(), 5);
+    }
+
+    #[test]
+    fn labeled_divergence() {
+        let mut yadon = Yadon::new(Some(0), Some(8));
+        yadon.labeled_scope("header", |yadon| {
+            assert_eq!(yadon.write(&[1, 2]).unwrap(), 2);
+            assert_eq!(yadon.seek(SeekFrom::Current(1)).unwrap(), 3);
+        });
+        assert_eq!(yadon.write(&[0]).unwrap(), 1);
+        yadon.push_label("body");
+        assert_eq!(yadon.write(&[3; 4]).unwrap(), 4);
+        yadon.pop_label();
+        yadon.labeled_scope("header", |yadon| yadon.flush().unwrap());
+
+        let labels: Vec<_> = yadon.labels().collect();
+        assert_eq!(labels, vec![("header", 0..2), ("body", 3..4), ("header", 4..5)]);
+        assert!(yadon.label_of(2).is_none());
+        assert!(Arc::ptr_eq(yadon.label_of(0).unwrap(), yadon.label_of(4).unwrap()));
+        assert!(format!("{:?}", yadon).contains("\"body\""));
+
+        let mut target = [0u8; 6];
+        match yadon.apply(&mut Cursor::new(&mut target[..]), true) {
+            Err(error @ ApplyError::NumBytesWrittenDiverge(_)) => {
+                assert!(error.to_string().contains(", in \"body\""));
+                match error {
+                    ApplyError::NumBytesWrittenDiverge(diff) => assert_eq!(diff.label.as_deref(), Some("body")),
+                    _ => unreachable!(),
+                }
+            },
+            res => panic!("Apply did not fail with a diverged write: {:?}", res),
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Write(usize),
+        WriteVectored(usize),
+        Seek(SeekFrom),
+        Flush,
+        Sync,
+        Interrupted,
+    }
+
+    /// Target which records the calls made on it.
+    struct EventLog {
+        inner: Cursor<Vec<u8>>,
+        events: Vec<Event>,
+        /// Whether vectored writes are passed on to `inner`, rather than only writing the first buffer.
+        vectored: bool,
+        /// How many times each write, seek or read fails with `ErrorKind::Interrupted` before succeeding.
+        interruptions: usize,
+        /// How many more times the current write, seek or read will be interrupted.
+        interruptions_left: usize,
+        /// The most bytes a single write will accept, if limited.
+        max_write: Option<usize>,
+        /// Whether flushing fails.
+        failing_flush: bool,
+        /// If set, the write after this many more writes fails, once.
+        writes_before_failure: Option<usize>,
+        /// Whether writes claim to have written everything, but leave the last byte as it was.
+        torn_writes: bool,
+    }
+
+    impl EventLog {
+        fn new(len: usize) -> Self {
+            EventLog {
+                inner: Cursor::new(vec![0u8; len]),
+                events: vec![],
+                vectored: false,
+                interruptions: 0,
+                interruptions_left: 0,
+                max_write: None,
+                failing_flush: false,
+                writes_before_failure: None,
+                torn_writes: false,
+            }
+        }
+
+        fn interrupt(&mut self) -> std::io::Result<()> {
+            if self.interruptions_left > 0 {
+                self.interruptions_left -= 1;
+                self.events.push(Event::Interrupted);
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            self.interruptions_left = self.interruptions;
+            Ok(())
+        }
+    }
+
+    impl Write for EventLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.interrupt()?;
+            match self.writes_before_failure {
+                Some(0) => {
+                    self.writes_before_failure = None;
+                    return Err(std::io::ErrorKind::BrokenPipe.into());
+                },
+                Some(writes) => self.writes_before_failure = Some(writes - 1),
+                None => {},
+            }
+            let buf = &buf[0..buf.len().min(self.max_write.unwrap_or(usize::MAX))];
+            if self.torn_writes && !buf.is_empty() {
+                self.inner.write_all(&buf[0..buf.len() - 1])?;
+                self.inner.seek(SeekFrom::Current(1))?;
+                self.events.push(Event::Write(buf.len()));
+                return Ok(buf.len());
+            }
+            let written = self.inner.write(buf)?;
+            self.events.push(Event::Write(written));
+            Ok(written)
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+            if !self.vectored {
+                let buf = bufs.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &**buf);
+                return self.write(buf);
+            }
+            self.interrupt()?;
+            let written = self.inner.write_vectored(bufs)?;
+            self.events.push(Event::WriteVectored(written));
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            if self.failing_flush {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            self.events.push(Event::Flush);
+            Ok(())
+        }
+    }
+
+    impl SyncTarget for EventLog {
+        fn sync(&mut self) -> std::io::Result<()> {
+            self.events.push(Event::Sync);
+            Ok(())
+        }
+    }
+
+    impl Read for EventLog {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt()?;
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for EventLog {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.interrupt()?;
+            self.events.push(Event::Seek(pos));
+            self.inner.seek(pos)
+        }
     }
 
     fn assert_multi_write<T1, T2>(a: &mut T1, b: &mut T2, buf: &[u8]) -> std::io::Result<usize>
@@ -375,8 +6329,7 @@ mod tests {
                 Ok(a_bytes)
             },
             (a_res, b_res) => {
-                assert!(false, "results differ: {:?} and {:?}", a_res, b_res);
-                a_res
+                panic!("results differ: {:?} and {:?}", a_res, b_res);
             }
         }
     }
@@ -393,8 +6346,7 @@ mod tests {
                 Ok(a_pos)
             },
             (a_res, b_res) => {
-                assert!(false, "results differ: {:?} and {:?}", a_res, b_res);
-                a_res
+                panic!("results differ: {:?} and {:?}", a_res, b_res);
             }
         }
     }
@@ -0,0 +1,185 @@
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use crate::apply::retry_interrupted;
+use crate::{invalid_seek, offset_position, ApplyError, ApplyOptions, LengthMode, OverflowPolicy, WriteOperation, Yadon};
+
+/// Confines writes to the `len` bytes of an inner target starting at `base`, as though they were a target of their own,
+/// so that a recording whose positions start at 0 can be applied into a region of something bigger without being able
+/// to write outside it. Positions are relative to the start of the region, and seeks from the end are from its end.
+/// A write which would extend past the end is cut short by default, or fails with `ErrorKind::WriteZero` without
+/// writing anything with `OverflowPolicy::Error`; seeking to before the start fails with `ErrorKind::InvalidInput`.
+/// Borrow the inner target with `LimitedTarget::new(&mut target, ..)` to keep it.
+/// # Example
+/// ```
+/// use yadon::{LimitedTarget, Yadon};
+/// use std::io::{Cursor, Seek, SeekFrom, Write};
+/// let mut yadon = Yadon::new(Some(0), None);
+/// yadon.write_all(b"ab").unwrap();
+/// yadon.seek(SeekFrom::Start(3)).unwrap();
+/// yadon.write_all(b"cd").unwrap();
+///
+/// let mut file = Cursor::new(vec![b'.'; 8]);
+/// yadon.apply(&mut LimitedTarget::new(&mut file, 2, 4), false).unwrap();
+/// assert_eq!(file.get_ref(), b"..ab.c..");
+/// ```
+#[derive(Debug)]
+pub struct LimitedTarget<T> where T: Seek {
+    inner: T,
+    base: u64,
+    len: u64,
+    position: u64,
+    /// Whether `inner` is known to be at `base + position`.
+    positioned: bool,
+    overflow_policy: OverflowPolicy,
+    /// Where to seek `inner` back to once done with it, if asked to.
+    restore: Option<u64>,
+}
+
+impl<T> LimitedTarget<T> where T: Seek {
+    /// Confines writes to the `len` bytes of `inner` starting at `base`, positioned at the start of them. `inner` isn't
+    /// moved there until something is written or sought. A region which would end past `u64::MAX` is cut short there.
+    pub fn new(inner: T, base: u64, len: u64) -> Self {
+        LimitedTarget {
+            inner,
+            base,
+            len: len.min(u64::MAX - base),
+            position: 0,
+            positioned: false,
+            overflow_policy: OverflowPolicy::Truncate,
+            restore: None,
+        }
+    }
+
+    /// Sets what's done with a write which would extend past the end of the region.
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self
+    }
+
+    /// Seeks `inner` back to where it's positioned now once the adapter is dropped, so that whatever else is using it
+    /// carries on from there. Errors from seeking back are ignored.
+    pub fn restoring_position(mut self) -> std::io::Result<Self> {
+        self.restore = Some(self.inner.stream_position()?);
+        Ok(self)
+    }
+
+    /// The inner target.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// The inner target. Moving it leaves the adapter's position as it was, so it's moved back before the next write.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.positioned = false;
+        &mut self.inner
+    }
+
+    /// Where the region starts in the inner target.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// How many bytes long the region is.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the region is empty, so that nothing can be written to it.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Write for LimitedTarget<T> where T: Write + Seek {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let available = self.len.saturating_sub(self.position);
+        if buf.len() as u64 > available && self.overflow_policy == OverflowPolicy::Error {
+            return Err(std::io::Error::new(ErrorKind::WriteZero, "write extends past the end of the region"));
+        }
+        let len = (buf.len() as u64).min(available) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        if !self.positioned {
+            self.inner.seek(SeekFrom::Start(self.base + self.position))?;
+            self.positioned = true;
+        }
+        let written = self.inner.write(&buf[0..len])?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T> Seek for LimitedTarget<T> where T: Seek {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(offset) => offset_position(self.position, offset)?,
+            SeekFrom::End(offset) => offset_position(self.len, offset)?,
+        };
+        self.positioned = false;
+        self.inner.seek(SeekFrom::Start(self.base.checked_add(position).ok_or_else(invalid_seek)?))?;
+        self.positioned = true;
+        self.position = position;
+        Ok(position)
+    }
+}
+
+impl<T> Drop for LimitedTarget<T> where T: Seek {
+    fn drop(&mut self) {
+        if let Some(position) = self.restore {
+            let _ = self.inner.seek(SeekFrom::Start(position));
+        }
+    }
+}
+
+impl Yadon {
+    /// Applies the stored operations as `apply_with_options()` does, but as if every position were `base_offset` further
+    /// into the target, so that one recording can patch each of several slots of a container, such as an asset in
+    /// different archives, without copying and translating it as `translated()` does. The target is wrapped in a
+    /// `LimitedTarget` for the slot, so the stored operations are left as they are; their expected positions are checked
+    /// against positions in the slot, so positions given with a divergence are those of the recording rather than the
+    /// target. Without a `start`, the recording starts wherever the target is, as it would if applied on its own, so
+    /// the target must be at or after `base_offset`; if it's before it, applying fails with `ErrorKind::InvalidInput`
+    /// before anything is applied.
+    ///
+    /// Seeks from the end are shifted too, landing relative to the end of the slot, `base_offset + length`, rather than
+    /// that of the target, so the recording must have a fixed `length`; if it doesn't, and there are seeks from the end,
+    /// applying fails with `ApplyError::UnsupportedOperation` before anything is applied. Seeking, as a relative seek
+    /// might, to before `base_offset` fails with `ErrorKind::InvalidInput`.
+    /// # Example
+    /// ```
+    /// use yadon::{ApplyOptions, Yadon};
+    /// use std::io::{Cursor, Seek, SeekFrom, Write};
+    /// let mut yadon = Yadon::new(Some(0), Some(4));
+    /// yadon.write_all(b"a").unwrap();
+    /// yadon.seek(SeekFrom::End(-1)).unwrap();
+    /// yadon.write_all(b"z").unwrap();
+    ///
+    /// let mut archive = Cursor::new(vec![b'.'; 12]);
+    /// yadon.apply_at(&mut archive, 0, &ApplyOptions::default()).unwrap();
+    /// yadon.apply_at(&mut archive, 8, &ApplyOptions::default()).unwrap();
+    /// assert_eq!(archive.get_ref(), b"a..z....a..z");
+    /// ```
+    pub fn apply_at<T>(&self, target: &mut T, base_offset: u64, options: &ApplyOptions) -> Result<usize, ApplyError>
+    where T: Write + Seek {
+        let end = self.length.filter(|_| self.length_mode == LengthMode::Fixed);
+        let seeks_from_end = self.operations.iter().any(|operation| {
+            matches!(operation, WriteOperation::Seek(SeekFrom::End(_), _) | WriteOperation::DeferredSeek(SeekFrom::End(_)))
+        });
+        if end.is_none() && seeks_from_end {
+            return Err(ApplyError::UnsupportedOperation("seek from the end with no fixed length"));
+        }
+        let mut limited = LimitedTarget::new(target, base_offset, end.unwrap_or(u64::MAX));
+        if self.start.is_none() {
+            let position = retry_interrupted(|| limited.inner.stream_position())?;
+            limited.position = position.checked_sub(base_offset)
+                .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "target is before the base offset"))?;
+            limited.positioned = true;
+        }
+        self.apply_with_options(&mut limited, options)
+    }
+}
@@ -0,0 +1,109 @@
+use std::convert::TryFrom;
+use std::io::{Seek, SeekFrom, Write};
+use std::ops::Range;
+use crate::{ApplyError, CheckPolicy, Yadon};
+
+/// A writable memory mapping, such as `memmap2::MmapMut`, which `Yadon::apply_to_mapping()` can write into directly.
+pub trait MappedTarget {
+    /// The mapped bytes.
+    fn bytes_mut(&mut self) -> &mut [u8];
+    /// Writes `len` bytes of the mapping, starting at `offset`, back to the underlying storage.
+    fn flush_range(&mut self, offset: usize, len: usize) -> std::io::Result<()>;
+}
+
+/// When `Yadon::apply_to_mapping()` flushes what it wrote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapFlush {
+    /// Flush each contiguous extent once the writes to it end, and whenever a `WriteOperation::Flush` is applied.
+    PerExtent,
+    /// Flush once at the end, from the lowest to the highest byte written.
+    AtEnd,
+    /// Don't flush, leaving it to the caller or the operating system.
+    Never,
+}
+
+/// Adapts a mapping to `Write + Seek`, writing into it as `Cursor` writes into a slice, and tracking what needs
+/// flushing.
+struct MappingWriter<'a, M> {
+    map: &'a mut M,
+    position: u64,
+    flush: MapFlush,
+    /// The bytes written which haven't been flushed yet.
+    dirty: Option<Range<usize>>,
+}
+
+impl<'a, M> MappingWriter<'a, M> where M: MappedTarget {
+    fn flush_dirty(&mut self) -> std::io::Result<()> {
+        match self.dirty.take() {
+            Some(dirty) => self.map.flush_range(dirty.start, dirty.end - dirty.start),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, M> Write for MappingWriter<'a, M> where M: MappedTarget {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let bytes = self.map.bytes_mut();
+        let start = usize::try_from(self.position).unwrap_or(usize::MAX).min(bytes.len());
+        let len = buf.len().min(bytes.len() - start);
+        bytes[start..start + len].copy_from_slice(&buf[0..len]);
+        self.position += len as u64;
+        if len == 0 || self.flush == MapFlush::Never {
+            return Ok(len);
+        }
+        let written = start..start + len;
+        self.dirty = match self.dirty.take() {
+            None => Some(written),
+            Some(dirty) if self.flush == MapFlush::AtEnd => Some(dirty.start.min(written.start)..dirty.end.max(written.end)),
+            Some(dirty) if dirty.end == written.start => Some(dirty.start..written.end),
+            Some(dirty) => {
+                self.map.flush_range(dirty.start, dirty.end - dirty.start)?;
+                Some(written)
+            },
+        };
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.flush {
+            MapFlush::PerExtent => self.flush_dirty(),
+            MapFlush::AtEnd | MapFlush::Never => Ok(()),
+        }
+    }
+}
+
+impl<'a, M> Seek for MappingWriter<'a, M> where M: MappedTarget {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.position = offset;
+                return Ok(offset);
+            },
+            SeekFrom::Current(offset) => (self.position, offset),
+            SeekFrom::End(offset) => (self.map.bytes_mut().len() as u64, offset),
+        };
+        self.position = base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl Yadon {
+    /// Applies the stored operations on a memory mapping, writing directly into it, as `apply_to_slice()` does on the
+    /// mapped bytes. The mapping's length acts as the length of the target, so writes past its end are cut short, and
+    /// diverge if `check_policy` checks writes. `flush` sets when what was written is flushed back to the underlying
+    /// storage; if applying fails, what was written before is still flushed, unless `flush` is `MapFlush::Never`.
+    ///
+    /// The stored operations own their data, so they never alias the mapping. If other handles map the same file,
+    /// they'll observe the writes as they happen, so nothing else should be writing to the mapped range meanwhile.
+    pub fn apply_to_mapping<M>(&self, map: &mut M, check_policy: CheckPolicy, flush: MapFlush) -> Result<usize, ApplyError>
+    where M: MappedTarget {
+        let mut writer = MappingWriter { map, position: 0, flush, dirty: None };
+        let applied = self.apply_with_policy(&mut writer, check_policy);
+        let flushed = writer.flush_dirty();
+        let bytes_written = applied?;
+        flushed?;
+        Ok(bytes_written)
+    }
+}
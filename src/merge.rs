@@ -0,0 +1,125 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::io::SeekFrom;
+use crate::label::Labels;
+use crate::{LengthMode, MergeError, WriteOperation, Yadon};
+
+impl Yadon {
+    /// Merges two recordings of the same target, such as those of two subsystems which recorded separately, into one
+    /// which applies this one's operations, then `other`'s. A seek to `other.start`, if it has one, goes between them, so
+    /// that `other` starts where it was recorded to rather than wherever this one ends; if it has none, it carries on
+    /// from there, and the expected positions of its relative seeks are found again from where this one leaves the
+    /// position. Unlike `compose()`, the operations are moved rather than copied, so `WriteOperation::Custom` is kept.
+    ///
+    /// As both are of the same target, the merged recording requires the base checksums of both, is limited to the
+    /// fewer applies of the two, and has the generation stamp of whichever has one. It keeps this recording's `start`
+    /// and recording settings, with the ranges `other` wrote to added to those written so far, and `other`'s length, if
+    /// it has one.
+    ///
+    /// Fails with `MergeError::LengthMismatch` if the recordings have different lengths, which is only known if `other`
+    /// neither resizes nor grows its length, with `MergeError::GenerationStampMismatch` if they stamp different
+    /// generations, with `MergeError::Inconsistent` if `other`'s seeks don't agree with where this one leaves the
+    /// position, and with `MergeError::InStore` if the operations of either are in the store given to `with_store()`.
+    /// # Example
+    /// ```
+    /// use yadon::Yadon;
+    /// let mut header = Yadon::new(Some(0), Some(8));
+    /// header.write(b"HDR").unwrap();
+    /// let mut trailer = Yadon::new(Some(6), Some(8));
+    /// trailer.write(b"TR").unwrap();
+    ///
+    /// let merged = header.merge(trailer).unwrap();
+    /// let mut target = [0u8; 8];
+    /// merged.materialize_into_slice(&mut target).unwrap();
+    /// assert_eq!(&target, b"HDR\0\0\0TR");
+    /// ```
+    pub fn merge(mut self, mut other: Yadon) -> Result<Yadon, MergeError> {
+        self.check_mergeable(&other)?;
+        let operations = core::mem::take(&mut other.operations);
+        self.append_merged(&other, operations)?;
+        Ok(self)
+    }
+
+    /// Merges `other` onto the end of this recording, as `merge()` does, but copying its operations. Fails as `merge()`
+    /// does, or with `MergeError::UnsupportedOperation` if `other` holds a `WriteOperation::Custom`, which can't be
+    /// copied; this recording is left as it was if it fails.
+    pub fn extend_from(&mut self, other: &Yadon) -> Result<(), MergeError> {
+        self.check_mergeable(other)?;
+        let operations = other.operations.iter()
+            .map(|operation| operation.try_copy().ok_or(MergeError::UnsupportedOperation(operation.name())))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.append_merged(other, operations)
+    }
+
+    /// Fails if `other` can't be of the same target as this recording.
+    fn check_mergeable(&self, other: &Yadon) -> Result<(), MergeError> {
+        if self.in_store() || other.in_store() {
+            return Err(MergeError::InStore);
+        }
+        let resizes = other.operations.iter().any(|operation| matches!(operation, WriteOperation::SetLen(_)));
+        if let (Some(first), Some(second), LengthMode::Fixed, false) = (self.length, other.length, other.length_mode, resizes) {
+            if first != second {
+                return Err(MergeError::LengthMismatch { first, second });
+            }
+        }
+        if let (Some(first), Some(second)) = (self.generation_stamp, other.generation_stamp) {
+            if first != second {
+                return Err(MergeError::GenerationStampMismatch { first, second });
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends `operations`, which are `other`'s, with a seek to its `start` before them if it has one, re-simulates
+    /// them, and takes on `other`'s metadata. Leaves this recording as it was if re-simulating fails.
+    fn append_merged(&mut self, other: &Yadon, operations: Vec<WriteOperation>) -> Result<(), MergeError> {
+        let count = self.operations.len();
+        if let Some(start) = other.start {
+            self.operations.push(WriteOperation::Seek(SeekFrom::Start(start), start));
+            #[cfg(feature = "track-callers")]
+            self.locations.resize(self.operations.len(), None);
+        }
+        let offset = self.operations.len();
+        self.operations.extend(operations);
+        #[cfg(feature = "track-callers")]
+        {
+            self.locations.resize(offset, None);
+            self.locations.extend_from_slice(&other.locations);
+            self.locations.resize(self.operations.len(), None);
+        }
+        let fill_in: Vec<bool> = self.operations.iter().enumerate().map(|(index, operation)| {
+            index >= offset && other.start.is_none() && matches!(operation, WriteOperation::Seek(SeekFrom::Current(_), _))
+        }).collect();
+        let labels = core::mem::take(&mut self.labels);
+        let mut runs: Vec<(String, Range<usize>)> = labels.iter()
+            .map(|(label, operations)| (label.to_string(), operations))
+            .collect();
+        runs.extend(other.labels.iter().map(|(label, operations)| {
+            (label.to_string(), operations.start + offset..operations.end + offset)
+        }));
+        let stack = labels.stack().map(ToString::to_string).collect();
+        self.labels = Labels::from_parts(stack, runs, self.operations.len())
+            .expect("runs of each recording are in order and within it");
+        if let Err((_, reason)) = self.resimulate_filling(|index| fill_in[index]) {
+            self.operations.truncate(count);
+            #[cfg(feature = "track-callers")]
+            self.locations.truncate(count);
+            self.labels = labels;
+            return Err(MergeError::Inconsistent(reason));
+        }
+        if other.length.is_some() {
+            self.length = other.length;
+        }
+        for extent in other.written_extents.iter() {
+            self.written_extents.insert(extent);
+        }
+        self.base_checksums.extend(other.base_checksums.iter().cloned());
+        self.max_applies = match (self.max_applies, other.max_applies) {
+            (Some(first), Some(second)) => Some(first.min(second)),
+            (first, second) => first.or(second),
+        };
+        self.generation_stamp = self.generation_stamp.or(other.generation_stamp);
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::fmt::Debug;
+#[cfg(feature = "std")]
+use std::io::{Seek, Write};
+use crate::io::{self as io, SeekFrom};
+use crate::HexPreview;
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Write + Seek operations which were called on Yadon
+pub enum WriteOperation {
+    /// Write something, and check that the number of bytes written matches.
+    Write(Vec<u8>, usize),
+    /// Seek somewhere, and check that the resulting position matches.
+    Seek(#[cfg_attr(feature = "serde", serde(with = "crate::serialize::SeekFromDef"))] SeekFrom, u64),
+    /// Seek somewhere relative to an end which wasn't known when recording, without checking the resulting position.
+    DeferredSeek(#[cfg_attr(feature = "serde", serde(with = "crate::serialize::SeekFromDef"))] SeekFrom),
+    /// Flush the target, at the same point `flush()` was called on Yadon.
+    Flush,
+    /// Write `len` copies of `byte`, and check that the number of bytes written matches `len`.
+    Fill { byte: u8, len: u64 },
+    /// Write `pattern` `count` times, and check that the number of bytes written matches.
+    Repeat { pattern: Vec<u8>, count: u64 },
+    /// Resize the target. Requires a target implementing `SetLen`.
+    SetLen(u64),
+    /// Durability barrier: everything written so far must reach storage before continuing. Requires a target
+    /// implementing `SyncTarget`, unless `ApplyOptions::sync_fallback` allows degrading it to a flush.
+    Sync,
+    /// Copy `len` bytes within the target from `src` to `dst`, leaving the position after the copied bytes, and check
+    /// that `len` bytes were written. Requires a readable target.
+    CopyWithin { src: u64, dst: u64, len: u64 },
+    /// Check that the target contains `expected` at `offset`, failing the apply otherwise. The position is left where
+    /// it was. Requires a readable target.
+    AssertBytes { offset: u64, expected: Vec<u8> },
+    /// User-defined operation, and check that its outcome matches the result of simulating it. Only available with
+    /// the `std` feature, as it's applied to a `WriteSeek`. With the `serde` feature, this can't be serialized.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(Box<dyn ApplyOp>, SimResult),
+}
+
+/// Number of bytes a `WriteOperation::Repeat` of `pattern` `count` times writes. Fails with `ErrorKind::InvalidInput`
+/// if that doesn't fit in a `u64`, which `Yadon::write_repeated()` never records, but which `operations` or a store
+/// can still be given.
+pub(crate) fn repeated_len(pattern: &[u8], count: u64) -> io::Result<u64> {
+    (pattern.len() as u64).checked_mul(count)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "repeated write is too large"))
+}
+
+impl WriteOperation {
+    /// A preview of the bytes the operation writes or checks, for logging it without all of them: the data of a
+    /// `WriteOperation::Write`, the pattern of a `WriteOperation::Repeat`, or what a `WriteOperation::AssertBytes`
+    /// expects. Other operations have no bytes of their own.
+    pub fn preview(&self) -> Option<HexPreview<'_>> {
+        match self {
+            WriteOperation::Write(data, _) => Some(HexPreview::new(data)),
+            WriteOperation::Repeat { pattern, .. } => Some(HexPreview::new(pattern)),
+            WriteOperation::AssertBytes { expected, .. } => Some(HexPreview::new(expected)),
+            _ => None,
+        }
+    }
+
+    /// Number of bytes the simulation expects this operation to write. A `WriteOperation::Repeat` too long to count
+    /// saturates at `u64::MAX`; applying it fails with `ErrorKind::InvalidInput`.
+    #[cfg(feature = "std")]
+    pub(crate) fn expected_bytes_written(&self) -> u64 {
+        match self {
+            WriteOperation::Write(_, expected_bytes_written) => *expected_bytes_written as u64,
+            WriteOperation::Fill { len, .. } | WriteOperation::CopyWithin { len, .. } => *len,
+            WriteOperation::Repeat { pattern, count } => (pattern.len() as u64).saturating_mul(*count),
+            #[cfg(feature = "std")]
+            WriteOperation::Custom(_, expected) => expected.bytes_written,
+            _ => 0,
+        }
+    }
+
+    /// A copy of the operation, unless it's a `WriteOperation::Custom`, which can't be copied.
+    pub(crate) fn try_copy(&self) -> Option<WriteOperation> {
+        Some(match self {
+            WriteOperation::Write(data, len) => WriteOperation::Write(data.clone(), *len),
+            WriteOperation::Seek(pos, expected_position) => WriteOperation::Seek(*pos, *expected_position),
+            WriteOperation::DeferredSeek(pos) => WriteOperation::DeferredSeek(*pos),
+            WriteOperation::Flush => WriteOperation::Flush,
+            WriteOperation::Fill { byte, len } => WriteOperation::Fill { byte: *byte, len: *len },
+            WriteOperation::Repeat { pattern, count } => WriteOperation::Repeat { pattern: pattern.clone(), count: *count },
+            WriteOperation::SetLen(len) => WriteOperation::SetLen(*len),
+            WriteOperation::Sync => WriteOperation::Sync,
+            WriteOperation::CopyWithin { src, dst, len } => WriteOperation::CopyWithin { src: *src, dst: *dst, len: *len },
+            WriteOperation::AssertBytes { offset, expected } => {
+                WriteOperation::AssertBytes { offset: *offset, expected: expected.clone() }
+            },
+            #[cfg(feature = "std")]
+            WriteOperation::Custom(..) => return None,
+        })
+    }
+
+    /// Short name of the kind of operation, for use in errors.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            WriteOperation::Write(_, _) => "write",
+            WriteOperation::Seek(_, _) => "seek",
+            WriteOperation::DeferredSeek(_) => "deferred_seek",
+            WriteOperation::Flush => "flush",
+            WriteOperation::Fill { .. } => "fill",
+            WriteOperation::Repeat { .. } => "repeat",
+            WriteOperation::SetLen(_) => "set_len",
+            WriteOperation::Sync => "sync",
+            WriteOperation::CopyWithin { .. } => "copy_within",
+            WriteOperation::AssertBytes { .. } => "assert_bytes",
+            #[cfg(feature = "std")]
+            WriteOperation::Custom(_, _) => "custom",
+        }
+    }
+}
+
+/// Anything which is `Write + Seek`, usable as a trait object.
+#[cfg(feature = "std")]
+pub trait WriteSeek: Write + Seek {}
+
+#[cfg(feature = "std")]
+impl<T> WriteSeek for T where T: Write + Seek + ?Sized {}
+
+/// A user-defined operation, which can be recorded with `Yadon::record_custom()` and is replayed in order with the other
+/// operations.
+/// # Example
+/// ```
+/// use yadon::{ApplyOp, ApplyOutcome, SimResult, WriteSeek, Yadon};
+/// use std::io::{Cursor, Seek, SeekFrom, Write};
+///
+/// /// Writes some bytes, then seeks back to where it started.
+/// #[derive(Debug)]
+/// struct WriteInPlace(Vec<u8>);
+///
+/// impl ApplyOp for WriteInPlace {
+///     fn simulate(&self, pos: u64, len: Option<u64>) -> SimResult {
+///         let available = len.map_or(u64::MAX, |len| len.saturating_sub(pos));
+///         SimResult { position: pos, bytes_written: available.min(self.0.len() as u64) }
+///     }
+///
+///     fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome> {
+///         let position = target.stream_position()?;
+///         let bytes_written = target.write(&self.0)? as u64;
+///         target.seek(SeekFrom::Start(position))?;
+///         Ok(ApplyOutcome { position, bytes_written })
+///     }
+/// }
+///
+/// let mut yadon = Yadon::new(Some(0), Some(4));
+/// yadon.write(&[1]).unwrap();
+/// let result = yadon.record_custom(WriteInPlace(vec![2, 3, 4, 5])).unwrap();
+/// assert_eq!(result, SimResult { position: 1, bytes_written: 3 });
+/// yadon.write(&[6]).unwrap();
+///
+/// let mut target = [0u8; 4];
+/// yadon.apply(&mut Cursor::new(&mut target[..]), true).unwrap();
+/// assert_eq!(target, [1, 6, 3, 4]);
+/// ```
+#[cfg(feature = "std")]
+pub trait ApplyOp: Debug + Send + Sync {
+    /// Simulates the operation while recording, given the virtual position `pos` and the emulated length `len`. The
+    /// result is kept so that `apply()` can check the outcome of applying the operation against it.
+    fn simulate(&self, pos: u64, len: Option<u64>) -> SimResult;
+
+    /// Applies the operation to the target.
+    fn apply(&self, target: &mut dyn WriteSeek) -> std::io::Result<ApplyOutcome>;
+}
+
+/// The effect of a user-defined operation on the recorder, as predicted by `ApplyOp::simulate()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimResult {
+    /// Where the virtual position is after the operation.
+    pub position: u64,
+    /// How many bytes the operation writes.
+    pub bytes_written: u64,
+}
+
+/// The effect of a user-defined operation on a target, as reported by `ApplyOp::apply()`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApplyOutcome {
+    /// Where the target's position is after the operation.
+    pub position: u64,
+    /// How many bytes the operation wrote.
+    pub bytes_written: u64,
+}
@@ -0,0 +1,135 @@
+use core::fmt::Write as _;
+use core::ops::Range;
+use std::collections::BTreeSet;
+use std::io::{SeekFrom, Write};
+use crate::apply::recorded_bytes;
+use crate::{ApplyError, PatchError, Yadon};
+
+impl Yadon {
+    /// Resolves the stored writes to the bytes they leave at each offset, as a list of offsets and the bytes written
+    /// there, in the order of their offsets. Writes which overlap or touch are coalesced into one patch, with each byte
+    /// coming from the last write which covers it, so that no two patches overlap, and applying them in any order has
+    /// the same effect as applying the stored writes in order. As with `ApplyOrder::Offset`, only writes, seeks and
+    /// flushes can be resolved, and every write needs a known offset. The generation stamp, if there is one, is
+    /// resolved as a write of its generation.
+    pub fn to_patches(&self) -> Result<Vec<(u64, Vec<u8>)>, ApplyError> {
+        let stamp = self.stamp_operation();
+        let mut writes = self.stamped_write_extents()?;
+        writes.sort_by_key(|(_, extent)| extent.start);
+        let operation_at = |index: usize| self.operations.get(index).or(stamp.as_ref()).expect("write index is in range");
+
+        let mut patches = Vec::new();
+        let mut writes = writes.into_iter().peekable();
+        while let Some(first) = writes.next() {
+            let start = first.1.start;
+            let mut end = first.1.end;
+            let mut run = vec![first];
+            while let Some(write) = writes.next_if(|(_, extent)| extent.start <= end) {
+                end = end.max(write.1.end);
+                run.push(write);
+            }
+            run.sort_by_key(|(index, _)| *index);
+            let mut data = vec![0; (end - start) as usize];
+            for (index, extent) in &run {
+                let range = (extent.start - start) as usize..(extent.end - start) as usize;
+                recorded_bytes(operation_at(*index), 0, &mut data[range]);
+            }
+            patches.push((start, data));
+        }
+        Ok(patches)
+    }
+
+    /// Records writing each of `patches`, in order, as a seek from the start to its offset followed by a write of its
+    /// bytes, with `start` and `length` as given to `new()`. Patches which overlap are recorded as they are, so later
+    /// ones win, and empty ones are skipped. Fails with `PatchError::Overflow` if a patch ends past the largest offset
+    /// a `u64` can hold, or with `PatchError::OutOfBounds` if `length` is set and a patch ends past it, without
+    /// recording anything.
+    pub fn from_patches<I>(patches: I, start: Option<u64>, length: Option<u64>) -> Result<Yadon, PatchError>
+    where I: IntoIterator<Item = (u64, Vec<u8>)> {
+        let patches: Vec<_> = patches.into_iter().collect();
+        for (index, (offset, data)) in patches.iter().enumerate() {
+            let end = offset.checked_add(data.len() as u64).ok_or(PatchError::Overflow { index })?;
+            if let Some(length) = length.filter(|&length| end > length) {
+                return Err(PatchError::OutOfBounds { index, end, length });
+            }
+        }
+        let mut yadon = Yadon::new(start, length);
+        for (offset, data) in patches.into_iter().filter(|(_, data)| !data.is_empty()) {
+            yadon.seek(SeekFrom::Start(offset)).expect("seeking from the start can't fail");
+            yadon.write_all(&data).expect("writes within the length can't fail");
+        }
+        Ok(yadon)
+    }
+
+    /// Renders the bytes which the stored writes leave within `range` as a hex dump, 16 bytes to a line: the offset of
+    /// the line, the bytes in hex, then as ASCII between bars, with `.` for anything unprintable. Bytes which aren't
+    /// written are shown as `--`, and blank in the ASCII, and bytes outside `range` are left blank. Lines with nothing
+    /// written are left out, with a `*` line where any were. The writes are resolved as `to_patches()` does.
+    /// # Example
+    /// ```
+    /// use yadon::Yadon;
+    /// use std::io::{Seek, SeekFrom, Write};
+    /// let mut yadon = Yadon::new(Some(0), None);
+    /// yadon.seek(SeekFrom::Start(0x1c)).unwrap();
+    /// yadon.write_all(b"yadon\0\x01").unwrap();
+    /// yadon.seek(SeekFrom::Start(0x80)).unwrap();
+    /// yadon.write_all(&[0xff]).unwrap();
+    /// assert_eq!(yadon.hexdump(0x10..0x84).unwrap(), "\
+    /// 00000010  -- -- -- -- -- -- -- --  -- -- -- -- 79 61 64 6f  |            yado|
+    /// 00000020  6e 00 01 -- -- -- -- --  -- -- -- -- -- -- -- --  |n..             |
+    /// *
+    /// 00000080  ff -- -- --                                       |.               |
+    /// ");
+    /// ```
+    pub fn hexdump(&self, range: Range<u64>) -> Result<String, ApplyError> {
+        let stamp = self.stamp_operation();
+        let operation_at = |index: usize| self.operations.get(index).or(stamp.as_ref()).expect("write index is in range");
+        let writes: Vec<_> = self.stamped_write_extents()?.into_iter()
+            .map(|(index, extent)| (index, extent.start.max(range.start)..extent.end.min(range.end), extent.start))
+            .filter(|(_, within, _)| !within.is_empty())
+            .collect();
+        let lines: BTreeSet<u64> = writes.iter().flat_map(|(_, within, _)| within.start / 16..=(within.end - 1) / 16).collect();
+
+        let mut dump = String::new();
+        let mut next_line = None;
+        for line in lines {
+            if next_line.is_some_and(|next_line| next_line != line) {
+                dump.push_str("*\n");
+            }
+            next_line = Some(line + 1);
+            let start = line * 16;
+            let mut bytes = [None; 16];
+            for (index, within, write_start) in &writes {
+                let overlap = within.start.max(start)..within.end.min(start + 16);
+                if overlap.is_empty() {
+                    continue;
+                }
+                let mut recorded = vec![0; (overlap.end - overlap.start) as usize];
+                recorded_bytes(operation_at(*index), overlap.start - write_start, &mut recorded);
+                for (offset, byte) in overlap.clone().zip(recorded) {
+                    bytes[(offset - start) as usize] = Some(byte);
+                }
+            }
+            let within = |i: usize| range.contains(&(start + i as u64));
+            write!(dump, "{:08x} ", start).expect("writing to a string can't fail");
+            for (i, byte) in bytes.iter().enumerate() {
+                if i % 8 == 0 {
+                    dump.push(' ');
+                }
+                match byte {
+                    Some(byte) => write!(dump, "{:02x} ", byte).expect("writing to a string can't fail"),
+                    None if within(i) => dump.push_str("-- "),
+                    None => dump.push_str("   "),
+                }
+            }
+            dump.push_str(" |");
+            dump.extend(bytes.iter().map(|byte| match byte {
+                Some(byte) if byte.is_ascii_graphic() || *byte == b' ' => char::from(*byte),
+                Some(_) => '.',
+                None => ' ',
+            }));
+            dump.push_str("|\n");
+        }
+        Ok(dump)
+    }
+}
@@ -0,0 +1,100 @@
+use std::convert::TryFrom;
+use std::io::{Seek, SeekFrom, Write};
+use crate::{ApplyError, Yadon};
+
+/// Targets which are written at explicit offsets rather than through a cursor, like `positioned_io::WriteAt`.
+pub trait WriteAt {
+    /// Writes bytes from `buf` at offset `pos`, returning how many were written.
+    fn write_at(&mut self, pos: u64, buf: &[u8]) -> std::io::Result<usize>;
+    /// Flushes what has been written to the underlying storage.
+    fn flush(&mut self) -> std::io::Result<()>;
+    /// The length of the target, if it's known.
+    fn size(&self) -> std::io::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+impl WriteAt for Vec<u8> {
+    /// Writes `buf` at `pos`, first extending the vector with zeroes if it's shorter than `pos`.
+    fn write_at(&mut self, pos: u64, buf: &[u8]) -> std::io::Result<usize> {
+        let pos = usize::try_from(pos)
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "position does not fit in memory"))?;
+        let end = pos.checked_add(buf.len())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "write does not fit in memory"))?;
+        if self.len() < end {
+            self.resize(end, 0);
+        }
+        self[pos..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> std::io::Result<Option<u64>> {
+        Ok(Some(self.len() as u64))
+    }
+}
+
+/// Adapts a `WriteAt` target to `Write + Seek`, resolving every write to an absolute offset. Seeks are only tracked,
+/// and `SeekFrom::End` seeks resolve against `len_hint`, or else the target's size.
+pub(crate) struct Positioned<'a, T> {
+    pub(crate) target: &'a mut T,
+    pub(crate) position: u64,
+    len_hint: Option<u64>,
+}
+
+impl<'a, T> Positioned<'a, T> {
+    pub(crate) fn new(target: &'a mut T, len_hint: Option<u64>) -> Self {
+        Positioned { target, position: 0, len_hint }
+    }
+}
+
+impl<'a, T> Write for Positioned<'a, T> where T: WriteAt {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.target.write_at(self.position, buf)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.target.flush()
+    }
+}
+
+impl<'a, T> Seek for Positioned<'a, T> where T: WriteAt {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.position = offset;
+                return Ok(offset);
+            },
+            SeekFrom::Current(offset) => (self.position, offset),
+            SeekFrom::End(offset) => match self.len_hint {
+                Some(len) => (len, offset),
+                None => match self.target.size()? {
+                    Some(len) => (len, offset),
+                    None => return Err(std::io::Error::new(
+                        std::io::ErrorKind::Unsupported,
+                        "seeking from the end needs the length of the target, but it isn't known",
+                    )),
+                },
+            },
+        };
+        self.position = base.checked_add_signed(offset).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+        })?;
+        Ok(self.position)
+    }
+}
+
+impl Yadon {
+    /// Applies the stored operations on a target which is written at explicit offsets, as `apply()` does with
+    /// `check_return_values` set, without a cursor. Seeks are only tracked. `SeekFrom::End` seeks resolve against
+    /// `len_hint`, or if that isn't given, the target's size, and fail with `ErrorKind::Unsupported` if neither is
+    /// known. If `start` isn't set, applying starts at offset 0.
+    pub fn apply_write_at<T>(&self, target: &mut T, len_hint: Option<u64>) -> Result<usize, ApplyError> where T: WriteAt {
+        self.apply(&mut Positioned::new(target, len_hint), true)
+    }
+}
@@ -0,0 +1,67 @@
+use core::fmt::{self, Debug, Display};
+
+/// Bytes, shown as the first `HexPreview::LIMIT` of them in hex, followed by how many there are in all, so that the
+/// data of a large write can be logged without dumping all of it, as over a slow link to a debugger. Needs neither
+/// `std` nor `alloc`. A logger with its own formatting, such as `defmt`, can show the same from `head()` and `len()`.
+/// # Example
+/// ```
+/// use yadon::HexPreview;
+/// assert_eq!(format!("{}", HexPreview::new(&[0xde, 0xad])), "de ad (2 bytes)");
+/// let preview = HexPreview::new(&[0xff; 1024]);
+/// assert_eq!(preview.head(), &[0xff; 16]);
+/// assert_eq!(format!("{}", preview), "ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff ff ... (1024 bytes)");
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct HexPreview<'a>(&'a [u8]);
+
+impl<'a> HexPreview<'a> {
+    /// How many bytes are shown.
+    pub const LIMIT: usize = 16;
+
+    /// A preview of `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        HexPreview(bytes)
+    }
+
+    /// The bytes which are shown, which are the first `LIMIT` of them, or all of them if there are fewer.
+    pub fn head(&self) -> &'a [u8] {
+        &self.0[0..self.0.len().min(Self::LIMIT)]
+    }
+
+    /// How many bytes there are in all.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether some of the bytes aren't shown.
+    pub fn is_truncated(&self) -> bool {
+        self.0.len() > Self::LIMIT
+    }
+}
+
+impl Display for HexPreview<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.head() {
+            write!(f, "{:02x} ", byte)?;
+        }
+        if self.is_truncated() {
+            write!(f, "... ")?;
+        }
+        match self.len() {
+            1 => write!(f, "(1 byte)"),
+            len => write!(f, "({} bytes)", len),
+        }
+    }
+}
+
+/// Shown as `Display` shows it.
+impl Debug for HexPreview<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::check::Checker;
+use crate::io::SeekFrom;
+use crate::operation::repeated_len;
+use crate::slice::SliceCursor;
+use crate::{invalid_seek, ApplyError, CheckPolicy, WriteOperation, Yadon};
+
+impl Yadon {
+    /// Lays the stored operations down onto `buf`, as `apply_to_slice()` does with `CheckPolicy::Strict`, but without
+    /// needing `std`. The slice never grows, so writes past its end are cut short and diverge, and whatever it held is
+    /// left where nothing is written. Resizing, durability barriers, copies, preconditions and custom operations can't
+    /// be laid down this way, nor can base checksums or a generation stamp be checked, so any of those fail with
+    /// `ApplyError::UnsupportedOperation` before anything is written. Returns the number of bytes written.
+    pub fn materialize_into_slice(&self, buf: &mut [u8]) -> Result<usize, ApplyError> {
+        self.replay_into_slice(buf, CheckPolicy::Strict, false)
+    }
+
+    /// Lays the stored operations down onto `buf` directly, checking the results of seeks and writes according to
+    /// `check_policy`. With `apply_custom`, custom operations are applied to a `Cursor` over the slice, at the position
+    /// they're reached at, rather than being unsupported; there are none without `std`.
+    #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+    pub(crate) fn replay_into_slice(&self, buf: &mut [u8], check_policy: CheckPolicy, apply_custom: bool)
+        -> Result<usize, ApplyError> {
+        self.check_can_apply()?;
+        if !self.base_checksums.is_empty() {
+            return Err(ApplyError::UnsupportedOperation("base checksum"));
+        }
+        if self.generation_stamp.is_some() {
+            return Err(ApplyError::UnsupportedOperation("generation stamp"));
+        }
+        let unsupported = self.operations.iter().find(|operation| !match operation {
+            WriteOperation::Write(..)
+            | WriteOperation::Seek(..)
+            | WriteOperation::DeferredSeek(_)
+            | WriteOperation::Flush
+            | WriteOperation::Fill { .. }
+            | WriteOperation::Repeat { .. } => true,
+            #[cfg(feature = "std")]
+            WriteOperation::Custom(..) => apply_custom,
+            _ => false,
+        });
+        if let Some(unsupported) = unsupported {
+            return Err(ApplyError::UnsupportedOperation(unsupported.name()));
+        }
+
+        let mut checker = Checker::new(self, check_policy);
+        let mut cursor = SliceCursor::new(buf);
+        // Where the cursor is, as far as the checker knows, so that divergences give the same offsets as applying does.
+        let mut position = None;
+        if let Some(start) = self.start {
+            position = Some(cursor.seek(SeekFrom::Start(start)).ok_or_else(invalid_seek)?);
+        }
+        let mut total_bytes_written = 0;
+        for (index, operation) in self.operations.iter().enumerate() {
+            checker.begin(index, operation, position);
+            let bytes_written = match operation {
+                WriteOperation::Write(data, expected_bytes_written) => {
+                    let bytes_written = cursor.write_pattern(data, data.len() as u64);
+                    checker.written(*expected_bytes_written, bytes_written)?
+                },
+                WriteOperation::Fill { byte, len } => {
+                    let bytes_written = cursor.write_pattern(&[*byte], *len);
+                    checker.written(*len as usize, bytes_written)?
+                },
+                WriteOperation::Repeat { pattern, count } => {
+                    let expected_bytes_written = repeated_len(pattern, *count)? as usize;
+                    let bytes_written = cursor.write_pattern(pattern, expected_bytes_written as u64);
+                    checker.written(expected_bytes_written, bytes_written)?
+                },
+                WriteOperation::Seek(pos, expected_position) => {
+                    let new_position = cursor.seek(*pos).ok_or_else(invalid_seek)?;
+                    position = Some(new_position);
+                    checker.position(*expected_position, new_position)?;
+                    continue;
+                },
+                WriteOperation::DeferredSeek(pos) => {
+                    position = Some(cursor.seek(*pos).ok_or_else(invalid_seek)?);
+                    continue;
+                },
+                #[cfg(feature = "std")]
+                WriteOperation::Custom(custom, expected) => {
+                    let outcome = cursor.with_cursor(|cursor| custom.apply(cursor))?;
+                    position = Some(outcome.position);
+                    let bytes_written = checker.written(expected.bytes_written as usize, outcome.bytes_written as usize)?;
+                    checker.position(expected.position, outcome.position)?;
+                    total_bytes_written += bytes_written;
+                    continue;
+                },
+                _ => continue,
+            };
+            position = position.map(|position| position + bytes_written as u64);
+            total_bytes_written += bytes_written;
+        }
+        self.count_apply();
+        Ok(total_bytes_written)
+    }
+}
@@ -0,0 +1,598 @@
+//! A line-oriented text script of `Yadon`'s operations, for dumping a recording, editing it by hand, and loading it
+//! back. The format is described on `Yadon::from_script()`.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::extent::Extents;
+use crate::io::SeekFrom;
+use crate::label::Labels;
+use crate::{LengthMode, OverflowPolicy, ScriptError, WriteOperation, Yadon};
+
+impl Yadon {
+    /// Writes the recording to `out` as a script, in the format `from_script()` describes, which it reads back. Settings which have their default values are left out. Data which is all printable
+    /// ASCII is written as a quoted string, and anything else as hex. A `WriteOperation::Custom` is written as a
+    /// `custom` line with its `Debug` output, but such a script can't be read back. Operations in the store given to
+    /// `with_store()` are read back from it, failing with `fmt::Error` if it can't be read.
+    pub fn to_script(&self, mut out: impl fmt::Write) -> fmt::Result {
+        if let Some(start) = self.start {
+            writeln!(out, "start {:#x}", start)?;
+        }
+        if let Some(length) = self.length {
+            writeln!(out, "length {:#x}", length)?;
+        }
+        if self.length_mode == LengthMode::Growable {
+            writeln!(out, "length_mode growable")?;
+        }
+        if self.overflow_policy == OverflowPolicy::Error {
+            writeln!(out, "overflow_policy error")?;
+        }
+        for (flag, name) in [(self.defer_end_seeks, "defer_end_seeks"), (self.append_only, "append_only"), (self.deny_overwrite, "deny_overwrite")] {
+            if flag {
+                writeln!(out, "{}", name)?;
+            }
+        }
+        if self.gap_fill != 0 {
+            writeln!(out, "gap_fill {:#04x}", self.gap_fill)?;
+        }
+        let applied = self.applied.load(Ordering::Relaxed);
+        if applied != 0 {
+            writeln!(out, "applied {}", applied)?;
+        }
+        if let Some(max_applies) = self.max_applies {
+            writeln!(out, "max_applies {}", max_applies)?;
+        }
+        if let Some((offset, generation)) = self.generation_stamp {
+            writeln!(out, "generation_stamp {:#x} {}", offset, generation)?;
+        }
+        for (range, crc32) in &self.base_checksums {
+            writeln!(out, "base_checksum {:#x}..{:#x} {:#010x}", range.start, range.end, crc32)?;
+        }
+        for range in self.written_extents.iter() {
+            writeln!(out, "written {:#x}..{:#x}", range.start, range.end)?;
+        }
+        for range in self.reserved_regions.iter() {
+            writeln!(out, "reserved {:#x}..{:#x}", range.start, range.end)?;
+        }
+        if self.labels.stack().next().is_some() {
+            out.write_str("label_stack")?;
+            for label in self.labels.stack() {
+                out.write_char(' ')?;
+                write_quoted(&mut out, label.as_bytes())?;
+            }
+            out.write_char('\n')?;
+        }
+
+        let mut label = None;
+        self.try_for_each_recorded(|index, operation| {
+            let label_of = self.labels.label_of(index).map(|label| &**label);
+            if label_of != label {
+                match label_of {
+                    Some(label_of) => {
+                        out.write_str("label ")?;
+                        write_quoted(&mut out, label_of.as_bytes())?;
+                        out.write_char('\n')?;
+                    },
+                    None => writeln!(out, "label none")?,
+                }
+                label = label_of;
+            }
+            match operation {
+                WriteOperation::Write(data, _) => {
+                    out.write_str("write ")?;
+                    write_data(&mut out, data)?;
+                },
+                WriteOperation::Seek(SeekFrom::End(offset), position) => {
+                    write!(out, "seek end {} at {:#x}", Signed(*offset), position)?;
+                },
+                WriteOperation::Seek(pos, _) => write!(out, "seek {}", Pos(*pos))?,
+                WriteOperation::DeferredSeek(pos) => write!(out, "deferred_seek {}", Pos(*pos))?,
+                WriteOperation::Flush => out.write_str("flush")?,
+                WriteOperation::Fill { byte, len } => write!(out, "fill {:#04x} {}", byte, len)?,
+                WriteOperation::Repeat { pattern, count } => {
+                    write!(out, "repeat {} ", count)?;
+                    write_data(&mut out, pattern)?;
+                },
+                WriteOperation::SetLen(len) => write!(out, "set_len {:#x}", len)?,
+                WriteOperation::Sync => out.write_str("sync")?,
+                WriteOperation::CopyWithin { src, dst, len } => write!(out, "copy {:#x} {:#x} {}", src, dst, len)?,
+                WriteOperation::AssertBytes { offset, expected } => {
+                    write!(out, "assert {:#x} ", offset)?;
+                    write_data(&mut out, expected)?;
+                },
+                #[cfg(feature = "std")]
+                WriteOperation::Custom(op, result) => {
+                    out.write_str("custom ")?;
+                    write_quoted(&mut out, format!("{:?}", op).as_bytes())?;
+                    write!(out, " # ends at {:#x}, having written {}", result.position, result.bytes_written)?;
+                },
+            }
+            out.write_char('\n')
+        }, |_| fmt::Error)
+    }
+
+    /// Reads a script, such as one written by `to_script()`, which recording can carry on from. Fails with the line
+    /// and column of the first problem found, which for a problem found by simulating the operations is where the
+    /// operation which caused it starts.
+    ///
+    /// Each line holds one directive, made of words separated by spaces. Everything after a `#` outside quotes is a
+    /// comment, and blank lines are skipped. Numbers are decimal, or hex with a `0x` prefix, and signed ones may start
+    /// with `-` or `+`. Ranges are two numbers joined by `..`, such as `0x10..0x20`. Data is either a quoted string,
+    /// whose bytes are those of its characters, with the escapes `\\`, `\"`, `\n`, `\r`, `\t`, `\0` and `\xNN`, or `hex`
+    /// followed by a quoted string of hex digits, in which whitespace is ignored, such as `hex "DE AD BE EF"`.
+    ///
+    /// These directives give the settings, and may appear anywhere, though `to_script()` writes them first:
+    ///
+    /// - `start <offset>` and `length <len>`, which are unset if left out. The length is the one recording would carry
+    ///   on from, which resizing operations in the script don't change.
+    /// - `length_mode fixed|growable` and `overflow_policy truncate|error`.
+    /// - `defer_end_seeks`, `append_only` and `deny_overwrite`, which set those flags.
+    /// - `gap_fill <byte>`, `applied <count>`, `max_applies <count>` and `generation_stamp <offset> <generation>`.
+    /// - `base_checksum <range> <crc32>`, `written <range>` and `reserved <range>`, each of which adds one more.
+    /// - `label_stack "<label>"...`, the labels pushed when recording stopped, outermost first.
+    ///
+    /// These record one operation each, in order:
+    ///
+    /// - `write <data>`
+    /// - `seek start <offset>`, `seek current <offset>`, or `seek end <offset> at <position>`
+    /// - `deferred_seek current <offset>` or `deferred_seek end <offset>`
+    /// - `flush` and `sync`
+    /// - `fill <byte> <len>`
+    /// - `repeat <count> <data>`
+    /// - `set_len <len>`
+    /// - `copy <src> <dst> <len>`
+    /// - `assert <offset> <data>`
+    ///
+    /// `label "<label>"` attaches a label to the operations after it, until the next `label`, and `label none` stops
+    /// attaching one. The expected length of each write and the expected position of each seek are found again by
+    /// simulating the operations, except for seeks from the end, whose position depends on the length when they were
+    /// recorded, so is given after `at`.
+    /// # Example
+    /// ```
+    /// use yadon::Yadon;
+    /// let yadon = Yadon::from_script("
+    ///     start 0
+    ///     seek start 0x200   # the table
+    ///     write hex \"DE AD BE EF\"
+    ///     fill 0x00 1024
+    ///     write \"end\\0\"
+    /// ").unwrap();
+    /// let mut script = String::new();
+    /// yadon.to_script(&mut script).unwrap();
+    /// assert_eq!(script, "start 0x0\nseek start 0x200\nwrite hex \"DE AD BE EF\"\nfill 0x00 1024\nwrite hex \"65 6E 64 00\"\n");
+    /// ```
+    pub fn from_script(script: &str) -> Result<Yadon, ScriptError> {
+        let mut yadon = Yadon::new(None, None);
+        let (mut written, mut reserved) = (Vec::new(), Vec::new());
+        let (mut label_stack, mut label_runs) = (Vec::new(), Vec::<(String, Range<usize>)>::new());
+        let mut label: Option<String> = None;
+        let mut applied = 0;
+        // Where each operation starts, and whether its expected result is to be filled in.
+        let mut origins = Vec::new();
+
+        for (line_index, text) in script.lines().enumerate() {
+            let mut line = Line::new(line_index + 1, text)?;
+            let (column, directive) = match line.next_word() {
+                Some(word) => word,
+                None => continue,
+            };
+            let operation = match directive {
+                "start" => {
+                    yadon.start = Some(line.u64()?);
+                    None
+                },
+                "length" => {
+                    yadon.length = Some(line.u64()?);
+                    None
+                },
+                "length_mode" => {
+                    yadon.length_mode = match line.word("a length mode")? {
+                        (_, "fixed") => LengthMode::Fixed,
+                        (_, "growable") => LengthMode::Growable,
+                        (column, _) => return Err(line.error_at(column, "expected `fixed` or `growable`")),
+                    };
+                    None
+                },
+                "overflow_policy" => {
+                    yadon.overflow_policy = match line.word("an overflow policy")? {
+                        (_, "truncate") => OverflowPolicy::Truncate,
+                        (_, "error") => OverflowPolicy::Error,
+                        (column, _) => return Err(line.error_at(column, "expected `truncate` or `error`")),
+                    };
+                    None
+                },
+                "defer_end_seeks" => {
+                    yadon.defer_end_seeks = true;
+                    None
+                },
+                "append_only" => {
+                    yadon.append_only = true;
+                    None
+                },
+                "deny_overwrite" => {
+                    yadon.deny_overwrite = true;
+                    None
+                },
+                "gap_fill" => {
+                    yadon.gap_fill = line.u8()?;
+                    None
+                },
+                "applied" => {
+                    applied = line.u64()?;
+                    None
+                },
+                "max_applies" => {
+                    yadon.max_applies = Some(line.u64()?);
+                    None
+                },
+                "generation_stamp" => {
+                    yadon.generation_stamp = Some((line.u64()?, line.u64()?));
+                    None
+                },
+                "base_checksum" => {
+                    let range = line.range()?;
+                    let crc32 = line.u64()?;
+                    let crc32 = u32::try_from(crc32).map_err(|_| line.error("checksum doesn't fit in 32 bits"))?;
+                    yadon.base_checksums.push((range, crc32));
+                    None
+                },
+                "written" => {
+                    written.push(line.range()?);
+                    None
+                },
+                "reserved" => {
+                    reserved.push(line.range()?);
+                    None
+                },
+                "label_stack" => {
+                    while !line.is_empty() {
+                        label_stack.push(line.label()?);
+                    }
+                    None
+                },
+                "label" => {
+                    label = match line.peek_word() {
+                        Some("none") => {
+                            line.next_word();
+                            None
+                        },
+                        _ => Some(line.label()?),
+                    };
+                    None
+                },
+                "write" => Some((WriteOperation::Write(line.data()?, 0), true)),
+                "seek" => Some(match line.word("where to seek from")? {
+                    (_, "start") => (WriteOperation::Seek(SeekFrom::Start(line.u64()?), 0), true),
+                    (_, "current") => (WriteOperation::Seek(SeekFrom::Current(line.i64()?), 0), true),
+                    (_, "end") => {
+                        let offset = line.i64()?;
+                        line.keyword("at")?;
+                        (WriteOperation::Seek(SeekFrom::End(offset), line.u64()?), false)
+                    },
+                    (column, _) => return Err(line.error_at(column, "expected `start`, `current` or `end`")),
+                }),
+                "deferred_seek" => Some(match line.word("where to seek from")? {
+                    (_, "current") => (WriteOperation::DeferredSeek(SeekFrom::Current(line.i64()?)), false),
+                    (_, "end") => (WriteOperation::DeferredSeek(SeekFrom::End(line.i64()?)), false),
+                    (column, _) => return Err(line.error_at(column, "expected `current` or `end`")),
+                }),
+                "flush" => Some((WriteOperation::Flush, false)),
+                "sync" => Some((WriteOperation::Sync, false)),
+                "fill" => Some((WriteOperation::Fill { byte: line.u8()?, len: line.u64()? }, false)),
+                "repeat" => {
+                    let count = line.u64()?;
+                    Some((WriteOperation::Repeat { pattern: line.data()?, count }, false))
+                },
+                "set_len" => Some((WriteOperation::SetLen(line.u64()?), false)),
+                "copy" => Some((WriteOperation::CopyWithin { src: line.u64()?, dst: line.u64()?, len: line.u64()? }, false)),
+                "assert" => {
+                    let offset = line.u64()?;
+                    Some((WriteOperation::AssertBytes { offset, expected: line.data()? }, false))
+                },
+                "custom" => return Err(line.error_at(column, "custom operations can't be read back")),
+                _ => return Err(line.error_at(column, "unknown directive")),
+            };
+            line.finish()?;
+            if let Some((operation, fill_in)) = operation {
+                let index = yadon.operations.len();
+                if let Some(label) = &label {
+                    match label_runs.last_mut() {
+                        Some((last, operations)) if last == label && operations.end == index => operations.end += 1,
+                        _ => label_runs.push((label.clone(), index..index + 1)),
+                    }
+                }
+                yadon.operations.push(operation);
+                origins.push((line.number, column, fill_in));
+            }
+        }
+
+        yadon.written_extents = Extents::from_ranges(written).expect("ranges are checked to be non-empty");
+        yadon.reserved_regions = Extents::from_ranges(reserved).expect("ranges are checked to be non-empty");
+        yadon.labels = Labels::from_parts(label_stack, label_runs, yadon.operations.len()).expect("label runs are in order");
+        yadon.applied = AtomicU64::new(applied);
+        yadon.resimulate_filling(|index| origins[index].2).map_err(|(index, message)| {
+            let (line, column, _) = origins[index];
+            ScriptError { line, column, message }
+        })?;
+        Ok(yadon)
+    }
+}
+
+/// A seek position other than from the end, as a script writes it.
+struct Pos(SeekFrom);
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            SeekFrom::Start(offset) => write!(f, "start {:#x}", offset),
+            SeekFrom::Current(offset) => write!(f, "current {}", Signed(offset)),
+            SeekFrom::End(offset) => write!(f, "end {}", Signed(offset)),
+        }
+    }
+}
+
+/// A signed offset, in hex.
+struct Signed(i64);
+
+impl fmt::Display for Signed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            offset if offset < 0 => write!(f, "-{:#x}", offset.unsigned_abs()),
+            offset => write!(f, "{:#x}", offset),
+        }
+    }
+}
+
+fn write_data(out: &mut impl fmt::Write, data: &[u8]) -> fmt::Result {
+    if data.iter().all(|&byte| byte == b' ' || byte.is_ascii_graphic()) {
+        return write_quoted(out, data);
+    }
+    out.write_str("hex \"")?;
+    for (i, byte) in data.iter().enumerate() {
+        if i > 0 {
+            out.write_char(' ')?;
+        }
+        write!(out, "{:02X}", byte)?;
+    }
+    out.write_char('"')
+}
+
+fn write_quoted(out: &mut impl fmt::Write, data: &[u8]) -> fmt::Result {
+    out.write_char('"')?;
+    for chunk in data.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => out.write_str("\\\\")?,
+                '"' => out.write_str("\\\"")?,
+                '\n' => out.write_str("\\n")?,
+                '\r' => out.write_str("\\r")?,
+                '\t' => out.write_str("\\t")?,
+                '\0' => out.write_str("\\0")?,
+                c if c.is_control() => {
+                    for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                        write!(out, "\\x{:02x}", byte)?;
+                    }
+                },
+                c => out.write_char(c)?,
+            }
+        }
+        for byte in chunk.invalid() {
+            write!(out, "\\x{:02x}", byte)?;
+        }
+    }
+    out.write_char('"')
+}
+
+/// A word, or the contents of a quoted string, and the column it starts at.
+enum Token<'a> {
+    Word(usize, &'a str),
+    Quoted(usize, Vec<u8>),
+}
+
+/// The tokens of one line of a script, being read in turn.
+struct Line<'a> {
+    number: usize,
+    tokens: core::iter::Peekable<alloc::vec::IntoIter<Token<'a>>>,
+    /// The column just past the last token, where a missing token is reported.
+    end: usize,
+}
+
+impl<'a> Line<'a> {
+    /// Splits `text` into tokens, leaving out any comment.
+    fn new(number: usize, text: &'a str) -> Result<Self, ScriptError> {
+        let error = |column: usize, message: &str| ScriptError { line: number, column, message: message.to_string() };
+        let mut tokens = Vec::new();
+        let mut chars = text.char_indices().zip(1..).peekable();
+        let mut end = 1;
+        while let Some(((at, c), column)) = chars.next() {
+            if c == '#' {
+                break;
+            }
+            if c.is_whitespace() {
+                continue;
+            }
+            if c != '"' {
+                let mut word_end = at + c.len_utf8();
+                end = column + 1;
+                while let Some(&((next_at, next), next_column)) = chars.peek() {
+                    if next.is_whitespace() || next == '"' || next == '#' {
+                        break;
+                    }
+                    word_end = next_at + next.len_utf8();
+                    end = next_column + 1;
+                    chars.next();
+                }
+                tokens.push(Token::Word(column, &text[at..word_end]));
+                continue;
+            }
+            let mut bytes = Vec::new();
+            loop {
+                let ((_, c), escape_column) = chars.next().ok_or_else(|| error(column, "string isn't closed"))?;
+                let escaped = match c {
+                    '"' => {
+                        end = escape_column + 1;
+                        break;
+                    },
+                    '\\' => match chars.next() {
+                        Some(((_, '\\'), _)) => b'\\',
+                        Some(((_, '"'), _)) => b'"',
+                        Some(((_, 'n'), _)) => b'\n',
+                        Some(((_, 'r'), _)) => b'\r',
+                        Some(((_, 't'), _)) => b'\t',
+                        Some(((_, '0'), _)) => 0,
+                        Some(((_, 'x'), _)) => {
+                            let mut digit = || chars.next().and_then(|((_, c), _)| c.to_digit(16));
+                            match (digit(), digit()) {
+                                (Some(high), Some(low)) => (high << 4 | low) as u8,
+                                _ => return Err(error(escape_column, "`\\x` must be followed by two hex digits")),
+                            }
+                        },
+                        _ => return Err(error(escape_column, "unknown escape")),
+                    },
+                    c => {
+                        bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        continue;
+                    },
+                };
+                bytes.push(escaped);
+            }
+            tokens.push(Token::Quoted(column, bytes));
+        }
+        Ok(Line { number, tokens: tokens.into_iter().peekable(), end })
+    }
+
+    fn error_at(&self, column: usize, message: &str) -> ScriptError {
+        ScriptError { line: self.number, column, message: message.to_string() }
+    }
+
+    /// An error at the next token, or past the end of the line if there isn't one.
+    fn error(&mut self, message: &str) -> ScriptError {
+        let column = match self.tokens.peek() {
+            Some(Token::Word(column, _)) | Some(Token::Quoted(column, _)) => *column,
+            None => self.end,
+        };
+        self.error_at(column, message)
+    }
+
+    fn is_empty(&mut self) -> bool {
+        self.tokens.peek().is_none()
+    }
+
+    fn peek_word(&mut self) -> Option<&'a str> {
+        match self.tokens.peek() {
+            Some(Token::Word(_, word)) => Some(*word),
+            _ => None,
+        }
+    }
+
+    fn next_word(&mut self) -> Option<(usize, &'a str)> {
+        match self.tokens.peek() {
+            Some(&Token::Word(column, word)) => {
+                self.tokens.next();
+                Some((column, word))
+            },
+            _ => None,
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<(usize, &'a str), ScriptError> {
+        self.next_word().ok_or_else(|| self.error(&format!("expected {}", what)))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ScriptError> {
+        match self.peek_word() {
+            Some(word) if word == keyword => {
+                self.tokens.next();
+                Ok(())
+            },
+            _ => Err(self.error(&format!("expected `{}`", keyword))),
+        }
+    }
+
+    fn u64(&mut self) -> Result<u64, ScriptError> {
+        let (column, word) = self.word("a number")?;
+        parse_u64(word).ok_or_else(|| self.error_at(column, "expected a number"))
+    }
+
+    fn i64(&mut self) -> Result<i64, ScriptError> {
+        let (column, word) = self.word("a number")?;
+        let (negative, digits) = match word.as_bytes().first() {
+            Some(b'-') => (true, &word[1..]),
+            Some(b'+') => (false, &word[1..]),
+            _ => (false, word),
+        };
+        parse_u64(digits)
+            .and_then(|magnitude| match negative {
+                true => 0i64.checked_sub_unsigned(magnitude),
+                false => i64::try_from(magnitude).ok(),
+            })
+            .ok_or_else(|| self.error_at(column, "expected a signed number which fits in 64 bits"))
+    }
+
+    fn u8(&mut self) -> Result<u8, ScriptError> {
+        let (column, word) = self.word("a byte")?;
+        parse_u64(word).and_then(|byte| u8::try_from(byte).ok()).ok_or_else(|| self.error_at(column, "expected a byte"))
+    }
+
+    fn range(&mut self) -> Result<Range<u64>, ScriptError> {
+        let (column, word) = self.word("a range")?;
+        let range = word.split_once("..")
+            .and_then(|(start, end)| Some(parse_u64(start)?..parse_u64(end)?))
+            .ok_or_else(|| self.error_at(column, "expected a range, such as `0x10..0x20`"))?;
+        if range.is_empty() {
+            return Err(self.error_at(column, "range is empty"));
+        }
+        Ok(range)
+    }
+
+    fn quoted(&mut self, what: &str) -> Result<(usize, Vec<u8>), ScriptError> {
+        match self.tokens.peek() {
+            Some(Token::Quoted(..)) => match self.tokens.next() {
+                Some(Token::Quoted(column, bytes)) => Ok((column, bytes)),
+                _ => unreachable!("token was just peeked"),
+            },
+            _ => Err(self.error(&format!("expected {}", what))),
+        }
+    }
+
+    fn label(&mut self) -> Result<String, ScriptError> {
+        let (column, bytes) = self.quoted("a quoted label")?;
+        String::from_utf8(bytes).map_err(|_| self.error_at(column, "label isn't valid UTF-8"))
+    }
+
+    fn data(&mut self) -> Result<Vec<u8>, ScriptError> {
+        if self.peek_word() != Some("hex") {
+            return Ok(self.quoted("a quoted string, or `hex` followed by one")?.1);
+        }
+        self.tokens.next();
+        let (column, text) = self.quoted("a quoted string of hex digits")?;
+        let digits = text.iter()
+            .filter(|byte| !byte.is_ascii_whitespace())
+            .map(|&byte| char::from(byte).to_digit(16).map(|digit| digit as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| self.error_at(column, "expected only hex digits"))?;
+        if digits.len() % 2 != 0 {
+            return Err(self.error_at(column, "odd number of hex digits"));
+        }
+        Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+    }
+
+    /// Checks that nothing is left on the line.
+    fn finish(&mut self) -> Result<(), ScriptError> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(self.error("unexpected extra token")),
+        }
+    }
+}
+
+fn parse_u64(word: &str) -> Option<u64> {
+    match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => word.parse().ok(),
+    }
+}
@@ -0,0 +1,139 @@
+//! `Serialize` and `Deserialize` for `Yadon`, behind the `serde` feature. `WriteOperation`, `OverflowPolicy` and
+//! `LengthMode` derive theirs where they're defined.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, Ordering};
+use serde::de::Error as _;
+use serde::ser::{Error as _, SerializeSeq};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::extent::Extents;
+use crate::io::SeekFrom;
+use crate::label::Labels;
+use crate::{LengthMode, OverflowPolicy, WriteOperation, Yadon};
+
+/// `SeekFrom`, which has no serde impls of its own.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SeekFrom")]
+pub(crate) enum SeekFromDef {
+    Start(u64),
+    End(i64),
+    Current(i64),
+}
+
+/// What is serialized of a `Yadon`. Where each operation was recorded from isn't kept, and the virtual position and
+/// what depends on it are found again from the operations when deserializing.
+#[derive(Serialize)]
+#[serde(rename = "Yadon")]
+struct YadonRef<'a> {
+    operations: Operations<'a>,
+    start: Option<u64>,
+    length: Option<u64>,
+    defer_end_seeks: bool,
+    append_only: bool,
+    deny_overwrite: bool,
+    written_extents: Vec<Range<u64>>,
+    reserved_regions: Vec<Range<u64>>,
+    overflow_policy: OverflowPolicy,
+    length_mode: LengthMode,
+    label_stack: Vec<&'a str>,
+    labels: Vec<(&'a str, Range<usize>)>,
+    base_checksums: &'a [(Range<u64>, u32)],
+    applied: u64,
+    max_applies: Option<u64>,
+    generation_stamp: Option<(u64, u64)>,
+    gap_fill: u8,
+}
+
+/// The operations recorded into a `Yadon`, serialized as a sequence whether they're in `operations` or the store given
+/// to `with_store()`.
+struct Operations<'a>(&'a Yadon);
+
+impl Serialize for Operations<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut operations = serializer.serialize_seq(Some(self.0.operation_count()))?;
+        self.0.try_for_each_recorded(|_, operation| operations.serialize_element(operation), S::Error::custom)?;
+        operations.end()
+    }
+}
+
+/// What is deserialized into a `Yadon`, with the same fields as `YadonRef`.
+#[derive(Deserialize)]
+#[serde(rename = "Yadon")]
+struct YadonOwned {
+    operations: Vec<WriteOperation>,
+    start: Option<u64>,
+    length: Option<u64>,
+    defer_end_seeks: bool,
+    append_only: bool,
+    deny_overwrite: bool,
+    written_extents: Vec<Range<u64>>,
+    reserved_regions: Vec<Range<u64>>,
+    overflow_policy: OverflowPolicy,
+    length_mode: LengthMode,
+    label_stack: Vec<String>,
+    labels: Vec<(String, Range<usize>)>,
+    base_checksums: Vec<(Range<u64>, u32)>,
+    applied: u64,
+    max_applies: Option<u64>,
+    generation_stamp: Option<(u64, u64)>,
+    gap_fill: u8,
+}
+
+/// Serializes everything needed to apply the stored operations elsewhere, or to carry on recording. Operations in the
+/// store given to `with_store()` are read back from it. Fails if any of the operations is a `WriteOperation::Custom`,
+/// or if the store can't be read.
+impl Serialize for Yadon {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        YadonRef {
+            operations: Operations(self),
+            start: self.start,
+            length: self.length,
+            defer_end_seeks: self.defer_end_seeks,
+            append_only: self.append_only,
+            deny_overwrite: self.deny_overwrite,
+            written_extents: self.written_extents.iter().collect(),
+            reserved_regions: self.reserved_regions.iter().collect(),
+            overflow_policy: self.overflow_policy,
+            length_mode: self.length_mode,
+            label_stack: self.labels.stack().collect(),
+            labels: self.labels.iter().collect(),
+            base_checksums: &self.base_checksums,
+            applied: self.applied.load(Ordering::Relaxed),
+            max_applies: self.max_applies,
+            generation_stamp: self.generation_stamp,
+            gap_fill: self.gap_fill,
+        }.serialize(serializer)
+    }
+}
+
+/// Deserializes a `Yadon` which recording can carry on from. The stored operations are simulated from `start` to find
+/// the virtual position, and each write's expected length and each seek's expected position are checked against the
+/// simulation, so that a recording which doesn't agree with itself fails to deserialize. Seeks from the end are taken
+/// to have ended where they say, as the length they were resolved against may have changed since.
+impl<'de> Deserialize<'de> for Yadon {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        let owned = YadonOwned::deserialize(deserializer)?;
+        let labels = Labels::from_parts(owned.label_stack, owned.labels, owned.operations.len()).map_err(D::Error::custom)?;
+        let mut yadon = Yadon {
+            operations: owned.operations,
+            defer_end_seeks: owned.defer_end_seeks,
+            append_only: owned.append_only,
+            deny_overwrite: owned.deny_overwrite,
+            written_extents: Extents::from_ranges(owned.written_extents).map_err(D::Error::custom)?,
+            reserved_regions: Extents::from_ranges(owned.reserved_regions).map_err(D::Error::custom)?,
+            overflow_policy: owned.overflow_policy,
+            length_mode: owned.length_mode,
+            labels,
+            base_checksums: owned.base_checksums,
+            applied: AtomicU64::new(owned.applied),
+            max_applies: owned.max_applies,
+            generation_stamp: owned.generation_stamp,
+            gap_fill: owned.gap_fill,
+            ..Yadon::new(owned.start, owned.length)
+        };
+        yadon.resimulate().map_err(D::Error::custom)?;
+        Ok(yadon)
+    }
+}
@@ -0,0 +1,121 @@
+use std::convert::TryFrom;
+use std::io::{Seek, Write};
+use crate::{ApplyOptions, ApplyOrder, SessionError, Yadon};
+
+/// Applies the stored operations of a `Yadon` in steps which can be resumed, keeping track of how far applying has got.
+/// Operations are always applied in the order they were recorded, whatever `ApplyOptions::order` is.
+/// # Example
+/// ```
+/// use yadon::Yadon;
+/// use std::io::{Cursor, Write};
+/// let mut yadon = Yadon::new(Some(2), Some(8));
+/// yadon.write_all(&[1, 2]).unwrap();
+/// yadon.write_all(&[3, 4, 5]).unwrap();
+///
+/// // The second write doesn't fit in this target.
+/// let mut session = yadon.session(Default::default());
+/// let error = session.run(&mut Cursor::new(&mut [0u8; 4][..])).unwrap_err();
+/// assert_eq!(error.ops_completed, 1);
+///
+/// // The state can be saved, and the session resumed from it later on.
+/// let state = session.state().to_bytes();
+/// let mut session = yadon.resume_session(Default::default(), yadon::SessionState::from_bytes(&state).unwrap());
+/// let mut target = Cursor::new(vec![0u8; 8]);
+/// assert_eq!(session.resume(&mut target).unwrap(), 3);
+/// assert_eq!(target.get_ref(), &[0, 0, 0, 0, 3, 4, 5, 0]);
+/// ```
+#[derive(Debug)]
+pub struct ApplySession<'y> {
+    yadon: &'y Yadon,
+    options: ApplyOptions,
+    state: SessionState,
+}
+
+/// How far an `ApplySession` has got, which is enough to resume it, even in another process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SessionState {
+    /// Index of the next operation to apply.
+    pub next_op: usize,
+    /// Where the target must be positioned to apply the next operation, or `None` if that isn't known. Once some
+    /// operations have been applied, the session can only be resumed if this is known.
+    pub position: Option<u64>,
+}
+
+impl SessionState {
+    /// Length of the encoding from `to_bytes()`.
+    pub const ENCODED_LEN: usize = 17;
+
+    /// Encodes the state as the index of the next operation, followed by whether the position is known and the
+    /// position, as little-endian integers.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0u8; Self::ENCODED_LEN];
+        bytes[0..8].copy_from_slice(&(self.next_op as u64).to_le_bytes());
+        if let Some(position) = self.position {
+            bytes[8] = 1;
+            bytes[9..17].copy_from_slice(&position.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Decodes a state encoded by `to_bytes()`, or returns `None` if `bytes` isn't one.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != Self::ENCODED_LEN {
+            return None;
+        }
+        let next_op = u64::from_le_bytes(<[u8; 8]>::try_from(&bytes[0..8]).ok()?);
+        let position = u64::from_le_bytes(<[u8; 8]>::try_from(&bytes[9..17]).ok()?);
+        let position = match bytes[8] {
+            0 if position == 0 => None,
+            1 => Some(position),
+            _ => return None,
+        };
+        Some(SessionState {
+            next_op: usize::try_from(next_op).ok()?,
+            position,
+        })
+    }
+}
+
+impl Yadon {
+    /// Starts a session which applies the stored operations in resumable steps.
+    pub fn session(&self, options: ApplyOptions) -> ApplySession<'_> {
+        self.resume_session(options, SessionState::default())
+    }
+
+    /// Continues a session from a state saved with `ApplySession::state()`.
+    pub fn resume_session(&self, options: ApplyOptions, state: SessionState) -> ApplySession<'_> {
+        ApplySession {
+            yadon: self,
+            options: ApplyOptions {
+                order: ApplyOrder::Recorded,
+                ..options
+            },
+            state,
+        }
+    }
+}
+
+impl<'y> ApplySession<'y> {
+    /// How far the session has got.
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Whether every operation has been applied.
+    pub fn is_finished(&self) -> bool {
+        self.state.next_op >= self.yadon.operations.len()
+    }
+
+    /// Applies every operation from the first, returning the number of bytes written. If applying fails, the session
+    /// keeps track of how many operations were applied completely, so that `resume()` can continue after them.
+    pub fn run<T>(&mut self, target: &mut T) -> Result<usize, SessionError> where T: Write + Seek {
+        self.state = SessionState::default();
+        self.resume(target)
+    }
+
+    /// Applies the operations which haven't been applied yet, returning the number of bytes written. Unless nothing
+    /// has been applied yet, the target is first seeked to `SessionState::position`.
+    pub fn resume<T>(&mut self, target: &mut T) -> Result<usize, SessionError> where T: Write + Seek {
+        self.yadon.apply_from(target, &self.options, &mut self.state)
+    }
+}
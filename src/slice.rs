@@ -0,0 +1,50 @@
+use core::convert::TryFrom;
+use crate::io::SeekFrom;
+
+/// A position in a byte slice, which is written to as `Cursor` writes to a slice.
+pub(crate) struct SliceCursor<'a> {
+    buf: &'a mut [u8],
+    position: u64,
+}
+
+impl<'a> SliceCursor<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        SliceCursor { buf, position: 0 }
+    }
+
+    /// Writes as many of the first `len` bytes of `pattern` repeated as fit before the end, returning how many were
+    /// written.
+    pub(crate) fn write_pattern(&mut self, pattern: &[u8], len: u64) -> usize {
+        if pattern.is_empty() {
+            return 0;
+        }
+        let start = usize::try_from(self.position).unwrap_or(usize::MAX).min(self.buf.len());
+        let len = usize::try_from(len).unwrap_or(usize::MAX).min(self.buf.len() - start);
+        for (i, byte) in self.buf[start..start + len].iter_mut().enumerate() {
+            *byte = pattern[i % pattern.len()];
+        }
+        self.position += len as u64;
+        len
+    }
+
+    /// Seeks to `pos`, returning the new position, or `None` without moving if it would be negative or wouldn't fit in
+    /// a `u64`.
+    pub(crate) fn seek(&mut self, pos: SeekFrom) -> Option<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::End(offset) => (self.buf.len() as u64).checked_add_signed(offset)?,
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)?,
+        };
+        Some(self.position)
+    }
+
+    /// Runs `f` on a `Cursor` over the slice at the current position, moving to wherever it leaves the `Cursor`.
+    #[cfg(feature = "std")]
+    pub(crate) fn with_cursor<R>(&mut self, f: impl FnOnce(&mut std::io::Cursor<&mut [u8]>) -> R) -> R {
+        let mut cursor = std::io::Cursor::new(&mut *self.buf);
+        cursor.set_position(self.position);
+        let result = f(&mut cursor);
+        self.position = cursor.position();
+        result
+    }
+}
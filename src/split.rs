@@ -0,0 +1,90 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::label::Labels;
+use crate::Yadon;
+
+impl Yadon {
+    /// Splits the recording in two at `index`, as `Vec::split_off()` does, so that it can be applied in stages. This
+    /// recording keeps the operations before `index`, and the ones from `index` on are returned as a recording whose
+    /// `start` is where this one leaves the virtual position, so that applying it on its own seeks there first, and
+    /// applying this one, then it, does what applying the whole recording did. Labels are split along with the
+    /// operations they cover.
+    ///
+    /// As with `compose()`, which puts them back together, this recording keeps its `start`, base checksums and apply
+    /// limit, and the returned one has the generation stamp, along with the length and recording settings, so that
+    /// recording carries on into it; it has the same apply limit. If the virtual position at `index` isn't known, as
+    /// after a deferred seek from the end, the returned recording has no `start`, so it carries on from wherever
+    /// applying this one left the target. Panics if `index` is greater than the number of operations, if the stored
+    /// operations don't agree with one another, or if they're in the store given to `with_store()`, which can't be
+    /// split.
+    /// # Example
+    /// ```
+    /// use yadon::Yadon;
+    /// let mut yadon = Yadon::new(Some(0), Some(8));
+    /// yadon.write(b"phase").unwrap();
+    /// yadon.write(b"two").unwrap();
+    ///
+    /// let second = yadon.split_off(1);
+    /// assert_eq!(second.start, Some(5));
+    /// let mut target = *b"........";
+    /// yadon.materialize_into_slice(&mut target).unwrap();
+    /// assert_eq!(&target, b"phase...");
+    /// second.materialize_into_slice(&mut target).unwrap();
+    /// assert_eq!(&target, b"phasetwo");
+    /// ```
+    pub fn split_off(&mut self, index: usize) -> Yadon {
+        #[cfg(feature = "std")]
+        assert!(self.store.is_none(), "operations in a store can't be split");
+        let count = self.operation_count();
+        assert!(index <= count, "split index {} is past the {} operations", index, count);
+        let operations = self.operations.split_off(index);
+        #[cfg(feature = "track-callers")]
+        let locations = {
+            self.locations.resize(index + operations.len(), None);
+            self.locations.split_off(index)
+        };
+        let labels = core::mem::take(&mut self.labels);
+        let mut head_runs: Vec<(String, Range<usize>)> = Vec::new();
+        let mut tail_runs: Vec<(String, Range<usize>)> = Vec::new();
+        for (label, run) in labels.iter() {
+            if run.start < index {
+                head_runs.push((label.to_string(), run.start..run.end.min(index)));
+            }
+            if run.end > index {
+                tail_runs.push((label.to_string(), run.start.max(index) - index..run.end - index));
+            }
+        }
+        self.labels = Labels::from_parts(Vec::new(), head_runs, index).expect("runs before the split are in order");
+        let stack = labels.stack().map(ToString::to_string).collect();
+        let tail_labels = Labels::from_parts(stack, tail_runs, operations.len()).expect("runs after the split are in order");
+
+        let (position, end_unresolved, written_end) = (self.virtual_position, self.end_unresolved, self.written_end);
+        self.resimulate().expect("operations before the split agree with one another");
+        let start = match self.end_unresolved {
+            true => None,
+            false => self.virtual_position.or(self.start),
+        };
+        let mut tail = Yadon {
+            operations,
+            #[cfg(feature = "track-callers")]
+            locations,
+            labels: tail_labels,
+            defer_end_seeks: self.defer_end_seeks,
+            append_only: self.append_only,
+            deny_overwrite: self.deny_overwrite,
+            written_extents: self.written_extents.clone(),
+            reserved_regions: self.reserved_regions.clone(),
+            overflow_policy: self.overflow_policy,
+            length_mode: self.length_mode,
+            max_applies: self.max_applies,
+            generation_stamp: self.generation_stamp.take(),
+            gap_fill: self.gap_fill,
+            ..Yadon::new(start, self.length)
+        };
+        tail.resimulate().expect("operations after the split carry on from where those before it leave the position");
+        // The tail is where recording carries on, so it takes on where recording had got to.
+        (tail.virtual_position, tail.end_unresolved, tail.written_end) = (position, end_unresolved, written_end);
+        tail
+    }
+}
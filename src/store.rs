@@ -0,0 +1,210 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use crate::wire::{decode_operation, put_operation};
+use crate::{ApplyError, WriteOperation, Yadon};
+
+/// Somewhere to store operations as they're recorded, given to `Yadon::with_store()`.
+pub trait OpSink {
+    /// Stores `operation` after those stored before it. A store which can't hold a `WriteOperation::Custom` should
+    /// fail with `ErrorKind::InvalidInput` without storing anything of it, so that recording can carry on.
+    fn append(&mut self, operation: WriteOperation) -> std::io::Result<()>;
+
+    /// Number of operations stored.
+    fn len(&self) -> usize;
+
+    /// Whether no operations are stored.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// An `OpSink` whose operations can be read back, in the order they were stored, so that `Yadon::apply_store()` can
+/// apply them.
+pub trait OpStore: OpSink {
+    /// Calls `f` with the index of each stored operation and the operation, in order, stopping at the first error `f`
+    /// returns. Fails with `ApplyError::Io` if the operations can't be read.
+    fn for_each_operation(&self, f: &mut dyn FnMut(usize, &WriteOperation) -> Result<(), ApplyError>) -> Result<(), ApplyError>;
+}
+
+/// Where `Yadon` stores operations unless it's given another `OpStore`.
+impl OpSink for Vec<WriteOperation> {
+    fn append(&mut self, operation: WriteOperation) -> std::io::Result<()> {
+        self.push(operation);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+}
+
+impl OpStore for Vec<WriteOperation> {
+    fn for_each_operation(&self, f: &mut dyn FnMut(usize, &WriteOperation) -> Result<(), ApplyError>) -> Result<(), ApplyError> {
+        self.iter().enumerate().try_for_each(|(index, operation)| f(index, operation))
+    }
+}
+
+/// Identifies an `OpFile`: the magic bytes `YOPS`, then the format version, which is 1.
+const OP_FILE_HEADER: &[u8; 5] = b"YOPS\x01";
+
+/// An `OpStore` which appends each operation to a file as it's stored, so that a long recording needn't be kept in
+/// memory. After the 5 byte header, each operation is a record of its length, as 8 little-endian bytes, followed by the
+/// operation encoded as the `wire` module describes. Reading the operations back opens the file again, so they can be
+/// read while more are appended. `WriteOperation::Custom` can't be stored.
+#[derive(Debug)]
+pub struct OpFile {
+    path: PathBuf,
+    file: File,
+    len: usize,
+}
+
+impl OpFile {
+    /// Creates a file at `path` to store operations in, replacing whatever is there.
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<OpFile> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::create(&path)?;
+        file.write_all(OP_FILE_HEADER)?;
+        Ok(OpFile { path, file, len: 0 })
+    }
+
+    /// Opens a file created by `create()`, to read the operations stored in it, or store more after them. Fails with
+    /// `ErrorKind::InvalidData` if it isn't one, or ends partway through a record.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<OpFile> {
+        let path = path.as_ref().to_path_buf();
+        let mut reader = Records::open(&path)?;
+        let mut len = 0;
+        while reader.skip()? {
+            len += 1;
+        }
+        let file = OpenOptions::new().append(true).open(&path)?;
+        Ok(OpFile { path, file, len })
+    }
+
+    /// Where the operations are stored.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl OpSink for OpFile {
+    /// Appends `operation` to the file as one write. Fails with `ErrorKind::InvalidInput` for a
+    /// `WriteOperation::Custom`, without writing anything.
+    fn append(&mut self, operation: WriteOperation) -> std::io::Result<()> {
+        if let WriteOperation::Custom(..) = operation {
+            return Err(std::io::Error::new(ErrorKind::InvalidInput, "custom operations can't be stored in a file"));
+        }
+        let mut record = vec![0; 8];
+        put_operation(&mut record, &operation);
+        let len = record.len() as u64 - 8;
+        record[0..8].copy_from_slice(&len.to_le_bytes());
+        self.file.write_all(&record)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl OpStore for OpFile {
+    fn for_each_operation(&self, f: &mut dyn FnMut(usize, &WriteOperation) -> Result<(), ApplyError>) -> Result<(), ApplyError> {
+        let mut reader = Records::open(&self.path)?;
+        for index in 0..self.len {
+            let record = reader.next()?
+                .ok_or_else(|| std::io::Error::new(ErrorKind::UnexpectedEof, "operation file is shorter than it was"))?;
+            let operation = decode_operation(&record)
+                .map_err(|error| std::io::Error::new(ErrorKind::InvalidData, error.to_string()))?;
+            f(index, &operation)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the records of an `OpFile` in turn.
+struct Records {
+    reader: BufReader<File>,
+}
+
+impl Records {
+    fn open(path: &Path) -> std::io::Result<Records> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; OP_FILE_HEADER.len()];
+        reader.read_exact(&mut header).map_err(|_| invalid("file is too short to hold operations"))?;
+        if &header != OP_FILE_HEADER {
+            return Err(invalid("file doesn't hold operations"));
+        }
+        Ok(Records { reader })
+    }
+
+    /// Reads the length of the next record, or `None` if the file ends where the record would start.
+    fn record_len(&mut self) -> std::io::Result<Option<u64>> {
+        let mut len = [0; 8];
+        let mut filled = 0;
+        while filled < len.len() {
+            match self.reader.read(&mut len[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(invalid("file ends partway through a record")),
+                Ok(read) => filled += read,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {},
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(Some(u64::from_le_bytes(len)))
+    }
+
+    /// Reads the next record, or `None` if there are no more.
+    fn next(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        let len = match self.record_len()? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        let mut record = Vec::new();
+        (&mut self.reader).take(len).read_to_end(&mut record)?;
+        if (record.len() as u64) < len {
+            return Err(invalid("file ends partway through a record"));
+        }
+        Ok(Some(record))
+    }
+
+    /// Skips the next record, returning whether there was one.
+    fn skip(&mut self) -> std::io::Result<bool> {
+        let len = match self.record_len()? {
+            Some(len) => len,
+            None => return Ok(false),
+        };
+        let position = self.reader.stream_position()?;
+        let end = self.reader.get_ref().metadata()?.len();
+        if end - position < len {
+            return Err(invalid("file ends partway through a record"));
+        }
+        self.reader.seek_relative(len as i64)?;
+        Ok(true)
+    }
+}
+
+fn invalid(reason: &'static str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, reason)
+}
+
+impl Yadon {
+    /// Stores the operations recorded from now on in `store`, rather than in `operations`, starting by moving those
+    /// already recorded there. `apply_store()` applies them, and `to_bytes()` reads them back; what else needs them in
+    /// `operations`, such as `apply()` or `materialize()`, fails with `ApplyError::InStore`. If storing an operation
+    /// fails, the write or seek recording it fails with the error, and so does anything recorded after it, as the
+    /// store no longer holds what was recorded; a custom operation the store refuses, as `OpFile` does, only fails
+    /// itself.
+    pub fn with_store<S>(mut self, mut store: S) -> std::io::Result<Self> where S: OpStore + Send + Sync + 'static {
+        for operation in core::mem::take(&mut self.operations) {
+            store.append(operation)?;
+        }
+        self.store = Some(Box::new(store));
+        Ok(self)
+    }
+
+    /// The store given to `with_store()`, if there is one.
+    pub fn store(&self) -> Option<&(dyn OpStore + Send + Sync)> {
+        self.store.as_deref()
+    }
+}
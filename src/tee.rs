@@ -0,0 +1,82 @@
+use std::io::{Seek, SeekFrom, Write};
+use crate::{WriteOperation, Yadon};
+
+/// Writes through to an inner writer, while recording each write, seek and flush as it was carried out, so that the
+/// same changes can be applied to other targets later. Each write is recorded as the bytes the inner writer took,
+/// which may be fewer than were given, and each seek with the position the inner writer ended up at, so applying the
+/// recording to a copy of what the inner writer started as reproduces what it ends up as. Borrow the inner writer
+/// with `YadonTee::new(&mut writer)` to keep it.
+/// # Example
+/// ```
+/// use yadon::YadonTee;
+/// use std::io::{Cursor, Seek, SeekFrom, Write};
+/// let mut primary = Cursor::new(vec![0u8; 8]);
+/// let mut tee = YadonTee::new(&mut primary).unwrap();
+/// tee.seek(SeekFrom::End(-3)).unwrap();
+/// tee.write_all(b"tee").unwrap();
+/// let (_, yadon) = tee.into_parts();
+///
+/// let mut replica = Cursor::new(vec![0u8; 8]);
+/// yadon.apply(&mut replica, true).unwrap();
+/// assert_eq!(replica.into_inner(), primary.into_inner());
+/// ```
+#[derive(Debug)]
+pub struct YadonTee<W> {
+    inner: W,
+    yadon: Yadon,
+}
+
+impl<W> YadonTee<W> where W: Write + Seek {
+    /// Wraps `inner`, recording from where it's positioned. The recording has that position as its `start`, and no
+    /// `length`.
+    pub fn new(mut inner: W) -> std::io::Result<Self> {
+        let start = inner.stream_position()?;
+        Ok(YadonTee { inner, yadon: Yadon::new(Some(start), None) })
+    }
+
+    /// The inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// What has been recorded so far.
+    pub fn yadon(&self) -> &Yadon {
+        &self.yadon
+    }
+
+    /// Unwraps the inner writer and the recording of what was done to it.
+    pub fn into_parts(self) -> (W, Yadon) {
+        (self.inner, self.yadon)
+    }
+}
+
+impl<W> Write for YadonTee<W> where W: Write + Seek {
+    /// Writes `buf` to the inner writer, recording as many bytes of it as the inner writer took. Nothing is recorded if
+    /// the inner writer fails.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.yadon.write(&buf[0..written]).expect("writes to a recording with no length can't fail");
+        Ok(written)
+    }
+
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()?;
+        self.yadon.flush().expect("recording a flush can't fail");
+        Ok(())
+    }
+}
+
+impl<W> Seek for YadonTee<W> where W: Write + Seek {
+    /// Seeks the inner writer, recording the position it ended up at as where the seek is expected to end up, even for
+    /// a `SeekFrom::End` seek, which the recording has no length to resolve by itself. Nothing is recorded if the
+    /// inner writer fails.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = self.inner.seek(pos)?;
+        self.yadon.virtual_position = Some(position);
+        self.yadon.push_operation(WriteOperation::Seek(pos, position))?;
+        Ok(position)
+    }
+}
@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+use crate::fork::ConcatenateError;
+use crate::io::SeekFrom;
+use crate::{TranslateError, WriteOperation, Yadon};
+
+impl Yadon {
+    /// Moves every position this recording refers to by `delta`, so that it can be applied to a copy of its target with
+    /// `delta` bytes inserted before it, such as a ROM with a copier header, or with them removed. Seeks from the start,
+    /// copies, resizes and byte assertions are rewritten, as are `start`, `length`, the ranges required to have base
+    /// checksums, the generation stamp, reserved regions and the ranges written so far. As the target's length moves by
+    /// `delta` too, seeks from the end are kept, and only their expected positions are moved, as are those of relative
+    /// seeks; the bytes written are left as they are. A base checksum range ending at `u64::MAX` still ends at the end
+    /// of the target.
+    ///
+    /// Fails with `TranslateError::OutOfRange` naming the first operation which would then land below zero or past
+    /// `u64::MAX`, with `TranslateError::FieldOutOfRange` if one of the other positions would, and with
+    /// `TranslateError::UnsupportedOperation` if there's a `WriteOperation::Custom`, whose positions can't be known.
+    /// Fails with `TranslateError::InStore` if the operations are in the store given to `with_store()`. This recording is
+    /// left as it was if it fails.
+    /// # Example
+    /// ```
+    /// use yadon::Yadon;
+    /// let mut yadon = Yadon::new(Some(2), Some(4));
+    /// yadon.write(b"!!").unwrap();
+    /// yadon.translate(3).unwrap();
+    ///
+    /// let mut target = *b"HDRabcd";
+    /// yadon.materialize_into_slice(&mut target).unwrap();
+    /// assert_eq!(&target, b"HDRab!!");
+    /// ```
+    pub fn translate(&mut self, delta: i64) -> Result<(), TranslateError> {
+        if self.in_store() {
+            return Err(TranslateError::InStore);
+        }
+        if let Some(index) = self.operations.iter().position(|operation| operation.try_copy().is_none()) {
+            return Err(TranslateError::UnsupportedOperation { index, name: self.operations[index].name() });
+        }
+        let shift = |value: u64, field: &'static str| value.checked_add_signed(delta).ok_or(TranslateError::FieldOutOfRange(field));
+        let start = self.start.map(|start| shift(start, "start")).transpose()?;
+        let length = self.length.map(|length| shift(length, "length")).transpose()?;
+        let base_checksums = self.base_checksums.iter().map(|(range, crc32)| {
+            let end = match range.end {
+                u64::MAX => u64::MAX,
+                end => shift(end, "base checksum")?,
+            };
+            Ok((shift(range.start, "base checksum")?..end, *crc32))
+        }).collect::<Result<Vec<_>, _>>()?;
+        let generation_stamp = self.generation_stamp
+            .map(|(offset, generation)| Ok((shift(offset, "generation stamp")?, generation)))
+            .transpose()?;
+        let reserved_regions = self.reserved_regions.translated(delta).ok_or(TranslateError::FieldOutOfRange("reserved region"))?;
+        let written_extents = self.written_extents.translated(delta).ok_or(TranslateError::FieldOutOfRange("written range"))?;
+        let forward = |value: u64| value.checked_add_signed(delta);
+        let back = |value: u64| Some(value.wrapping_sub(delta as u64));
+        for index in 0..self.operations.len() {
+            let operation = &mut self.operations[index];
+            let landed = match translate_operation(operation, &forward) {
+                None => Some(index),
+                Some(()) => match operation {
+                    WriteOperation::CopyWithin { src, len, .. } if src.checked_add(*len).is_none() => Some(index + 1),
+                    _ => None,
+                },
+            };
+            if let Some(translated) = landed {
+                for operation in &mut self.operations[..translated] {
+                    translate_operation(operation, &back);
+                }
+                return Err(TranslateError::OutOfRange { index });
+            }
+        }
+        let untranslated = (self.start, self.length);
+        (self.start, self.length) = (start, length);
+        if let Err((index, _)) = self.resimulate_filling(|_| false) {
+            for operation in &mut self.operations {
+                translate_operation(operation, &back);
+            }
+            (self.start, self.length) = untranslated;
+            return Err(TranslateError::OutOfRange { index });
+        }
+        self.base_checksums = base_checksums;
+        self.generation_stamp = generation_stamp;
+        self.reserved_regions = reserved_regions;
+        self.written_extents = written_extents;
+        Ok(())
+    }
+
+    /// A copy of this recording with every position moved by `delta`, as `translate()` does, leaving this one as it
+    /// is. Fails as `translate()` does.
+    pub fn translated(&self, delta: i64) -> Result<Yadon, TranslateError> {
+        if let Some(index) = self.operations.iter().position(|operation| operation.try_copy().is_none()) {
+            return Err(TranslateError::UnsupportedOperation { index, name: self.operations[index].name() });
+        }
+        let mut translated = Yadon::concatenate(&[self]).map_err(|error| match error {
+            ConcatenateError::Uncopyable(name) => unreachable!("{} operations were checked for", name),
+            ConcatenateError::Inconsistent(reason) => TranslateError::Inconsistent(reason),
+            ConcatenateError::InStore => TranslateError::InStore,
+        })?;
+        translated.translate(delta)?;
+        Ok(translated)
+    }
+}
+
+/// Moves the positions `operation` refers to with `shift`. Returns `None`, with `operation` left as it was, if one of
+/// them can't be moved.
+fn translate_operation(operation: &mut WriteOperation, shift: &dyn Fn(u64) -> Option<u64>) -> Option<()> {
+    match operation {
+        WriteOperation::Seek(pos, expected_position) => {
+            let moved = shift(*expected_position)?;
+            if let SeekFrom::Start(offset) = pos {
+                *offset = moved;
+            }
+            *expected_position = moved;
+        },
+        WriteOperation::CopyWithin { src, dst, .. } => {
+            let (moved_src, moved_dst) = (shift(*src)?, shift(*dst)?);
+            *src = moved_src;
+            *dst = moved_dst;
+        },
+        WriteOperation::SetLen(len) => *len = shift(*len)?,
+        WriteOperation::AssertBytes { offset, .. } => *offset = shift(*offset)?,
+        _ => {},
+    }
+    Some(())
+}
@@ -0,0 +1,113 @@
+//! Recording the writes of a UPS patch. A patch is `UPS1`, then the input and output sizes as varints, encoded as in
+//! BPS patches, then hunks until the 12 byte footer, which is the CRC-32s of the input, the output and the rest of the
+//! patch, each 4 little-endian bytes. Each hunk is a varint of how many bytes to skip from where the last hunk ended,
+//! then bytes to XOR with the input from there, ended by a 0 byte, which takes up a byte of the output but leaves it
+//! as it was. The input reads as 0 past its end, so the output is the input, resized to the output size, with the
+//! hunks applied.
+
+use core::convert::TryFrom;
+use core::ops::Range;
+use std::io::{SeekFrom, Write};
+use crate::bps::{read_varint, VarintError};
+use crate::crc::crc32;
+use crate::{UpsError, Yadon};
+
+/// What every UPS patch starts with.
+const MAGIC: &[u8; 4] = b"UPS1";
+/// The length of the CRC-32s every UPS patch ends with.
+const FOOTER_LEN: usize = 12;
+
+impl Yadon {
+    /// Records the writes which the UPS patch `patch` makes to `source`, with the bytes each hunk gives by XORing it
+    /// with `source`. The patch is checked against its own CRC-32, `source` against the input size and CRC-32, and the
+    /// patched source against the output CRC-32, before anything is recorded. Each run of bytes which the patch changes
+    /// becomes a seek from the start, followed by a write. The recording requires the target to have the input CRC-32,
+    /// as `require_base_checksum()` does, or if the output is shorter, to have the CRC-32 of as much of the input as
+    /// is kept. Its `length` is the output size, and if that differs from the input size, resizing to it is recorded
+    /// before the writes, so `materialize()` should be used to lay it down onto a copy of the source. Patches are only
+    /// applied forwards, from input to output.
+    pub fn from_ups(patch: &[u8], source: &[u8]) -> Result<Yadon, UpsError> {
+        if !patch.starts_with(MAGIC) {
+            return Err(UpsError::BadMagic);
+        }
+        let footer = patch.len().checked_sub(FOOTER_LEN).filter(|&footer| footer >= MAGIC.len())
+            .ok_or(UpsError::Truncated { offset: MAGIC.len() })?;
+        let expected = |at: usize| u32::from_le_bytes([patch[at], patch[at + 1], patch[at + 2], patch[at + 3]]);
+        let actual = crc32(&patch[..footer + 8]);
+        if actual != expected(footer + 8) {
+            return Err(UpsError::PatchChecksum { expected: expected(footer + 8), actual });
+        }
+
+        let body = &patch[..footer];
+        let mut offset = MAGIC.len();
+        let input_len = varint(body, &mut offset)?;
+        let output_len_offset = offset;
+        let output_len = varint(body, &mut offset)?;
+        if input_len != source.len() as u64 {
+            return Err(UpsError::InputSize { expected: input_len, actual: source.len() as u64 });
+        }
+        let actual = crc32(source);
+        if actual != expected(footer) {
+            return Err(UpsError::InputChecksum { expected: expected(footer), actual });
+        }
+
+        let mut target = Vec::new();
+        let output_len_usize = usize::try_from(output_len).ok()
+            .filter(|&output_len| target.try_reserve_exact(output_len).is_ok())
+            .ok_or(UpsError::Invalid { offset: output_len_offset, reason: "output is too long to hold in memory" })?;
+        target.extend_from_slice(&source[..source.len().min(output_len_usize)]);
+        target.resize(output_len_usize, 0);
+        // The runs of bytes which the hunks change, merged where they touch.
+        let mut changed: Vec<Range<usize>> = Vec::new();
+        let mut position = 0usize;
+        while offset < footer {
+            let hunk_offset = offset;
+            let skip = varint(body, &mut offset)?;
+            position = usize::try_from(skip).ok().and_then(|skip| position.checked_add(skip))
+                .ok_or(UpsError::Invalid { offset: hunk_offset, reason: "hunk skips past the end of the output" })?;
+            loop {
+                let byte = *body.get(offset).ok_or(UpsError::Truncated { offset: hunk_offset })?;
+                offset += 1;
+                if byte == 0 {
+                    position = position.saturating_add(1);
+                    break;
+                }
+                let changed_byte = target.get_mut(position)
+                    .ok_or(UpsError::Invalid { offset: offset - 1, reason: "hunk changes bytes past the end of the output" })?;
+                *changed_byte ^= byte;
+                match changed.last_mut() {
+                    Some(last) if last.end == position => last.end += 1,
+                    _ => changed.push(position..position + 1),
+                }
+                position += 1;
+            }
+        }
+        let actual = crc32(&target);
+        if actual != expected(footer + 4) {
+            return Err(UpsError::OutputChecksum { expected: expected(footer + 4), actual });
+        }
+
+        let mut yadon = Yadon::new(Some(0), Some(input_len));
+        if output_len < input_len {
+            yadon.require_base_checksum(0..output_len, crc32(&source[..output_len_usize]));
+        } else {
+            yadon.require_base_checksum(0..input_len, expected(footer));
+        }
+        if output_len != input_len {
+            yadon.set_len(output_len).expect("resizing can't fail");
+        }
+        for range in changed {
+            yadon.seek(SeekFrom::Start(range.start as u64)).expect("seeking from the start can't fail");
+            yadon.write_all(&target[range]).expect("writes within the length can't fail");
+        }
+        Ok(yadon)
+    }
+}
+
+fn varint(body: &[u8], offset: &mut usize) -> Result<u64, UpsError> {
+    let start = *offset;
+    read_varint(body, offset).map_err(|error| match error {
+        VarintError::Truncated => UpsError::Truncated { offset: start },
+        VarintError::TooLarge => UpsError::Invalid { offset: start, reason: "varint doesn't fit in 64 bits" },
+    })
+}
@@ -0,0 +1,100 @@
+use std::io::{Seek, SeekFrom, Write};
+use crate::{invalid_seek, offset_position, Yadon};
+
+/// Records into a region of a `Yadon`, as though the region were a file of its own, so that what records into it
+/// doesn't need to know where the region is. Positions are relative to the start of the region, seeks from the end are
+/// from its end, and writes are cut short at its end. What's recorded goes into the parent recording, with each seek
+/// recorded as a seek from the start to where it lands in the parent, so the parent stays consistent whatever it's
+/// applied to. Windows can be opened on windows with `window()`.
+/// # Example
+/// ```
+/// use yadon::Yadon;
+/// use std::io::{Seek, SeekFrom, Write};
+/// let mut yadon = Yadon::new(Some(0), Some(8));
+/// let mut window = yadon.window(2, 4);
+/// window.seek(SeekFrom::End(-2)).unwrap();
+/// assert_eq!(window.write(b"end!").unwrap(), 2);
+///
+/// assert_eq!(yadon.materialize(Some(b"........")).unwrap(), b"....en..");
+/// ```
+#[derive(Debug)]
+pub struct YadonWindow<'a> {
+    parent: &'a mut Yadon,
+    base: u64,
+    len: u64,
+    position: u64,
+}
+
+impl Yadon {
+    /// Opens a window onto the `len` bytes of the recording starting at `base`, which records into them as though they
+    /// were a file of their own, positioned at its start. A window which would end past `u64::MAX` is cut short there.
+    pub fn window(&mut self, base: u64, len: u64) -> YadonWindow<'_> {
+        YadonWindow { len: len.min(u64::MAX - base), parent: self, base, position: 0 }
+    }
+}
+
+impl<'a> YadonWindow<'a> {
+    /// Where the window starts, in the recording it was opened on; for a window opened on a window, that's in the
+    /// outermost recording.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// How many bytes long the window is.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the window is empty, so that nothing can be written to it.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Opens a window onto the `len` bytes of this window starting at `base`, as `Yadon::window()` does. The window is
+    /// cut short at the end of this one.
+    pub fn window(&mut self, base: u64, len: u64) -> YadonWindow<'_> {
+        let len = len.min(self.len.saturating_sub(base));
+        YadonWindow { parent: &mut *self.parent, base: self.base.saturating_add(base), len, position: 0 }
+    }
+}
+
+impl<'a> Write for YadonWindow<'a> {
+    /// Records as much of `buf` as fits before the end of the window, seeking the parent to the window's position first
+    /// if it isn't there already, as after something else was recorded into it.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = (buf.len() as u64).min(self.len.saturating_sub(self.position)) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+        let position = self.base + self.position;
+        if self.parent.virtual_position != Some(position) {
+            self.parent.seek(SeekFrom::Start(position))?;
+        }
+        let written = self.parent.write(&buf[0..len])?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.parent.flush()
+    }
+}
+
+impl<'a> Seek for YadonWindow<'a> {
+    /// Moves the position within the window, recording a seek from the start of the parent to where it lands there.
+    /// Fails, recording nothing, if the position would be before the start of the window, or past `u64::MAX` in the
+    /// parent.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(offset) => offset_position(self.position, offset)?,
+            SeekFrom::End(offset) => offset_position(self.len, offset)?,
+        };
+        self.parent.seek(SeekFrom::Start(self.base.checked_add(position).ok_or_else(invalid_seek)?))?;
+        self.position = position;
+        Ok(position)
+    }
+}
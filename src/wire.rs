@@ -0,0 +1,503 @@
+//! The binary encoding of `Yadon` written by `Yadon::to_bytes()` and read by `Yadon::from_bytes()`. It doesn't depend
+//! on serde, and will keep being readable by later versions of this crate.
+//!
+//! Integers are unsigned LEB128 varints, of at most 10 bytes, unless said otherwise. Signed integers are zigzag
+//! encoded first. An optional value is a byte which is 0 for `None` or 1 for `Some`, followed by the value. Byte
+//! strings and labels are a varint length followed by that many bytes. Labels are UTF-8. In order, an encoding holds:
+//!
+//! - The magic bytes `YADN`, then the format version as one byte, which is 1.
+//! - `start` and `length`, both optional.
+//! - A byte of flags: `defer_end_seeks` is bit 0, `append_only` bit 1, `deny_overwrite` bit 2, bit 3 is set for
+//!   `OverflowPolicy::Error`, and bit 4 for `LengthMode::Growable`. The other bits must be clear.
+//! - The gap fill byte, the apply count, and the optional apply limit.
+//! - The optional generation stamp, as its offset then the generation.
+//! - The number of base checksums, then each as the start and end of its range, then the CRC-32 as 4 little-endian
+//!   bytes.
+//! - The written extents, then the reserved regions, each as their number, then the start and end of each.
+//! - The labels currently pushed, as their number then each label, outermost first. Then the runs of operations carrying
+//!   a label, as their number, then each as the label followed by the start and end of its range of indices.
+//! - The number of operations, then each as a tag byte, the length of the rest of the operation as a varint, then the
+//!   rest, as follows. A seek position is a byte which is 0 for `SeekFrom::Start`, 1 for `SeekFrom::End`, or 2 for
+//!   `SeekFrom::Current`, followed by the offset, signed for the latter two.
+//!
+//! | Tag | Operation | Rest |
+//! |-----|-----------|------|
+//! | 1 | `Write` | expected bytes written, then the data filling the rest |
+//! | 2 | `Seek` | seek position, then the expected position |
+//! | 3 | `DeferredSeek` | seek position |
+//! | 4 | `Flush` | nothing |
+//! | 5 | `Fill` | the byte, then the length |
+//! | 6 | `Repeat` | repetition count, then the pattern filling the rest |
+//! | 7 | `SetLen` | the length |
+//! | 8 | `Sync` | nothing |
+//! | 9 | `CopyWithin` | source, destination, then length |
+//! | 10 | `AssertBytes` | offset, then the expected bytes filling the rest |
+//!
+//! Nothing may follow the last operation. Where each operation was recorded from isn't kept.
+
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, Ordering};
+use crate::extent::Extents;
+use crate::io::{self as io, SeekFrom};
+use crate::label::Labels;
+use crate::{DecodeError, LengthMode, OverflowPolicy, WriteOperation, Yadon};
+
+const MAGIC: &[u8; 4] = b"YADN";
+const VERSION: u8 = 1;
+
+const DEFER_END_SEEKS: u8 = 1 << 0;
+const APPEND_ONLY: u8 = 1 << 1;
+const DENY_OVERWRITE: u8 = 1 << 2;
+const OVERFLOW_ERROR: u8 = 1 << 3;
+const GROWABLE: u8 = 1 << 4;
+
+const WRITE: u8 = 1;
+const SEEK: u8 = 2;
+const DEFERRED_SEEK: u8 = 3;
+const FLUSH: u8 = 4;
+const FILL: u8 = 5;
+const REPEAT: u8 = 6;
+const SET_LEN: u8 = 7;
+const SYNC: u8 = 8;
+const COPY_WITHIN: u8 = 9;
+const ASSERT_BYTES: u8 = 10;
+
+impl Yadon {
+    /// Encodes everything needed to apply the stored operations elsewhere, or to carry on recording, in the versioned
+    /// binary format described in the `wire` module. Where each operation was recorded from isn't kept. Operations in
+    /// the store given to `with_store()` are read back from it, and encoded as though they were in `operations`.
+    /// Panics if any of the operations is a `WriteOperation::Custom`, which can't be encoded, or if the store can't be
+    /// read; `to_writer()` fails instead.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Encodes the recording as `to_bytes()` does, writing it to `writer`. Fails with `ErrorKind::InvalidInput` if any
+    /// of the operations is a `WriteOperation::Custom`, or with the error from reading the store given to
+    /// `with_store()` if it can't be read, without writing anything.
+    #[cfg(feature = "std")]
+    pub fn to_writer<W>(&self, mut writer: W) -> std::io::Result<()> where W: std::io::Write {
+        writer.write_all(&self.encode()?)
+    }
+
+    /// Encodes the recording for `to_bytes()` and `to_writer()`, reading the store given to `with_store()`, if there
+    /// is one, once.
+    fn encode(&self) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        put_option(&mut out, self.start);
+        put_option(&mut out, self.length);
+        let mut flags = 0;
+        for (set, flag) in [
+            (self.defer_end_seeks, DEFER_END_SEEKS),
+            (self.append_only, APPEND_ONLY),
+            (self.deny_overwrite, DENY_OVERWRITE),
+            (self.overflow_policy == OverflowPolicy::Error, OVERFLOW_ERROR),
+            (self.length_mode == LengthMode::Growable, GROWABLE),
+        ] {
+            if set {
+                flags |= flag;
+            }
+        }
+        out.push(flags);
+        out.push(self.gap_fill);
+        put_varint(&mut out, self.applied.load(Ordering::Relaxed));
+        put_option(&mut out, self.max_applies);
+        match self.generation_stamp {
+            Some((offset, generation)) => {
+                out.push(1);
+                put_varint(&mut out, offset);
+                put_varint(&mut out, generation);
+            },
+            None => out.push(0),
+        }
+        put_varint(&mut out, self.base_checksums.len() as u64);
+        for (range, crc32) in &self.base_checksums {
+            put_range(&mut out, range);
+            out.extend_from_slice(&crc32.to_le_bytes());
+        }
+        for extents in [&self.written_extents, &self.reserved_regions] {
+            put_varint(&mut out, extents.iter().count() as u64);
+            for range in extents.iter() {
+                put_range(&mut out, &range);
+            }
+        }
+        put_varint(&mut out, self.labels.stack().count() as u64);
+        for label in self.labels.stack() {
+            put_bytes(&mut out, label.as_bytes());
+        }
+        put_varint(&mut out, self.labels.iter().count() as u64);
+        for (label, operations) in self.labels.iter() {
+            put_bytes(&mut out, label.as_bytes());
+            put_varint(&mut out, operations.start as u64);
+            put_varint(&mut out, operations.end as u64);
+        }
+
+        put_varint(&mut out, self.operation_count() as u64);
+        #[cfg(feature = "std")]
+        if let Some(store) = &self.store {
+            store.for_each_operation(&mut |_, operation| Ok(put_encodable(&mut out, operation)?))?;
+            return Ok(out);
+        }
+        for operation in &self.operations {
+            put_encodable(&mut out, operation)?;
+        }
+        Ok(out)
+    }
+
+    /// Decodes a recording encoded by `to_bytes()`, which recording can carry on from. Everything is checked as it's
+    /// read, and, as when deserializing with serde, the operations are simulated to find the virtual position and
+    /// checked to agree with one another. Fails with a `DecodeError` rather than panicking, whatever `bytes` holds.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Yadon, DecodeError> {
+        let mut input = Input { bytes, offset: 0 };
+        if input.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(DecodeError::BadMagic);
+        }
+        let version = input.byte()?;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let start = input.option()?;
+        let length = input.option()?;
+        let flags_offset = input.offset;
+        let flags = input.byte()?;
+        if flags & !(DEFER_END_SEEKS | APPEND_ONLY | DENY_OVERWRITE | OVERFLOW_ERROR | GROWABLE) != 0 {
+            return Err(DecodeError::Invalid { offset: flags_offset, reason: "unknown flags are set" });
+        }
+        let gap_fill = input.byte()?;
+        let applied = input.varint()?;
+        let max_applies = input.option()?;
+        let generation_stamp = match input.flag()? {
+            true => Some((input.varint()?, input.varint()?)),
+            false => None,
+        };
+        let mut base_checksums = Vec::new();
+        for _ in 0..input.varint()? {
+            let range = input.range()?;
+            let crc32 = input.take(4)?;
+            base_checksums.push((range, u32::from_le_bytes([crc32[0], crc32[1], crc32[2], crc32[3]])));
+        }
+        let written_extents = input.extents()?;
+        let reserved_regions = input.extents()?;
+        let mut label_stack = Vec::new();
+        for _ in 0..input.varint()? {
+            label_stack.push(input.label()?);
+        }
+        let mut label_runs = Vec::new();
+        for _ in 0..input.varint()? {
+            let label = input.label()?;
+            let start = input.usize()?;
+            let end = input.usize()?;
+            label_runs.push((label, start..end));
+        }
+
+        let mut operations = Vec::new();
+        for _ in 0..input.varint()? {
+            operations.push(input.operation()?);
+        }
+        if !input.rest().is_empty() {
+            return Err(DecodeError::TrailingBytes { offset: input.offset });
+        }
+
+        let labels = Labels::from_parts(label_stack, label_runs, operations.len())
+            .map_err(|reason| DecodeError::Inconsistent(reason.into()))?;
+        let mut yadon = Yadon {
+            operations,
+            defer_end_seeks: flags & DEFER_END_SEEKS != 0,
+            append_only: flags & APPEND_ONLY != 0,
+            deny_overwrite: flags & DENY_OVERWRITE != 0,
+            written_extents,
+            reserved_regions,
+            overflow_policy: if flags & OVERFLOW_ERROR != 0 { OverflowPolicy::Error } else { OverflowPolicy::Truncate },
+            length_mode: if flags & GROWABLE != 0 { LengthMode::Growable } else { LengthMode::Fixed },
+            labels,
+            base_checksums,
+            applied: AtomicU64::new(applied),
+            max_applies,
+            generation_stamp,
+            gap_fill,
+            ..Yadon::new(start, length)
+        };
+        yadon.resimulate().map_err(DecodeError::Inconsistent)?;
+        Ok(yadon)
+    }
+
+    /// Decodes a recording as `from_bytes()` does, reading `reader` to its end.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R>(mut reader: R) -> Result<Yadon, DecodeError> where R: std::io::Read {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(DecodeError::Io)?;
+        Yadon::from_bytes(&bytes)
+    }
+}
+
+/// Appends `operation`, as a tag byte, then the length of the rest of it, then the rest. Panics if it's a
+/// `WriteOperation::Custom`, which can't be encoded.
+pub(crate) fn put_operation(out: &mut Vec<u8>, operation: &WriteOperation) {
+    let mut body = Vec::new();
+    let tag = match operation {
+        WriteOperation::Write(data, expected_bytes_written) => {
+            put_varint(&mut body, *expected_bytes_written as u64);
+            body.extend_from_slice(data);
+            WRITE
+        },
+        WriteOperation::Seek(pos, expected_position) => {
+            put_seek(&mut body, *pos);
+            put_varint(&mut body, *expected_position);
+            SEEK
+        },
+        WriteOperation::DeferredSeek(pos) => {
+            put_seek(&mut body, *pos);
+            DEFERRED_SEEK
+        },
+        WriteOperation::Flush => FLUSH,
+        WriteOperation::Fill { byte, len } => {
+            body.push(*byte);
+            put_varint(&mut body, *len);
+            FILL
+        },
+        WriteOperation::Repeat { pattern, count } => {
+            put_varint(&mut body, *count);
+            body.extend_from_slice(pattern);
+            REPEAT
+        },
+        WriteOperation::SetLen(len) => {
+            put_varint(&mut body, *len);
+            SET_LEN
+        },
+        WriteOperation::Sync => SYNC,
+        WriteOperation::CopyWithin { src, dst, len } => {
+            put_varint(&mut body, *src);
+            put_varint(&mut body, *dst);
+            put_varint(&mut body, *len);
+            COPY_WITHIN
+        },
+        WriteOperation::AssertBytes { offset, expected } => {
+            put_varint(&mut body, *offset);
+            body.extend_from_slice(expected);
+            ASSERT_BYTES
+        },
+        #[cfg(feature = "std")]
+        WriteOperation::Custom(..) => panic!("custom operations can't be encoded"),
+    };
+    out.push(tag);
+    put_bytes(out, &body);
+}
+
+/// Appends `operation` as `put_operation()` does, failing with `ErrorKind::InvalidInput` rather than panicking if it's a
+/// `WriteOperation::Custom`.
+fn put_encodable(out: &mut Vec<u8>, operation: &WriteOperation) -> io::Result<()> {
+    #[cfg(feature = "std")]
+    if let WriteOperation::Custom(..) = operation {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "custom operations can't be encoded"));
+    }
+    put_operation(out, operation);
+    Ok(())
+}
+
+/// Decodes an operation written by `put_operation()`, which must fill `bytes`.
+#[cfg(feature = "std")]
+pub(crate) fn decode_operation(bytes: &[u8]) -> Result<WriteOperation, DecodeError> {
+    let mut input = Input { bytes, offset: 0 };
+    let operation = input.operation()?;
+    if !input.rest().is_empty() {
+        return Err(DecodeError::TrailingBytes { offset: input.offset });
+    }
+    Ok(operation)
+}
+
+fn put_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn put_signed(out: &mut Vec<u8>, value: i64) {
+    put_varint(out, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn put_option(out: &mut Vec<u8>, value: Option<u64>) {
+    match value {
+        Some(value) => {
+            out.push(1);
+            put_varint(out, value);
+        },
+        None => out.push(0),
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    put_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn put_range(out: &mut Vec<u8>, range: &Range<u64>) {
+    put_varint(out, range.start);
+    put_varint(out, range.end);
+}
+
+fn put_seek(out: &mut Vec<u8>, pos: SeekFrom) {
+    match pos {
+        SeekFrom::Start(offset) => {
+            out.push(0);
+            put_varint(out, offset);
+        },
+        SeekFrom::End(offset) => {
+            out.push(1);
+            put_signed(out, offset);
+        },
+        SeekFrom::Current(offset) => {
+            out.push(2);
+            put_signed(out, offset);
+        },
+    }
+}
+
+/// The input to decode, up to where the current part of it ends, and how far it has been decoded. Offsets in errors are
+/// from the start of the whole input.
+struct Input<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Input<'a> {
+    /// Decodes an operation, as written by `put_operation()`.
+    fn operation(&mut self) -> Result<WriteOperation, DecodeError> {
+        let tag_offset = self.offset;
+        let tag = self.byte()?;
+        let len = self.usize()?;
+        let end = self.offset.checked_add(len).filter(|&end| end <= self.bytes.len())
+            .ok_or(DecodeError::Truncated { offset: self.bytes.len() })?;
+        let mut body = Input { bytes: &self.bytes[0..end], offset: self.offset };
+        self.offset = end;
+        let operation = match tag {
+            WRITE => {
+                let expected_bytes_written = body.usize()?;
+                WriteOperation::Write(body.take_rest().to_owned(), expected_bytes_written)
+            },
+            SEEK => WriteOperation::Seek(body.seek()?, body.varint()?),
+            DEFERRED_SEEK => WriteOperation::DeferredSeek(body.seek()?),
+            FLUSH => WriteOperation::Flush,
+            FILL => WriteOperation::Fill { byte: body.byte()?, len: body.varint()? },
+            REPEAT => {
+                let count = body.varint()?;
+                WriteOperation::Repeat { pattern: body.take_rest().to_owned(), count }
+            },
+            SET_LEN => WriteOperation::SetLen(body.varint()?),
+            SYNC => WriteOperation::Sync,
+            COPY_WITHIN => WriteOperation::CopyWithin { src: body.varint()?, dst: body.varint()?, len: body.varint()? },
+            ASSERT_BYTES => {
+                let offset = body.varint()?;
+                WriteOperation::AssertBytes { offset, expected: body.take_rest().to_owned() }
+            },
+            tag => return Err(DecodeError::UnknownTag { tag, offset: tag_offset }),
+        };
+        if !body.rest().is_empty() {
+            return Err(DecodeError::Invalid { offset: body.offset, reason: "operation has bytes left over" });
+        }
+        Ok(operation)
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.bytes[self.offset..]
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.rest().len() < len {
+            return Err(DecodeError::Truncated { offset: self.bytes.len() });
+        }
+        let taken = &self.rest()[0..len];
+        self.offset += len;
+        Ok(taken)
+    }
+
+    fn take_rest(&mut self) -> &'a [u8] {
+        let rest = self.rest();
+        self.offset = self.bytes.len();
+        rest
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn flag(&mut self) -> Result<bool, DecodeError> {
+        let offset = self.offset;
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::Invalid { offset, reason: "optional value is neither absent nor present" }),
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let offset = self.offset;
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            // The tenth byte only has room for the top bit.
+            if shift == 63 && byte > 1 {
+                break;
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Invalid { offset, reason: "varint doesn't fit in a u64" })
+    }
+
+    fn signed(&mut self) -> Result<i64, DecodeError> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn usize(&mut self) -> Result<usize, DecodeError> {
+        let offset = self.offset;
+        usize::try_from(self.varint()?).map_err(|_| DecodeError::Invalid { offset, reason: "value doesn't fit in a usize" })
+    }
+
+    fn option(&mut self) -> Result<Option<u64>, DecodeError> {
+        match self.flag()? {
+            true => Ok(Some(self.varint()?)),
+            false => Ok(None),
+        }
+    }
+
+    fn range(&mut self) -> Result<Range<u64>, DecodeError> {
+        Ok(self.varint()?..self.varint()?)
+    }
+
+    fn extents(&mut self) -> Result<Extents, DecodeError> {
+        let offset = self.offset;
+        let mut ranges = Vec::new();
+        for _ in 0..self.varint()? {
+            ranges.push(self.range()?);
+        }
+        Extents::from_ranges(ranges).map_err(|reason| DecodeError::Invalid { offset, reason })
+    }
+
+    fn label(&mut self) -> Result<String, DecodeError> {
+        let len = self.usize()?;
+        let offset = self.offset;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_owned()).map_err(|_| DecodeError::Invalid { offset, reason: "label isn't UTF-8" })
+    }
+
+    fn seek(&mut self) -> Result<SeekFrom, DecodeError> {
+        let offset = self.offset;
+        match self.byte()? {
+            0 => Ok(SeekFrom::Start(self.varint()?)),
+            1 => Ok(SeekFrom::End(self.signed()?)),
+            2 => Ok(SeekFrom::Current(self.signed()?)),
+            _ => Err(DecodeError::Invalid { offset, reason: "seek is from neither the start, end nor current position" }),
+        }
+    }
+}
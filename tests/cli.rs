@@ -0,0 +1,98 @@
+use std::io::{SeekFrom, Write};
+use std::path::Path;
+use std::process::{Command, Output};
+use yadon::Yadon;
+
+fn yadon(args: &[&Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_yadon")).args(args).output().expect("the binary runs")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+fn patch() -> Yadon {
+    let mut yadon = Yadon::new(Some(0), Some(16));
+    yadon.assert_bytes_at(0, b"yado").unwrap();
+    yadon.seek(SeekFrom::Start(4)).unwrap();
+    yadon.write_all(b"n!").unwrap();
+    yadon.seek(SeekFrom::Start(12)).unwrap();
+    yadon.write_all(&[0xff; 4]).unwrap();
+    yadon
+}
+
+#[test]
+fn apply_show_verify_and_export() {
+    let dir = tempfile::tempdir().unwrap();
+    let (log, file, ips) = (dir.path().join("log"), dir.path().join("file"), dir.path().join("out.ips"));
+    std::fs::write(&log, patch().to_bytes()).unwrap();
+    std::fs::write(&file, b"yado------------").unwrap();
+
+    let output = yadon(&["show".as_ref(), &log]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), patch().to_string());
+
+    let output = yadon(&["verify".as_ref(), &log, &file]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stderr(&output).contains("reflects the first 2 operations, but not operation 2"));
+
+    let output = yadon(&["apply".as_ref(), &log, &file]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "applied 5 operations, writing 6 bytes\n");
+    assert_eq!(std::fs::read(&file).unwrap(), b"yadon!------\xff\xff\xff\xff");
+
+    let output = yadon(&["verify".as_ref(), &log, &file]);
+    assert!(output.status.success());
+    assert!(stdout(&output).ends_with("reflects all 5 operations\n"));
+
+    let output = yadon(&["export-ips".as_ref(), &log, &ips]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("assert_bytes"));
+    let writes = Yadon::from_patches(vec![(4, b"n!".to_vec()), (12, vec![0xff; 4])], Some(0), Some(16)).unwrap();
+    std::fs::write(&log, writes.to_bytes()).unwrap();
+    let output = yadon(&["export-ips".as_ref(), &log, &ips]);
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(&ips).unwrap(), writes.to_ips().unwrap());
+
+    // A script is read just as an encoded recording is.
+    let mut script = String::new();
+    patch().to_script(&mut script).unwrap();
+    std::fs::write(&log, script).unwrap();
+    let output = yadon(&["verify".as_ref(), &log, &file]);
+    assert!(output.status.success());
+}
+
+#[test]
+fn exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let (log, file) = (dir.path().join("log"), dir.path().join("file"));
+    std::fs::write(&log, patch().to_bytes()).unwrap();
+
+    assert_eq!(yadon(&[]).status.code(), Some(1));
+    assert_eq!(yadon(&["show".as_ref()]).status.code(), Some(1));
+    let output = yadon(&["apply".as_ref(), &log, &file]);
+    assert_eq!(output.status.code(), Some(2));
+
+    std::fs::write(&file, b"yada------------").unwrap();
+    let output = yadon(&["apply".as_ref(), &log, &file]);
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(std::fs::read(&file).unwrap(), b"yada------------");
+
+    let mut end_seek = Yadon::new(Some(0), Some(16));
+    end_seek.seek(SeekFrom::End(-4)).unwrap();
+    end_seek.write_all(b"tail").unwrap();
+    std::fs::write(&log, end_seek.to_bytes()).unwrap();
+    std::fs::write(&file, b"too short").unwrap();
+    assert_eq!(yadon(&["verify".as_ref(), &log, &file]).status.code(), Some(3));
+    assert_eq!(yadon(&["apply".as_ref(), &log, &file]).status.code(), Some(3));
+    assert_eq!(std::fs::read(&file).unwrap(), b"too short");
+
+    std::fs::write(&log, b"not a recording\n").unwrap();
+    let output = yadon(&["show".as_ref(), &log]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("line 1"));
+}